@@ -22,17 +22,22 @@ pub fn impl_deser_macro(ast: &syn::DeriveInput) -> TokenStream {
             let variants: Vec<quote::__private::TokenStream> = data
                 .variants
                 .iter()
-                .map(|variant| deserialize_variant(variant, &name))
+                .enumerate()
+                .map(|(index, variant)| deserialize_variant(variant, &name, index))
                 .collect();
 
+            // Enums are encoded as a one byte discriminant, followed by the
+            // variant's fields. See `serialize::impl_ser_macro`.
             let expanded: quote::__private::TokenStream = quote! {
                 impl BitcoinDeserialize for #name {
-                    fn bitcoin_deserialize<R: std::io::Read>(&self, mut target: R) -> Result<#name, std::io::Error>
+                    fn bitcoin_deserialize<R: std::io::Read>(mut target: R) -> Result<Self, SerializationError>
                     {
-                        match *self {
+                        let mut discriminant = [0u8; 1];
+                        std::io::Read::read_exact(&mut target, &mut discriminant)?;
+                        match discriminant[0] {
                             #(#variants)*
+                            _ => Err(SerializationError::Parse("invalid enum discriminant")),
                         }
-                        Ok(())
                     }
                 }
             };
@@ -71,7 +76,11 @@ fn deserialize_field(field: &syn::Field, _index: usize) -> quote::__private::Tok
     let ty = field.ty.clone();
     match field.ident.clone() {
         Some(name) => {
-            quote! { #name: <#ty as BitcoinDeserialize>::bitcoin_deserialize(&mut target)?, }
+            // Record the field name, so nested errors report a path to the field
+            quote! {
+                #name: <#ty as BitcoinDeserialize>::bitcoin_deserialize(&mut target)
+                    .map_err(|e| e.context(stringify!(#name)))?,
+            }
         }
         None => {
             quote! {<#ty>::bitcoin_deserialize(&mut target)?,}
@@ -85,35 +94,41 @@ fn deserialize_field(field: &syn::Field, _index: usize) -> quote::__private::Tok
     // quote! { #name: format!("shared::<{}>::deserialize(target),", #ty)  }
 }
 
-fn deserialize_variant(variant: &syn::Variant, name: &syn::Ident) -> quote::__private::TokenStream {
+fn deserialize_variant(
+    variant: &syn::Variant,
+    name: &syn::Ident,
+    index: usize,
+) -> quote::__private::TokenStream {
     let ident = variant.ident.clone();
-
-    // let subfields: Vec<quote::__private::TokenStream> = variant
-    //     .fields
-    //     .iter()
-    //     .map(|field| {
-    //         let ident = field
-    //             .ident
-    //             .clone()
-    //             .expect("Can only derive serialize for named variant fields");
-    //         quote! { ref #ident , }
-    //     })
-    //     .collect();
+    let discriminant = discriminant(variant, index);
 
     let statements: Vec<quote::__private::TokenStream> = variant
         .fields
         .iter()
-        .map(|field| {
-            let ty = field.ty.clone();
-            // let ident = field
-            //     .ident
-            //     .clone()
-            //     .expect("Can only derive serialize for named variant fields");
-            quote! { #ty::bitcoin_deserialize(&mut target)?; }
-        })
+        .zip(0..1_000_000)
+        .map(|(field, index)| deserialize_field(field, index))
         .collect();
 
-    quote! { #name::#ident {
-        #(#statements)*
-    } }
+    match variant.fields {
+        syn::Fields::Named(_) => quote! {
+            #discriminant => Ok(#name::#ident { #(#statements)* }),
+        },
+        syn::Fields::Unnamed(_) => quote! {
+            #discriminant => Ok(#name::#ident ( #(#statements)* )),
+        },
+        syn::Fields::Unit => quote! {
+            #discriminant => Ok(#name::#ident),
+        },
+    }
+}
+
+/// Returns the one byte discriminant for the variant at `index`.
+pub(crate) fn discriminant(variant: &syn::Variant, index: usize) -> u8 {
+    if index > u8::MAX as usize {
+        panic!(
+            "can't derive bitcoin serialization for enums with more than 256 variants, found {}",
+            variant.ident
+        );
+    }
+    index as u8
 }
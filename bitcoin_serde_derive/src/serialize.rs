@@ -30,9 +30,9 @@ pub fn impl_ser_macro(ast: &syn::DeriveInput) -> TokenStream {
             let variants: Vec<quote::__private::TokenStream> = data
                 .variants
                 .iter()
-                .map(|variant| serialize_variant(variant, &name))
+                .enumerate()
+                .map(|(index, variant)| serialize_variant(variant, &name, index))
                 .collect();
-            // vec![quoted]
 
             let expanded: quote::__private::TokenStream = quote! {
                 impl BitcoinSerialize for #name {
@@ -72,40 +72,39 @@ fn serialize_field(field: &syn::Field, index: usize) -> quote::__private::TokenS
 //     quote! { #ident.serialize(target)?; }
 // }
 
-fn serialize_variant(variant: &syn::Variant, name: &syn::Ident) -> quote::__private::TokenStream {
+/// Serialize an enum variant as its one byte discriminant (the variant's
+/// position in the enum), followed by each of its fields in order.
+fn serialize_variant(
+    variant: &syn::Variant,
+    name: &syn::Ident,
+    index: usize,
+) -> quote::__private::TokenStream {
     let ident = variant.ident.clone();
+    let discriminant = crate::deserialize::discriminant(variant, index);
 
-    let subfields: Vec<quote::__private::TokenStream> = variant
+    let bindings: Vec<syn::Ident> = variant
         .fields
         .iter()
-        .map(|field| {
-            if let Some(ident) = field.ident.clone() {
-                quote! { ref #ident , }
-            } else {
-                quote!(ref inner)
-            }
+        .enumerate()
+        .map(|(index, field)| match field.ident.clone() {
+            Some(ident) => ident,
+            None => quote::format_ident!("field_{}", index),
         })
         .collect();
 
-    let statements: Vec<quote::__private::TokenStream> = variant
-        .fields
+    let statements: Vec<quote::__private::TokenStream> = bindings
         .iter()
-        .map(|field| {
-            if let Some(ident) = field.ident.clone() {
-                quote! { #ident.bitcoin_serialize(&mut target)?; }
-            } else {
-                quote! { inner.bitcoin_serialize(&mut target)?;}
-            }
-        })
+        .map(|binding| quote! { #binding.bitcoin_serialize(&mut target)?; })
         .collect();
 
-    if subfields.len() > 0 {
-        quote! { #name::#ident ( #(#subfields)* ) => {
-            #(#statements)*
-        },}
-    } else {
-        quote! { #name::#ident => {
-            #(#statements)*
-        },}
-    }
+    let pattern = match variant.fields {
+        syn::Fields::Named(_) => quote! { #name::#ident { #(ref #bindings),* } },
+        syn::Fields::Unnamed(_) => quote! { #name::#ident ( #(ref #bindings),* ) },
+        syn::Fields::Unit => quote! { #name::#ident },
+    };
+
+    quote! { #pattern => {
+        target.write_all(&[#discriminant])?;
+        #(#statements)*
+    },}
 }
@@ -54,6 +54,100 @@ impl<C> Amount<C> {
     }
 }
 
+impl<C> Amount<C>
+where
+    C: Constraint,
+{
+    /// Multiply this amount by `multiplier`, returning an error if the product
+    /// is outside the valid range for the constraint.
+    pub fn checked_mul(self, multiplier: u64) -> Result<Amount<C>> {
+        let product = i128::from(self.0) * i128::from(multiplier);
+        let product = i64::try_from(product).map_err(|_| Error::MultiplicationOverflow {
+            amount: self.0,
+            multiplier,
+        })?;
+        product.try_into()
+    }
+
+    /// Divide this amount by `divisor`, rounding towards zero. Returns an error
+    /// if `divisor` is zero.
+    pub fn checked_div(self, divisor: u64) -> Result<Amount<C>> {
+        if divisor == 0 {
+            return Err(Error::DivideByZero { amount: self.0 });
+        }
+        let quotient = i128::from(self.0) / i128::from(divisor);
+        i64::try_from(quotient)
+            .expect("the quotient is no larger than the amount")
+            .try_into()
+    }
+
+    /// Sum `amounts`, returning an error if any partial sum is outside the
+    /// valid range for the constraint.
+    ///
+    /// For `NonNegative` amounts, this is an error if the total is more than
+    /// the money supply cap, [`MAX_MONEY`].
+    pub fn sum<I>(amounts: I) -> Result<Amount<C>>
+    where
+        I: IntoIterator<Item = Amount<C>>,
+    {
+        amounts.into_iter().checked_sum()
+    }
+
+    /// Format this amount as a decimal number of BTC, without trailing zeros.
+    ///
+    /// For example, 150,000,000 satoshis is `"1.5"`, and 1 satoshi is
+    /// `"0.00000001"`.
+    pub fn to_btc_string(&self) -> String {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        // Valid amounts are much smaller than i64::MAX, so this can't overflow
+        let satoshis = self.0.abs();
+        let (whole, fraction) = (satoshis / COIN, satoshis % COIN);
+
+        if fraction == 0 {
+            format!("{}{}", sign, whole)
+        } else {
+            let fraction = format!("{:08}", fraction);
+            format!("{}{}.{}", sign, whole, fraction.trim_end_matches('0'))
+        }
+    }
+
+    /// Parse a decimal number of BTC, with at most 8 decimal places.
+    ///
+    /// Returns an error if `s` isn't a decimal number, has more precision than
+    /// a satoshi, or is outside the valid range for the constraint.
+    pub fn from_btc_str(s: &str) -> Result<Amount<C>> {
+        let parse_error = || Error::Parse {
+            input: s.to_owned(),
+        };
+        let is_digits =
+            |digits: &str| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit());
+
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, s),
+        };
+        let (whole, fraction) = match unsigned.find('.') {
+            Some(point) => (&unsigned[..point], &unsigned[point + 1..]),
+            None => (unsigned, "0"),
+        };
+        // More whole digits than this are out of range for any constraint
+        if !is_digits(whole) || whole.len() > 18 || !is_digits(fraction) || fraction.len() > 8 {
+            return Err(parse_error());
+        }
+
+        let whole: i128 = whole.parse().map_err(|_| parse_error())?;
+        let fraction: i128 = format!("{:0<8}", fraction)
+            .parse()
+            .map_err(|_| parse_error())?;
+        let satoshis = whole * i128::from(COIN) + fraction;
+        let satoshis = if negative { -satoshis } else { satoshis };
+
+        i64::try_from(satoshis)
+            .map_err(|_| parse_error())?
+            .try_into()
+    }
+}
+
 impl<C> std::ops::Add<Amount<C>> for Amount<C>
 where
     C: Constraint,
@@ -244,13 +338,7 @@ impl std::ops::Mul<u64> for Amount<NonNegative> {
     type Output = Result<Amount<NonNegative>>;
 
     fn mul(self, rhs: u64) -> Self::Output {
-        let value = (self.0 as u64)
-            .checked_mul(rhs)
-            .ok_or(Error::MultiplicationOverflow {
-                amount: self.0,
-                multiplier: rhs,
-            })?;
-        value.try_into()
+        self.checked_mul(rhs)
     }
 }
 
@@ -266,12 +354,24 @@ impl std::ops::Div<u64> for Amount<NonNegative> {
     type Output = Result<Amount<NonNegative>>;
 
     fn div(self, rhs: u64) -> Self::Output {
-        let quotient = (self.0 as u64)
-            .checked_div(rhs)
-            .ok_or(Error::DivideByZero { amount: self.0 })?;
-        Ok(quotient
-            .try_into()
-            .expect("division by a positive integer always stays within the constraint"))
+        self.checked_div(rhs)
+    }
+}
+
+/// Summation of an iterator of `Amount`s, returning an error if any partial
+/// sum is outside the valid range for the constraint.
+pub trait CheckedSum<C>: Iterator<Item = Amount<C>> {
+    /// Sum the amounts in this iterator, checking the range after each addition.
+    fn checked_sum(self) -> Result<Amount<C>>;
+}
+
+impl<C, I> CheckedSum<C> for I
+where
+    C: Constraint,
+    I: Iterator<Item = Amount<C>>,
+{
+    fn checked_sum(self) -> Result<Amount<C>> {
+        self.fold(0i64.try_into(), |sum, amount| sum + amount)
     }
 }
 
@@ -293,6 +393,8 @@ pub enum Error {
     MultiplicationOverflow { amount: i64, multiplier: u64 },
     /// cannot divide amount {amount} by zero
     DivideByZero { amount: i64 },
+    /// could not parse {input:?} as a decimal BTC amount
+    Parse { input: String },
 }
 
 /// Marker type for `Amount` that allows negative values.
@@ -559,6 +661,176 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn checked_sum() -> Result<()> {
+        zebra_test::init();
+
+        let one: Amount<NonNegative> = 1.try_into()?;
+        let max: Amount<NonNegative> = MAX_MONEY.try_into()?;
+
+        let sum = vec![one, one, one].into_iter().checked_sum()?;
+        assert_eq!(sum, Amount::<NonNegative>::try_from(3)?);
+
+        let empty = Vec::<Amount<NonNegative>>::new()
+            .into_iter()
+            .checked_sum()?;
+        assert_eq!(empty, Amount::<NonNegative>::try_from(0)?);
+
+        vec![max, one]
+            .into_iter()
+            .checked_sum()
+            .expect_err("sum above MAX_MONEY is an error");
+
+        Ok(())
+    }
+
+    #[test]
+    fn sum_checks_money_supply_cap() -> Result<()> {
+        zebra_test::init();
+
+        let half = Amount::<NonNegative>::try_from(MAX_MONEY / 2)?;
+        let rest = Amount::<NonNegative>::try_from(MAX_MONEY - MAX_MONEY / 2)?;
+        let one = Amount::<NonNegative>::try_from(1)?;
+
+        assert_eq!(
+            Amount::sum(vec![half, rest])?,
+            Amount::<NonNegative>::try_from(MAX_MONEY)?
+        );
+        assert_eq!(
+            Amount::<NonNegative>::sum(Vec::new())?,
+            Amount::<NonNegative>::try_from(0)?
+        );
+
+        Amount::sum(vec![half, rest, one]).expect_err("sum above MAX_MONEY is an error");
+        Amount::sum(vec![rest, one, half]).expect_err("sum above MAX_MONEY is an error");
+
+        Ok(())
+    }
+
+    #[test]
+    fn btc_string_edge_cases() -> Result<()> {
+        zebra_test::init();
+
+        let cases = [
+            (0, "0"),
+            (1, "0.00000001"),
+            (10, "0.0000001"),
+            (150_000_000, "1.5"),
+            (COIN, "1"),
+            (MAX_MONEY, "21000000"),
+        ];
+        for &(satoshis, btc) in cases.iter() {
+            let amount = Amount::<NonNegative>::try_from(satoshis)?;
+            assert_eq!(amount.to_btc_string(), btc);
+            assert_eq!(Amount::<NonNegative>::from_btc_str(btc)?, amount);
+        }
+
+        let negative = Amount::<NegativeAllowed>::try_from(-150_000_000)?;
+        assert_eq!(negative.to_btc_string(), "-1.5");
+        assert_eq!(Amount::<NegativeAllowed>::from_btc_str("-1.5")?, negative);
+
+        // Trailing zeros are accepted when parsing
+        assert_eq!(
+            Amount::<NonNegative>::from_btc_str("1.50000000")?,
+            Amount::<NonNegative>::try_from(150_000_000)?
+        );
+
+        for invalid in ["", "-", "1.", ".5", "1.000000001", "1e8", "+1", " 1"].iter() {
+            assert_eq!(
+                Amount::<NonNegative>::from_btc_str(invalid),
+                Err(Error::Parse {
+                    input: invalid.to_string()
+                })
+            );
+        }
+        assert!(matches!(
+            Amount::<NonNegative>::from_btc_str("21000000.00000001"),
+            Err(Error::Contains { .. })
+        ));
+        assert!(matches!(
+            Amount::<NonNegative>::from_btc_str("-0.00000001"),
+            Err(Error::Contains { .. })
+        ));
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn btc_string_round_trip(amount in any::<Amount<NonNegative>>()) {
+            zebra_test::init();
+
+            let btc = amount.to_btc_string();
+            prop_assert_eq!(Amount::<NonNegative>::from_btc_str(&btc), Ok(amount));
+        }
+
+        /// Amounts with trailing decimal zeros, including whole BTC amounts.
+        #[test]
+        fn rounded_btc_string_round_trip(satoshis in 0..=MAX_MONEY, zeros in 0..=8u32) {
+            zebra_test::init();
+
+            let scale = 10i64.pow(zeros);
+            let amount = Amount::<NonNegative>::try_from(satoshis / scale * scale)?;
+            let btc = amount.to_btc_string();
+            prop_assert!(!btc.contains('.') || !btc.ends_with('0'));
+            prop_assert_eq!(Amount::<NonNegative>::from_btc_str(&btc), Ok(amount));
+        }
+    }
+
+    #[test]
+    fn fee_rate_with_checked_mul_and_div() -> Result<()> {
+        zebra_test::init();
+
+        // A 2,250 satoshi fee for a 225 vbyte transaction
+        let fee = Amount::<NonNegative>::try_from(2_250)?;
+        let fee_rate = fee.checked_mul(1000)?.checked_div(225)?;
+        assert_eq!(fee_rate, Amount::<NonNegative>::try_from(10_000)?);
+
+        // Division rounds towards zero, including for negative amounts
+        let change = Amount::<NegativeAllowed>::try_from(-7)?;
+        assert_eq!(
+            change.checked_div(2)?,
+            Amount::<NegativeAllowed>::try_from(-3)?
+        );
+        assert_eq!(
+            change.checked_mul(3)?,
+            Amount::<NegativeAllowed>::try_from(-21)?
+        );
+
+        assert_eq!(
+            fee.checked_div(0),
+            Err(Error::DivideByZero { amount: 2_250 })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn checked_mul_overflow() -> Result<()> {
+        zebra_test::init();
+
+        let max = Amount::<NonNegative>::try_from(MAX_MONEY)?;
+        assert_eq!(max.checked_mul(1)?, max);
+        max.checked_mul(2)
+            .expect_err("product above MAX_MONEY is an error");
+
+        let one = Amount::<NonNegative>::try_from(1)?;
+        assert_eq!(
+            one.checked_mul(u64::MAX),
+            Err(Error::MultiplicationOverflow {
+                amount: 1,
+                multiplier: u64::MAX,
+            })
+        );
+
+        let neg_max = Amount::<NegativeAllowed>::try_from(-MAX_MONEY)?;
+        neg_max
+            .checked_mul(2)
+            .expect_err("product below -MAX_MONEY is an error");
+
+        Ok(())
+    }
+
     #[test]
     fn add_with_diff_constraints() -> Result<()> {
         zebra_test::init();
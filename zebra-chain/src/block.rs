@@ -15,7 +15,7 @@ mod tests;
 
 use std::{convert::TryInto, fmt, io::Read, iter::FromIterator, sync::Arc};
 
-use crate::compactint::CompactInt;
+use crate::{cached::Cached, compactint::CompactInt};
 use crate::{BitcoinDeserialize, BitcoinSerialize, SerializationError};
 use bitcoin_serde_derive::BtcSerialize;
 use bytes::{Buf, BytesMut};
@@ -28,7 +28,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{fmt::DisplayToDebug, transaction::Transaction, transparent};
 
-use self::serialize::MAX_BLOCK_BYTES;
+use self::serialize::{MAX_BLOCK_BYTES, MAX_BLOCK_SERIALIZED_SIZE, MIN_TRANSACTION_SIZE};
 
 /// A Bitcoin block, containing a header and a list of transactions.
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, BtcSerialize)]
@@ -72,16 +72,18 @@ impl fmt::Display for Block {
 
 impl Block {
     /// Return the block height reported in the coinbase transaction, if any.
-    /// Invariant: This method assumes that the height is pre-cached by some early construction/deserialization function.
-    /// TODO: this invariant is not upheld by the implementation of bitcoin_deserialize for transparent::Input
-    /// Instead, it needs to be delegated to a higher-level function which is aware of the status of BIP34 activation.
+    ///
+    /// The BIP34 height is cached when a version 2 or later block is
+    /// deserialized. Version 1 blocks don't have a height in their coinbase,
+    /// so this method returns `None` for them. It also returns `None` if the
+    /// height in the coinbase data is malformed.
     pub fn coinbase_height(&self) -> Option<Height> {
         if let Some(height) = self.header.reported_height() {
             return Some(Height(height as u32));
         }
         self.transactions
             .get(0)
-            .and_then(|tx| tx.inputs.get(0))
+            .and_then(|tx| tx.inputs().get(0))
             .and_then(|input| match input {
                 transparent::Input::Coinbase { ref height, .. } => match height {
                     Some(cached_height) => cached_height.value(),
@@ -91,9 +93,39 @@ impl Block {
             })
     }
 
-    /// Compute the hash of this block.
+    /// Returns the hash of this block's header.
+    ///
+    /// See [`Header::hash`] for when this uses a cached hash.
     pub fn hash(&self) -> Hash {
-        Hash::from(self)
+        self.header.hash()
+    }
+
+    /// Returns the hash of this block's parent.
+    pub fn previous_hash(&self) -> Hash {
+        self.header.previous_block_hash
+    }
+
+    /// Returns true if `parent` is the parent of this block.
+    pub fn links_to(&self, parent: &Block) -> bool {
+        self.previous_hash() == parent.hash()
+    }
+
+    /// Returns the height of a block whose parent is at `parent_height`, or
+    /// `None` if that height is out of range.
+    pub fn height_after(parent_height: Height) -> Option<Height> {
+        parent_height.next()
+    }
+
+    /// Returns the exact serialized length (in bytes) of this block,
+    /// without serializing it.
+    pub fn serialized_size(&self) -> usize {
+        Header::len()
+            + CompactInt::size(self.transactions.len())
+            + self
+                .transactions
+                .iter()
+                .map(|tx| tx.serialized_size_with_header())
+                .sum::<usize>()
     }
 
     /// TODO: re-implement structural validation
@@ -105,7 +137,9 @@ impl Block {
     pub fn deserialize_from_buf(src: &mut BytesMut) -> Result<Self, SerializationError> {
         let header = Header::deserialize_from_buf(src.split_to(Header::len()))?;
 
-        let mut src = src.reader().take(MAX_BLOCK_BYTES - Header::len() as u64);
+        let mut src = src
+            .reader()
+            .take(MAX_BLOCK_SERIALIZED_SIZE - Header::len() as u64);
         let tx_count = {
             let tx_count = CompactInt::bitcoin_deserialize(&mut src)?;
             tx_count.value()
@@ -117,23 +151,43 @@ impl Block {
         //     return Err(SerializationError::Parse("Block contains no transactions"));
         // }
         // Sanity check number of transactions to prevent DOS attacks
-        if tx_count > MAX_BLOCK_BYTES / (36 * 4) {
+        if tx_count > MAX_BLOCK_BYTES / MIN_TRANSACTION_SIZE {
             return Err(SerializationError::Parse(
-                "Block contained too many transactions for each to have at least one input",
+                "Block contained too many transactions to fit in the maximum block size",
             ));
         }
         let mut transactions = Vec::with_capacity(
             tx_count
                 .try_into()
-                .expect("usize::min must be greater than MAX_BLOCK_BYTES / (36 * 4)"),
+                .expect("usize::MAX must be greater than MAX_BLOCK_BYTES / MIN_TRANSACTION_SIZE"),
         );
 
-        // TODO: Parse block height
-        if header.version >= 2 {}
-
         // Parse and validate remaining transactions
-        for _ in 0..tx_count {
-            transactions.push(<Arc<Transaction>>::bitcoin_deserialize(&mut src)?);
+        for index in 0..tx_count {
+            let mut transaction = Transaction::bitcoin_deserialize(&mut src)
+                .map_err(|e| e.at_index(index as usize).context("transactions"))?;
+            // Version 2 and later blocks should have a BIP34 height in their
+            // coinbase. It is only required after BIP34 activates, so the
+            // block verifier enforces it, and malformed heights are left empty.
+            if index == 0 && header.version >= 2 {
+                if let Some(transparent::Input::Coinbase { height, data, .. }) =
+                    transaction.inputs.get_mut(0)
+                {
+                    *height = data.bip34_height().ok().map(Cached::from);
+                }
+            }
+            transactions.push(Arc::new(transaction));
+        }
+
+        // The witness data is limited by the total size, so also check the
+        // size without it
+        let stripped_size = Header::len()
+            + CompactInt::size(transactions.len())
+            + transactions.iter().map(|tx| tx.len()).sum::<usize>();
+        if stripped_size as u64 > MAX_BLOCK_BYTES {
+            return Err(SerializationError::Parse(
+                "block is larger than the maximum block size, excluding witness data",
+            ));
         }
         // let actual_merkle_root = merkle::Root::from_iter(transactions.iter().map(|tx| tx.hash()));
         // if !(actual_merkle_root == header.merkle_root) {
@@ -148,6 +202,6 @@ impl Block {
 
 impl<'a> From<&'a Block> for Hash {
     fn from(block: &'a Block) -> Hash {
-        (&block.header).into()
+        block.header.hash()
     }
 }
@@ -4,7 +4,7 @@ use crate::work::difficulty::CompactDifficulty;
 
 use super::*;
 
-use crate::LedgerState;
+use crate::{cached::Cached, transaction, transparent, LedgerState};
 use chrono::{TimeZone, Utc};
 use proptest::{
     arbitrary::{any, Arbitrary},
@@ -18,9 +18,17 @@ impl Arbitrary for Block {
         let transactions_strategy = Transaction::vec_strategy(ledger_state, 2);
 
         (any::<Header>(), transactions_strategy)
-            .prop_map(|(mut header, transactions)| {
+            .prop_map(move |(mut header, mut transactions)| {
                 header.merkle_root =
-                    crate::block::merkle::Root::from_iter(transactions.iter().map(|tx| tx.hash()));
+                    merkle::Root::arbitrary_from_txids(transactions.iter().map(|tx| tx.hash()));
+                // Arbitrary headers are version 2 or later, so deserialization
+                // caches the BIP34 height in the coinbase input
+                let coinbase = Arc::make_mut(&mut transactions[0]);
+                if let Some(transparent::Input::Coinbase { height, .. }) =
+                    coinbase.inputs.get_mut(0)
+                {
+                    *height = Some(Cached::from(Height(ledger_state.tip_height.0 + 1)));
+                }
                 Self {
                     header,
                     transactions,
@@ -50,6 +58,20 @@ impl Block {
     }
 }
 
+impl merkle::Root {
+    /// Returns the Merkle root of `txids`, so that an arbitrary header is
+    /// consistent with the transactions generated for its block.
+    ///
+    /// Header-only tests can use `any::<merkle::Root>()`, which generates a
+    /// random root that doesn't match any transactions.
+    pub fn arbitrary_from_txids<I>(txids: I) -> Self
+    where
+        I: IntoIterator<Item = transaction::Hash>,
+    {
+        txids.into_iter().collect()
+    }
+}
+
 impl Arbitrary for Header {
     type Parameters = ();
 
@@ -60,7 +82,7 @@ impl Arbitrary for Header {
             any::<Hash>(),
             any::<merkle::Root>(),
             // time is interpreted as u32 in the spec, but rust timestamps are i64
-            (0i64..(u32::MAX as i64)),
+            (0i64..=(u32::MAX as i64)),
             any::<CompactDifficulty>(),
             (0u32..(u32::MAX)),
         )
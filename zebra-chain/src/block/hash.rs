@@ -1,10 +1,13 @@
 use std::{fmt, io};
 
-use crate::serialization::{sha256d, BitcoinDeserialize, BitcoinSerialize, SerializationError};
+use crate::serialization::{
+    serde_helpers, sha256d, BitcoinDeserialize, BitcoinSerialize, MinSerializedSize,
+    SerializationError,
+};
 use bitcoin_serde_derive::BtcSerialize;
 #[cfg(any(test, feature = "proptest-impl"))]
 use proptest_derive::Arbitrary;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use super::Header;
 
@@ -15,8 +18,9 @@ use super::Header;
 /// binds the entire contents of the block and is used to identify entire blocks.
 ///
 /// Note: Zebra displays transaction and block hashes in big-endian byte-order,
-/// following the u256 convention set by Bitcoin and zcashd.
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, BtcSerialize)]
+/// following the u256 convention set by Bitcoin and zcashd. Human-readable
+/// serde formats use the same hex string, and binary formats use the raw bytes.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, BtcSerialize)]
 #[cfg_attr(any(test, feature = "proptest-impl"), derive(Arbitrary))]
 pub struct Hash(pub [u8; 32]);
 
@@ -44,6 +48,18 @@ impl fmt::Debug for Hash {
     }
 }
 
+impl Serialize for Hash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde_helpers::serialize_hash(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        serde_helpers::deserialize_hash(deserializer).map(Hash)
+    }
+}
+
 impl BitcoinDeserialize for Hash {
     fn bitcoin_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError>
     where
@@ -53,6 +69,10 @@ impl BitcoinDeserialize for Hash {
     }
 }
 
+impl MinSerializedSize for Hash {
+    const MIN_SERIALIZED_SIZE: usize = 32;
+}
+
 impl<'a> From<&'a Header> for Hash {
     fn from(block_header: &'a Header) -> Self {
         let mut hash_writer = sha256d::Writer::default();
@@ -74,3 +94,30 @@ impl std::str::FromStr for Hash {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENESIS: &str = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+
+    #[test]
+    fn blockhash_serde_json_is_hex() {
+        zebra_test::init();
+
+        let hash: Hash = GENESIS.parse().unwrap();
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(json, format!("\"{}\"", GENESIS));
+        assert_eq!(serde_json::from_str::<Hash>(&json).unwrap(), hash);
+    }
+
+    #[test]
+    fn blockhash_serde_bincode_is_bytes() {
+        zebra_test::init();
+
+        let hash: Hash = GENESIS.parse().unwrap();
+        let bytes = bincode::serialize(&hash).unwrap();
+        assert_eq!(bytes, hash.0.to_vec());
+        assert_eq!(bincode::deserialize::<Hash>(&bytes).unwrap(), hash);
+    }
+}
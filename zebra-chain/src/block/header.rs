@@ -6,7 +6,7 @@ use thiserror::Error;
 
 use crate::{
     compactint::CompactInt,
-    serialization::{sha256d, SmallUnixTime},
+    serialization::{sha256d, MinSerializedSize, SmallUnixTime},
     BitcoinDeserialize, BitcoinSerialize, SerializationError,
 };
 use bitcoin_serde_derive::{BtcDeserialize, BtcSerialize};
@@ -21,6 +21,10 @@ use super::{merkle, Hash, Height};
 /// backwards reference (previous header hash) present in the block
 /// header. Each block points backwards to its parent, all the way
 /// back to the genesis block (the first block in the blockchain).
+///
+/// Unlike Zcash headers, Bitcoin headers don't contain an Equihash solution.
+/// The proof of work is the header hash itself, which must be less than or
+/// equal to the target encoded in `difficulty_threshold`.
 #[derive(
     Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, BtcSerialize, BtcDeserialize,
 )]
@@ -76,7 +80,7 @@ pub struct Header {
 /// TODO: Use this error as the source for zebra_consensus::error::BlockError::Time,
 /// and make `BlockError::Time` add additional context.
 /// See https://github.com/ZcashFoundation/zebra/issues/1021 for more details.
-#[derive(Error, Debug)]
+#[derive(Error, Debug, PartialEq)]
 pub enum BlockTimeError {
     #[error("invalid time {0:?} in block header {1:?} {2:?}: block time is more than 2 hours in the future ({3:?}). Hint: check your machine's date, time, and time zone.")]
     InvalidBlockTime(
@@ -134,6 +138,28 @@ impl Header {
         }
     }
 
+    /// Returns the hash of this header.
+    ///
+    /// Headers parsed by [`Header::deserialize_from_buf`] cache their hash, so
+    /// this doesn't need to re-serialize them. Other headers are serialized
+    /// and hashed on each call.
+    pub fn hash(&self) -> Hash {
+        self.hash.value().unwrap_or_else(|| Hash::from(self))
+    }
+
+    /// Returns true if this header has a cached hash.
+    pub fn has_cached_hash(&self) -> bool {
+        self.hash.has_value()
+    }
+
+    /// Clears the cached hash.
+    ///
+    /// Call this after modifying the fields of a parsed header, so that
+    /// [`Header::hash`] returns the hash of the modified header.
+    pub fn clear_cached_hash(&mut self) {
+        self.hash = Cached::new();
+    }
+
     pub fn reported_height(&self) -> Option<usize> {
         self.reported_height.value()
     }
@@ -155,8 +181,22 @@ impl Header {
 
         // Deserialize the ehader
         let mut src = std::io::Cursor::new(src);
+        let version = u32::bitcoin_deserialize(&mut src)?;
+        // Bitcoin treats the version as an i32. Version 0 was never valid, and
+        // a set high bit is a negative version, which is below every version
+        // required by BIP 34, 66, and 65.
+        if version == 0 {
+            return Err(SerializationError::Parse(
+                "block version must be at least 1",
+            ));
+        }
+        if version & 0x8000_0000 != 0 {
+            return Err(SerializationError::Parse(
+                "block version must not have the high bit set",
+            ));
+        }
         Ok(Header {
-            version: u32::bitcoin_deserialize(&mut src)?,
+            version,
             previous_block_hash: Hash::bitcoin_deserialize(&mut src)?,
             merkle_root: merkle::Root::bitcoin_deserialize(&mut src)?,
             time: SmallUnixTime::bitcoin_deserialize(&mut src)?,
@@ -188,6 +228,11 @@ impl BitcoinDeserialize for CountedHeader {
         })
     }
 }
+/// A header, followed by a transaction count of at least one byte.
+impl MinSerializedSize for CountedHeader {
+    const MIN_SERIALIZED_SIZE: usize = Header::len() + 1;
+}
+
 impl BitcoinSerialize for CountedHeader {
     fn bitcoin_serialize<W: Write>(&self, mut target: W) -> Result<(), std::io::Error> {
         self.header.bitcoin_serialize(&mut target)?;
@@ -3,6 +3,7 @@ use bitcoin_serde_derive::BtcSerialize;
 
 use std::{
     convert::TryFrom,
+    fmt,
     ops::{Add, Sub},
 };
 
@@ -12,25 +13,42 @@ use std::{
 /// to get a difference of block heights, represented as an `i32`,
 /// and height differences can be added to block heights to get new heights.
 ///
+/// Heights are displayed, parsed, and serialized with serde as a plain
+/// integer.
+///
 /// # Invariants
 ///
 /// Users should not construct block heights greater than `Height::MAX`.
 #[derive(
     Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, Deserialize, BtcSerialize,
 )]
+#[serde(try_from = "u32")]
 pub struct Height(pub u32);
 
+impl TryFrom<u32> for Height {
+    type Error = SerializationError;
+
+    fn try_from(height: u32) -> Result<Self, Self::Error> {
+        if Height(height) <= Height::MAX {
+            Ok(Height(height))
+        } else {
+            Err(SerializationError::Parse("Height exceeds maximum height"))
+        }
+    }
+}
+
+impl fmt::Display for Height {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 impl BitcoinDeserialize for Height {
     fn bitcoin_deserialize<R: std::io::Read>(mut reader: R) -> Result<Self, SerializationError>
     where
         Self: Sized,
     {
-        let inner = u32::bitcoin_deserialize(&mut reader)?;
-        if Height(inner) <= Height::MAX {
-            Ok(Height(inner))
-        } else {
-            Err(SerializationError::Parse("Height exceeds maximum height"))
-        }
+        Height::try_from(u32::bitcoin_deserialize(&mut reader)?)
     }
 }
 
@@ -38,8 +56,7 @@ impl std::str::FromStr for Height {
     type Err = SerializationError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.parse() {
-            Ok(h) if (Height(h) <= Height::MAX) => Ok(Height(h)),
-            Ok(_) => Err(SerializationError::Parse("Height exceeds maximum height")),
+            Ok(h) => Height::try_from(h),
             Err(_) => Err(SerializationError::Parse("Height(u32) integer parse error")),
         }
     }
@@ -65,6 +82,39 @@ impl Height {
     /// `Height::MAX.0` can't be used in match range patterns, use this
     /// alias instead.
     pub const MAX_AS_U32: u32 = Self::MAX.0;
+
+    /// Returns the height `rhs` blocks after this one, or `None` if it would
+    /// be greater than `Height::MAX`.
+    pub fn checked_add(self, rhs: u32) -> Option<Height> {
+        let height = Height(self.0.checked_add(rhs)?);
+        if height <= Height::MAX {
+            Some(height)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the height `rhs` blocks before this one, or `None` if it would
+    /// be less than `Height::MIN`, or this height is greater than
+    /// `Height::MAX`.
+    pub fn checked_sub(self, rhs: u32) -> Option<Height> {
+        if self > Height::MAX {
+            return None;
+        }
+        Some(Height(self.0.checked_sub(rhs)?))
+    }
+
+    /// Returns the height of the next block, or `None` if this is
+    /// `Height::MAX`.
+    pub fn next(self) -> Option<Height> {
+        self.checked_add(1)
+    }
+
+    /// Returns the height of the previous block, or `None` if this is the
+    /// genesis height.
+    pub fn previous(self) -> Option<Height> {
+        self.checked_sub(1)
+    }
 }
 
 impl Add<Height> for Height {
@@ -200,3 +250,51 @@ fn operator_tests() {
     assert_eq!(-1, Height(Height::MAX_AS_U32 - 1) - Height::MAX);
     assert_eq!(-(Height::MAX_AS_U32 as i32), Height(0) - Height::MAX);
 }
+
+#[test]
+fn checked_arithmetic_bounds() {
+    zebra_test::init();
+
+    assert!(Height(1) < Height(2));
+    assert!(Height::MIN < Height::MAX);
+
+    assert_eq!(Some(Height(3)), Height(1).checked_add(2));
+    assert_eq!(Some(Height::MAX), Height(0).checked_add(Height::MAX_AS_U32));
+    assert_eq!(None, Height::MAX.checked_add(1));
+    assert_eq!(None, Height(1).checked_add(u32::MAX));
+    assert_eq!(None, Height(Height::MAX_AS_U32 + 1).checked_add(0));
+
+    assert_eq!(Some(Height(1)), Height(3).checked_sub(2));
+    assert_eq!(
+        Some(Height::MIN),
+        Height::MAX.checked_sub(Height::MAX_AS_U32)
+    );
+    assert_eq!(None, Height::MIN.checked_sub(1));
+    assert_eq!(None, Height(Height::MAX_AS_U32 + 1).checked_sub(1));
+
+    assert_eq!(Some(Height(1)), Height::MIN.next());
+    assert_eq!(None, Height::MAX.next());
+    assert_eq!(Some(Height(Height::MAX_AS_U32 - 1)), Height::MAX.previous());
+    assert_eq!(None, Height::MIN.previous());
+}
+
+#[test]
+fn display_parse_and_serde() {
+    zebra_test::init();
+
+    let height: Height = "709632".parse().expect("height is in range");
+    assert_eq!(height, Height(709_632));
+    assert_eq!(height.to_string(), "709632");
+    assert_eq!(Height::MAX.to_string().parse::<Height>(), Ok(Height::MAX));
+
+    assert_eq!(
+        "500000000".parse::<Height>(),
+        Err(SerializationError::Parse("Height exceeds maximum height"))
+    );
+    assert!("-1".parse::<Height>().is_err());
+    assert!("4294967296".parse::<Height>().is_err());
+
+    assert_eq!(serde_json::to_string(&height).unwrap(), "709632");
+    assert_eq!(serde_json::from_str::<Height>("709632").unwrap(), height);
+    assert!(serde_json::from_str::<Height>("500000000").is_err());
+}
@@ -17,6 +17,27 @@
 /// This excludes the size of the Witness structure
 pub const MAX_BLOCK_BYTES: u64 = 1_000_000;
 
+/// The maximum size of a serialized Bitcoin block, including witness data.
+///
+/// Witness bytes have a weight of 1, and all other bytes have a weight of 4,
+/// so a block within the 4,000,000 weight limit is never larger than this.
+pub const MAX_BLOCK_SERIALIZED_SIZE: u64 = 4_000_000;
+
+/// The size of the smallest structurally valid transaction, in bytes.
+///
+/// A transaction needs at least one input and one output, so the smallest
+/// possible serialization is:
+/// - 4 bytes of version,
+/// - 1 byte of input count, and a 41 byte input with an empty script
+///   (36 byte outpoint, 1 byte script length, 4 byte sequence),
+/// - 1 byte of output count, and a 9 byte output with an empty script
+///   (8 byte value, 1 byte script length),
+/// - 4 bytes of lock time.
+///
+/// Witness data is excluded from `MAX_BLOCK_BYTES`, and only ever adds to a
+/// transaction's size, so this is also a lower bound for segwit transactions.
+pub const MIN_TRANSACTION_SIZE: u64 = 4 + 1 + 41 + 1 + 9 + 4;
+
 // impl ZcashSerialize for Header {
 //     fn zcash_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
 //         writer.write_u32::<LittleEndian>(self.version)?;
@@ -4,7 +4,8 @@ use std::{iter::FromIterator, sync::Arc};
 use transparent::CoinbaseData;
 
 use crate::{
-    serialization::{BitcoinDeserialize, BitcoinSerialize, SmallUnixTime},
+    compactint::CompactInt,
+    serialization::{BitcoinDeserialize, SmallUnixTime},
     transaction::{LockTime, Transaction},
     transparent,
 };
@@ -36,6 +37,28 @@ pub fn oversized_single_transaction_block() -> Block {
     single_transaction_block(true)
 }
 
+/// Returns the largest `count` for which `block_size(count)` is no more than
+/// `MAX_BLOCK_BYTES`.
+///
+/// `block_size` must be the exact serialized size of a block with `count`
+/// repeated items, and must increase with `count`.
+fn max_count_within_limit(block_size: impl Fn(usize) -> usize) -> usize {
+    let limit = MAX_BLOCK_BYTES as usize;
+
+    // Start from a linear estimate, then correct for the variable-length
+    // item count.
+    let item_size = block_size(1) - block_size(0);
+    let mut count = (limit - block_size(0)) / item_size;
+    while block_size(count) > limit {
+        count -= 1;
+    }
+    while block_size(count + 1) <= limit {
+        count += 1;
+    }
+
+    count
+}
+
 // Implementation of block generation with multiple transactions
 fn multi_transaction_block(oversized: bool) -> Block {
     // A dummy transaction
@@ -57,16 +80,13 @@ fn multi_transaction_block(oversized: bool) -> Block {
     // A block header
     let mut header = block_header();
 
-    // Serialize header
-    let mut data_header = Vec::new();
-    header
-        .bitcoin_serialize(&mut data_header)
-        .expect("Block header should serialize");
-
-    // Calculate the number of transactions we need
-    let mut max_transactions_in_block =
-        (MAX_BLOCK_BYTES as usize - data_header.len() - coinbase.len())
-            / zebra_test::vectors::DUMMY_TX1[..].len();
+    // Calculate the number of transactions we need, after the coinbase
+    let mut max_transactions_in_block = max_count_within_limit(|count| {
+        Header::len()
+            + CompactInt::size(count + 1)
+            + coinbase.serialized_size_with_header()
+            + count * tx.serialized_size_with_header()
+    });
     if oversized {
         max_transactions_in_block += 1;
     }
@@ -87,7 +107,7 @@ fn multi_transaction_block(oversized: bool) -> Block {
     }
 }
 
-// Implementation of block generation with one transaction and multiple inputs
+// Implementation of block generation with one transaction and multiple outputs
 fn single_transaction_block(oversized: bool) -> Block {
     // Dummy input and output
     let coinbase = transparent::Input::Coinbase {
@@ -101,46 +121,26 @@ fn single_transaction_block(oversized: bool) -> Block {
     // A block header
     let mut header = block_header();
 
-    // Serialize header
-    let mut data_header = Vec::new();
-    header
-        .bitcoin_serialize(&mut data_header)
-        .expect("Block header should serialize");
-
-    // Serialize a LockTime
     let lock_time = LockTime::Time(SmallUnixTime(DateTime::<Utc>::from_utc(
         NaiveDateTime::from_timestamp(61, 0),
         Utc,
     )));
-    let mut data_locktime = Vec::new();
-    lock_time
-        .bitcoin_serialize(&mut data_locktime)
-        .expect("LockTime should serialize");
-
-    // Calculate the number of inputs we need
-    // let mut max_inputs_in_tx = (MAX_BLOCK_BYTES as usize
-    //     - data_header.len()
-    //     - zebra_test::vectors::DUMMY_OUTPUT1[..].len()
-    //     - data_locktime.len())
-    //     / (zebra_test::vectors::DUMMY_INPUT1[..].len());
-    let mut max_outputs_in_tx =
-        (MAX_BLOCK_BYTES as usize - data_header.len() - coinbase.len() - data_locktime.len())
-            / zebra_test::vectors::DUMMY_OUTPUT1[..].len();
-
-    // The above calculation is wrong somehow. This makes it correct. TODO: Troubleshoot
-    max_outputs_in_tx -= 5;
 
+    // Calculate the number of outputs we need, using a transaction
+    // without any outputs as the base size
+    let empty_transaction = Transaction::new(1, vec![coinbase.clone()], Vec::new(), lock_time);
+    let mut max_outputs_in_tx = max_count_within_limit(|count| {
+        Header::len() + CompactInt::size(1) + empty_transaction.serialized_size_with_header()
+            - CompactInt::size(0)
+            + CompactInt::size(count)
+            + count * output.len()
+    });
     if oversized {
         max_outputs_in_tx += 1;
     }
 
-    // Create inputs to be just below the limit
+    // Create outputs to be just below or just above the limit
     let inputs = vec![coinbase];
-    // let inputs = std::iter::once(coinbase)
-    //     .chain(std::iter::repeat(input))
-    //     .take(max_inputs_in_tx)
-    //     .collect::<Vec<_>>();
-
     let outputs = std::iter::repeat(output).take(max_outputs_in_tx).collect();
 
     // Create a big transaction
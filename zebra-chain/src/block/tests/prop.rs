@@ -67,7 +67,7 @@ proptest! {
         } else {
             let serialization_err = bytes.bitcoin_deserialize_into::<Block>()
                 .expect_err("blocks larger than the maximum size should fail");
-            match serialization_err {
+            match serialization_err.root_cause() {
                 SerializationError::Io(io_err) => {
                     prop_assert_eq![io_err.kind(), ErrorKind::UnexpectedEof];
                 }
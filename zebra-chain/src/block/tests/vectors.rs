@@ -8,7 +8,10 @@ use crate::{
 };
 use crate::{serialization::SmallUnixTime, transaction::LockTime};
 
-use super::super::{serialize::MAX_BLOCK_BYTES, *};
+use super::super::{
+    serialize::{MAX_BLOCK_BYTES, MAX_BLOCK_SERIALIZED_SIZE, MIN_TRANSACTION_SIZE},
+    *,
+};
 use super::generate; // XXX this should be rewritten as strategies
 
 #[test]
@@ -67,6 +70,91 @@ fn deserialize_blockheader() {
     }
 }
 
+#[test]
+fn parsed_header_caches_its_hash() {
+    zebra_test::init();
+
+    for block in zebra_test::vectors::BLOCKS.iter() {
+        let header = Header::deserialize_from_buf(&block[..Header::len()])
+            .expect("blockheader test vector should deserialize");
+        assert!(header.has_cached_hash());
+        assert_eq!(header.hash(), Hash::from(&header));
+
+        let block = block
+            .bitcoin_deserialize_into::<Block>()
+            .expect("block test vector should deserialize");
+        assert!(block.header.has_cached_hash());
+        assert_eq!(block.hash(), header.hash());
+    }
+
+    // Headers without a cached hash are hashed on demand
+    let mut header = generate::block_header();
+    assert!(!header.has_cached_hash());
+    assert_eq!(header.hash(), Hash::from(&header));
+
+    // Clearing the cache picks up changes to the header fields
+    let mut parsed = Header::deserialize_from_buf(&zebra_test::vectors::DUMMY_HEADER[..])
+        .expect("dummy header should deserialize");
+    parsed.nonce = parsed.nonce.wrapping_add(1);
+    parsed.clear_cached_hash();
+    header.nonce = parsed.nonce;
+    assert_eq!(parsed.hash(), header.hash());
+}
+
+#[test]
+fn header_time_round_trips_at_u32_boundary() {
+    zebra_test::init();
+
+    let round_trip = |time: DateTime<Utc>| {
+        let mut header = generate::block_header();
+        header.time = SmallUnixTime(time);
+        header
+            .bitcoin_serialize_to_vec()
+            .expect("header should serialize")
+            .bitcoin_deserialize_into::<Header>()
+            .expect("header should deserialize")
+            .time
+    };
+
+    let last_time = Utc.timestamp(u32::MAX as i64, 0);
+    assert_eq!(round_trip(last_time), SmallUnixTime(last_time));
+    assert_eq!(
+        round_trip(Utc.timestamp(0, 0)),
+        SmallUnixTime(Utc.timestamp(0, 0))
+    );
+
+    // Later times don't fit in the header, so they are clamped
+    assert_eq!(
+        round_trip(last_time + Duration::seconds(1)),
+        SmallUnixTime(last_time)
+    );
+}
+
+#[test]
+fn deserialize_blockheader_version_range() {
+    zebra_test::init();
+
+    let header_with_version = |version: u32| {
+        let mut bytes = zebra_test::vectors::DUMMY_HEADER.to_vec();
+        bytes[..4].copy_from_slice(&version.to_le_bytes());
+        Header::deserialize_from_buf(&bytes[..])
+    };
+
+    for &version in &[1, 2, 4, 0x2000_0000, 0x3fff_ffff] {
+        let header = header_with_version(version).expect("version should be accepted");
+        assert_eq!(header.version, version);
+    }
+
+    assert_eq!(
+        header_with_version(0).unwrap_err(),
+        SerializationError::Parse("block version must be at least 1")
+    );
+    assert_eq!(
+        header_with_version(0x8000_0000).unwrap_err(),
+        SerializationError::Parse("block version must not have the high bit set")
+    );
+}
+
 #[test]
 fn deserialize_block() {
     zebra_test::init();
@@ -121,38 +209,166 @@ fn block_test_vectors_unique() {
     );
 }
 
-// FIXME: Uncomment once height design is finalized
-// #[test]
-// fn block_test_vectors_height_mainnet() {
-//     zebra_test::init();
+#[test]
+fn block_test_vectors_height_mainnet() {
+    zebra_test::init();
 
-//     block_test_vectors_height(Network::Mainnet);
-// }
+    for (&height, block) in zebra_test::vectors::MAINNET_BLOCKS.iter() {
+        let block = block
+            .bitcoin_deserialize_into::<Block>()
+            .expect("block is structurally valid");
 
-// #[test]
-// fn block_test_vectors_height_testnet() {
-//     zebra_test::init();
+        if block.header.version < 2 {
+            // Pre-BIP34 coinbases don't have a height
+            assert_eq!(block.coinbase_height(), None);
+        } else {
+            assert_eq!(
+                block.coinbase_height().expect("block height is valid").0,
+                height,
+                "deserialized height must match BTreeMap key height"
+            );
+        }
+    }
+}
 
-//     block_test_vectors_height(Network::Testnet);
-// }
-//
-// fn block_test_vectors_height(network: Network) {
-//     let block_iter = match network {
-//         Network::Mainnet => zebra_test::vectors::MAINNET_BLOCKS.iter(),
-//         Network::Testnet => zebra_test::vectors::TESTNET_BLOCKS.iter(),
-//     };
-
-//     for (&height, block) in block_iter {
-//         let block = block
-//             .bitcoin_deserialize_into::<Block>()
-//             .expect("block is structurally valid");
-//         assert_eq!(
-//             block.coinbase_height().expect("block height is valid").0,
-//             height,
-//             "deserialized height must match BTreeMap key height"
-//         );
-//     }
-// }
+#[test]
+fn block_links_to_parent() {
+    zebra_test::init();
+
+    let parent = zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES
+        .bitcoin_deserialize_into::<Block>()
+        .expect("block is structurally valid");
+    let mut child = Block {
+        header: generate::block_header(),
+        transactions: parent.transactions.clone(),
+    };
+    assert!(!child.links_to(&parent));
+
+    child.header.previous_block_hash = parent.hash();
+    assert_eq!(child.previous_hash(), parent.hash());
+    assert!(child.links_to(&parent));
+    assert!(!parent.links_to(&child));
+
+    assert_eq!(Block::height_after(Height(0)), Some(Height(1)));
+    assert_eq!(Block::height_after(Height::MAX), None);
+}
+
+#[test]
+fn nested_errors_report_their_field_path() {
+    zebra_test::init();
+
+    let block = zebra_test::vectors::BLOCK_MAINNET_347500_BYTES
+        .bitcoin_deserialize_into::<Block>()
+        .expect("block test vector should deserialize");
+    let data = block
+        .bitcoin_serialize_to_vec()
+        .expect("block should serialize");
+
+    // Replace the length of the first lock script in the third transaction
+    // with one that is much larger than any valid script.
+    let transaction = &block.transactions[2];
+    assert!(transaction.witnesses.is_empty());
+    let transaction_start = Header::len()
+        + CompactInt::size(block.transactions.len())
+        + block.transactions[..2]
+            .iter()
+            .map(|tx| tx.serialized_size_with_header())
+            .sum::<usize>();
+    let lock_script_start = transaction_start
+        + 4
+        + CompactInt::size(transaction.inputs.len())
+        + transaction
+            .inputs
+            .iter()
+            .map(|input| input.len())
+            .sum::<usize>()
+        + CompactInt::size(transaction.outputs.len())
+        + 8;
+    let lock_script_len = transaction.outputs[0].lock_script.0.len();
+    assert_eq!(data[lock_script_start] as usize, lock_script_len);
+    let mut bad_script = data[..lock_script_start].to_vec();
+    bad_script.extend_from_slice(&[0xfe, 0xff, 0xff, 0xff, 0xff]);
+    bad_script.extend_from_slice(&data[lock_script_start + 1..]);
+
+    let error =
+        Block::bitcoin_deserialize(&bad_script[..]).expect_err("script length is too large");
+    assert_eq!(error.path(), Some("transactions[2].outputs[0].lock_script"));
+    assert_eq!(
+        error.root_cause(),
+        &SerializationError::Parse("declared length exceeds the maximum length")
+    );
+}
+
+// TODO: add a testnet height test, once we have Bitcoin testnet block vectors
+
+#[test]
+fn bip34_height_encodings() {
+    zebra_test::init();
+
+    let height = |data: &[u8]| transparent::CoinbaseData(data.to_vec()).bip34_height();
+
+    // Heights around BIP34 activation, at block 227,931, followed by miner data
+    assert_eq!(height(&[0x03, 0x5a, 0x7a, 0x03, 0x2f]), Ok(Height(227_930)));
+    assert_eq!(height(&[0x03, 0x5b, 0x7a, 0x03, 0x2f]), Ok(Height(227_931)));
+    assert_eq!(height(&[0x03, 0x5c, 0x7a, 0x03]), Ok(Height(227_932)));
+
+    // Small heights use OP_1 to OP_16
+    assert_eq!(height(&[0x51]), Ok(Height(1)));
+    assert_eq!(height(&[0x60, 0x2f]), Ok(Height(16)));
+
+    // Other push lengths, including a zero byte that holds the sign bit
+    assert_eq!(height(&[0x01, 0x7f]), Ok(Height(127)));
+    assert_eq!(height(&[0x02, 0x80, 0x00]), Ok(Height(128)));
+    assert_eq!(
+        height(&[0x04, 0xff, 0xff, 0xff, 0x1d]),
+        Ok(Height(0x1dff_ffff))
+    );
+
+    // Zero padding is only allowed for the sign bit
+    assert_eq!(
+        height(&[0x05, 0xff, 0x64, 0xcd, 0x1d, 0x00]),
+        Err(SerializationError::Parse(
+            "BIP34 height in coinbase data is not minimally encoded"
+        ))
+    );
+
+    // Malformed encodings
+    for &data in [
+        &[][..],
+        &[0x00][..],
+        &[0x06, 1, 2, 3, 4, 5, 6][..],
+        &[0x4c, 0x01, 0x01][..],
+        &[0x03, 0x5b, 0x7a][..],
+        &[0x01, 0x80][..],
+        &[0x01, 0x10][..],
+        &[0x02, 0x7f, 0x00][..],
+        &[0x05, 0xff, 0xff, 0xff, 0xff, 0x00][..],
+    ]
+    .iter()
+    {
+        assert!(height(data).is_err(), "{:?} is not a valid height", data);
+    }
+}
+
+#[test]
+fn malformed_bip34_height_deserializes_without_height() {
+    zebra_test::init();
+
+    // Replace the push length of the BIP34 height in block 415,000's coinbase
+    let mut block_bytes = zebra_test::vectors::BLOCK_MAINNET_415000_BYTES.to_vec();
+    let height_offset = block_bytes
+        .windows(4)
+        .position(|window| window == [0x03, 0x18, 0x55, 0x06])
+        .expect("block contains its coinbase height");
+    block_bytes[height_offset] = 0x00;
+
+    // BIP34 is enforced by the block verifier, not the parser
+    let block = block_bytes
+        .bitcoin_deserialize_into::<Block>()
+        .expect("block is structurally valid");
+    assert!(block.header.version >= 2);
+    assert_eq!(block.coinbase_height(), None);
+}
 
 #[test]
 fn block_limits_multi_tx() {
@@ -187,8 +403,8 @@ fn block_limits_multi_tx() {
 
     assert!(data.len() > MAX_BLOCK_BYTES as usize);
 
-    // // Deserialize will now fail
-    // Block::bitcoin_deserialize(&data[..]).expect_err("block should not deserialize");
+    // Deserialize will now fail
+    Block::bitcoin_deserialize(&data[..]).expect_err("block should not deserialize");
 }
 
 #[test]
@@ -226,6 +442,144 @@ fn block_limits_single_tx() {
     Block::bitcoin_deserialize(&data[..]).expect_err("block should not deserialize");
 }
 
+#[test]
+fn block_limits_exclude_witness_data() {
+    zebra_test::init();
+
+    // A block with a single witness transaction, whose witness is `witness_len`
+    // bytes long
+    let witness_block = |witness_len: usize| {
+        let input = transparent::Input::PrevOut {
+            outpoint: transparent::OutPoint {
+                hash: crate::transaction::Hash([1; 32]),
+                index: 0,
+            },
+            unlock_script: transparent::Script(Vec::new()),
+            sequence: 0,
+        };
+        let output = transparent::Output {
+            value: 0i64.try_into().unwrap(),
+            lock_script: transparent::Script(Vec::new()),
+        };
+        let mut tx = Transaction::new(1, vec![input], vec![output], LockTime::Height(Height(0)));
+        *tx.witnesses_mut() = vec![vec![vec![0; witness_len]]];
+
+        Block {
+            header: generate::block_header(),
+            transactions: vec![Arc::new(tx)],
+        }
+    };
+
+    // Segwit blocks can be larger than MAX_BLOCK_BYTES, as long as their
+    // stripped size is within it
+    let block = witness_block(2 * MAX_BLOCK_BYTES as usize);
+    let data = block.bitcoin_serialize_to_vec().unwrap();
+    assert!(data.len() > MAX_BLOCK_BYTES as usize);
+    let block2 = Block::bitcoin_deserialize(&data[..])
+        .expect("segwit block should deserialize as its stripped size is below the limit");
+    assert_eq!(block, block2);
+
+    // But the whole block is still limited
+    let block = witness_block(MAX_BLOCK_SERIALIZED_SIZE as usize);
+    let data = block.bitcoin_serialize_to_vec().unwrap();
+    assert!(data.len() > MAX_BLOCK_SERIALIZED_SIZE as usize);
+    Block::bitcoin_deserialize(&data[..]).expect_err("block should not deserialize");
+}
+
+#[test]
+fn generated_blocks_straddle_limit() {
+    zebra_test::init();
+
+    let limit = MAX_BLOCK_BYTES as usize;
+
+    for block in &[
+        generate::large_multi_transaction_block(),
+        generate::oversized_multi_transaction_block(),
+        generate::large_single_transaction_block(),
+        generate::oversized_single_transaction_block(),
+    ] {
+        let data = block.bitcoin_serialize_to_vec().unwrap();
+        assert_eq!(block.serialized_size(), data.len());
+        for tx in block.transactions.iter() {
+            assert_eq!(
+                tx.serialized_size_with_header(),
+                tx.bitcoin_serialize_to_vec().unwrap().len()
+            );
+        }
+    }
+
+    // Each oversized block has exactly one more item than its large
+    // counterpart, so the two blocks are on either side of the limit.
+    let large = generate::large_multi_transaction_block();
+    let oversized = generate::oversized_multi_transaction_block();
+    assert!(large.serialized_size() <= limit);
+    assert!(oversized.serialized_size() > limit);
+    assert_eq!(oversized.transactions.len(), large.transactions.len() + 1);
+
+    let large = generate::large_single_transaction_block();
+    let oversized = generate::oversized_single_transaction_block();
+    assert!(large.serialized_size() <= limit);
+    assert!(oversized.serialized_size() > limit);
+    assert_eq!(
+        oversized.transactions[0].outputs.len(),
+        large.transactions[0].outputs.len() + 1
+    );
+}
+
+#[test]
+fn min_transaction_size() {
+    zebra_test::init();
+
+    let input = transparent::Input::PrevOut {
+        outpoint: transparent::OutPoint {
+            hash: crate::transaction::Hash([0; 32]),
+            index: 0,
+        },
+        unlock_script: transparent::Script(Vec::new()),
+        sequence: 0,
+    };
+    let output = transparent::Output {
+        value: 0i64.try_into().unwrap(),
+        lock_script: transparent::Script(Vec::new()),
+    };
+    let tx = Transaction::new(1, vec![input], vec![output], LockTime::Height(Height(0)));
+
+    let mut data = Vec::new();
+    tx.bitcoin_serialize(&mut data)
+        .expect("transaction should serialize");
+    assert_eq!(data.len() as u64, MIN_TRANSACTION_SIZE);
+}
+
+#[test]
+fn block_limits_transaction_count() {
+    zebra_test::init();
+
+    let header = generate::block_header();
+    let max_tx_count = (MAX_BLOCK_BYTES / MIN_TRANSACTION_SIZE) as usize;
+
+    let block_bytes = |tx_count: usize| {
+        let mut data = Vec::new();
+        header
+            .bitcoin_serialize(&mut data)
+            .expect("header should serialize");
+        CompactInt::from(tx_count)
+            .bitcoin_serialize(&mut data)
+            .expect("tx count should serialize");
+        data
+    };
+
+    // At the limit, the count is accepted, and parsing fails on the missing transactions
+    let result = Block::bitcoin_deserialize(&block_bytes(max_tx_count)[..]);
+    assert!(matches!(
+        result.as_ref().map_err(SerializationError::root_cause),
+        Err(SerializationError::Io(_))
+    ));
+
+    // Above the limit, the count itself is rejected
+    let result = Block::bitcoin_deserialize(&block_bytes(max_tx_count + 1)[..]);
+    assert!(matches!(result, Err(SerializationError::Parse(_))));
+}
+
 /// Test wrapper for `BlockHeader.time_is_valid_at`.
 ///
 /// Generates a block header, sets its `time` to `block_header_time`, then
@@ -1,8 +1,16 @@
 use crate::{BitcoinDeserialize, BitcoinSerialize, SerializationError};
 // use crate::{Deserializable, DeserializationError, Serializable};
 use byteorder::{LittleEndian, WriteBytesExt};
+use std::convert::TryFrom;
 // use bytes::Buf;
 
+/// The maximum length or count that a `CompactInt` may declare when it
+/// prefixes a variable-length structure.
+///
+/// This matches the `MAX_SIZE` limit in Bitcoin Core. Every valid structure is
+/// much smaller, because messages are limited to a few megabytes.
+pub const MAX_COMPACT_LEN: u64 = 0x0200_0000;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CompactInt(u64);
 
@@ -19,12 +27,27 @@ impl CompactInt {
         self.0
     }
 
+    /// Returns this value as a length, if it is no greater than `max`.
+    ///
+    /// Decoders should check declared lengths with this method before
+    /// allocating or looping, so a huge declared length fails cleanly.
+    pub fn checked_len(&self, max: u64) -> Result<usize, SerializationError> {
+        if self.0 > max {
+            return Err(SerializationError::Parse(
+                "declared length exceeds the maximum length",
+            ));
+        }
+        usize::try_from(self.0)
+            .map_err(|_| SerializationError::Parse("declared length does not fit in usize"))
+    }
+
+    /// Returns the serialized length (in bytes) of `value` as a `CompactInt`.
     pub fn size(value: usize) -> usize {
         if value < 253 {
             1
-        } else if value < std::u16::MAX as usize {
-            2
-        } else if value < std::u32::MAX as usize {
+        } else if value <= std::u16::MAX as usize {
+            3
+        } else if value <= std::u32::MAX as usize {
             5
         } else {
             9
@@ -72,3 +95,47 @@ impl BitcoinDeserialize for CompactInt {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_len_enforces_maximum() {
+        zebra_test::init();
+
+        assert_eq!(CompactInt::from(1000).checked_len(1000), Ok(1000));
+        assert_eq!(
+            CompactInt::from(1001).checked_len(1000),
+            Err(SerializationError::Parse(
+                "declared length exceeds the maximum length"
+            ))
+        );
+        assert!(CompactInt(u64::MAX).checked_len(MAX_COMPACT_LEN).is_err());
+    }
+
+    #[test]
+    fn oversized_vec_length_errors() {
+        zebra_test::init();
+
+        // A `u64::MAX` length prefix, with no items following
+        let bytes = [0xff; 9];
+        assert_eq!(
+            Vec::<u8>::bitcoin_deserialize(&bytes[..]),
+            Err(SerializationError::Parse(
+                "declared length exceeds the maximum length"
+            ))
+        );
+        assert!(String::bitcoin_deserialize(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn size_matches_serialized_length() {
+        zebra_test::init();
+
+        for &value in &[0, 252, 253, 0xffff, 0x1_0000, 0xffff_ffff, 0x1_0000_0000] {
+            let bytes = CompactInt::from(value).bitcoin_serialize_to_vec().unwrap();
+            assert_eq!(CompactInt::size(value), bytes.len(), "value {:#x}", value);
+        }
+    }
+}
@@ -11,6 +11,13 @@
 //!
 //! Typically, consensus parameters are accessed via a function that takes a
 //! `Network` and `block::Height`.
+//!
+//! Bitcoin doesn't bind transactions to network upgrades, so unlike Zcash,
+//! there are no consensus branch ids:
+//!
+//! ```compile_fail
+//! use zebra_chain::parameters::ConsensusBranchId;
+//! ```
 
 mod genesis;
 mod network;
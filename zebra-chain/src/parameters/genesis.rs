@@ -1,6 +1,13 @@
 //! Genesis consensus parameters for each Zcash network.
 
-use crate::{block, parameters::Network};
+use std::sync::Arc;
+
+use bytes::BytesMut;
+
+use crate::{
+    block::{self, Block},
+    parameters::Network,
+};
 
 /// The previous block hash for the genesis block.
 ///
@@ -19,3 +26,45 @@ pub fn genesis_hash(network: Network) -> block::Hash {
     .parse()
     .expect("hard-coded hash parses")
 }
+
+/// Returns the genesis block for `network`.
+pub fn genesis_block(network: Network) -> Arc<Block> {
+    let block_hex = match network {
+        Network::Mainnet => MAINNET_GENESIS_BLOCK,
+        Network::Testnet => TESTNET_GENESIS_BLOCK,
+    };
+    let block_bytes = hex::decode(block_hex).expect("hard-coded block is valid hex");
+
+    Arc::new(
+        Block::deserialize_from_buf(&mut BytesMut::from(&block_bytes[..]))
+            .expect("hard-coded block parses"),
+    )
+}
+
+/// The serialized mainnet genesis block.
+///
+/// `bitcoin-cli getblock $(bitcoin-cli getblockhash 0) 0`
+const MAINNET_GENESIS_BLOCK: &str = concat!(
+    "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd",
+    "7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c",
+    "0101000000010000000000000000000000000000000000000000000000000000000000000000ffff",
+    "ffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c",
+    "6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73",
+    "ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a6",
+    "7962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5f",
+    "ac00000000",
+);
+
+/// The serialized testnet genesis block.
+///
+/// It only differs from the mainnet genesis block in its header time and nonce.
+const TESTNET_GENESIS_BLOCK: &str = concat!(
+    "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd",
+    "7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4adae5494dffff001d1aa4ae18",
+    "0101000000010000000000000000000000000000000000000000000000000000000000000000ffff",
+    "ffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c",
+    "6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73",
+    "ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a6",
+    "7962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5f",
+    "ac00000000",
+);
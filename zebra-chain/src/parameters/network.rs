@@ -1,9 +1,16 @@
 use std::fmt;
 
+use primitive_types::U256;
+
+use crate::work::difficulty::{CompactDifficulty, ExpandedDifficulty};
+
 #[cfg(any(test, feature = "proptest-impl"))]
 use proptest_derive::Arbitrary;
 
 /// An enum describing the possible network choices.
+///
+/// The derived `Arbitrary` impl generates every variant, so address and
+/// parameter proptests automatically cover any networks added here.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 #[cfg_attr(any(test, feature = "proptest-impl"), derive(Arbitrary))]
 pub enum Network {
@@ -30,6 +37,59 @@ impl Network {
             Network::Testnet => 18333,
         }
     }
+
+    /// Returns the magic bytes that start every p2p message on this network.
+    pub const fn magic(self) -> [u8; 4] {
+        match self {
+            Network::Mainnet => [0xf9, 0xbe, 0xb4, 0xd9],
+            Network::Testnet => [0x0b, 0x11, 0x09, 0x07],
+        }
+    }
+
+    /// Returns the network that uses `magic`, or `None` if the magic is
+    /// unknown.
+    pub fn from_magic(magic: [u8; 4]) -> Option<Network> {
+        [Network::Mainnet, Network::Testnet]
+            .iter()
+            .copied()
+            .find(|network| network.magic() == magic)
+    }
+
+    /// Returns the easiest target difficulty allowed on this network, called
+    /// `PoWLimit` in Bitcoin Core.
+    ///
+    /// Bitcoin's proof of work is the double SHA-256 hash of the block header,
+    /// so there is no Equihash solution to check. A block is valid if its
+    /// header hash is less than or equal to the target in its
+    /// `difficulty_threshold`, and that target is no easier than this limit.
+    pub fn pow_limit(self) -> ExpandedDifficulty {
+        // Bitcoin Core converts the PoWLimit into a compact representation
+        // before using it in difficulty checks, which drops some low bits.
+        self.pow_limit_compact()
+            .to_expanded()
+            .expect("difficulty limits are valid expanded values")
+    }
+
+    /// Returns [`Network::pow_limit`] in compact form, which is the
+    /// `difficulty_threshold` of the easiest allowed block.
+    pub fn pow_limit_compact(self) -> CompactDifficulty {
+        let limit: U256 = match self {
+            /* 2^224 - 1 */
+            Network::Mainnet => U256::from_str_radix(
+                "00000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                16,
+            )
+            .expect("Mainnet difficulty is valid hex number"),
+            /* 2^224 - 1 */
+            Network::Testnet => U256::from_str_radix(
+                "00000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                16,
+            )
+            .expect("Testnet difficulty is valid hex number"),
+        };
+
+        ExpandedDifficulty::from(limit).to_compact()
+    }
 }
 
 impl Default for Network {
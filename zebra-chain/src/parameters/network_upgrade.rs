@@ -5,10 +5,10 @@ use NetworkUpgrade::*;
 use crate::block;
 use crate::parameters::{Network, Network::*};
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
 use std::ops::Bound::*;
 
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, TimeZone, Utc};
 
 /// A Zcash network upgrade.
 ///
@@ -48,6 +48,12 @@ pub enum NetworkUpgrade {
     /// [`BIP 144`](https://github.com/bitcoin/bips/blob/master/bip-0144.mediawiki) and
     /// [`BIP 145`](https://github.com/bitcoin/bips/blob/master/bip-0145.mediawiki)
     SegWit,
+    /// The Taproot Network Upgrade adds Schnorr signatures and version 1 witness
+    /// programs, which commit to a tweaked public key and an optional script tree.
+    /// Taproot includes [`BIP 340`](https://github.com/bitcoin/bips/blob/master/bip-0340.mediawiki),
+    /// [`BIP 341`](https://github.com/bitcoin/bips/blob/master/bip-0341.mediawiki) and
+    /// [`BIP 342`](https://github.com/bitcoin/bips/blob/master/bip-0342.mediawiki)
+    Taproot,
 }
 
 /// Mainnet network upgrade activation heights.
@@ -61,6 +67,7 @@ pub(crate) const MAINNET_ACTIVATION_HEIGHTS: &[(block::Height, NetworkUpgrade)]
     (block::Height(388381), BIP65), // 000000000000000004c2b624ed5d7756c508d90fd0da2c7c679febfa6c4735f0
     (block::Height(419328), CSV), // 000000000000000004a1b34462cb8aeebd5799177f7a29cf28f2d1961716b5b5
     (block::Height(481824), SegWit), // 0000000000000000001c8018d9cb3b742ef25114f27563e3fc4a1902167f9893
+    (block::Height(709632), Taproot), // 0000000000000000000687bca986194dc2c1f949318629b44bb54ec0a94d8597
 ];
 
 /// Testnet network upgrade activation heights.
@@ -74,43 +81,43 @@ pub(crate) const TESTNET_ACTIVATION_HEIGHTS: &[(block::Height, NetworkUpgrade)]
     (block::Height(581885), BIP65), // 00000000007f6655f22f98e72ed80d8b06dc761d5da09df0fa1dc4be4f861eb6
     (block::Height(770112), CSV), // 00000000025e930139bac5c6c31a403776da130831ab85be56578f3fa75369bb
     (block::Height(834624), SegWit), // 00000000002b980fcd729daaa248fd9316a5200e9b367f4ff2c42453e84201ca
+    (block::Height(2011968), Taproot),
 ];
 
-/// The Consensus Branch Id, used to bind transactions and blocks to a
-/// particular network upgrade.
+/// The [BIP 9] version bits deployment parameters for a soft fork.
+///
+/// Miners signal readiness for the soft fork by setting `bit` in their block
+/// versions, between `start_time` and `timeout`.
+///
+/// [BIP 9]: https://github.com/bitcoin/bips/blob/master/bip-0009.mediawiki
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
-pub struct ConsensusBranchId(u32);
+pub struct VersionBitsDeployment {
+    /// The block version bit used to signal for the soft fork.
+    pub bit: u8,
+    /// The median time past after which signalling starts.
+    pub start_time: DateTime<Utc>,
+    /// The median time past after which the deployment fails, if it hasn't
+    /// locked in.
+    pub timeout: DateTime<Utc>,
+}
 
-impl From<ConsensusBranchId> for u32 {
-    fn from(branch: ConsensusBranchId) -> u32 {
-        branch.0
+impl VersionBitsDeployment {
+    /// Returns the deployment parameters for `bit`, with `start_time` and
+    /// `timeout` as Unix timestamps.
+    fn new(bit: u8, start_time: i64, timeout: i64) -> VersionBitsDeployment {
+        VersionBitsDeployment {
+            bit,
+            start_time: Utc.timestamp(start_time, 0),
+            timeout: Utc.timestamp(timeout, 0),
+        }
     }
-}
 
-impl From<u32> for ConsensusBranchId {
-    fn from(branch: u32) -> ConsensusBranchId {
-        ConsensusBranchId(branch)
+    /// Returns the block version bit mask for this deployment.
+    pub fn mask(&self) -> u32 {
+        1 << self.bit
     }
 }
 
-// /// Network Upgrade Consensus Branch Ids.
-// ///
-// /// Branch ids are the same for mainnet and testnet. If there is a testnet
-// /// rollback after a bug, the branch id changes.
-// ///
-// /// Branch ids were introduced in the Overwinter upgrade, so there are no
-// /// Genesis or BeforeOverwinter branch ids.
-// ///
-// /// This is actually a bijective map, but it is const, so we use a vector, and
-// /// do the uniqueness check in the unit tests.
-// pub(crate) const CONSENSUS_BRANCH_IDS: &[(NetworkUpgrade, ConsensusBranchId)] = &[
-//     (Overwinter, ConsensusBranchId(0x5ba81b19)),
-//     (Sapling, ConsensusBranchId(0x76b809bb)),
-//     (Blossom, ConsensusBranchId(0x2bb40e60)),
-//     (Heartwood, ConsensusBranchId(0xf5b9230b)),
-//     (Canopy, ConsensusBranchId(0xe9ff75a6)),
-// ];
-
 /// The target block spacing
 const POW_TARGET_SPACING: i64 = 10 * 60;
 
@@ -139,10 +146,32 @@ impl NetworkUpgrade {
         .collect()
     }
 
-    pub fn branch_id(&self) -> u32 {
-        eprintln!("Error: called NetworkUpgrade::branch_id() Branch Ids were introduced in ZIP 200 and do not exist in Bitcoin.");
-        // FIXME
-        0
+    /// Returns the [BIP 9] version bits deployment parameters for this network
+    /// upgrade on `network`.
+    ///
+    /// Returns None for the genesis rules, and for the soft forks that were
+    /// deployed using block version numbers instead of version bits.
+    ///
+    /// [BIP 9]: https://github.com/bitcoin/bips/blob/master/bip-0009.mediawiki
+    pub fn version_bit_deployment(&self, network: Network) -> Option<VersionBitsDeployment> {
+        let deployment = match (self, network) {
+            (Genesis, _) | (BIP34, _) | (BIP66, _) | (BIP65, _) => return None,
+            (CSV, Mainnet) => VersionBitsDeployment::new(0, 1_462_060_800, 1_493_596_800),
+            (CSV, Testnet) => VersionBitsDeployment::new(0, 1_456_790_400, 1_493_596_800),
+            (SegWit, Mainnet) => VersionBitsDeployment::new(1, 1_479_168_000, 1_510_704_000),
+            (SegWit, Testnet) => VersionBitsDeployment::new(1, 1_462_060_800, 1_493_596_800),
+            (Taproot, _) => VersionBitsDeployment::new(2, 1_619_222_400, 1_628_640_000),
+        };
+        Some(deployment)
+    }
+
+    /// Returns true if this network upgrade is active on `network` at `height`.
+    ///
+    /// Later network upgrades don't deactivate earlier ones.
+    pub fn is_active_at(&self, network: Network, height: block::Height) -> bool {
+        NetworkUpgrade::activation_list(network)
+            .range(..=height)
+            .any(|(_, nu)| nu == self)
     }
 
     /// Returns the current network upgrade for `network` and `height`.
@@ -179,7 +208,9 @@ impl NetworkUpgrade {
     /// Returns the target block spacing for the network upgrade.
     pub fn target_spacing(&self) -> Duration {
         match self {
-            Genesis | BIP34 | BIP66 | BIP65 | CSV | SegWit => Duration::seconds(POW_TARGET_SPACING),
+            Genesis | BIP34 | BIP66 | BIP65 | CSV | SegWit | Taproot => {
+                Duration::seconds(POW_TARGET_SPACING)
+            }
         }
     }
 
@@ -268,12 +299,3 @@ impl NetworkUpgrade {
         }
     }
 }
-
-// impl ConsensusBranchId {
-//     /// Returns the current consensus branch id for `network` and `height`.
-//     ///
-//     /// Returns None if the network has no branch id at this height.
-//     pub fn current(network: Network, height: block::Height) -> Option<ConsensusBranchId> {
-//         NetworkUpgrade::current(network, height).branch_id()
-//     }
-// }
@@ -2,7 +2,7 @@
 
 use std::collections::HashSet;
 
-use crate::block;
+use crate::{block, work::difficulty::CompactDifficulty};
 
 use super::*;
 
@@ -29,6 +29,38 @@ fn activation_bijective() {
     assert_eq!(TESTNET_ACTIVATION_HEIGHTS.len(), testnet_nus.len());
 }
 
+/// Check that each network's magic bytes round-trip.
+#[test]
+fn network_magic() {
+    zebra_test::init();
+
+    assert_eq!(Mainnet.magic(), [0xf9, 0xbe, 0xb4, 0xd9]);
+    assert_eq!(Testnet.magic(), [0x0b, 0x11, 0x09, 0x07]);
+
+    for &network in &[Mainnet, Testnet] {
+        assert_eq!(Network::from_magic(network.magic()), Some(network));
+    }
+
+    // The regtest and signet magics aren't supported
+    assert_eq!(Network::from_magic([0xfa, 0xbf, 0xb5, 0xda]), None);
+    assert_eq!(Network::from_magic([0x0a, 0x03, 0xcf, 0x40]), None);
+    assert_eq!(Network::from_magic([0; 4]), None);
+}
+
+/// Check the proof of work limits against Bitcoin Core's `powLimit` bits.
+#[test]
+fn pow_limits() {
+    zebra_test::init();
+
+    for &network in &[Mainnet, Testnet] {
+        assert_eq!(network.pow_limit_compact(), CompactDifficulty(0x1d00ffff));
+        assert_eq!(
+            Some(network.pow_limit()),
+            network.pow_limit_compact().to_expanded()
+        );
+    }
+}
+
 #[test]
 fn activation_extremes_mainnet() {
     zebra_test::init();
@@ -112,83 +144,99 @@ fn activation_consistent(network: Network) {
     }
 }
 
-// /// Check that the network upgrades and branch ids are unique.
-// #[test]
-// fn branch_id_bijective() {
-//     zebra_test::init();
-
-//     let branch_id_list = NetworkUpgrade::branch_id_list();
-//     let nus: HashSet<&NetworkUpgrade> = branch_id_list.keys().collect();
-//     assert_eq!(CONSENSUS_BRANCH_IDS.len(), nus.len());
-
-//     let branch_ids: HashSet<&ConsensusBranchId> = branch_id_list.values().collect();
-//     assert_eq!(CONSENSUS_BRANCH_IDS.len(), branch_ids.len());
-// }
-
-// #[test]
-// fn branch_id_extremes_mainnet() {
-//     zebra_test::init();
-//     branch_id_extremes(Mainnet)
-// }
-
-// #[test]
-// fn branch_id_extremes_testnet() {
-//     zebra_test::init();
-//     branch_id_extremes(Testnet)
-// }
-
-// /// Test the branch_id_list, branch_id, and current functions for `network` with
-// /// extreme values.
-// fn branch_id_extremes(network: Network) {
-//     // Branch ids were introduced in Overwinter
-//     assert_eq!(
-//         NetworkUpgrade::branch_id_list().get(&BeforeOverwinter),
-//         None
-//     );
-//     assert_eq!(ConsensusBranchId::current(network, block::Height(0)), None);
-//     assert_eq!(
-//         NetworkUpgrade::branch_id_list().get(&Overwinter).cloned(),
-//         Overwinter.branch_id()
-//     );
-
-//     // We assume that the last upgrade we know about continues forever
-//     // (even if we suspect that won't be true)
-//     assert_ne!(
-//         NetworkUpgrade::branch_id_list().get(&NetworkUpgrade::current(network, block::Height::MAX)),
-//         None
-//     );
-//     assert_ne!(
-//         ConsensusBranchId::current(network, block::Height::MAX),
-//         None
-//     );
-// }
-
-// #[test]
-// fn branch_id_consistent_mainnet() {
-//     zebra_test::init();
-//     branch_id_consistent(Mainnet)
-// }
-
-// #[test]
-// fn branch_id_consistent_testnet() {
-//     zebra_test::init();
-//     branch_id_consistent(Testnet)
-// }
-
-// /// Check that the branch_id and current functions are consistent for `network`.
-// fn branch_id_consistent(network: Network) {
-//     let branch_id_list = NetworkUpgrade::branch_id_list();
-//     let network_upgrades: HashSet<&NetworkUpgrade> = branch_id_list.keys().collect();
-
-//     for &network_upgrade in network_upgrades {
-//         let height = network_upgrade.activation_height(network);
-
-//         // Skip network upgrades that don't have activation heights yet
-//         if let Some(height) = height {
-//             assert_eq!(
-//                 ConsensusBranchId::current(network, height),
-//                 network_upgrade.branch_id()
-//             );
-//         }
-//     }
-// }
+#[test]
+fn taproot_activation() {
+    zebra_test::init();
+
+    assert_eq!(
+        NetworkUpgrade::current(Mainnet, block::Height(709632)),
+        Taproot
+    );
+    assert_eq!(
+        NetworkUpgrade::current(Mainnet, block::Height(709631)),
+        SegWit
+    );
+    assert_eq!(
+        NetworkUpgrade::next(Mainnet, block::Height(709631)),
+        Some(Taproot)
+    );
+    assert_eq!(
+        NetworkUpgrade::current(Testnet, block::Height(2011968)),
+        Taproot
+    );
+}
+
+#[test]
+fn csv_and_segwit_activation() {
+    zebra_test::init();
+
+    for &(network, csv_height, segwit_height) in
+        &[(Mainnet, 419_328, 481_824), (Testnet, 770_112, 834_624)]
+    {
+        assert!(!CSV.is_active_at(network, block::Height(csv_height - 1)));
+        assert!(CSV.is_active_at(network, block::Height(csv_height)));
+        assert!(!SegWit.is_active_at(network, block::Height(segwit_height - 1)));
+        assert!(SegWit.is_active_at(network, block::Height(segwit_height)));
+
+        // Earlier upgrades stay active
+        assert!(CSV.is_active_at(network, block::Height(segwit_height)));
+        assert!(BIP34.is_active_at(network, block::Height::MAX));
+        assert!(Genesis.is_active_at(network, block::Height(0)));
+    }
+}
+
+#[test]
+fn version_bit_deployments() {
+    zebra_test::init();
+
+    for &network in &[Mainnet, Testnet] {
+        for &nu in &[Genesis, BIP34, BIP66, BIP65] {
+            assert_eq!(nu.version_bit_deployment(network), None);
+        }
+
+        let csv = CSV.version_bit_deployment(network).expect("CSV uses BIP 9");
+        let segwit = SegWit
+            .version_bit_deployment(network)
+            .expect("SegWit uses BIP 9");
+        let taproot = Taproot
+            .version_bit_deployment(network)
+            .expect("Taproot uses BIP 9");
+        assert_eq!((csv.bit, segwit.bit, taproot.bit), (0, 1, 2));
+        assert_eq!(segwit.mask(), 0x0000_0002);
+
+        for deployment in &[csv, segwit, taproot] {
+            assert!(deployment.start_time < deployment.timeout);
+        }
+    }
+
+    assert_eq!(
+        SegWit
+            .version_bit_deployment(Mainnet)
+            .expect("SegWit uses BIP 9")
+            .start_time
+            .timestamp(),
+        1_479_168_000
+    );
+}
+
+/// Check that the hard-coded genesis blocks match the genesis hashes.
+#[test]
+fn genesis_blocks() {
+    zebra_test::init();
+
+    for &network in &[Mainnet, Testnet] {
+        let block = genesis_block(network);
+        assert_eq!(block.hash(), genesis_hash(network));
+        assert_eq!(
+            block.header.previous_block_hash,
+            GENESIS_PREVIOUS_BLOCK_HASH
+        );
+        assert_eq!(block.transactions.len(), 1);
+    }
+
+    // Only the header time and nonce differ
+    assert_eq!(
+        genesis_block(Mainnet).transactions,
+        genesis_block(Testnet).transactions
+    );
+}
@@ -14,12 +14,14 @@ mod error;
 // mod zcash_debitcoin_serialize;
 // mod zcash_bitcoin_serialize;
 
-// pub(crate) mod serde_helpers;
+pub(crate) mod serde_helpers;
 
 pub mod sha256d;
 
 use chrono::{DateTime, Utc};
-pub use deserialize::{BitcoinDeserialize, BitcoinDeserializeInto};
+pub use deserialize::{
+    bitcoin_deserialize_bounded_vec, BitcoinDeserialize, BitcoinDeserializeInto, MinSerializedSize,
+};
 pub use error::SerializationError;
 // pub use read_zcash::ReadZcashExt;
 // pub use write_zcash::WriteZcashExt;
@@ -31,6 +33,7 @@ pub use error::SerializationError;
 
 use crate::compactint::CompactInt;
 use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+use bytes::{BufMut, BytesMut};
 use std::net::IpAddr;
 
 /// A 32-bit unix timestamp.
@@ -44,11 +47,42 @@ pub trait BitcoinSerialize {
     // fn bitcoin_serialize(&self, target: &mut Vec<u8>);
     fn bitcoin_serialize<W: std::io::Write>(&self, target: W) -> Result<(), std::io::Error>;
 
+    /// Serialize each item in `items`, in order, without a length prefix.
+    ///
+    /// Types with a cheaper bulk encoding can override this. `Vec<T>` uses it
+    /// for its items, which lets `Vec<u8>` write all its bytes at once
+    /// without needing a specialized impl.
+    fn bitcoin_serialize_slice<W: std::io::Write>(
+        items: &[Self],
+        mut target: W,
+    ) -> Result<(), std::io::Error>
+    where
+        Self: Sized,
+    {
+        for item in items {
+            item.bitcoin_serialize(&mut target)?;
+        }
+        Ok(())
+    }
+
     fn bitcoin_serialize_to_vec(&self) -> Result<Vec<u8>, std::io::Error> {
         let mut data = Vec::new();
         self.bitcoin_serialize(&mut data)?;
         Ok(data)
     }
+
+    /// Serialize `self` onto the end of `target`, so callers can reuse one
+    /// buffer for many items.
+    ///
+    /// If serialization fails, `target` is left unchanged.
+    fn bitcoin_serialize_to_bytes_mut(&self, target: &mut BytesMut) -> Result<(), std::io::Error> {
+        let start_len = target.len();
+        let result = self.bitcoin_serialize((&mut *target).writer());
+        if result.is_err() {
+            target.truncate(start_len);
+        }
+        result
+    }
 }
 impl BitcoinSerialize for bool {
     fn bitcoin_serialize<W>(&self, mut target: W) -> Result<(), std::io::Error>
@@ -140,15 +174,21 @@ impl BitcoinSerialize for BigUnixTime {
     }
 }
 
-// TODO: Uncomment when specialization stabilizes
-// impl BitcoinSerialize for u8 {
-//     fn bitcoin_serialize<W>(&self, mut target: W) -> Result<(), std::io::Error>
-//     where
-//         W: std::io::Write,
-//     {
-//         target.write_all(&[*self])
-//     }
-// }
+impl BitcoinSerialize for u8 {
+    fn bitcoin_serialize<W>(&self, mut target: W) -> Result<(), std::io::Error>
+    where
+        W: std::io::Write,
+    {
+        target.write_all(&[*self])
+    }
+
+    fn bitcoin_serialize_slice<W>(items: &[Self], mut target: W) -> Result<(), std::io::Error>
+    where
+        W: std::io::Write,
+    {
+        target.write_all(items)
+    }
+}
 
 impl BitcoinSerialize for std::net::Ipv6Addr {
     fn bitcoin_serialize<W>(&self, mut target: W) -> Result<(), std::io::Error>
@@ -227,17 +267,6 @@ impl BitcoinSerialize for [u8; 32] {
     }
 }
 
-impl BitcoinSerialize for Vec<u8> {
-    fn bitcoin_serialize<W>(&self, mut target: W) -> Result<(), std::io::Error>
-    where
-        W: std::io::Write,
-    {
-        CompactInt::from(self.len()).bitcoin_serialize(&mut target)?;
-        target.write_all(self)?;
-        Ok(())
-    }
-}
-
 impl<T> BitcoinSerialize for Vec<T>
 where
     T: BitcoinSerialize,
@@ -247,10 +276,7 @@ where
         W: std::io::Write,
     {
         CompactInt::from(self.len()).bitcoin_serialize(&mut target)?;
-        for item in self.iter() {
-            item.bitcoin_serialize(&mut target)?
-        }
-        Ok(())
+        T::bitcoin_serialize_slice(self, &mut target)
     }
 }
 
@@ -290,3 +316,165 @@ impl<T: BitcoinSerialize> BitcoinSerialize for Option<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bitcoin_serde_derive::{BtcDeserialize, BtcSerialize};
+
+    use bytes::BytesMut;
+
+    use super::{
+        bitcoin_deserialize_bounded_vec, BitcoinDeserialize, BitcoinDeserializeInto,
+        BitcoinSerialize, CompactInt, SerializationError,
+    };
+    use crate::{block::Block, transaction::Transaction};
+
+    #[derive(Clone, Debug, PartialEq, Eq, BtcSerialize, BtcDeserialize)]
+    enum TwoVariants {
+        Tuple(u32, bool),
+        Named { magic: [u8; 4], value: i64 },
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, BtcSerialize, BtcDeserialize)]
+    enum WithUnit {
+        Empty,
+        Value(u16),
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, BtcSerialize, BtcDeserialize)]
+    struct WithByte {
+        flags: u8,
+        count: u32,
+        data: Vec<u8>,
+    }
+
+    #[test]
+    fn derived_struct_with_u8_round_trip() {
+        zebra_test::init();
+
+        let value = WithByte {
+            flags: 0xab,
+            count: 2,
+            data: vec![1, 2, 3],
+        };
+        let bytes = value.bitcoin_serialize_to_vec().unwrap();
+        assert_eq!(bytes, vec![0xab, 2, 0, 0, 0, 3, 1, 2, 3]);
+        assert_eq!(WithByte::bitcoin_deserialize(&bytes[..]).unwrap(), value);
+    }
+
+    #[test]
+    fn byte_vec_matches_item_by_item_encoding() {
+        zebra_test::init();
+
+        let data: Vec<u8> = (0..=255).collect();
+        let bulk = data.bitcoin_serialize_to_vec().unwrap();
+
+        let mut item_by_item = Vec::new();
+        CompactInt::from(data.len())
+            .bitcoin_serialize(&mut item_by_item)
+            .unwrap();
+        for byte in data.iter() {
+            byte.bitcoin_serialize(&mut item_by_item).unwrap();
+        }
+
+        assert_eq!(bulk, item_by_item);
+        assert_eq!(Vec::<u8>::bitcoin_deserialize(&bulk[..]).unwrap(), data);
+    }
+
+    #[test]
+    fn serialize_into_shared_bytes_mut() {
+        zebra_test::init();
+
+        let block = zebra_test::vectors::BLOCK_MAINNET_1_BYTES
+            .bitcoin_deserialize_into::<Block>()
+            .unwrap();
+        let transaction = block.transactions[0].clone();
+
+        let mut buffer = BytesMut::new();
+        block.bitcoin_serialize_to_bytes_mut(&mut buffer).unwrap();
+        let block_len = buffer.len();
+        transaction
+            .bitcoin_serialize_to_bytes_mut(&mut buffer)
+            .unwrap();
+        assert_eq!(
+            buffer.len(),
+            block_len + transaction.serialized_size_with_header()
+        );
+
+        let mut block_bytes = buffer.split_to(block_len);
+        assert_eq!(
+            &block_bytes[..],
+            &zebra_test::vectors::BLOCK_MAINNET_1_BYTES[..]
+        );
+        assert_eq!(
+            Block::deserialize_from_buf(&mut block_bytes).unwrap(),
+            block
+        );
+        assert_eq!(
+            Transaction::bitcoin_deserialize(&buffer[..]).unwrap(),
+            *transaction
+        );
+    }
+
+    #[test]
+    fn bounded_vec_rejects_oversized_count() {
+        zebra_test::init();
+
+        let data: Vec<u8> = vec![1, 2, 3];
+        let bytes = data.bitcoin_serialize_to_vec().unwrap();
+        assert_eq!(
+            bitcoin_deserialize_bounded_vec::<u8, _>(&bytes[..], bytes.len()),
+            Ok(data)
+        );
+
+        // A count of 2^24 items, which is below `MAX_COMPACT_LEN`, followed
+        // by a single item
+        let bytes = [0xfe, 0x00, 0x00, 0x00, 0x01, 0x01];
+        assert_eq!(
+            bitcoin_deserialize_bounded_vec::<u8, _>(&bytes[..], bytes.len()),
+            Err(SerializationError::Parse(
+                "declared item count is larger than the remaining data"
+            ))
+        );
+    }
+
+    #[test]
+    fn derived_enum_round_trip() {
+        zebra_test::init();
+
+        let tuple = TwoVariants::Tuple(0x0403_0201, true);
+        let bytes = tuple.bitcoin_serialize_to_vec().unwrap();
+        assert_eq!(bytes, vec![0, 1, 2, 3, 4, 1]);
+        assert_eq!(TwoVariants::bitcoin_deserialize(&bytes[..]).unwrap(), tuple);
+
+        let named = TwoVariants::Named {
+            magic: *b"abcd",
+            value: -1,
+        };
+        let bytes = named.bitcoin_serialize_to_vec().unwrap();
+        assert_eq!(bytes[0], 1);
+        assert_eq!(&bytes[1..5], b"abcd");
+        assert_eq!(&bytes[5..], &[0xff; 8]);
+        assert_eq!(TwoVariants::bitcoin_deserialize(&bytes[..]).unwrap(), named);
+
+        for value in &[WithUnit::Empty, WithUnit::Value(0xbeef)] {
+            let bytes = value.bitcoin_serialize_to_vec().unwrap();
+            assert_eq!(&WithUnit::bitcoin_deserialize(&bytes[..]).unwrap(), value);
+        }
+        assert_eq!(WithUnit::Empty.bitcoin_serialize_to_vec().unwrap(), vec![0]);
+    }
+
+    #[test]
+    fn derived_enum_rejects_unknown_discriminant() {
+        zebra_test::init();
+
+        assert_eq!(
+            TwoVariants::bitcoin_deserialize(&[2, 0, 0, 0, 0, 0][..]),
+            Err(SerializationError::Parse("invalid enum discriminant"))
+        );
+        assert!(matches!(
+            WithUnit::bitcoin_deserialize(&[][..]),
+            Err(SerializationError::Io(_))
+        ));
+    }
+}
@@ -1,7 +1,8 @@
 use super::{BigUnixTime, CompactInt, SmallUnixTime};
+use crate::compactint::MAX_COMPACT_LEN;
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
 use chrono::{TimeZone, Utc};
-use std::net::{Ipv6Addr, SocketAddr};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::{
     io,
     net::IpAddr::{V4, V6},
@@ -100,21 +101,60 @@ where
     T: BitcoinDeserialize,
 {
     fn bitcoin_deserialize<R: io::Read>(mut reader: R) -> Result<Vec<T>> {
-        let len = CompactInt::bitcoin_deserialize(&mut reader)?.value() as usize;
+        let len = CompactInt::bitcoin_deserialize(&mut reader)?.checked_len(MAX_COMPACT_LEN)?;
         // Limit preallocation to about 1000 items since blind preallocation is a DOS vulnerability
         // TODO: Replace with SafeAllocate when specialization stabilizes
         let blind_alloc_limit = 1024;
         let mut result: Vec<T> = Vec::with_capacity(std::cmp::min(len, blind_alloc_limit));
-        for _ in 0..len {
-            result.push(T::bitcoin_deserialize(&mut reader)?);
+        for index in 0..len {
+            result.push(T::bitcoin_deserialize(&mut reader).map_err(|e| e.at_index(index))?);
         }
         Ok(result)
     }
 }
 
+/// A type whose serialized values are never shorter than
+/// [`MinSerializedSize::MIN_SERIALIZED_SIZE`] bytes.
+///
+/// Lists of these types can be checked against the remaining data before
+/// deserializing any items, using [`bitcoin_deserialize_bounded_vec`].
+pub trait MinSerializedSize {
+    /// The minimum serialized size of a value, in bytes.
+    const MIN_SERIALIZED_SIZE: usize;
+}
+
+/// Deserialize a `Vec<T>` from `reader`, which has `remaining` bytes left,
+/// including the `CompactInt` count.
+///
+/// The `Vec<T>` impl reads items until the data runs out, so a huge declared
+/// count does work proportional to that count. This helper rejects any count
+/// that can't fit in the remaining bytes before reading the first item.
+pub fn bitcoin_deserialize_bounded_vec<T, R>(mut reader: R, remaining: usize) -> Result<Vec<T>>
+where
+    T: BitcoinDeserialize + MinSerializedSize,
+    R: io::Read,
+{
+    let len = CompactInt::bitcoin_deserialize(&mut reader)?.checked_len(MAX_COMPACT_LEN)?;
+    let max_len = remaining.saturating_sub(CompactInt::size(len)) / T::MIN_SERIALIZED_SIZE;
+    if len > max_len {
+        return Err(SerializationError::Parse(
+            "declared item count is larger than the remaining data",
+        ));
+    }
+    let mut result = Vec::with_capacity(len);
+    for index in 0..len {
+        result.push(T::bitcoin_deserialize(&mut reader).map_err(|e| e.at_index(index))?);
+    }
+    Ok(result)
+}
+
+impl MinSerializedSize for u8 {
+    const MIN_SERIALIZED_SIZE: usize = 1;
+}
+
 impl BitcoinDeserialize for String {
     fn bitcoin_deserialize<R: io::Read>(mut reader: R) -> Result<String> {
-        let len = CompactInt::bitcoin_deserialize(&mut reader)?.value() as usize;
+        let len = CompactInt::bitcoin_deserialize(&mut reader)?.checked_len(MAX_COMPACT_LEN)?;
         // Limit preallocation to about 1000 items since blind preallocation is a DOS vulnerability
         // TODO: Replace with SafeAllocate when specialization stabilizes
         eprint!("agent_len: {}", len);
@@ -131,9 +171,11 @@ impl BitcoinDeserialize for SocketAddr {
         let octets = <[u8; 16]>::bitcoin_deserialize(&mut reader)?;
         let v6_addr = Ipv6Addr::from(octets);
 
-        let addr = match v6_addr.to_ipv4() {
-            Some(v4_addr) => V4(v4_addr),
-            None => V6(v6_addr),
+        // IPv4 addresses are sent as IPv4-mapped IPv6 addresses. Other IPv6
+        // addresses, like `::1`, must not be converted to IPv4.
+        let addr = match octets {
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, a, b, c, d] => V4(Ipv4Addr::new(a, b, c, d)),
+            _ => V6(v6_addr),
         };
         Ok(SocketAddr::from((addr, reader.read_u16::<BigEndian>()?)))
     }
@@ -13,4 +13,152 @@ pub enum SerializationError {
     // XXX refine errors
     #[error("parse error: {0}")]
     Parse(&'static str),
+    /// A nested field failed to deserialize.
+    ///
+    /// The path names the field, relative to the outermost type being
+    /// deserialized, for example `transactions[2].outputs[0].lock_script`.
+    #[error("{path}: {source}")]
+    Context {
+        /// The path to the field that failed.
+        path: String,
+        /// The error from the field.
+        source: Box<SerializationError>,
+    },
+    /// A peer sent an inventory list with more entries than the protocol
+    /// allows.
+    ///
+    /// The whole message has been read, so the connection can reject it and
+    /// keep decoding later messages.
+    #[error("{command} message has {count} inventory entries, more than the protocol allows")]
+    OversizedInventory {
+        /// The command of the oversized message, for example `inv`.
+        command: &'static str,
+        /// The number of entries in the message.
+        count: usize,
+    },
+}
+
+impl SerializationError {
+    /// Record that this error happened while deserializing `field`.
+    ///
+    /// Deserializers call this as an error is returned through each level of
+    /// nesting, so the outermost field is added last.
+    pub fn context(self, field: &'static str) -> Self {
+        self.with_prefix(field.to_owned())
+    }
+
+    /// Record that this error happened while deserializing the item at
+    /// `index` in a list.
+    pub fn at_index(self, index: usize) -> Self {
+        self.with_prefix(format!("[{}]", index))
+    }
+
+    /// Returns the path to the field that failed, if any context was added.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            SerializationError::Context { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Returns the underlying error, without any context.
+    pub fn root_cause(&self) -> &SerializationError {
+        match self {
+            SerializationError::Context { source, .. } => source.root_cause(),
+            error => error,
+        }
+    }
+
+    fn with_prefix(self, prefix: String) -> Self {
+        match self {
+            SerializationError::Context { path, source } => {
+                let separator = if path.starts_with('[') { "" } else { "." };
+                SerializationError::Context {
+                    path: format!("{}{}{}", prefix, separator, path),
+                    source,
+                }
+            }
+            error => SerializationError::Context {
+                path: prefix,
+                source: Box::new(error),
+            },
+        }
+    }
+}
+
+/// Compares `Parse` errors by message, `Io` errors by [`io::ErrorKind`],
+/// `Context` errors by path and underlying error, and `OversizedInventory`
+/// errors by command and count.
+///
+/// `io::Error` doesn't implement `PartialEq`, so this lets tests assert the
+/// exact error returned by a deserializer.
+impl PartialEq for SerializationError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (SerializationError::Io(a), SerializationError::Io(b)) => a.kind() == b.kind(),
+            (SerializationError::Parse(a), SerializationError::Parse(b)) => a == b,
+            (
+                SerializationError::Context { path, source },
+                SerializationError::Context {
+                    path: other_path,
+                    source: other_source,
+                },
+            ) => path == other_path && source == other_source,
+            (
+                SerializationError::OversizedInventory { command, count },
+                SerializationError::OversizedInventory {
+                    command: other_command,
+                    count: other_count,
+                },
+            ) => command == other_command && count == other_count,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialization_error_eq() {
+        zebra_test::init();
+
+        assert_eq!(
+            SerializationError::Parse("bad data"),
+            SerializationError::Parse("bad data")
+        );
+        assert_ne!(
+            SerializationError::Parse("bad data"),
+            SerializationError::Parse("other data")
+        );
+
+        let eof = || SerializationError::from(io::Error::from(io::ErrorKind::UnexpectedEof));
+        assert_eq!(eof(), eof());
+        assert_ne!(
+            eof(),
+            SerializationError::from(io::Error::from(io::ErrorKind::InvalidData))
+        );
+        assert_ne!(eof(), SerializationError::Parse("bad data"));
+    }
+
+    #[test]
+    fn context_builds_a_field_path() {
+        zebra_test::init();
+
+        let error = SerializationError::Parse("bad data")
+            .context("lock_script")
+            .at_index(0)
+            .context("outputs")
+            .at_index(2)
+            .context("transactions");
+
+        assert_eq!(error.path(), Some("transactions[2].outputs[0].lock_script"));
+        assert_eq!(error.root_cause(), &SerializationError::Parse("bad data"));
+        assert_eq!(
+            error.to_string(),
+            "transactions[2].outputs[0].lock_script: parse error: bad data"
+        );
+        assert_eq!(SerializationError::Parse("bad data").path(), None);
+    }
 }
@@ -0,0 +1,42 @@
+//! Serde helpers shared by several types.
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// The binary serde representation of a 32-byte hash.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Hash")]
+struct RawHash([u8; 32]);
+
+/// Serialize a 32-byte hash stored in internal byte order.
+///
+/// Human-readable formats like JSON get the byte-reversed hex string shown by
+/// block explorers, and binary formats get the raw bytes.
+pub(crate) fn serialize_hash<S: Serializer>(
+    bytes: &[u8; 32],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        let mut reversed_bytes = *bytes;
+        reversed_bytes.reverse();
+        serializer.serialize_str(&hex::encode(reversed_bytes))
+    } else {
+        RawHash(*bytes).serialize(serializer)
+    }
+}
+
+/// Deserialize a 32-byte hash written by [`serialize_hash`], returning it in
+/// internal byte order.
+pub(crate) fn deserialize_hash<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<[u8; 32], D::Error> {
+    if deserializer.is_human_readable() {
+        let hex_string = String::deserialize(deserializer)?;
+        let mut bytes = [0; 32];
+        hex::decode_to_slice(&hex_string, &mut bytes[..])
+            .map_err(|_| de::Error::custom("hash must be 64 hex characters"))?;
+        bytes.reverse();
+        Ok(bytes)
+    } else {
+        RawHash::deserialize(deserializer).map(|raw| raw.0)
+    }
+}
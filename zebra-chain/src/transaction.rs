@@ -1,14 +1,16 @@
 //! Transactions and transaction-related structures.
 
-use crate::BitcoinSerialize;
-use crate::{cached::Cached, compactint::CompactInt};
-use bitcoin_serde_derive::BtcSerialize;
+use std::convert::TryInto;
+
+use crate::{block, cached::Cached, compactint::CompactInt, serialization::SmallUnixTime};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 mod hash;
 mod lock_time;
 mod memo;
 mod serialize;
+mod sighash;
 
 #[cfg(any(test, feature = "proptest-impl"))]
 mod arbitrary;
@@ -16,29 +18,70 @@ mod arbitrary;
 mod tests;
 
 pub use hash::Hash;
-pub use lock_time::LockTime;
+pub use lock_time::{LockTime, RelativeLockTime};
 pub use memo::Memo;
+pub use sighash::SIGHASH_ALL;
 
 use crate::transparent;
 
-/// A Bitcoin transaction. Note that this implementation doesn't yet support SegWit.
+/// If this bit is set in an input's sequence number, the sequence number is
+/// not a [BIP68] relative lock time.
+///
+/// [BIP68]: https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki
+pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+
+/// If this bit is set in a [BIP68] relative lock time, the lock is a time in
+/// units of 512 seconds. Otherwise, it is a number of blocks.
+///
+/// [BIP68]: https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki
+pub const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+
+/// The bits of a sequence number that hold a [BIP68] relative lock time.
+///
+/// [BIP68]: https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki
+pub const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000_ffff;
+
+/// Time-based relative lock times are in units of `1 << 9` (512) seconds.
+pub const SEQUENCE_LOCKTIME_GRANULARITY: u32 = 9;
+
+/// An input sequence number that disables the transaction's lock time, if
+/// every input uses it.
+pub const SEQUENCE_FINAL: u32 = u32::MAX;
+
+/// A Bitcoin transaction.
+///
+/// Transactions with witness data are serialized in the [BIP144] format, and
+/// transactions without witnesses use the legacy format.
 ///
 /// A transaction is an encoded data structure that facilitates the transfer of
 /// value between two public key addresses. Everything is
 /// designed to ensure that transactions can created, propagated on the network,
 /// validated, and finally added to the global ledger of transactions (the
 /// blockchain).
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, BtcSerialize)]
+///
+/// [BIP144]: https://github.com/bitcoin/bips/blob/master/bip-0144.mediawiki
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Transaction {
-    /// The transaction version. Versions greater than 1 mean that BIP 68 applies
-    pub version: i32,
-    /// The transaction Inputs
-    pub inputs: Vec<transparent::Input>,
-    /// The transaction Outputs
-    pub outputs: Vec<transparent::Output>,
-    /// The transaction LockTime
-    pub locktime: LockTime,
+    /// The transaction version, see [`Transaction::version`].
+    pub(crate) version: i32,
+    /// The transaction Inputs, see [`Transaction::inputs`].
+    pub(crate) inputs: Vec<transparent::Input>,
+    /// The transaction Outputs, see [`Transaction::outputs`].
+    pub(crate) outputs: Vec<transparent::Output>,
+    /// The witness stack for each input, see [`Transaction::witnesses`].
+    pub(crate) witnesses: Vec<Vec<Vec<u8>>>,
+    /// The transaction LockTime, see [`Transaction::locktime`].
+    pub(crate) locktime: LockTime,
     hash: Cached<Hash>,
+    wtxid: Cached<Hash>,
+    /// The serialized length without witness data, see [`Transaction::len`].
+    size: Cached<usize>,
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of times this thread has calculated a transaction length.
+    pub(crate) static LEN_CALCULATIONS: std::cell::Cell<usize> = std::cell::Cell::new(0);
 }
 
 impl Transaction {
@@ -52,8 +95,11 @@ impl Transaction {
             version,
             inputs,
             outputs,
+            witnesses: Vec::new(),
             locktime,
             hash: Cached::new(),
+            wtxid: Cached::new(),
+            size: Cached::new(),
         }
     }
     /// Get the hash of this transaction.
@@ -66,6 +112,97 @@ impl Transaction {
         Hash::from(self)
     }
 
+    /// Get the witness transaction id (wtxid) of this transaction.
+    ///
+    /// Unlike [`Transaction::hash`], this hashes the full serialization,
+    /// including the witness data. Transactions without witness data have the
+    /// same wtxid and txid. Coinbase transactions always have an all-zero
+    /// wtxid, as specified in [BIP141].
+    ///
+    /// [BIP141]: https://github.com/bitcoin/bips/blob/master/bip-0141.mediawiki
+    pub fn wtxid(&self) -> Hash {
+        if self.is_coinbase() {
+            return Hash([0; 32]);
+        }
+        if let Some(wtxid) = self.wtxid.value() {
+            return wtxid;
+        }
+        if !self.has_witness() {
+            return self.hash();
+        }
+        Hash::from_witness_serialization(self)
+    }
+
+    /// Returns the version of this transaction.
+    ///
+    /// Versions greater than 1 mean that BIP 68 applies.
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Sets the version of this transaction, and clears the cached values.
+    pub fn set_version(&mut self, version: i32) {
+        self.clear_cached_values();
+        self.version = version;
+    }
+
+    /// Returns the lock time of this transaction.
+    pub fn locktime(&self) -> LockTime {
+        self.locktime
+    }
+
+    /// Sets the lock time of this transaction, and clears the cached values.
+    pub fn set_locktime(&mut self, locktime: LockTime) {
+        self.clear_cached_values();
+        self.locktime = locktime;
+    }
+
+    /// Returns the inputs of this transaction.
+    pub fn inputs(&self) -> &[transparent::Input] {
+        &self.inputs
+    }
+
+    /// Returns the outputs of this transaction.
+    pub fn outputs(&self) -> &[transparent::Output] {
+        &self.outputs
+    }
+
+    /// Returns the inputs of this transaction, for modification.
+    ///
+    /// The inputs are part of the transaction's hashes and size, so this
+    /// clears the cached values.
+    pub fn inputs_mut(&mut self) -> &mut Vec<transparent::Input> {
+        self.clear_cached_values();
+        &mut self.inputs
+    }
+
+    /// Returns the outputs of this transaction, for modification.
+    ///
+    /// The outputs are part of the transaction's hashes and size, so this
+    /// clears the cached values.
+    pub fn outputs_mut(&mut self) -> &mut Vec<transparent::Output> {
+        self.clear_cached_values();
+        &mut self.outputs
+    }
+
+    /// Returns the witness stack for each input of this transaction, in input
+    /// order.
+    ///
+    /// Empty if the transaction has no witness data. Otherwise, there is one
+    /// (possibly empty) stack per input.
+    pub fn witnesses(&self) -> &[Vec<Vec<u8>>] {
+        &self.witnesses
+    }
+
+    /// Returns the witness stacks of this transaction, for modification.
+    ///
+    /// The witnesses are part of the transaction's wtxid, so this clears the
+    /// cached values.
+    pub fn witnesses_mut(&mut self) -> &mut Vec<Vec<Vec<u8>>> {
+        self.clear_cached_values();
+        &mut self.witnesses
+    }
+
     pub fn contains_coinbase_input(&self) -> bool {
         self.inputs
             .iter()
@@ -80,12 +217,160 @@ impl Transaction {
                 Some(transparent::Input::Coinbase { .. })
             )
     }
-    /// Returns the serialized length (in bytes) of a transaction.
+    /// Returns the witness reserved value of this coinbase transaction, if it
+    /// has one.
     ///
-    /// Note that this implementation is not BIPs 141/144 compliant since we haven't yet implemented SegWit
+    /// SegWit coinbase transactions have a single 32 byte item in their input's
+    /// witness stack, which is used in the block's witness commitment. Returns
+    /// `None` for non-coinbase transactions, and for coinbase transactions
+    /// without a well-formed witness.
+    pub fn coinbase_witness_reserved(&self) -> Option<[u8; 32]> {
+        if !self.is_coinbase() {
+            return None;
+        }
+        match self.witnesses.first().map(Vec::as_slice) {
+            Some([reserved]) => reserved.as_slice().try_into().ok(),
+            _ => None,
+        }
+    }
+
+    /// Set the witness reserved value of this coinbase transaction, replacing
+    /// any existing witness data.
+    ///
+    /// The coinbase input's witness becomes a single item stack containing
+    /// `reserved`, so the transaction is serialized in the SegWit format.
+    ///
+    /// # Panics
+    ///
+    /// If this transaction is not a coinbase transaction.
+    pub fn set_coinbase_witness_reserved(&mut self, reserved: [u8; 32]) {
+        assert!(
+            self.is_coinbase(),
+            "only coinbase transactions have a witness reserved value"
+        );
+        self.witnesses = vec![vec![reserved.to_vec()]];
+        *self.wtxid.mut_value() = None;
+    }
+
+    /// Clears the cached txid, wtxid, and serialized length.
+    ///
+    /// Call this after modifying the fields of a parsed transaction, so that
+    /// [`Transaction::hash`], [`Transaction::wtxid`], and [`Transaction::len`]
+    /// describe the modified transaction.
+    pub fn clear_cached_values(&mut self) {
+        self.hash = Cached::new();
+        self.wtxid = Cached::new();
+        self.size = Cached::new();
+    }
+
+    /// Returns `true` if any input of this transaction has a [BIP68] relative
+    /// lock time.
+    ///
+    /// Relative lock times only apply to transactions with version 2 or
+    /// later, so the sequence numbers of earlier transactions never lock them.
+    /// Coinbase inputs don't spend an output, so they also never have a
+    /// relative lock time.
+    ///
+    /// [BIP68]: https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki
+    pub fn has_relative_lock_time(&self) -> bool {
+        if self.version < 2 || self.is_coinbase() {
+            return false;
+        }
+        self.inputs
+            .iter()
+            .any(|input| input.relative_lock().is_some())
+    }
+
+    /// Returns `true` if this transaction's [BIP68] relative lock times allow
+    /// it to be included in a block at `block_height`.
+    ///
+    /// `block_time` is the median time past of the block's parent.
+    /// `confirmation` returns the height of the block that confirmed an
+    /// outpoint, and the median time past of that block's parent. Inputs whose
+    /// outpoint `confirmation` can't find are treated as locked.
+    ///
+    /// Transactions without relative lock times always pass, as described in
+    /// [`Transaction::has_relative_lock_time`].
+    ///
+    /// [BIP68]: https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki
+    pub fn relative_locks_satisfied<F>(
+        &self,
+        block_height: block::Height,
+        block_time: DateTime<Utc>,
+        mut confirmation: F,
+    ) -> bool
+    where
+        F: FnMut(&transparent::OutPoint) -> Option<(block::Height, DateTime<Utc>)>,
+    {
+        if !self.has_relative_lock_time() {
+            return true;
+        }
+
+        self.inputs.iter().all(|input| {
+            let (outpoint, lock) = match (input, input.relative_lock()) {
+                (transparent::Input::PrevOut { outpoint, .. }, Some(lock)) => (outpoint, lock),
+                _ => return true,
+            };
+            let (height, time) = match confirmation(outpoint) {
+                Some(confirmation) => confirmation,
+                None => return false,
+            };
+
+            match lock {
+                RelativeLockTime::Blocks(blocks) => {
+                    i64::from(block_height.0) - i64::from(height.0) >= i64::from(blocks)
+                }
+                RelativeLockTime::Seconds(seconds) => {
+                    block_time.timestamp() - time.timestamp() >= i64::from(seconds)
+                }
+            }
+        })
+    }
+
+    /// Returns `true` if this transaction's lock time allows it to be
+    /// included in a block at `block_height`, with time `block_time`.
+    ///
+    /// A transaction is final if its lock time is zero, or its lock height or
+    /// time is before the block's, or every input has a [`SEQUENCE_FINAL`]
+    /// sequence number. Times are compared in whole seconds, like Bitcoin's
+    /// `IsFinalTx`.
+    pub fn is_final(&self, block_height: block::Height, block_time: DateTime<Utc>) -> bool {
+        let unlocked = match self.locktime {
+            LockTime::Height(block::Height(0)) => true,
+            LockTime::Height(height) => height < block_height,
+            LockTime::Time(SmallUnixTime(time)) => time.timestamp() < block_time.timestamp(),
+        };
+
+        unlocked
+            || self
+                .inputs
+                .iter()
+                .all(|input| input.sequence() == SEQUENCE_FINAL)
+    }
+
+    /// Returns `true` if this transaction has any witness data.
+    ///
+    /// Transactions whose witness stacks are all empty are serialized in the
+    /// legacy format.
+    pub fn has_witness(&self) -> bool {
+        self.witnesses.iter().any(|stack| !stack.is_empty())
+    }
+
+    /// Returns the serialized length (in bytes) of a transaction, without
+    /// its witness data.
+    ///
+    /// This is the length of the serialization used to compute the txid. It
+    /// is cached when the transaction is deserialized.
     pub fn len(&self) -> usize {
-        let mut size = 0;
-        size += 4 + CompactInt::size(self.inputs.len());
+        self.size.value().unwrap_or_else(|| self.calculate_len())
+    }
+
+    /// Walk the inputs and outputs to calculate [`Transaction::len`].
+    fn calculate_len(&self) -> usize {
+        #[cfg(test)]
+        LEN_CALCULATIONS.with(|count| count.set(count.get() + 1));
+
+        let mut size = 4 + CompactInt::size(self.inputs.len());
         for input in self.inputs.iter() {
             size += input.len();
         }
@@ -95,4 +380,35 @@ impl Transaction {
         }
         size + 4
     }
+
+    /// Returns the length (in bytes) that witness data adds to the serialized
+    /// transaction: the marker and flag bytes, and a witness stack per input.
+    ///
+    /// Returns zero if the transaction has no witness data.
+    pub fn witness_len(&self) -> usize {
+        if !self.has_witness() {
+            return 0;
+        }
+
+        let empty_stack = Vec::new();
+        let stacks_len: usize = (0..self.inputs.len())
+            .map(|index| {
+                let stack = self.witnesses.get(index).unwrap_or(&empty_stack);
+                CompactInt::size(stack.len())
+                    + stack
+                        .iter()
+                        .map(|item| CompactInt::size(item.len()) + item.len())
+                        .sum::<usize>()
+            })
+            .sum();
+
+        2 + stacks_len
+    }
+
+    /// Returns the exact serialized length (in bytes) of this transaction,
+    /// including the version, input and output counts, lock time, and any
+    /// witness data.
+    pub fn serialized_size_with_header(&self) -> usize {
+        self.len() + self.witness_len()
+    }
 }
@@ -21,8 +21,11 @@ impl Transaction {
                 version: 1,
                 inputs,
                 outputs,
+                witnesses: Vec::new(),
                 locktime,
                 hash: Cached::new(),
+                wtxid: Cached::new(),
+                size: Cached::new(),
             })
             .boxed()
     }
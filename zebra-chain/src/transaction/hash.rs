@@ -3,9 +3,11 @@ use std::fmt;
 
 #[cfg(any(test, feature = "proptest-impl"))]
 use proptest_derive::Arbitrary;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::serialization::{sha256d, BitcoinDeserialize, BitcoinSerialize, SerializationError};
+use crate::serialization::{
+    serde_helpers, sha256d, BitcoinDeserialize, BitcoinSerialize, SerializationError,
+};
 use bitcoin_serde_derive::{BtcDeserialize, BtcSerialize};
 
 use super::Transaction;
@@ -13,13 +15,41 @@ use super::Transaction;
 /// A transaction hash.
 ///
 /// Note: Zebra displays transaction and block hashes in big-endian byte-order,
-/// following the u256 convention set by Bitcoin and zcashd.
-#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, BtcSerialize, BtcDeserialize)]
+/// following the u256 convention set by Bitcoin and zcashd. Human-readable
+/// serde formats use the same hex string, and binary formats use the raw bytes.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, BtcSerialize, BtcDeserialize)]
 #[cfg_attr(any(test, feature = "proptest-impl"), derive(Arbitrary))]
 pub struct Hash(pub [u8; 32]);
 
+impl Serialize for Hash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde_helpers::serialize_hash(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        serde_helpers::deserialize_hash(deserializer).map(Hash)
+    }
+}
+
 impl<'a> From<&'a Transaction> for Hash {
     fn from(transaction: &'a Transaction) -> Self {
+        let mut hash_writer = sha256d::Writer::default();
+        transaction
+            .bitcoin_serialize_without_witness(&mut hash_writer)
+            .expect("Transactions must serialize into the hash.");
+        Self(hash_writer.finish())
+    }
+}
+
+impl Hash {
+    /// Hash the full serialization of `transaction`, including any witness
+    /// data.
+    ///
+    /// Use [`Transaction::wtxid`] instead, which handles coinbase transactions
+    /// and uses the cached value.
+    pub(super) fn from_witness_serialization(transaction: &Transaction) -> Self {
         let mut hash_writer = sha256d::Writer::default();
         transaction
             .bitcoin_serialize(&mut hash_writer)
@@ -49,9 +79,15 @@ impl fmt::Debug for Hash {
 impl std::str::FromStr for Hash {
     type Err = SerializationError;
 
+    /// Parses a big-endian hex transaction ID, as displayed by block
+    /// explorers, reversing it into the internal byte order.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut bytes = [0; 32];
-        if hex::decode_to_slice(s, &mut bytes[..]).is_err() {
+        if s.len() != 64 {
+            Err(SerializationError::Parse(
+                "transaction hash must be 64 hex characters",
+            ))
+        } else if hex::decode_to_slice(s, &mut bytes[..]).is_err() {
             Err(SerializationError::Parse("hex decoding error"))
         } else {
             bytes.reverse();
@@ -76,4 +112,52 @@ mod tests {
             r#"transaction::Hash("3166411bd5343e0b284a108f39a929fbbb62619784f8c6dafe520703b5b446bf")"#
         );
     }
+
+    #[test]
+    fn transactionhash_display_roundtrip() {
+        zebra_test::init();
+
+        // The first transaction in block 170, spending the block 9 coinbase
+        let txid = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+        let hash: Hash = txid.parse().unwrap();
+        assert_eq!(hash.0[0], 0x16);
+        assert_eq!(hash.0[31], 0xf4);
+        assert_eq!(hash.to_string(), txid);
+    }
+
+    #[test]
+    fn transactionhash_from_str_rejects_bad_input() {
+        zebra_test::init();
+
+        let short = "4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+        assert_eq!(short.len(), 63);
+        assert!(short.parse::<Hash>().is_err());
+
+        let non_hex = "g4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+        assert!(non_hex.parse::<Hash>().is_err());
+    }
+
+    #[test]
+    fn transactionhash_serde_json_is_hex() {
+        zebra_test::init();
+
+        let txid = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+        let hash: Hash = txid.parse().unwrap();
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(json, format!("\"{}\"", txid));
+        assert_eq!(serde_json::from_str::<Hash>(&json).unwrap(), hash);
+
+        assert!(serde_json::from_str::<Hash>("\"f4184fc5\"").is_err());
+    }
+
+    #[test]
+    fn transactionhash_serde_bincode_is_bytes() {
+        zebra_test::init();
+
+        let txid = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+        let hash: Hash = txid.parse().unwrap();
+        let bytes = bincode::serialize(&hash).unwrap();
+        assert_eq!(bytes, hash.0.to_vec());
+        assert_eq!(bincode::deserialize::<Hash>(&bytes).unwrap(), hash);
+    }
 }
@@ -58,6 +58,20 @@ impl LockTime {
     }
 }
 
+/// A [BIP68] relative lock time, decoded from an input's sequence number.
+///
+/// Relative lock times are measured from the block that confirmed the output
+/// spent by the input.
+///
+/// [BIP68]: https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RelativeLockTime {
+    /// Unlock after this many blocks.
+    Blocks(u16),
+    /// Unlock after this many seconds, which is always a multiple of 512.
+    Seconds(u32),
+}
+
 impl BitcoinSerialize for LockTime {
     fn bitcoin_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
         // This implementation does not check the invariants on `LockTime` so that the
@@ -3,35 +3,134 @@
 
 use std::{io, sync::Arc};
 
-use crate::serialization::{BitcoinDeserialize, BitcoinSerialize, SerializationError};
+use crate::{
+    compactint::{CompactInt, MAX_COMPACT_LEN},
+    serialization::{BitcoinDeserialize, BitcoinSerialize, SerializationError},
+};
+
+use super::*;
+use crate::transparent;
 
 /// The maximum size of a transaction (excluding the witness) in bytes.
 pub const MAX_TX_SIZE: u64 = 1_000_000;
 
-use super::*;
-use crate::transparent;
+/// The maximum size of a transaction (including the witness) in bytes.
+pub const MAX_TX_SIZE_WITH_WITNESS: u64 = 4_000_000;
+
+/// The marker byte that replaces the input count in a BIP144 serialization.
+const WITNESS_MARKER: u8 = 0x00;
+
+/// The flag byte that follows the marker in a BIP144 serialization.
+const WITNESS_FLAG: u8 = 0x01;
+
+impl Transaction {
+    /// Serialize this transaction in the legacy format, without witness data.
+    ///
+    /// This is the serialization that is hashed to compute the txid.
+    pub fn bitcoin_serialize_without_witness<W: io::Write>(
+        &self,
+        mut writer: W,
+    ) -> Result<(), io::Error> {
+        self.version.bitcoin_serialize(&mut writer)?;
+        self.inputs.bitcoin_serialize(&mut writer)?;
+        self.outputs.bitcoin_serialize(&mut writer)?;
+        self.locktime.bitcoin_serialize(&mut writer)
+    }
+}
+
+/// Serializes a transaction, using the BIP144 format if it has witness data.
+impl BitcoinSerialize for Transaction {
+    fn bitcoin_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
+        if !self.has_witness() {
+            return self.bitcoin_serialize_without_witness(writer);
+        }
+
+        self.version.bitcoin_serialize(&mut writer)?;
+        writer.write_all(&[WITNESS_MARKER, WITNESS_FLAG])?;
+        self.inputs.bitcoin_serialize(&mut writer)?;
+        self.outputs.bitcoin_serialize(&mut writer)?;
+        // Each input has a witness stack, even if it is empty.
+        let empty_stack = Vec::new();
+        for index in 0..self.inputs.len() {
+            self.witnesses
+                .get(index)
+                .unwrap_or(&empty_stack)
+                .bitcoin_serialize(&mut writer)?;
+        }
+        self.locktime.bitcoin_serialize(&mut writer)
+    }
+}
 
-/// Deserializes a transaction, calculating and caching its TxID.
+/// Deserializes a transaction, calculating and caching its TxID, WTxID, and
+/// length.
 impl BitcoinDeserialize for Transaction {
     fn bitcoin_deserialize<R: std::io::Read>(src: R) -> Result<Self, SerializationError> {
-        // Put a sanity limit of 1 MB (a whole block) on the size of transaction to protect against DOS attacks
-        let mut src = src.take(MAX_TX_SIZE);
+        // Put a sanity limit of 4 MB (a whole block, including witnesses) on the
+        // size of transaction to protect against DOS attacks
+        let mut src = src.take(MAX_TX_SIZE_WITH_WITNESS);
         // Deserialize each field
         let version = i32::bitcoin_deserialize(&mut src)?;
-        let inputs = <Vec<transparent::Input>>::bitcoin_deserialize(&mut src)?;
-        let outputs = <Vec<transparent::Output>>::bitcoin_deserialize(&mut src)?;
+
+        // A zero input count is the BIP144 marker, and must be followed by the flag.
+        let mut input_count = CompactInt::bitcoin_deserialize(&mut src)?;
+        let has_witness = input_count.value() == u64::from(WITNESS_MARKER);
+        if has_witness {
+            if u8::bitcoin_deserialize(&mut src)? != WITNESS_FLAG {
+                return Err(SerializationError::Parse("invalid segwit flag"));
+            }
+            input_count = CompactInt::bitcoin_deserialize(&mut src)?;
+        }
+        let input_count = input_count.checked_len(MAX_COMPACT_LEN)?;
+        // Limit preallocation, since the count hasn't been checked against the data yet
+        let mut inputs = Vec::with_capacity(std::cmp::min(input_count, 1024));
+        for index in 0..input_count {
+            inputs.push(
+                transparent::Input::bitcoin_deserialize(&mut src)
+                    .map_err(|e| e.at_index(index).context("inputs"))?,
+            );
+        }
+
+        let outputs = <Vec<transparent::Output>>::bitcoin_deserialize(&mut src)
+            .map_err(|e| e.context("outputs"))?;
+
+        let mut witnesses = Vec::new();
+        if has_witness {
+            witnesses.reserve(inputs.len());
+            for index in 0..inputs.len() {
+                witnesses.push(
+                    <Vec<Vec<u8>>>::bitcoin_deserialize(&mut src)
+                        .map_err(|e| e.at_index(index).context("witnesses"))?,
+                );
+            }
+            if witnesses.iter().all(|stack| stack.is_empty()) {
+                return Err(SerializationError::Parse(
+                    "superfluous segwit witness record",
+                ));
+            }
+        }
+
         let locktime = LockTime::bitcoin_deserialize(&mut src)?;
-        let hash = Cached::new();
         let mut tx = Transaction {
             version,
             inputs,
             outputs,
+            witnesses,
             locktime,
-            hash,
+            hash: Cached::new(),
+            wtxid: Cached::new(),
+            size: Cached::new(),
         };
-        // Calculate and cache the TxID.
+        tx.size = Cached::from(tx.calculate_len());
+        if tx.len() as u64 > MAX_TX_SIZE {
+            return Err(SerializationError::Parse(
+                "transaction exceeds the maximum size",
+            ));
+        }
+        // Calculate and cache the TxID and WTxID.
         let own_hash = tx.hash();
         tx.hash = Cached::from(own_hash);
+        let own_wtxid = tx.wtxid();
+        tx.wtxid = Cached::from(own_wtxid);
         Ok(tx)
     }
 }
@@ -0,0 +1,347 @@
+//! Signature hashes and transparent input verification.
+
+use std::io::{self, Write};
+
+use secp256k1::{Message, PublicKey, Secp256k1, Signature};
+
+use crate::{
+    amount::{Amount, NonNegative},
+    serialization::{sha256d, BitcoinSerialize},
+    transparent::{
+        self, opcodes::*, Address, Instruction, PrivateKey, Script, ScriptError, VerifyFlags,
+    },
+};
+
+use super::{Hash, Transaction};
+
+/// Sign all inputs and all outputs.
+pub const SIGHASH_ALL: u8 = 0x01;
+
+impl Transaction {
+    /// Compute the legacy (pre-SegWit) `SIGHASH_ALL` signature hash for the
+    /// input at `input_index`, which is spending an output locked by
+    /// `script_code`.
+    ///
+    /// Every other unlock script is blanked, the signed input's unlock script
+    /// is replaced by `script_code`, and the hash type is appended to the
+    /// serialized transaction before double-SHA256 hashing.
+    ///
+    /// # Panics
+    ///
+    /// If `input_index` is out of range.
+    pub fn sighash_all_legacy(&self, input_index: usize, script_code: &Script) -> [u8; 32] {
+        assert!(
+            input_index < self.inputs.len(),
+            "input index must refer to an input of the transaction"
+        );
+
+        let inputs = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| match input {
+                transparent::Input::PrevOut {
+                    outpoint, sequence, ..
+                } => transparent::Input::PrevOut {
+                    outpoint: *outpoint,
+                    unlock_script: if index == input_index {
+                        script_code.clone()
+                    } else {
+                        Script(Vec::new())
+                    },
+                    sequence: *sequence,
+                },
+                coinbase @ transparent::Input::Coinbase { .. } => coinbase.clone(),
+            })
+            .collect();
+        let unsigned = Transaction::new(self.version, inputs, self.outputs.clone(), self.locktime);
+
+        let mut writer = sha256d::Writer::default();
+        unsigned
+            .bitcoin_serialize_without_witness(&mut writer)
+            .expect("sha256d::Writer is infallible");
+        writer
+            .write_all(&u32::from(SIGHASH_ALL).to_le_bytes())
+            .expect("sha256d::Writer is infallible");
+        writer.finish()
+    }
+
+    /// Compute the BIP143 (SegWit version 0) `SIGHASH_ALL` signature hash for
+    /// the input at `input_index`, which is spending an output worth `value`
+    /// and locked by `script_code`.
+    ///
+    /// Unlike the legacy signature hash, this commits to the value of the
+    /// spent output, and commits to the other inputs and the outputs using
+    /// separate hashes.
+    ///
+    /// # Panics
+    ///
+    /// If `input_index` is out of range.
+    pub fn sighash_all_segwit_v0(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        value: Amount<NonNegative>,
+    ) -> [u8; 32] {
+        assert!(
+            input_index < self.inputs.len(),
+            "input index must refer to an input of the transaction"
+        );
+
+        let mut writer = sha256d::Writer::default();
+        self.write_segwit_v0_preimage(input_index, script_code, value, &mut writer)
+            .expect("sha256d::Writer is infallible");
+        writer.finish()
+    }
+
+    /// Write the BIP143 signature hash preimage for the input at
+    /// `input_index` to `writer`.
+    fn write_segwit_v0_preimage<W: Write>(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        value: Amount<NonNegative>,
+        mut writer: W,
+    ) -> Result<(), io::Error> {
+        let input = &self.inputs[input_index];
+
+        let mut prevouts = sha256d::Writer::default();
+        let mut sequences = sha256d::Writer::default();
+        for input in self.inputs.iter() {
+            input_outpoint(input).bitcoin_serialize(&mut prevouts)?;
+            sequences.write_all(&input.sequence().to_le_bytes())?;
+        }
+        let mut outputs = sha256d::Writer::default();
+        for output in self.outputs.iter() {
+            output.bitcoin_serialize(&mut outputs)?;
+        }
+
+        writer.write_all(&self.version.to_le_bytes())?;
+        writer.write_all(&prevouts.finish())?;
+        writer.write_all(&sequences.finish())?;
+        input_outpoint(input).bitcoin_serialize(&mut writer)?;
+        script_code.bitcoin_serialize(&mut writer)?;
+        value.bitcoin_serialize(&mut writer)?;
+        writer.write_all(&input.sequence().to_le_bytes())?;
+        writer.write_all(&outputs.finish())?;
+        self.locktime.bitcoin_serialize(&mut writer)?;
+        writer.write_all(&u32::from(SIGHASH_ALL).to_le_bytes())
+    }
+
+    /// Verify the unlock script and witness of the input at `input_index`,
+    /// which spends `prevout`.
+    ///
+    /// Pay-to-public-key and pay-to-public-key-hash outputs are verified using
+    /// the legacy signature hash. If `flags` contains
+    /// [`VerifyFlags::WITNESS`], pay-to-witness-public-key-hash outputs are
+    /// verified using the BIP143 signature hash, and other inputs must not
+    /// have witness data. Other output types, including pay-to-script-hash and
+    /// pay-to-witness-script-hash, are not supported yet and return
+    /// [`ScriptError::UnsupportedScript`].
+    pub fn verify_input(
+        &self,
+        input_index: usize,
+        prevout: &transparent::Output,
+        flags: VerifyFlags,
+    ) -> Result<(), ScriptError> {
+        let unlock_script = match self.inputs.get(input_index) {
+            Some(transparent::Input::PrevOut { unlock_script, .. }) => unlock_script,
+            Some(transparent::Input::Coinbase { .. }) => return Err(ScriptError::CoinbaseInput),
+            None => return Err(ScriptError::InputIndex(input_index)),
+        };
+        let witness = self
+            .witnesses
+            .get(input_index)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let lock_script = &prevout.lock_script;
+
+        if lock_script.is_p2wpkh() || lock_script.is_p2wsh() {
+            if !flags.contains(VerifyFlags::WITNESS) {
+                return Err(ScriptError::UnsupportedScript(
+                    "witness programs without witness verification",
+                ));
+            }
+            if lock_script.is_p2wsh() {
+                return Err(ScriptError::UnsupportedScript("pay-to-witness-script-hash"));
+            }
+            return self.verify_p2wpkh_input(input_index, prevout, unlock_script, witness, flags);
+        }
+        if flags.contains(VerifyFlags::WITNESS) && !witness.is_empty() {
+            return Err(ScriptError::WitnessUnexpected);
+        }
+        if lock_script.is_p2sh() {
+            return Err(ScriptError::UnsupportedScript("pay-to-script-hash"));
+        }
+
+        let pushes = unlock_script
+            .instructions()
+            .map(|instruction| match instruction {
+                Ok(Instruction::PushBytes(data)) => Ok(data),
+                _ => Err(ScriptError::ScriptSigMismatch),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (signature, public_key) = if lock_script.is_p2pkh() {
+            match pushes.as_slice() {
+                [signature, public_key] => {
+                    if Address::hash_payload(public_key)[..] != lock_script.0[3..23] {
+                        return Err(ScriptError::PubKeyHashMismatch);
+                    }
+                    (*signature, *public_key)
+                }
+                _ => return Err(ScriptError::ScriptSigMismatch),
+            }
+        } else if lock_script.is_p2pk() {
+            match pushes.as_slice() {
+                [signature] => (*signature, &lock_script.0[1..lock_script.0.len() - 1]),
+                _ => return Err(ScriptError::ScriptSigMismatch),
+            }
+        } else {
+            return Err(ScriptError::UnsupportedScript("non-standard lock script"));
+        };
+
+        verify_signature(signature, public_key, flags, || {
+            self.sighash_all_legacy(input_index, lock_script)
+        })
+    }
+
+    /// Verify the witness of the input at `input_index`, which spends the
+    /// pay-to-witness-public-key-hash output `prevout`.
+    fn verify_p2wpkh_input(
+        &self,
+        input_index: usize,
+        prevout: &transparent::Output,
+        unlock_script: &Script,
+        witness: &[Vec<u8>],
+        flags: VerifyFlags,
+    ) -> Result<(), ScriptError> {
+        let pub_key_hash = &prevout.lock_script.0[2..22];
+
+        // Native witness programs must have an empty unlock script
+        if !unlock_script.0.is_empty() {
+            return Err(ScriptError::WitnessProgramMismatch);
+        }
+        let (signature, public_key) = match witness {
+            [signature, public_key] => (signature, public_key),
+            _ => return Err(ScriptError::WitnessProgramMismatch),
+        };
+        if Address::hash_payload(public_key)[..] != pub_key_hash[..] {
+            return Err(ScriptError::WitnessProgramMismatch);
+        }
+
+        // The script code is the pay-to-public-key-hash script for the
+        // witness program's hash
+        let mut script_code = vec![OP_DUP, OP_HASH160, 20];
+        script_code.extend_from_slice(pub_key_hash);
+        script_code.extend_from_slice(&[OP_EQUALVERIFY, OP_CHECKSIG]);
+
+        verify_signature(signature, public_key, flags, || {
+            self.sighash_all_segwit_v0(input_index, &Script(script_code), prevout.value)
+        })
+    }
+
+    /// Sign the input at `index`, which spends the pay-to-public-key-hash
+    /// output `prevout`, using `key`.
+    ///
+    /// The legacy signature hash is signed, and the input's unlock script is
+    /// replaced with `<sig> <pubkey>`. Only `SIGHASH_ALL` is supported.
+    pub fn sign_p2pkh_input(
+        &mut self,
+        index: usize,
+        key: &PrivateKey,
+        prevout: &transparent::Output,
+        sighash_type: u32,
+    ) -> Result<(), ScriptError> {
+        if sighash_type != u32::from(SIGHASH_ALL) {
+            return Err(ScriptError::UnsupportedSighashType(sighash_type as u8));
+        }
+        match self.inputs.get(index) {
+            Some(transparent::Input::PrevOut { .. }) => {}
+            Some(transparent::Input::Coinbase { .. }) => return Err(ScriptError::CoinbaseInput),
+            None => return Err(ScriptError::InputIndex(index)),
+        }
+
+        let lock_script = &prevout.lock_script;
+        if !lock_script.is_p2pkh() {
+            return Err(ScriptError::UnsupportedScript("non-p2pkh lock script"));
+        }
+
+        let public_key = key.public_key();
+        let public_key = if key.compressed {
+            public_key.serialize().to_vec()
+        } else {
+            public_key.serialize_uncompressed().to_vec()
+        };
+        if Address::hash_payload(&public_key)[..] != lock_script.0[3..23] {
+            return Err(ScriptError::PubKeyHashMismatch);
+        }
+
+        let sighash = self.sighash_all_legacy(index, lock_script);
+        let message = Message::from_slice(&sighash).expect("sighash is 32 bytes");
+        let signature = Secp256k1::signing_only().sign(&message, &key.key);
+
+        let mut signature = signature.serialize_der().to_vec();
+        signature.push(SIGHASH_ALL);
+
+        let mut script_sig = Script(Vec::with_capacity(2 + signature.len() + public_key.len()));
+        script_sig.push_data(&signature);
+        script_sig.push_data(&public_key);
+
+        if let Some(transparent::Input::PrevOut { unlock_script, .. }) = self.inputs.get_mut(index)
+        {
+            *unlock_script = script_sig;
+        }
+        // The unlock script is part of the txid and the serialized size, so
+        // drop any cached values.
+        self.clear_cached_values();
+
+        Ok(())
+    }
+}
+
+/// Returns the outpoint spent by `input`, or the null outpoint for coinbase
+/// inputs.
+fn input_outpoint(input: &transparent::Input) -> transparent::OutPoint {
+    match input {
+        transparent::Input::PrevOut { outpoint, .. } => *outpoint,
+        transparent::Input::Coinbase { .. } => transparent::OutPoint {
+            hash: Hash([0; 32]),
+            index: u32::MAX,
+        },
+    }
+}
+
+/// Verify `signature`, which ends with its hash type byte, against
+/// `public_key` and the signature hash returned by `sighash`.
+fn verify_signature(
+    signature: &[u8],
+    public_key: &[u8],
+    flags: VerifyFlags,
+    sighash: impl FnOnce() -> [u8; 32],
+) -> Result<(), ScriptError> {
+    let (hash_type, signature) = signature
+        .split_last()
+        .ok_or(ScriptError::InvalidSignatureEncoding)?;
+    if *hash_type != SIGHASH_ALL {
+        return Err(ScriptError::UnsupportedSighashType(*hash_type));
+    }
+
+    let mut signature = if flags.contains(VerifyFlags::DERSIG) {
+        Signature::from_der(signature)
+    } else {
+        Signature::from_der_lax(signature)
+    }
+    .map_err(|_| ScriptError::InvalidSignatureEncoding)?;
+    // libsecp256k1 only accepts low-S signatures, but high-S signatures
+    // are valid by consensus.
+    signature.normalize_s();
+
+    let public_key =
+        PublicKey::from_slice(public_key).map_err(|_| ScriptError::InvalidPublicKey)?;
+    let message = Message::from_slice(&sighash()).expect("sighash is 32 bytes");
+
+    Secp256k1::verification_only()
+        .verify(&message, &signature, &public_key)
+        .map_err(|_| ScriptError::SignatureMismatch)
+}
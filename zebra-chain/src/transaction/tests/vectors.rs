@@ -0,0 +1,744 @@
+use std::{convert::TryInto, sync::Arc};
+
+use chrono::TimeZone;
+
+use crate::{
+    block::{self, Block},
+    compactint::CompactInt,
+    serialization::{
+        BitcoinDeserialize, BitcoinDeserializeInto, BitcoinSerialize, SerializationError,
+    },
+    transparent::{self, opcodes::*, Address, PrivateKey, Script, ScriptError, VerifyFlags},
+};
+
+use super::super::*;
+
+/// Transaction a09208ad314fc0f9e805e0900657edcdba062b061fa411fca050800a32aa8a2f,
+/// which spends three pay-to-public-key-hash outputs.
+fn multi_input_transaction() -> Arc<Transaction> {
+    let block = zebra_test::vectors::BLOCK_MAINNET_347500_BYTES
+        .bitcoin_deserialize_into::<Block>()
+        .expect("block test vector should deserialize");
+    let transaction = block.transactions[39].clone();
+
+    assert_eq!(
+        transaction.hash().to_string(),
+        "a09208ad314fc0f9e805e0900657edcdba062b061fa411fca050800a32aa8a2f"
+    );
+    assert_eq!(transaction.inputs.len(), 3);
+
+    transaction
+}
+
+/// Reconstruct the pay-to-public-key-hash output spent by `input`.
+///
+/// The legacy signature hash doesn't commit to the value of the spent output,
+/// so the lock script is all we need to verify the signature.
+fn p2pkh_prevout(input: &transparent::Input) -> transparent::Output {
+    let public_key = match input {
+        transparent::Input::PrevOut { unlock_script, .. } => {
+            match unlock_script.instructions().last() {
+                Some(Ok(transparent::Instruction::PushBytes(public_key))) => public_key.to_vec(),
+                _ => panic!("test vector inputs end with a public key push"),
+            }
+        }
+        transparent::Input::Coinbase { .. } => panic!("test vector inputs are not coinbase"),
+    };
+
+    let mut lock_script = vec![OP_DUP, OP_HASH160, 20];
+    lock_script.extend_from_slice(&Address::hash_payload(&public_key));
+    lock_script.extend_from_slice(&[OP_EQUALVERIFY, OP_CHECKSIG]);
+
+    transparent::Output {
+        value: 0.try_into().expect("zero is a valid amount"),
+        lock_script: Script(lock_script),
+    }
+}
+
+#[test]
+fn verify_multi_input_mainnet_transaction() {
+    zebra_test::init();
+
+    let transaction = multi_input_transaction();
+
+    for (index, input) in transaction.inputs.iter().enumerate() {
+        let prevout = p2pkh_prevout(input);
+        transaction
+            .verify_input(index, &prevout, VerifyFlags::P2SH | VerifyFlags::DERSIG)
+            .expect("mainnet transaction inputs are valid");
+    }
+}
+
+#[test]
+fn verify_input_rejects_wrong_prevout() {
+    zebra_test::init();
+
+    let transaction = multi_input_transaction();
+
+    // Input 1 signs with a different key, so its lock script doesn't match input 0
+    let prevout = p2pkh_prevout(&transaction.inputs[1]);
+    assert_eq!(
+        transaction.verify_input(0, &prevout, VerifyFlags::empty()),
+        Err(ScriptError::PubKeyHashMismatch)
+    );
+
+    let prevout = p2pkh_prevout(&transaction.inputs[0]);
+    assert_eq!(
+        transaction.verify_input(3, &prevout, VerifyFlags::empty()),
+        Err(ScriptError::InputIndex(3))
+    );
+}
+
+/// A single-input, single-output transaction with an empty unlock script.
+fn unsigned_transaction() -> Transaction {
+    let input = transparent::Input::PrevOut {
+        outpoint: transparent::OutPoint {
+            hash: Hash([0x11; 32]),
+            index: 0,
+        },
+        unlock_script: Script(Vec::new()),
+        sequence: u32::MAX,
+    };
+    let output = transparent::Output {
+        value: 50_000.try_into().expect("valid amount"),
+        lock_script: Script(vec![OP_RETURN]),
+    };
+
+    Transaction::new(
+        1,
+        vec![input],
+        vec![output],
+        LockTime::Height(block::Height(0)),
+    )
+}
+
+/// The pay-to-public-key-hash output locked to `key`.
+fn p2pkh_output_for(key: &PrivateKey) -> transparent::Output {
+    let pub_key_hash = match key.address() {
+        Address::PayToPublicKeyHash { pub_key_hash, .. } => pub_key_hash,
+        Address::PayToScriptHash { .. } => unreachable!("private keys have p2pkh addresses"),
+    };
+
+    let mut lock_script = vec![OP_DUP, OP_HASH160, 20];
+    lock_script.extend_from_slice(&pub_key_hash);
+    lock_script.extend_from_slice(&[OP_EQUALVERIFY, OP_CHECKSIG]);
+
+    transparent::Output {
+        value: 100_000.try_into().expect("valid amount"),
+        lock_script: Script(lock_script),
+    }
+}
+
+#[test]
+fn sign_p2pkh_input_verifies() {
+    zebra_test::init();
+
+    for wif in &[
+        "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ",
+        "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617",
+    ] {
+        let key = PrivateKey::from_wif(wif).expect("test wif is valid");
+        let prevout = p2pkh_output_for(&key);
+
+        // Deserialized transactions cache their size
+        let mut transaction: Transaction = unsigned_transaction()
+            .bitcoin_serialize_to_vec()
+            .expect("vec serialization is infallible")
+            .bitcoin_deserialize_into()
+            .expect("unsigned transaction deserializes");
+        let unsigned_hash = transaction.hash();
+        let unsigned_len = transaction.len();
+        transaction
+            .sign_p2pkh_input(0, &key, &prevout, SIGHASH_ALL.into())
+            .expect("signing a p2pkh input succeeds");
+
+        assert_ne!(transaction.hash(), unsigned_hash);
+        assert!(transaction.len() > unsigned_len);
+        assert_eq!(
+            transaction.len(),
+            transaction
+                .bitcoin_serialize_to_vec()
+                .expect("vec serialization is infallible")
+                .len()
+        );
+        transaction
+            .verify_input(0, &prevout, VerifyFlags::P2SH | VerifyFlags::DERSIG)
+            .expect("freshly signed input verifies");
+    }
+}
+
+#[test]
+fn sign_p2pkh_input_errors() {
+    zebra_test::init();
+
+    let key = PrivateKey::from_wif("KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617")
+        .expect("test wif is valid");
+    let prevout = p2pkh_output_for(&key);
+    let mut transaction = unsigned_transaction();
+
+    assert_eq!(
+        transaction.sign_p2pkh_input(1, &key, &prevout, SIGHASH_ALL.into()),
+        Err(ScriptError::InputIndex(1))
+    );
+    assert_eq!(
+        transaction.sign_p2pkh_input(0, &key, &prevout, 0x02),
+        Err(ScriptError::UnsupportedSighashType(0x02))
+    );
+
+    let other_key = PrivateKey::random(key.network);
+    assert_eq!(
+        transaction.sign_p2pkh_input(0, &other_key, &prevout, SIGHASH_ALL.into()),
+        Err(ScriptError::PubKeyHashMismatch)
+    );
+}
+
+/// `unsigned_transaction` with a two-item witness stack on its only input.
+fn witness_transaction() -> Transaction {
+    let mut transaction = unsigned_transaction();
+    *transaction.witnesses_mut() = vec![vec![vec![0x30; 71], vec![0x02; 33]]];
+    transaction
+}
+
+#[test]
+fn witness_transaction_round_trip() {
+    zebra_test::init();
+
+    let legacy = unsigned_transaction();
+    let transaction = witness_transaction();
+    assert!(transaction.has_witness());
+    assert!(!legacy.has_witness());
+
+    let data = transaction
+        .bitcoin_serialize_to_vec()
+        .expect("transaction should serialize");
+    // The marker and flag follow the version.
+    assert_eq!(data[4..6], [0x00, 0x01]);
+    // Marker, flag, stack item count, and two length-prefixed items.
+    assert_eq!(transaction.witness_len(), 2 + 1 + (1 + 71) + (1 + 33));
+    assert_eq!(data.len(), transaction.serialized_size_with_header());
+    assert_eq!(data.len(), legacy.len() + transaction.witness_len());
+
+    let parsed: Transaction = data
+        .as_slice()
+        .bitcoin_deserialize_into()
+        .expect("witness transaction should deserialize");
+    assert_eq!(parsed.witnesses, transaction.witnesses);
+    assert_eq!(parsed.inputs, transaction.inputs);
+    assert_eq!(parsed.outputs, transaction.outputs);
+    assert_eq!(
+        parsed
+            .bitcoin_serialize_to_vec()
+            .expect("transaction should serialize"),
+        data
+    );
+
+    // The txid doesn't commit to the witness.
+    assert_eq!(parsed.hash(), legacy.hash());
+}
+
+#[test]
+fn legacy_transactions_round_trip_without_witness() {
+    zebra_test::init();
+
+    let block = zebra_test::vectors::BLOCK_MAINNET_347500_BYTES
+        .bitcoin_deserialize_into::<Block>()
+        .expect("block test vector should deserialize");
+
+    for transaction in block.transactions.iter() {
+        assert!(!transaction.has_witness());
+        assert_eq!(transaction.witness_len(), 0);
+
+        let data = transaction
+            .bitcoin_serialize_to_vec()
+            .expect("transaction should serialize");
+        let mut legacy = Vec::new();
+        transaction
+            .bitcoin_serialize_without_witness(&mut legacy)
+            .expect("transaction should serialize");
+        assert_eq!(data, legacy);
+
+        let parsed: Transaction = data
+            .as_slice()
+            .bitcoin_deserialize_into()
+            .expect("transaction should deserialize");
+        assert_eq!(&parsed, transaction.as_ref());
+    }
+}
+
+#[test]
+fn invalid_witness_encodings_are_rejected() {
+    zebra_test::init();
+
+    let transaction = witness_transaction();
+    let data = transaction
+        .bitcoin_serialize_to_vec()
+        .expect("transaction should serialize");
+
+    let mut bad_flag = data.clone();
+    bad_flag[5] = 0x02;
+    assert!(matches!(
+        bad_flag
+            .as_slice()
+            .bitcoin_deserialize_into::<Transaction>(),
+        Err(SerializationError::Parse("invalid segwit flag"))
+    ));
+
+    // Replace the witness stack, which sits between the outputs and the
+    // 4 byte locktime, with an empty one, keeping the marker and flag.
+    let stack_start = data.len() - 4 - (transaction.witness_len() - 2);
+    let mut empty_witness = data[..stack_start].to_vec();
+    empty_witness.push(0x00);
+    empty_witness.extend_from_slice(&data[data.len() - 4..]);
+    assert!(matches!(
+        empty_witness
+            .as_slice()
+            .bitcoin_deserialize_into::<Transaction>(),
+        Err(SerializationError::Parse(
+            "superfluous segwit witness record"
+        ))
+    ));
+}
+
+/// The signed native pay-to-witness-public-key-hash example transaction from
+/// [BIP143], which has one legacy input and one witness input.
+///
+/// [BIP143]: https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki
+const SEGWIT_TRANSACTION_HEX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+
+#[test]
+fn segwit_transaction_txid_and_wtxid() {
+    zebra_test::init();
+
+    let data = hex::decode(SEGWIT_TRANSACTION_HEX).expect("test vector is valid hex");
+    let transaction: Transaction = data
+        .as_slice()
+        .bitcoin_deserialize_into()
+        .expect("segwit test vector should deserialize");
+
+    assert!(transaction.has_witness());
+    assert_eq!(transaction.witnesses.len(), 2);
+    assert!(transaction.witnesses[0].is_empty());
+    assert_eq!(transaction.witnesses[1].len(), 2);
+    assert_eq!(transaction.len(), 233);
+    assert_eq!(transaction.serialized_size_with_header(), data.len());
+
+    assert_eq!(
+        transaction.hash().to_string(),
+        "e8151a2af31c368a35053ddd4bdb285a8595c769a3ad83e0fa02314a602d4609"
+    );
+    assert_eq!(
+        transaction.wtxid().to_string(),
+        "c36c38370907df2324d9ce9d149d191192f338b37665a82e78e76a12c909b762"
+    );
+
+    // Uncached hashes match the cached ones
+    let mut uncached = Transaction::new(
+        transaction.version,
+        transaction.inputs.clone(),
+        transaction.outputs.clone(),
+        transaction.locktime,
+    );
+    assert_eq!(uncached.hash(), transaction.hash());
+    assert_eq!(uncached.wtxid(), transaction.hash());
+    *uncached.witnesses_mut() = transaction.witnesses().to_vec();
+    assert_eq!(uncached.wtxid(), transaction.wtxid());
+}
+
+/// The outputs spent by the inputs of `SEGWIT_TRANSACTION_HEX`: a
+/// pay-to-public-key output, and a pay-to-witness-public-key-hash output.
+fn segwit_transaction_prevouts() -> Vec<transparent::Output> {
+    let p2pk =
+        hex::decode("2103c9f4836b9a4f77fc0d81f7bcb01b7f1b35916864b9476c241ce9fc198bd25432ac")
+            .expect("test vector is valid hex");
+    let p2wpkh = hex::decode("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1")
+        .expect("test vector is valid hex");
+
+    vec![
+        transparent::Output {
+            value: 625_000_000.try_into().expect("valid amount"),
+            lock_script: Script(p2pk),
+        },
+        transparent::Output {
+            value: 600_000_000.try_into().expect("valid amount"),
+            lock_script: Script(p2wpkh),
+        },
+    ]
+}
+
+#[test]
+fn segwit_v0_sighash_matches_bip143() {
+    zebra_test::init();
+
+    let transaction: Transaction = hex::decode(SEGWIT_TRANSACTION_HEX)
+        .expect("test vector is valid hex")
+        .as_slice()
+        .bitcoin_deserialize_into()
+        .expect("segwit test vector should deserialize");
+    let prevout = &segwit_transaction_prevouts()[1];
+
+    let script_code = Script(
+        hex::decode("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac")
+            .expect("test vector is valid hex"),
+    );
+    assert_eq!(
+        hex::encode(transaction.sighash_all_segwit_v0(1, &script_code, prevout.value)),
+        "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670"
+    );
+}
+
+#[test]
+fn verify_legacy_and_witness_inputs() {
+    zebra_test::init();
+
+    let transaction: Transaction = hex::decode(SEGWIT_TRANSACTION_HEX)
+        .expect("test vector is valid hex")
+        .as_slice()
+        .bitcoin_deserialize_into()
+        .expect("segwit test vector should deserialize");
+    let prevouts = segwit_transaction_prevouts();
+    let flags = VerifyFlags::P2SH | VerifyFlags::DERSIG | VerifyFlags::WITNESS;
+
+    for (index, prevout) in prevouts.iter().enumerate() {
+        transaction
+            .verify_input(index, prevout, flags)
+            .expect("BIP143 example inputs are valid");
+    }
+    assert!(matches!(
+        transaction.verify_input(1, &prevouts[1], VerifyFlags::empty()),
+        Err(ScriptError::UnsupportedScript(_))
+    ));
+
+    // The witness signature hash commits to the spent value
+    let mut wrong_value = prevouts[1].clone();
+    wrong_value.value = 500_000_000.try_into().expect("valid amount");
+    assert_eq!(
+        transaction.verify_input(1, &wrong_value, flags),
+        Err(ScriptError::SignatureMismatch)
+    );
+
+    // Native witness programs must have an empty unlock script
+    let mut malleated = transaction.clone();
+    if let transparent::Input::PrevOut { unlock_script, .. } = &mut malleated.inputs_mut()[1] {
+        *unlock_script = Script(vec![OP_0]);
+    }
+    assert_eq!(
+        malleated.verify_input(1, &prevouts[1], flags),
+        Err(ScriptError::WitnessProgramMismatch)
+    );
+
+    // Legacy inputs must not have witness data, once witnesses are verified
+    let mut unexpected = transaction;
+    unexpected.witnesses_mut()[0] = vec![vec![0x01]];
+    assert_eq!(
+        unexpected.verify_input(0, &prevouts[0], flags),
+        Err(ScriptError::WitnessUnexpected)
+    );
+    assert_eq!(
+        unexpected.verify_input(0, &prevouts[0], VerifyFlags::empty()),
+        Ok(())
+    );
+}
+
+#[test]
+fn wtxid_of_legacy_and_coinbase_transactions() {
+    zebra_test::init();
+
+    let block = zebra_test::vectors::BLOCK_MAINNET_347500_BYTES
+        .bitcoin_deserialize_into::<Block>()
+        .expect("block test vector should deserialize");
+
+    let (coinbase, transactions) = block
+        .transactions
+        .split_first()
+        .expect("blocks have a coinbase transaction");
+    assert_eq!(coinbase.wtxid(), Hash([0; 32]));
+    assert_ne!(coinbase.hash(), Hash([0; 32]));
+
+    for transaction in transactions {
+        assert_eq!(transaction.wtxid(), transaction.hash());
+    }
+}
+
+#[test]
+fn coinbase_witness_reserved_value() {
+    zebra_test::init();
+
+    // Block 653,600 is a SegWit block, so its coinbase has a reserved value.
+    // Skip the header and transaction count to read the first two transactions.
+    let mut src = &zebra_test::vectors::BLOCK_MAINNET_653600_BYTES[block::Header::len()..];
+    let tx_count = CompactInt::bitcoin_deserialize(&mut src).expect("block has a tx count");
+    assert!(tx_count.value() > 1);
+    let coinbase = Transaction::bitcoin_deserialize(&mut src).expect("coinbase should deserialize");
+    let spend = Transaction::bitcoin_deserialize(&mut src).expect("transaction should deserialize");
+    assert!(coinbase.has_witness());
+    assert_eq!(coinbase.coinbase_witness_reserved(), Some([0; 32]));
+    assert_eq!(spend.coinbase_witness_reserved(), None);
+
+    // Block 1 is a pre-SegWit block
+    let block = zebra_test::vectors::BLOCK_MAINNET_1_BYTES
+        .bitcoin_deserialize_into::<Block>()
+        .expect("block test vector should deserialize");
+    let mut coinbase = block.transactions[0].as_ref().clone();
+    assert_eq!(coinbase.coinbase_witness_reserved(), None);
+    let txid = coinbase.hash();
+
+    coinbase.set_coinbase_witness_reserved([0x42; 32]);
+    assert_eq!(coinbase.coinbase_witness_reserved(), Some([0x42; 32]));
+
+    let data = coinbase
+        .bitcoin_serialize_to_vec()
+        .expect("transaction should serialize");
+    assert_eq!(data[4..6], [0x00, 0x01]);
+    // A single item stack, with a 32 byte item, followed by the locktime
+    let witness_end = data.len() - 4;
+    assert_eq!(data[witness_end - 34..witness_end - 32], [0x01, 0x20]);
+    assert_eq!(data[witness_end - 32..witness_end], [0x42; 32]);
+
+    let parsed: Transaction = data
+        .as_slice()
+        .bitcoin_deserialize_into()
+        .expect("segwit coinbase should deserialize");
+    assert_eq!(parsed.coinbase_witness_reserved(), Some([0x42; 32]));
+    assert_eq!(parsed.hash(), txid);
+    assert_eq!(parsed.wtxid(), Hash([0; 32]));
+}
+
+#[test]
+fn relative_lock_times_need_version_two() {
+    zebra_test::init();
+
+    let block = zebra_test::vectors::BLOCK_MAINNET_347500_BYTES
+        .bitcoin_deserialize_into::<Block>()
+        .expect("block test vector should deserialize");
+    let mut transaction = block.transactions[39].as_ref().clone();
+
+    // A sequence of 10 would be a 10 block relative lock time
+    for input in transaction.inputs.iter_mut() {
+        if let transparent::Input::PrevOut { sequence, .. } = input {
+            *sequence = 10;
+        }
+    }
+    transaction.set_version(1);
+    assert!(!transaction.has_relative_lock_time());
+    transaction.set_version(2);
+    assert!(transaction.has_relative_lock_time());
+
+    // Setting the disable flag on every input removes the lock
+    for input in transaction.inputs.iter_mut() {
+        if let transparent::Input::PrevOut { sequence, .. } = input {
+            *sequence |= SEQUENCE_LOCKTIME_DISABLE_FLAG;
+        }
+    }
+    assert!(!transaction.has_relative_lock_time());
+
+    // Coinbase sequence numbers are never relative lock times
+    let mut coinbase = block.transactions[0].as_ref().clone();
+    coinbase.set_version(2);
+    if let transparent::Input::Coinbase { sequence, .. } = &mut coinbase.inputs[0] {
+        *sequence = 10;
+    }
+    assert!(!coinbase.has_relative_lock_time());
+}
+
+#[test]
+fn len_is_cached_on_deserialization() {
+    zebra_test::init();
+
+    let block = zebra_test::vectors::BLOCK_MAINNET_347500_BYTES
+        .bitcoin_deserialize_into::<Block>()
+        .expect("block test vector should deserialize");
+    for transaction in block.transactions.iter() {
+        let mut serialized = Vec::new();
+        transaction
+            .bitcoin_serialize_without_witness(&mut serialized)
+            .expect("serializing into a vec never fails");
+        assert_eq!(transaction.len(), serialized.len());
+    }
+
+    // Parsed transactions use their cached length
+    let mut transaction = block.transactions[39].as_ref().clone();
+    let calculations = LEN_CALCULATIONS.with(|count| count.get());
+    for _ in 0..10 {
+        transaction.len();
+    }
+    assert_eq!(LEN_CALCULATIONS.with(|count| count.get()), calculations);
+
+    // Modified transactions calculate their new length
+    let original_len = transaction.len();
+    transaction.outputs.push(transaction.outputs[0].clone());
+    transaction.clear_cached_values();
+    assert_eq!(
+        transaction.len(),
+        original_len + transaction.outputs[0].len()
+    );
+    assert_eq!(LEN_CALCULATIONS.with(|count| count.get()), calculations + 1);
+}
+
+#[test]
+fn lock_time_finality() {
+    zebra_test::init();
+
+    let block = zebra_test::vectors::BLOCK_MAINNET_347500_BYTES
+        .bitcoin_deserialize_into::<Block>()
+        .expect("block test vector should deserialize");
+    let mut transaction = block.transactions[39].as_ref().clone();
+    for input in transaction.inputs.iter_mut() {
+        if let transparent::Input::PrevOut { sequence, .. } = input {
+            *sequence = 0;
+        }
+    }
+
+    let height = block::Height(347_500);
+    let time = Utc.timestamp(1_425_000_000, 0);
+
+    // A zero lock time is always final
+    transaction.set_locktime(LockTime::Height(block::Height(0)));
+    assert!(transaction.is_final(block::Height(1), time));
+
+    // Height locks must be strictly below the block height
+    transaction.set_locktime(LockTime::Height(block::Height(347_499)));
+    assert!(transaction.is_final(height, time));
+    transaction.set_locktime(LockTime::Height(height));
+    assert!(!transaction.is_final(height, time));
+
+    // Time locks must be strictly before the block time, in whole seconds
+    transaction.set_locktime(LockTime::Time(SmallUnixTime(
+        Utc.timestamp(1_424_999_999, 0),
+    )));
+    assert!(transaction.is_final(height, time));
+    transaction.set_locktime(LockTime::Time(SmallUnixTime(time)));
+    assert!(!transaction.is_final(height, time));
+    assert!(!transaction.is_final(height, Utc.timestamp(1_425_000_000, 999_999_999)));
+
+    // Final sequence numbers on every input disable the lock time
+    for input in transaction.inputs.iter_mut() {
+        if let transparent::Input::PrevOut { sequence, .. } = input {
+            *sequence = SEQUENCE_FINAL;
+        }
+    }
+    assert!(transaction.is_final(height, time));
+    if let transparent::Input::PrevOut { sequence, .. } = &mut transaction.inputs[1] {
+        *sequence = SEQUENCE_FINAL - 1;
+    }
+    assert!(!transaction.is_final(height, time));
+}
+
+#[test]
+fn relative_lock_decoding() {
+    zebra_test::init();
+
+    let input = |sequence| transparent::Input::PrevOut {
+        outpoint: transparent::OutPoint {
+            hash: Hash([0; 32]),
+            index: 0,
+        },
+        unlock_script: Script(Vec::new()),
+        sequence,
+    };
+
+    // The disable flag turns off relative lock times
+    assert_eq!(input(SEQUENCE_FINAL).relative_lock(), None);
+    assert_eq!(input(SEQUENCE_LOCKTIME_DISABLE_FLAG).relative_lock(), None);
+    assert_eq!(
+        input(SEQUENCE_LOCKTIME_DISABLE_FLAG | 10).relative_lock(),
+        None
+    );
+
+    // Block-based locks use the low 16 bits, ignoring the other bits
+    assert_eq!(input(0).relative_lock(), Some(RelativeLockTime::Blocks(0)));
+    assert_eq!(
+        input(0xffff).relative_lock(),
+        Some(RelativeLockTime::Blocks(0xffff))
+    );
+    assert_eq!(
+        input(0x0001_0005).relative_lock(),
+        Some(RelativeLockTime::Blocks(5))
+    );
+
+    // Time-based locks are in units of 512 seconds
+    assert_eq!(
+        input(SEQUENCE_LOCKTIME_TYPE_FLAG).relative_lock(),
+        Some(RelativeLockTime::Seconds(0))
+    );
+    assert_eq!(
+        input(SEQUENCE_LOCKTIME_TYPE_FLAG | 1).relative_lock(),
+        Some(RelativeLockTime::Seconds(512))
+    );
+    assert_eq!(
+        input(SEQUENCE_LOCKTIME_TYPE_FLAG | 0xffff).relative_lock(),
+        Some(RelativeLockTime::Seconds(33_553_920))
+    );
+
+    let coinbase = transparent::Input::Coinbase {
+        height: None,
+        data: transparent::CoinbaseData(Vec::new()),
+        sequence: 0,
+    };
+    assert_eq!(coinbase.relative_lock(), None);
+}
+
+#[test]
+fn relative_locks_are_checked_against_confirmations() {
+    zebra_test::init();
+
+    let block = zebra_test::vectors::BLOCK_MAINNET_347500_BYTES
+        .bitcoin_deserialize_into::<Block>()
+        .expect("block test vector should deserialize");
+    let mut transaction = block.transactions[39].as_ref().clone();
+    transaction.set_version(2);
+    let sequences = [10, SEQUENCE_LOCKTIME_TYPE_FLAG | 2, SEQUENCE_FINAL];
+    for (input, new_sequence) in transaction.inputs.iter_mut().zip(sequences.iter()) {
+        if let transparent::Input::PrevOut { sequence, .. } = input {
+            *sequence = *new_sequence;
+        }
+    }
+
+    // Every spent output was confirmed at height 100
+    let confirmed_time = Utc.timestamp(1_400_000_000, 0);
+    let confirmed = |_: &transparent::OutPoint| Some((block::Height(100), confirmed_time));
+    let later = |seconds| confirmed_time + chrono::Duration::seconds(seconds);
+
+    assert!(transaction.relative_locks_satisfied(block::Height(110), later(1024), confirmed));
+    // One block too early
+    assert!(!transaction.relative_locks_satisfied(block::Height(109), later(1024), confirmed));
+    // One second too early
+    assert!(!transaction.relative_locks_satisfied(block::Height(110), later(1023), confirmed));
+    // Unknown spent outputs are locked
+    assert!(!transaction.relative_locks_satisfied(block::Height(110), later(1024), |_| None));
+
+    // Version 1 transactions don't have relative lock times
+    transaction.set_version(1);
+    assert!(transaction.relative_locks_satisfied(block::Height(100), confirmed_time, confirmed));
+}
+
+#[test]
+fn mutable_accessors_clear_cached_values() {
+    zebra_test::init();
+
+    // Deserialized transactions cache their hashes and size
+    let mut transaction: Transaction = unsigned_transaction()
+        .bitcoin_serialize_to_vec()
+        .expect("vec serialization is infallible")
+        .bitcoin_deserialize_into()
+        .expect("unsigned transaction deserializes");
+    let original_hash = transaction.hash();
+    let original_len = transaction.len();
+
+    let output = transaction.outputs()[0].clone();
+    transaction.outputs_mut().push(output);
+    assert_ne!(transaction.hash(), original_hash);
+    assert!(transaction.len() > original_len);
+
+    let hash_with_output = transaction.hash();
+    if let transparent::Input::PrevOut { sequence, .. } = &mut transaction.inputs_mut()[0] {
+        *sequence = 0;
+    }
+    assert_ne!(transaction.hash(), hash_with_output);
+    assert_eq!(
+        transaction.len(),
+        transaction
+            .bitcoin_serialize_to_vec()
+            .expect("vec serialization is infallible")
+            .len()
+    );
+}
@@ -7,7 +7,10 @@ mod script;
 mod serialize;
 
 pub use address::Address;
-pub use script::Script;
+pub use keys::PrivateKey;
+pub use script::{
+    opcodes, Instruction, Instructions, Script, ScriptError, ScriptType, VerifyFlags,
+};
 
 use crate::{
     cached::Cached, compactint::CompactInt, BitcoinDeserialize, BitcoinSerialize,
@@ -25,9 +28,17 @@ mod prop;
 
 use crate::{
     amount::{Amount, NonNegative},
-    block, transaction,
+    block,
+    parameters::Network,
+    transaction,
 };
 
+/// The minimum length of a coinbase input script, in bytes.
+pub const MIN_COINBASE_DATA_LEN: usize = 2;
+
+/// The maximum length of a coinbase input script, in bytes.
+pub const MAX_COINBASE_DATA_LEN: usize = 100;
+
 /// Arbitrary data inserted by miners into a coinbase transaction.
 #[derive(Clone, Eq, PartialEq, Serialize, Deserialize, BtcDeserialize, BtcSerialize)]
 pub struct CoinbaseData(
@@ -50,6 +61,17 @@ impl CoinbaseData {
     pub fn serialized_size(&self) -> usize {
         CompactInt::size(self.0.len()) + self.0.len()
     }
+
+    /// Parse the block height that starts this coinbase data, as specified
+    /// in [BIP34].
+    ///
+    /// Only coinbase data in version 2 and later blocks contains a height, and
+    /// it is only required after BIP34 activates.
+    ///
+    /// [BIP34]: https://github.com/bitcoin/bips/blob/master/bip-0034.mediawiki
+    pub fn bip34_height(&self) -> Result<block::Height, SerializationError> {
+        serialize::parse_coinbase_height(&self.0)
+    }
 }
 
 impl std::fmt::Debug for CoinbaseData {
@@ -120,11 +142,52 @@ impl Input {
                 ref unlock_script,
                 sequence: _,
             } => OutPoint::len() + unlock_script.serialized_size() + 4,
+            // Coinbase inputs are serialized with a null outpoint.
             Input::Coinbase {
                 height: _,
                 ref data,
                 sequence: _,
-            } => data.serialized_size() + 4,
+            } => OutPoint::len() + data.serialized_size() + 4,
+        }
+    }
+
+    /// Returns the sequence number of this input.
+    pub fn sequence(&self) -> u32 {
+        match *self {
+            Input::PrevOut { sequence, .. } | Input::Coinbase { sequence, .. } => sequence,
+        }
+    }
+
+    /// Returns the [BIP68] relative lock time in this input's sequence number,
+    /// if it has one.
+    ///
+    /// Returns `None` if the sequence number has the disable flag set, and
+    /// for coinbase inputs, which don't spend an output. Relative lock times
+    /// only apply to transactions with version 2 or later, which callers must
+    /// check separately.
+    ///
+    /// [BIP68]: https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki
+    pub fn relative_lock(&self) -> Option<transaction::RelativeLockTime> {
+        use transaction::{
+            RelativeLockTime, SEQUENCE_LOCKTIME_DISABLE_FLAG, SEQUENCE_LOCKTIME_GRANULARITY,
+            SEQUENCE_LOCKTIME_MASK, SEQUENCE_LOCKTIME_TYPE_FLAG,
+        };
+
+        let sequence = match *self {
+            Input::PrevOut { sequence, .. } => sequence,
+            Input::Coinbase { .. } => return None,
+        };
+        if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return None;
+        }
+
+        let value = sequence & SEQUENCE_LOCKTIME_MASK;
+        if sequence & SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+            Some(RelativeLockTime::Seconds(
+                value << SEQUENCE_LOCKTIME_GRANULARITY,
+            ))
+        } else {
+            Some(RelativeLockTime::Blocks(value as u16))
         }
     }
 }
@@ -144,7 +207,6 @@ impl Input {
 #[derive(
     Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Hash, BtcDeserialize, BtcSerialize,
 )]
-#[cfg_attr(any(test, feature = "proptest-impl"), derive(Arbitrary))]
 pub struct Output {
     /// Transaction value.
     // At https://en.bitcoin.it/wiki/Protocol_documentation#tx, this is an i64.
@@ -159,4 +221,10 @@ impl Output {
     pub fn len(&self) -> usize {
         8 + self.lock_script.serialized_size()
     }
+
+    /// Returns the address this output pays to on `network`, if its lock
+    /// script has one, see [`Script::extract_address`].
+    pub fn address(&self, network: Network) -> Option<Address> {
+        self.lock_script.extract_address(network)
+    }
 }
@@ -108,11 +108,9 @@ impl BitcoinSerialize for Address {
                 network,
                 script_hash,
             } => {
-                // Dev network doesn't have a recommendation so we
-                // default to testnet bytes if it's not mainnet.
                 match *network {
                     Network::Mainnet => writer.write_all(&magics::p2sh::MAINNET[..])?,
-                    _ => writer.write_all(&magics::p2sh::TESTNET[..])?,
+                    Network::Testnet => writer.write_all(&magics::p2sh::TESTNET[..])?,
                 }
                 writer.write_all(script_hash)?
             }
@@ -120,11 +118,9 @@ impl BitcoinSerialize for Address {
                 network,
                 pub_key_hash,
             } => {
-                // Dev network doesn't have a recommendation so we
-                // default to testnet bytes if it's not mainnet.
                 match *network {
                     Network::Mainnet => writer.write_all(&magics::p2pkh::MAINNET[..])?,
-                    _ => writer.write_all(&magics::p2pkh::TESTNET[..])?,
+                    Network::Testnet => writer.write_all(&magics::p2pkh::TESTNET[..])?,
                 }
                 writer.write_all(pub_key_hash)?
             }
@@ -209,7 +205,7 @@ impl Address {
     /// The resulting hash in both of these cases is always exactly 20
     /// bytes.
     /// https://en.bitcoin.it/Base58Check_encoding#Encoding_a_Bitcoin_address
-    fn hash_payload(bytes: &[u8]) -> [u8; 20] {
+    pub(crate) fn hash_payload(bytes: &[u8]) -> [u8; 20] {
         let sha_hash = Sha256::digest(bytes);
         let ripe_hash = Ripemd160::digest(&sha_hash);
         let mut payload = [0u8; 20];
@@ -358,4 +354,20 @@ proptest! {
 
         prop_assert_eq![taddr, taddr2];
     }
+
+    #[test]
+    fn transparent_address_network_roundtrip(network in any::<Network>(), hash in any::<[u8; 20]>()) {
+        zebra_test::init();
+
+        let addresses = [
+            Address::PayToPublicKeyHash { network, pub_key_hash: hash },
+            Address::PayToScriptHash { network, script_hash: hash },
+        ];
+
+        for taddr in addresses.iter() {
+            let taddr2: Address = taddr.to_string().parse().expect("t-addr string should parse");
+
+            prop_assert_eq![taddr, &taddr2];
+        }
+    }
 }
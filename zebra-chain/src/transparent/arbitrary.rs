@@ -1,8 +1,11 @@
 use proptest::{arbitrary::any, collection::vec, prelude::*};
 
-use crate::{block, LedgerState};
+use crate::{
+    amount::{Amount, NonNegative},
+    block, LedgerState,
+};
 
-use super::{CoinbaseData, Input, OutPoint, Script};
+use super::{opcodes::*, CoinbaseData, Input, OutPoint, Output, Script};
 
 impl Input {
     /// Construct a strategy for creating validish vecs of Inputs.
@@ -22,18 +25,26 @@ impl Arbitrary for Input {
     type Parameters = Option<block::Height>;
 
     fn arbitrary_with(height: Self::Parameters) -> Self::Strategy {
-        if let Some(_height) = height {
+        if let Some(height) = height {
             (vec(any::<u8>(), 0..95), any::<u32>())
-                .prop_map(move |(data, sequence)| Input::Coinbase {
-                    // FIXME: add this back once height design has finalized
-                    // height: Some(Cached::from(height)),
-                    height: None,
-                    data: CoinbaseData(data),
-                    sequence,
+                .prop_map(move |(extra_data, sequence)| {
+                    let mut data = bip34_height_script(height);
+                    data.extend(extra_data);
+                    Input::Coinbase {
+                        // The height is only cached when the coinbase is
+                        // deserialized as part of a block
+                        height: None,
+                        data: CoinbaseData(data),
+                        sequence,
+                    }
                 })
                 .boxed()
         } else {
-            (any::<OutPoint>(), any::<Script>(), any::<u32>())
+            (
+                any::<OutPoint>(),
+                prop_oneof![any::<Script>(), Script::p2pkh_unlock_strategy()],
+                any::<u32>(),
+            )
                 .prop_map(|(outpoint, unlock_script, sequence)| Input::PrevOut {
                     outpoint,
                     unlock_script,
@@ -45,3 +56,86 @@ impl Arbitrary for Input {
 
     type Strategy = BoxedStrategy<Self>;
 }
+
+impl Arbitrary for Output {
+    type Parameters = ();
+
+    fn arbitrary_with(_args: ()) -> Self::Strategy {
+        (
+            any::<Amount<NonNegative>>(),
+            prop_oneof![any::<Script>(), Script::standard_output_strategy()],
+        )
+            .prop_map(|(value, lock_script)| Output { value, lock_script })
+            .boxed()
+    }
+
+    type Strategy = BoxedStrategy<Self>;
+}
+
+impl Script {
+    /// Construct a strategy for creating P2PKH, P2SH, and P2WPKH lock scripts
+    /// with random hashes.
+    pub fn standard_output_strategy() -> BoxedStrategy<Self> {
+        any::<[u8; 20]>()
+            .prop_flat_map(|hash| {
+                let mut p2pkh = vec![OP_DUP, OP_HASH160, 20];
+                p2pkh.extend_from_slice(&hash);
+                p2pkh.extend_from_slice(&[OP_EQUALVERIFY, OP_CHECKSIG]);
+
+                let mut p2sh = vec![OP_HASH160, 20];
+                p2sh.extend_from_slice(&hash);
+                p2sh.push(OP_EQUAL);
+
+                let mut p2wpkh = vec![OP_0, 20];
+                p2wpkh.extend_from_slice(&hash);
+
+                prop_oneof![Just(p2pkh), Just(p2sh), Just(p2wpkh)]
+            })
+            .prop_map(Script)
+            .boxed()
+    }
+
+    /// Construct a strategy for creating P2PKH unlock scripts, which push a
+    /// DER-sized signature and a compressed public key.
+    ///
+    /// The signature and key bytes are random, so these scripts have the right
+    /// shape, but never verify.
+    pub fn p2pkh_unlock_strategy() -> BoxedStrategy<Self> {
+        (
+            vec(any::<u8>(), 70..=72),
+            prop_oneof![Just(0x02u8), Just(0x03u8)],
+            vec(any::<u8>(), 32),
+        )
+            .prop_map(|(signature, key_prefix, key)| {
+                let mut public_key = vec![key_prefix];
+                public_key.extend(key);
+
+                let mut script = Script(Vec::new());
+                script.push_data(&signature);
+                script.push_data(&public_key);
+                script
+            })
+            .boxed()
+    }
+}
+
+/// Returns a script that pushes `height`, as specified in BIP34.
+fn bip34_height_script(height: block::Height) -> Vec<u8> {
+    // OP_1 through OP_16
+    if (1..=16).contains(&height.0) {
+        return vec![0x50 + height.0 as u8];
+    }
+
+    let mut number = height.0.to_le_bytes().to_vec();
+    while number.last() == Some(&0) {
+        number.pop();
+    }
+    // Positive numbers need a clear sign bit in their most significant byte
+    if number.last().map_or(false, |&byte| byte & 0x80 != 0) {
+        number.push(0);
+    }
+
+    let mut script = Script(Vec::new());
+    script.push_data(&number);
+    script.0
+}
@@ -1,13 +1,30 @@
-//! Transparent key trait impls, around secp256k1::PublicKey
+//! Transparent key trait impls, around secp256k1::PublicKey, and
+//! a networked `PrivateKey` with WIF import and export.
 //!
 //! We don't impl Arbitrary for PublicKey since it's being pulled in
 //! from secp256k1 and we don't want to wrap it.
 
-use std::io;
+use std::{fmt, io};
 
-use secp256k1::PublicKey;
+use rand_core::{OsRng, RngCore};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
 
-use crate::{serialization::SerializationError, BitcoinDeserialize, BitcoinSerialize};
+use crate::{
+    parameters::Network, serialization::SerializationError, BitcoinDeserialize, BitcoinSerialize,
+};
+
+use super::Address;
+
+/// Magic numbers used to identify what networks WIF private keys
+/// are associated with.
+mod magics {
+    pub const MAINNET: u8 = 0x80;
+    pub const TESTNET: u8 = 0xef;
+
+    /// Appended to the key bytes when the matching public key should be
+    /// serialized in compressed form.
+    pub const COMPRESSED: u8 = 0x01;
+}
 
 impl BitcoinSerialize for PublicKey {
     fn bitcoin_serialize<W: io::Write>(&self, mut writer: W) -> Result<(), io::Error> {
@@ -24,3 +41,197 @@ impl BitcoinDeserialize for PublicKey {
             .map_err(|_| SerializationError::Parse("invalid secp256k1 compressed public key"))
     }
 }
+
+/// A transparent secp256k1 private key, tagged with the network it belongs to.
+///
+/// Private keys are imported and exported using Wallet Import Format:
+/// https://en.bitcoin.it/wiki/Wallet_import_format
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct PrivateKey {
+    /// Production, test, or other network
+    pub network: Network,
+    /// Whether the matching public key is serialized in compressed form.
+    pub compressed: bool,
+    /// The underlying secp256k1 secret key.
+    pub key: SecretKey,
+}
+
+impl fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Never print the secret key itself.
+        f.debug_struct("PrivateKey")
+            .field("network", &self.network)
+            .field("compressed", &self.compressed)
+            .finish()
+    }
+}
+
+impl PrivateKey {
+    /// Generate a new random compressed private key for `network`,
+    /// using the operating system's random number generator.
+    pub fn random(network: Network) -> Self {
+        let mut bytes = [0u8; 32];
+        let key = loop {
+            OsRng.fill_bytes(&mut bytes);
+            // Almost every 32 byte string is a valid key, but zero and
+            // values above the curve order are not.
+            if let Ok(key) = SecretKey::from_slice(&bytes) {
+                break key;
+            }
+        };
+
+        PrivateKey {
+            network,
+            compressed: true,
+            key,
+        }
+    }
+
+    /// Returns the public key corresponding to this private key.
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey::from_secret_key(&Secp256k1::signing_only(), &self.key)
+    }
+
+    /// Returns the pay-to-public-key-hash address for this key, hashing the
+    /// compressed or uncompressed public key as appropriate.
+    pub fn address(&self) -> Address {
+        let public_key = self.public_key();
+        let pub_key_hash = if self.compressed {
+            Address::hash_payload(&public_key.serialize()[..])
+        } else {
+            Address::hash_payload(&public_key.serialize_uncompressed()[..])
+        };
+
+        Address::PayToPublicKeyHash {
+            network: self.network,
+            pub_key_hash,
+        }
+    }
+
+    /// Parse a private key from its Wallet Import Format string.
+    pub fn from_wif(wif: &str) -> Result<Self, SerializationError> {
+        let bytes = bs58::decode(wif)
+            .with_check(None)
+            .into_vec()
+            .map_err(|_| SerializationError::Parse("wif decoding error"))?;
+
+        let network = match bytes.first() {
+            Some(&magics::MAINNET) => Network::Mainnet,
+            Some(&magics::TESTNET) => Network::Testnet,
+            _ => return Err(SerializationError::Parse("bad wif network prefix")),
+        };
+
+        let compressed = match bytes.len() {
+            33 => false,
+            34 if bytes[33] == magics::COMPRESSED => true,
+            _ => return Err(SerializationError::Parse("bad wif length")),
+        };
+
+        let key = SecretKey::from_slice(&bytes[1..33])
+            .map_err(|_| SerializationError::Parse("invalid secp256k1 secret key"))?;
+
+        Ok(PrivateKey {
+            network,
+            compressed,
+            key,
+        })
+    }
+
+    /// Encode this private key in Wallet Import Format.
+    pub fn to_wif(&self) -> String {
+        let mut bytes = Vec::with_capacity(34);
+        bytes.push(match self.network {
+            Network::Mainnet => magics::MAINNET,
+            Network::Testnet => magics::TESTNET,
+        });
+        bytes.extend_from_slice(&self.key[..]);
+        if self.compressed {
+            bytes.push(magics::COMPRESSED);
+        }
+
+        bs58::encode(bytes).with_check().into_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0c28fca386c7a227600b2fe50b7cae11ec86d3bf1fbe471be89827e19d72aa1d";
+
+    #[test]
+    fn wif_uncompressed_mainnet() {
+        zebra_test::init();
+
+        let wif = "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ";
+        let key = PrivateKey::from_wif(wif).expect("wif should parse");
+
+        assert_eq!(key.network, Network::Mainnet);
+        assert!(!key.compressed);
+        assert_eq!(hex::encode(&key.key[..]), SECRET);
+        assert_eq!(
+            key.address().to_string(),
+            "1GAehh7TsJAHuUAeKZcXf5CnwuGuGgyX2S"
+        );
+        assert_eq!(key.to_wif(), wif);
+    }
+
+    #[test]
+    fn wif_compressed_mainnet() {
+        zebra_test::init();
+
+        let wif = "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617";
+        let key = PrivateKey::from_wif(wif).expect("wif should parse");
+
+        assert_eq!(key.network, Network::Mainnet);
+        assert!(key.compressed);
+        assert_eq!(
+            hex::encode(&key.public_key().serialize()[..]),
+            "02d0de0aaeaefad02b8bdc8a01a1b8b11c696bd3d66a2c5f10780d95b7df42645c"
+        );
+        assert_eq!(
+            key.address().to_string(),
+            "1LoVGDgRs9hTfTNJNuXKSpywcbdvwRXpmK"
+        );
+        assert_eq!(key.to_wif(), wif);
+    }
+
+    #[test]
+    fn wif_compressed_testnet() {
+        zebra_test::init();
+
+        let wif = "cMzLdeGd5vEqxB8B6VFQoRopQ3sLAAvEzDAoQgvX54xwofSWj1fx";
+        let key = PrivateKey::from_wif(wif).expect("wif should parse");
+
+        assert_eq!(key.network, Network::Testnet);
+        assert_eq!(hex::encode(&key.key[..]), SECRET);
+        assert_eq!(
+            key.address().to_string(),
+            "n1KSZGmQgB8iSZqv6UVhGkCGUbEdw8Lm3Q"
+        );
+        assert_eq!(key.to_wif(), wif);
+    }
+
+    #[test]
+    fn wif_rejects_bad_input() {
+        zebra_test::init();
+
+        // Corrupted checksum
+        assert!(
+            PrivateKey::from_wif("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTK").is_err()
+        );
+        // A p2pkh address is not a private key
+        assert!(PrivateKey::from_wif("1GAehh7TsJAHuUAeKZcXf5CnwuGuGgyX2S").is_err());
+    }
+
+    #[test]
+    fn random_key_roundtrip() {
+        zebra_test::init();
+
+        let key = PrivateKey::random(Network::Testnet);
+        let parsed = PrivateKey::from_wif(&key.to_wif()).expect("generated wif should parse");
+
+        assert_eq!(parsed, key);
+        assert_eq!(parsed.address(), key.address());
+    }
+}
@@ -2,7 +2,7 @@ use zebra_test::prelude::*;
 
 use crate::{block, parameters::Network, LedgerState};
 
-use super::Input;
+use super::{Address, Input, Output, Script, ScriptType};
 
 #[test]
 fn coinbase_has_height() -> Result<()> {
@@ -47,3 +47,32 @@ fn input_coinbase_vecs_only_have_coinbase_input() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn standard_output_addresses_round_trip() -> Result<()> {
+    zebra_test::init();
+
+    let strategy = (
+        any::<Output>(),
+        Script::standard_output_strategy(),
+        any::<Network>(),
+    );
+
+    proptest!(|((mut output, lock_script, network) in strategy)| {
+        output.lock_script = lock_script;
+
+        match output.lock_script.classify() {
+            ScriptType::P2PKH | ScriptType::P2SH => {
+                let address = output.address(network);
+                prop_assert!(address.is_some());
+                let address = address.unwrap();
+                prop_assert_eq!(address.to_string().parse::<Address>()?, address);
+            }
+            // Address doesn't have a segwit variant yet
+            ScriptType::P2WPKH => prop_assert_eq!(output.address(network), None),
+            script_type => prop_assert!(false, "unexpected script type {:?}", script_type),
+        }
+    });
+
+    Ok(())
+}
@@ -1,13 +1,105 @@
 #![allow(clippy::unit_arg)]
 use crate::{
-    compactint::CompactInt,
+    compactint::{CompactInt, MAX_COMPACT_LEN},
+    parameters::Network,
     serialization::{BitcoinDeserialize, BitcoinSerialize, SerializationError},
 };
 use std::{
+    convert::{TryFrom, TryInto},
     fmt,
     io::{self, Read},
 };
 
+pub mod opcodes;
+
+use opcodes::*;
+
+use super::Address;
+
+/// The maximum size of a script that can be executed, in bytes.
+///
+/// Larger scripts are valid in outputs, but they can never be spent. This
+/// limit is enforced when scripts are executed, not when they are
+/// deserialized.
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+
+bitflags::bitflags! {
+    /// Script verification flags, selecting which soft-fork rules apply.
+    pub struct VerifyFlags: u32 {
+        /// Evaluate pay-to-script-hash subscripts (BIP 16).
+        const P2SH = 1 << 0;
+        /// Require strict DER encoding for signatures (BIP 66).
+        const DERSIG = 1 << 2;
+        /// Verify segregated witness programs (BIP 141).
+        const WITNESS = 1 << 11;
+    }
+}
+
+/// An error returned when a transparent input fails script verification.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+    /// The input index does not refer to an input of the transaction.
+    #[error("input index {0} is out of range")]
+    InputIndex(usize),
+    /// Coinbase inputs have no previous output to verify against.
+    #[error("coinbase inputs do not spend a previous output")]
+    CoinbaseInput,
+    /// The previous output uses a script that Zebra can't verify yet.
+    #[error("unsupported script: {0}")]
+    UnsupportedScript(&'static str),
+    /// The unlock script doesn't have the shape required by the lock script.
+    #[error("unlock script does not match the lock script template")]
+    ScriptSigMismatch,
+    /// The public key doesn't hash to the hash in the lock script.
+    #[error("public key does not match the lock script hash")]
+    PubKeyHashMismatch,
+    /// The public key is not a valid secp256k1 public key.
+    #[error("invalid public key encoding")]
+    InvalidPublicKey,
+    /// The signature is not validly encoded.
+    #[error("invalid signature encoding")]
+    InvalidSignatureEncoding,
+    /// The signature uses a hash type Zebra can't verify yet.
+    #[error("unsupported signature hash type {0:#04x}")]
+    UnsupportedSighashType(u8),
+    /// The signature does not verify against the public key.
+    #[error("signature verification failed")]
+    SignatureMismatch,
+    /// The input has witness data, but doesn't spend a witness program.
+    #[error("witness data on an input that does not spend a witness program")]
+    WitnessUnexpected,
+    /// The witness or unlock script doesn't have the shape required by the
+    /// witness program.
+    #[error("witness does not match the witness program")]
+    WitnessProgramMismatch,
+    /// The taproot tweak is not a valid secp256k1 scalar.
+    #[error("invalid taproot tweak")]
+    InvalidTaprootTweak,
+    /// The taproot output key is not the tweaked internal key.
+    #[error("taproot output key does not commit to the internal key")]
+    TaprootTweakMismatch,
+}
+
+/// The standard output script templates, as returned by [`Script::classify`].
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ScriptType {
+    /// Pay to public key hash: `OP_DUP OP_HASH160 <20 byte hash> OP_EQUALVERIFY OP_CHECKSIG`.
+    P2PKH,
+    /// Pay to script hash: `OP_HASH160 <20 byte hash> OP_EQUAL`.
+    P2SH,
+    /// Version 0 pay to witness public key hash: `OP_0 <20 byte hash>`.
+    P2WPKH,
+    /// Version 0 pay to witness script hash: `OP_0 <32 byte hash>`.
+    P2WSH,
+    /// Pay to public key: `<33 or 65 byte pubkey> OP_CHECKSIG`.
+    P2PK,
+    /// A data carrier output, which starts with `OP_RETURN`.
+    OpReturn,
+    /// Any other script.
+    NonStandard,
+}
+
 /// An encoding of a Bitcoin script.
 #[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Hash)]
 #[cfg_attr(
@@ -20,6 +112,311 @@ impl Script {
     pub fn serialized_size(&self) -> usize {
         CompactInt::size(self.0.len()) + self.0.len()
     }
+
+    /// Append an instruction that pushes `data`, using the smallest push
+    /// opcode that fits its length.
+    ///
+    /// Single bytes are always pushed as data, even if they could be pushed
+    /// using a small integer opcode like `OP_1`.
+    pub fn push_data(&mut self, data: &[u8]) {
+        match data.len() {
+            len if len <= OP_PUSHBYTES_75 as usize => self.0.push(len as u8),
+            len if len <= 0xff => {
+                self.0.push(OP_PUSHDATA1);
+                self.0.push(len as u8);
+            }
+            len if len <= 0xffff => {
+                self.0.push(OP_PUSHDATA2);
+                self.0.extend_from_slice(&(len as u16).to_le_bytes());
+            }
+            len => {
+                let len = u32::try_from(len).expect("script pushes are smaller than 4 GB");
+                self.0.push(OP_PUSHDATA4);
+                self.0.extend_from_slice(&len.to_le_bytes());
+            }
+        }
+        self.0.extend_from_slice(data);
+    }
+
+    /// Iterate over the instructions in this script.
+    ///
+    /// Each item is either a data push or a bare opcode. If a push runs past
+    /// the end of the script, the iterator yields an error and then stops.
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions {
+            remaining: &self.0[..],
+        }
+    }
+
+    /// Returns true if this is a pay-to-public-key script:
+    /// `<33 or 65 byte pubkey> OP_CHECKSIG`.
+    pub fn is_p2pk(&self) -> bool {
+        match self.0.as_slice() {
+            [33, key @ .., OP_CHECKSIG] if key.len() == 33 => true,
+            [65, key @ .., OP_CHECKSIG] if key.len() == 65 => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if this is a pay-to-public-key-hash script:
+    /// `OP_DUP OP_HASH160 <20 byte hash> OP_EQUALVERIFY OP_CHECKSIG`.
+    pub fn is_p2pkh(&self) -> bool {
+        matches!(
+            self.0.as_slice(),
+            [OP_DUP, OP_HASH160, 20, hash @ .., OP_EQUALVERIFY, OP_CHECKSIG] if hash.len() == 20
+        )
+    }
+
+    /// Returns true if this is a pay-to-script-hash script:
+    /// `OP_HASH160 <20 byte hash> OP_EQUAL`.
+    pub fn is_p2sh(&self) -> bool {
+        matches!(
+            self.0.as_slice(),
+            [OP_HASH160, 20, hash @ .., OP_EQUAL] if hash.len() == 20
+        )
+    }
+
+    /// Returns true if this is a version 0 pay-to-witness-public-key-hash
+    /// script: `OP_0 <20 byte hash>`.
+    pub fn is_p2wpkh(&self) -> bool {
+        matches!(self.0.as_slice(), [OP_0, 20, hash @ ..] if hash.len() == 20)
+    }
+
+    /// Returns true if this is a version 0 pay-to-witness-script-hash script:
+    /// `OP_0 <32 byte hash>`.
+    pub fn is_p2wsh(&self) -> bool {
+        matches!(self.0.as_slice(), [OP_0, 32, hash @ ..] if hash.len() == 32)
+    }
+
+    /// Returns the witness version and program if this is a segregated
+    /// witness output script: `OP_n <2 to 40 byte push>`, where `n` is
+    /// between 0 and 16.
+    ///
+    /// BIP 141 also requires version 0 programs to be 20 or 32 bytes long,
+    /// so other version 0 lengths return `None`.
+    pub fn witness_program(&self) -> Option<(u8, Vec<u8>)> {
+        let (version, program) = match self.0.as_slice() {
+            [version @ OP_0, len, program @ ..] | [version @ OP_1..=OP_16, len, program @ ..]
+                if *len as usize == program.len() =>
+            {
+                (*version, program)
+            }
+            _ => return None,
+        };
+
+        let version = match version {
+            OP_0 => 0,
+            op => op - OP_1 + 1,
+        };
+
+        match (version, program.len()) {
+            (0, 20) | (0, 32) => Some((version, program.to_vec())),
+            (0, _) => None,
+            (_, 2..=40) => Some((version, program.to_vec())),
+            _ => None,
+        }
+    }
+
+    /// Returns true if this script consists only of push operations.
+    ///
+    /// Like `zcashd` and Bitcoin Core, this treats every opcode up to and
+    /// including `OP_16` as a push. Standardness rules require input scripts to
+    /// be push-only, and a script with a truncated push is never push-only.
+    pub fn is_push_only(&self) -> bool {
+        self.instructions().all(|instruction| match instruction {
+            Ok(Instruction::PushBytes(_)) => true,
+            Ok(Instruction::Op(op)) => op <= OP_16,
+            Err(_) => false,
+        })
+    }
+
+    /// Returns true if every data push in this script uses the smallest
+    /// possible push opcode.
+    ///
+    /// This matches Bitcoin Core's `MINIMALDATA` policy: empty pushes must use
+    /// `OP_0`, single byte values from 1 to 16 and `0x81` must use `OP_1`
+    /// to `OP_16` and `OP_1NEGATE`, and longer pushes must use the shortest
+    /// length encoding. A script with a truncated push is never minimal.
+    pub fn has_minimal_pushes(&self) -> bool {
+        let mut instructions = self.instructions();
+        while let Some(&op) = instructions.remaining.first() {
+            match instructions.next() {
+                Some(Ok(Instruction::PushBytes(data))) if !is_minimal_push(op, data) => {
+                    return false
+                }
+                Some(Ok(_)) => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// Returns true if outputs with this lock script can never be spent.
+    ///
+    /// Scripts that start with `OP_RETURN`, or are longer than
+    /// [`MAX_SCRIPT_SIZE`], always fail, so their outputs don't need to be
+    /// stored in the UTXO set.
+    pub fn is_provably_unspendable(&self) -> bool {
+        self.0.first() == Some(&OP_RETURN) || self.0.len() > MAX_SCRIPT_SIZE
+    }
+
+    /// Returns the data embedded in this `OP_RETURN` script, or `None` if
+    /// this script doesn't start with `OP_RETURN`.
+    ///
+    /// If `OP_RETURN` is followed by multiple data pushes, their bytes are
+    /// concatenated. `OP_1NEGATE` and `OP_1` to `OP_16` contribute the single
+    /// byte number they push. A bare `OP_RETURN` has empty data. Returns
+    /// `None` if any instruction after `OP_RETURN` isn't a push, or is
+    /// truncated.
+    pub fn op_return_data(&self) -> Option<Vec<u8>> {
+        let mut instructions = self.instructions();
+        if instructions.next() != Some(Ok(Instruction::Op(OP_RETURN))) {
+            return None;
+        }
+
+        let mut data = Vec::new();
+        for instruction in instructions {
+            match instruction {
+                Ok(Instruction::PushBytes(bytes)) => data.extend_from_slice(bytes),
+                Ok(Instruction::Op(OP_1NEGATE)) => data.push(0x81),
+                Ok(Instruction::Op(op @ OP_1..=OP_16)) => data.push(op - OP_1 + 1),
+                _ => return None,
+            }
+        }
+        Some(data)
+    }
+
+    /// Returns the standard template that this output script matches.
+    pub fn classify(&self) -> ScriptType {
+        if self.is_p2pkh() {
+            ScriptType::P2PKH
+        } else if self.is_p2sh() {
+            ScriptType::P2SH
+        } else if self.is_p2wpkh() {
+            ScriptType::P2WPKH
+        } else if self.is_p2wsh() {
+            ScriptType::P2WSH
+        } else if self.is_p2pk() {
+            ScriptType::P2PK
+        } else if self.0.first() == Some(&OP_RETURN) {
+            ScriptType::OpReturn
+        } else {
+            ScriptType::NonStandard
+        }
+    }
+
+    /// Returns the address that this output script pays to on `network`, if
+    /// it has one.
+    ///
+    /// Pay to public key outputs are returned as the pay to public key hash
+    /// address of their key. [`Address`] only has base58 address types, so
+    /// segregated witness outputs return `None`, like `OP_RETURN` and
+    /// non-standard outputs.
+    pub fn extract_address(&self, network: Network) -> Option<Address> {
+        let mut hash = [0; 20];
+        match self.classify() {
+            ScriptType::P2PKH => {
+                hash.copy_from_slice(&self.0[3..23]);
+                Some(Address::PayToPublicKeyHash {
+                    network,
+                    pub_key_hash: hash,
+                })
+            }
+            ScriptType::P2SH => {
+                hash.copy_from_slice(&self.0[2..22]);
+                Some(Address::PayToScriptHash {
+                    network,
+                    script_hash: hash,
+                })
+            }
+            ScriptType::P2PK => Some(Address::PayToPublicKeyHash {
+                network,
+                pub_key_hash: Address::hash_payload(&self.0[1..self.0.len() - 1]),
+            }),
+            ScriptType::P2WPKH
+            | ScriptType::P2WSH
+            | ScriptType::OpReturn
+            | ScriptType::NonStandard => None,
+        }
+    }
+}
+
+/// Returns true if `data` is pushed using the smallest possible opcode, `op`.
+fn is_minimal_push(op: u8, data: &[u8]) -> bool {
+    match data {
+        [] => op == OP_0,
+        [value] if (1..=16).contains(value) || *value == 0x81 => false,
+        _ if data.len() <= OP_PUSHBYTES_75 as usize => op as usize == data.len(),
+        _ if data.len() <= 0xff => op == OP_PUSHDATA1,
+        _ if data.len() <= 0xffff => op == OP_PUSHDATA2,
+        _ => true,
+    }
+}
+
+/// A single instruction in a [`Script`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Instruction<'a> {
+    /// Push the contained bytes onto the stack, using any of the push opcodes
+    /// from `OP_0` up to `OP_PUSHDATA4`.
+    PushBytes(&'a [u8]),
+    /// Any other opcode.
+    Op(u8),
+}
+
+/// An iterator over the instructions of a [`Script`].
+#[derive(Clone, Debug)]
+pub struct Instructions<'a> {
+    remaining: &'a [u8],
+}
+
+impl<'a> Instructions<'a> {
+    /// Split `len` bytes of push data off the front of the remaining script.
+    fn take_push(&mut self, len: usize) -> Result<Instruction<'a>, SerializationError> {
+        if self.remaining.len() < len {
+            self.remaining = &[];
+            return Err(SerializationError::Parse(
+                "script push runs past the end of the script",
+            ));
+        }
+        let (data, rest) = self.remaining.split_at(len);
+        self.remaining = rest;
+        Ok(Instruction::PushBytes(data))
+    }
+
+    /// Read an explicit little-endian push length, `width` bytes long.
+    fn take_push_len(&mut self, width: usize) -> Result<usize, SerializationError> {
+        if self.remaining.len() < width {
+            self.remaining = &[];
+            return Err(SerializationError::Parse(
+                "script push length runs past the end of the script",
+            ));
+        }
+        let (len_bytes, rest) = self.remaining.split_at(width);
+        self.remaining = rest;
+        let mut len = [0u8; 8];
+        len[..width].copy_from_slice(len_bytes);
+        Ok(u64::from_le_bytes(len)
+            .try_into()
+            .expect("push lengths are at most 4 bytes"))
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<Instruction<'a>, SerializationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&op, rest) = self.remaining.split_first()?;
+        self.remaining = rest;
+
+        let instruction = match op {
+            OP_0..=OP_PUSHBYTES_75 => self.take_push(op as usize),
+            OP_PUSHDATA1 => self.take_push_len(1).and_then(|len| self.take_push(len)),
+            OP_PUSHDATA2 => self.take_push_len(2).and_then(|len| self.take_push(len)),
+            OP_PUSHDATA4 => self.take_push_len(4).and_then(|len| self.take_push(len)),
+            _ => Ok(Instruction::Op(op)),
+        };
+        Some(instruction)
+    }
 }
 
 impl fmt::Debug for Script {
@@ -40,35 +437,20 @@ impl BitcoinSerialize for Script {
 
 impl BitcoinDeserialize for Script {
     fn bitcoin_deserialize<R: io::Read>(mut reader: R) -> Result<Self, SerializationError> {
-        // XXX what is the max length of a script?
-        let len = CompactInt::bitcoin_deserialize(&mut reader)?.value();
+        // Check the declared length before reading, so a huge length can't
+        // cause a huge allocation. Scripts longer than MAX_SCRIPT_SIZE are
+        // valid in outputs, so they can't be rejected here.
+        let len = CompactInt::bitcoin_deserialize(&mut reader)?.checked_len(MAX_COMPACT_LEN)?;
         let mut bytes = Vec::new();
-        reader.take(len).read_to_end(&mut bytes)?;
+        reader.take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != len {
+            return Err(SerializationError::Parse(
+                "script is shorter than its length",
+            ));
+        }
         Ok(Script(bytes))
     }
 }
 
 #[cfg(test)]
-mod proptests {
-    use std::io::Cursor;
-
-    use proptest::prelude::*;
-
-    use super::*;
-    use crate::serialization::{BitcoinDeserialize, BitcoinSerialize};
-
-    proptest! {
-        #[test]
-        fn script_roundtrip(script in any::<Script>()) {
-            zebra_test::init();
-
-            let mut bytes = Cursor::new(Vec::new());
-            script.bitcoin_serialize(&mut bytes)?;
-
-            bytes.set_position(0);
-            let other_script = Script::bitcoin_deserialize(&mut bytes)?;
-
-            prop_assert_eq![script, other_script];
-        }
-    }
-}
+mod tests;
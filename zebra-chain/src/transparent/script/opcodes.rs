@@ -0,0 +1,28 @@
+//! Script opcodes used by Zebra's script inspection helpers.
+//!
+//! This is not a complete list of Bitcoin opcodes; only the opcodes needed to
+//! recognize pushes and standard script templates are included.
+
+/// Push an empty byte vector (also known as `OP_FALSE`).
+pub const OP_0: u8 = 0x00;
+/// The largest opcode which pushes its own value as a byte count.
+pub const OP_PUSHBYTES_75: u8 = 0x4b;
+/// The next byte contains the number of bytes to be pushed.
+pub const OP_PUSHDATA1: u8 = 0x4c;
+/// The next two bytes (little endian) contain the number of bytes to be pushed.
+pub const OP_PUSHDATA2: u8 = 0x4d;
+/// The next four bytes (little endian) contain the number of bytes to be pushed.
+pub const OP_PUSHDATA4: u8 = 0x4e;
+/// Push the number -1.
+pub const OP_1NEGATE: u8 = 0x4f;
+pub const OP_RESERVED: u8 = 0x50;
+/// Push the number 1 (also known as `OP_TRUE`).
+pub const OP_1: u8 = 0x51;
+/// Push the number 16.
+pub const OP_16: u8 = 0x60;
+pub const OP_RETURN: u8 = 0x6a;
+pub const OP_DUP: u8 = 0x76;
+pub const OP_EQUAL: u8 = 0x87;
+pub const OP_EQUALVERIFY: u8 = 0x88;
+pub const OP_HASH160: u8 = 0xa9;
+pub const OP_CHECKSIG: u8 = 0xac;
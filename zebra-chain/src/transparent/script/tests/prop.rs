@@ -0,0 +1,21 @@
+use std::io::Cursor;
+
+use proptest::prelude::*;
+
+use super::super::*;
+use crate::serialization::{BitcoinDeserialize, BitcoinSerialize};
+
+proptest! {
+    #[test]
+    fn script_roundtrip(script in any::<Script>()) {
+        zebra_test::init();
+
+        let mut bytes = Cursor::new(Vec::new());
+        script.bitcoin_serialize(&mut bytes)?;
+
+        bytes.set_position(0);
+        let other_script = Script::bitcoin_deserialize(&mut bytes)?;
+
+        prop_assert_eq![script, other_script];
+    }
+}
@@ -0,0 +1,292 @@
+use super::super::*;
+use crate::serialization::{BitcoinDeserialize, BitcoinSerialize};
+
+#[test]
+fn script_length_bounds() {
+    zebra_test::init();
+
+    // Scripts that are too large to execute still round-trip, because
+    // they are valid in outputs
+    for len in &[MAX_SCRIPT_SIZE, MAX_SCRIPT_SIZE + 1] {
+        let script = Script(vec![OP_1; *len]);
+        let bytes = script.bitcoin_serialize_to_vec().unwrap();
+        assert_eq!(Script::bitcoin_deserialize(&bytes[..]).unwrap(), script);
+    }
+
+    // A 4 GB length is rejected before reading the script
+    let bytes = [0xfe, 0xff, 0xff, 0xff, 0xff];
+    assert_eq!(
+        Script::bitcoin_deserialize(&bytes[..]),
+        Err(SerializationError::Parse(
+            "declared length exceeds the maximum length"
+        ))
+    );
+}
+
+#[test]
+fn truncated_script_is_rejected() {
+    zebra_test::init();
+
+    // The length says 3 bytes, but only 2 follow
+    let bytes = [0x03, OP_1, OP_2];
+    assert_eq!(
+        Script::bitcoin_deserialize(&bytes[..]),
+        Err(SerializationError::Parse(
+            "script is shorter than its length"
+        ))
+    );
+
+    // A missing script is also truncated
+    let bytes = [0x01];
+    assert_eq!(
+        Script::bitcoin_deserialize(&bytes[..]),
+        Err(SerializationError::Parse(
+            "script is shorter than its length"
+        ))
+    );
+}
+
+#[test]
+fn push_only_script_sig() {
+    zebra_test::init();
+
+    // A P2PKH scriptSig: <71-byte signature> <33-byte pubkey>
+    let mut bytes = vec![71];
+    bytes.extend_from_slice(&[0x30; 71]);
+    bytes.push(33);
+    bytes.extend_from_slice(&[0x02; 33]);
+    assert!(Script(bytes).is_push_only());
+
+    // Small integers and OP_PUSHDATA pushes are also pushes
+    let script = Script(vec![
+        OP_0,
+        OP_1,
+        OP_16,
+        OP_1NEGATE,
+        OP_PUSHDATA1,
+        2,
+        0xab,
+        0xcd,
+    ]);
+    assert!(script.is_push_only());
+
+    assert!(Script(Vec::new()).is_push_only());
+}
+
+#[test]
+fn non_push_script_sig() {
+    zebra_test::init();
+
+    let script = Script(vec![OP_1, OP_DUP]);
+    assert!(!script.is_push_only());
+
+    // A truncated push is not push-only
+    let script = Script(vec![OP_PUSHDATA2, 0xff]);
+    assert!(!script.is_push_only());
+    let script = Script(vec![5, 0x01, 0x02]);
+    assert!(!script.is_push_only());
+}
+
+#[test]
+fn push_data_uses_minimal_pushes() {
+    zebra_test::init();
+
+    for &len in &[0, 1, 75, 76, 255, 256, 0xffff, 0x1_0000] {
+        let data = vec![0xab; len];
+        let mut script = Script(Vec::new());
+        script.push_data(&data);
+
+        assert!(script.has_minimal_pushes(), "{} byte push", len);
+        assert_eq!(
+            script.instructions().collect::<Vec<_>>(),
+            vec![Ok(Instruction::PushBytes(&data[..]))],
+        );
+    }
+
+    let mut script = Script(Vec::new());
+    script.push_data(&[0xab; 300]);
+    assert_eq!(script.0[..3], [OP_PUSHDATA2, 0x2c, 0x01]);
+}
+
+#[test]
+fn minimal_pushes() {
+    zebra_test::init();
+
+    let mut bytes = vec![OP_0, OP_1, OP_1NEGATE, 2, 0xab, 0xcd, OP_PUSHDATA1, 76];
+    bytes.extend_from_slice(&[0xab; 76]);
+    bytes.push(OP_CHECKSIG);
+    assert!(Script(bytes).has_minimal_pushes());
+
+    // Each of these pushes has a shorter encoding
+    let non_minimal = [
+        vec![OP_PUSHDATA1, 0],
+        vec![1, 0x05],
+        vec![1, 0x81],
+        vec![OP_PUSHDATA1, 2, 0xab, 0xcd],
+        vec![OP_PUSHDATA2, 2, 0, 0xab, 0xcd],
+    ];
+    for bytes in non_minimal.iter() {
+        assert!(!Script(bytes.clone()).has_minimal_pushes(), "{:?}", bytes);
+    }
+
+    // A truncated push is not minimal
+    assert!(!Script(vec![5, 0x01, 0x02]).has_minimal_pushes());
+}
+
+#[test]
+fn op_return_data_single_push() {
+    zebra_test::init();
+
+    let mut bytes = vec![OP_RETURN, 11];
+    bytes.extend_from_slice(b"hello world");
+    assert_eq!(
+        Script(bytes).op_return_data(),
+        Some(b"hello world".to_vec())
+    );
+
+    // Multiple pushes are concatenated
+    let script = Script(vec![OP_RETURN, 2, 0xab, 0xcd, OP_PUSHDATA1, 1, 0xef]);
+    assert_eq!(script.op_return_data(), Some(vec![0xab, 0xcd, 0xef]));
+
+    // Small integer opcodes push their value
+    let script = Script(vec![OP_RETURN, OP_1, OP_16, OP_1NEGATE]);
+    assert_eq!(script.op_return_data(), Some(vec![1, 16, 0x81]));
+
+    assert_eq!(Script(vec![OP_RETURN]).op_return_data(), Some(Vec::new()));
+}
+
+#[test]
+fn op_return_data_rejects_other_scripts() {
+    zebra_test::init();
+
+    // A P2PKH script
+    let mut bytes = vec![OP_DUP, OP_HASH160, 20];
+    bytes.extend_from_slice(&[0x11; 20]);
+    bytes.extend_from_slice(&[OP_EQUALVERIFY, OP_CHECKSIG]);
+    assert_eq!(Script(bytes).op_return_data(), None);
+
+    // OP_RETURN must come first
+    assert_eq!(Script(vec![1, 0xab, OP_RETURN]).op_return_data(), None);
+    assert_eq!(Script(Vec::new()).op_return_data(), None);
+
+    // Non-push opcodes and truncated pushes aren't data
+    assert_eq!(Script(vec![OP_RETURN, OP_DUP]).op_return_data(), None);
+    assert_eq!(Script(vec![OP_RETURN, 5, 0x01]).op_return_data(), None);
+}
+
+#[test]
+fn provably_unspendable_scripts() {
+    zebra_test::init();
+
+    assert!(Script(vec![OP_RETURN]).is_provably_unspendable());
+    assert!(Script(vec![OP_RETURN, 2, 0xab, 0xcd]).is_provably_unspendable());
+    assert!(Script(vec![OP_1; MAX_SCRIPT_SIZE + 1]).is_provably_unspendable());
+
+    assert!(!Script(vec![OP_1; MAX_SCRIPT_SIZE]).is_provably_unspendable());
+    assert!(!Script(vec![1, 0xab, OP_RETURN]).is_provably_unspendable());
+    assert!(!Script(Vec::new()).is_provably_unspendable());
+}
+
+#[test]
+fn witness_program_v0_p2wpkh() {
+    zebra_test::init();
+
+    let mut bytes = vec![OP_0, 20];
+    bytes.extend_from_slice(&[0xab; 20]);
+    assert_eq!(Script(bytes).witness_program(), Some((0, vec![0xab; 20])));
+}
+
+#[test]
+fn witness_program_v1_taproot() {
+    zebra_test::init();
+
+    let mut bytes = vec![OP_1, 32];
+    bytes.extend_from_slice(&[0xcd; 32]);
+    assert_eq!(Script(bytes).witness_program(), Some((1, vec![0xcd; 32])));
+}
+
+#[test]
+fn witness_program_rejects_oversized_program() {
+    zebra_test::init();
+
+    let mut bytes = vec![OP_1, 41];
+    bytes.extend_from_slice(&[0xef; 41]);
+    assert_eq!(Script(bytes).witness_program(), None);
+
+    // Version 0 programs must be 20 or 32 bytes
+    let mut bytes = vec![OP_0, 21];
+    bytes.extend_from_slice(&[0xef; 21]);
+    assert_eq!(Script(bytes).witness_program(), None);
+}
+
+fn script(hex: &str) -> Script {
+    Script(hex::decode(hex).expect("script hex should decode"))
+}
+
+#[test]
+fn classify_standard_scripts() {
+    zebra_test::init();
+
+    // The genesis coinbase output, paying to an uncompressed public key
+    let p2pk = script(
+        "4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac",
+    );
+    assert_eq!(p2pk.classify(), ScriptType::P2PK);
+    assert_eq!(
+        p2pk.extract_address(Network::Mainnet)
+            .map(|address| address.to_string()),
+        Some("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".to_owned())
+    );
+
+    let p2pkh = script("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac");
+    assert_eq!(p2pkh.classify(), ScriptType::P2PKH);
+    assert_eq!(
+        p2pkh
+            .extract_address(Network::Mainnet)
+            .map(|address| address.to_string()),
+        Some("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".to_owned())
+    );
+
+    let p2sh = script("a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87");
+    assert_eq!(p2sh.classify(), ScriptType::P2SH);
+    assert_eq!(
+        p2sh.extract_address(Network::Mainnet)
+            .map(|address| address.to_string()),
+        Some("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy".to_owned())
+    );
+
+    // bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4
+    let p2wpkh = script("0014751e76e8199196d454941c45d1b3a323f1433bd6");
+    assert_eq!(p2wpkh.classify(), ScriptType::P2WPKH);
+    assert_eq!(p2wpkh.extract_address(Network::Mainnet), None);
+
+    // bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3
+    let p2wsh = script("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262");
+    assert_eq!(p2wsh.classify(), ScriptType::P2WSH);
+    assert_eq!(p2wsh.extract_address(Network::Mainnet), None);
+
+    // A coinbase witness commitment
+    let op_return =
+        script("6a24aa21a9ede2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf9");
+    assert_eq!(op_return.classify(), ScriptType::OpReturn);
+    assert_eq!(op_return.extract_address(Network::Mainnet), None);
+}
+
+#[test]
+fn classify_non_standard_scripts() {
+    zebra_test::init();
+
+    // Truncated templates, and a bare multisig output
+    let non_standard = [
+        "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888",
+        "a914b472a266d0bd89c13706a4132ccfb16f7c3b9f87",
+        "0015751e76e8199196d454941c45d1b3a323f1433bd600",
+        "512102678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb651ae",
+        "",
+    ];
+    for hex in non_standard.iter() {
+        let script = script(hex);
+        assert_eq!(script.classify(), ScriptType::NonStandard, "{}", hex);
+        assert_eq!(script.extract_address(Network::Mainnet), None);
+    }
+}
@@ -39,57 +39,59 @@ use super::{CoinbaseData, Input, OutPoint, Script};
 
 // Coinbase inputs include block heights (BIP34). These are not encoded
 // directly, but as a Bitcoin script that pushes the block height to the stack
-// when executed. The script data is otherwise unused. Because we want to
-// *parse* transactions into an internal representation where illegal states are
-// unrepresentable, we need just enough parsing of Bitcoin scripts to parse the
-// coinbase height and split off the rest of the (inert) coinbase data.
-// TODO: Throw error for heights below that at which BIP34 was activated.
-fn parse_coinbase_height(
-    mut data: Vec<u8>,
-) -> Result<(block::Height, CoinbaseData), SerializationError> {
+// when executed. The script data is otherwise unused, so we keep the whole
+// script in the `CoinbaseData`, and just parse enough of it to find the height.
+//
+// The height is a minimally encoded little-endian script number. Heights 1
+// through 16 are pushed using the OP_1 to OP_16 opcodes, and larger heights
+// using a 1 to 5 byte push opcode.
+pub(super) fn parse_coinbase_height(data: &[u8]) -> Result<block::Height, SerializationError> {
     use block::Height;
-    match (data.get(0), data.len()) {
-        // These options don't apply to Bitcoin, since BIP 34 wasn't activated until well after block 65535.
-        // Blocks 1 through 16 inclusive encode block height with OP_N opcodes.
-        // (Some(op_n @ 0x51..=0x60), len) if len >= 1 => Ok((
-        //     Height((op_n - 0x50) as u32),
-        //     CoinbaseData(data.split_off(1)),
-        // )),
-        // // Blocks 17 through 128 exclusive encode block height with the `0x01` opcode.
-        // // The Bitcoin encoding requires that the most significant byte is below 0x80.
-        // (Some(0x01), len) if len >= 2 && data[1] < 0x80 => {
-        //     Ok((Height(data[1] as u32), CoinbaseData(data.split_off(2))))
-        // }
-        // // Blocks 128 through 32768 exclusive encode block height with the `0x02` opcode.
-        // // The Bitcoin encoding requires that the most significant byte is below 0x80.
-        // (Some(0x02), len) if len >= 3 && data[2] < 0x80 => Ok((
-        //     Height(data[1] as u32 + ((data[2] as u32) << 8)),
-        //     CoinbaseData(data.split_off(3)),
-        // )),
-
-        // Blocks 65536 through 2**23 exclusive encode block height with the `0x03` opcode.
-        // The Bitcoin encoding requires that the most significant byte is below 0x80.
-        (Some(0x03), len) if len >= 4 && data[3] < 0x80 => Ok((
-            Height(data[1] as u32 + ((data[2] as u32) << 8) + ((data[3] as u32) << 16)),
-            CoinbaseData(data.split_off(4)),
-        )),
-        // As noted above, this is included for completeness.
-        // The Bitcoin encoding requires that the most significant byte is below 0x80.
-        (Some(0x04), len) if len >= 5 && data[4] < 0x80 => {
-            let h = data[1] as u32
-                + ((data[2] as u32) << 8)
-                + ((data[3] as u32) << 16)
-                + ((data[4] as u32) << 24);
-            if h <= Height::MAX.0 {
-                Ok((Height(h), CoinbaseData(data.split_off(5))))
-            } else {
-                Err(SerializationError::Parse("Invalid block height"))
-            }
-        }
-        _ => Err(SerializationError::Parse(
+
+    let (&push_len, rest) = data.split_first().ok_or(SerializationError::Parse(
+        "coinbase data is missing the BIP34 height",
+    ))?;
+    // OP_1 through OP_16
+    if (0x51..=0x60).contains(&push_len) {
+        return Ok(Height(u32::from(push_len - 0x50)));
+    }
+    let push_len = push_len as usize;
+    if !(1..=5).contains(&push_len) {
+        return Err(SerializationError::Parse(
             "Could not parse BIP34 height in coinbase data",
-        )),
+        ));
+    }
+    let bytes = rest.get(..push_len).ok_or(SerializationError::Parse(
+        "BIP34 height in coinbase data is truncated",
+    ))?;
+
+    let last = bytes[push_len - 1];
+    // The most significant bit is the sign bit, and heights can't be negative
+    if last & 0x80 != 0 {
+        return Err(SerializationError::Parse("Invalid block height"));
+    }
+    // A zero most significant byte is only allowed if it holds the sign bit
+    if last == 0 && (push_len == 1 || bytes[push_len - 2] & 0x80 == 0) {
+        return Err(SerializationError::Parse(
+            "BIP34 height in coinbase data is not minimally encoded",
+        ));
     }
+
+    let height = bytes
+        .iter()
+        .rev()
+        .fold(0u64, |height, &byte| (height << 8) | u64::from(byte));
+    // Heights 1 through 16 must use OP_1 to OP_16
+    if (1..=16).contains(&height) {
+        return Err(SerializationError::Parse(
+            "BIP34 height in coinbase data is not minimally encoded",
+        ));
+    }
+    if height > u64::from(Height::MAX.0) {
+        return Err(SerializationError::Parse("Invalid block height"));
+    }
+
+    Ok(Height(height as u32))
 }
 
 // fn coinbase_height_len(height: block::Height) -> usize {
@@ -185,11 +187,16 @@ impl BitcoinDeserialize for Input {
                 return Err(SerializationError::Parse("wrong index in coinbase"));
             }
             let len = CompactInt::bitcoin_deserialize(&mut reader)?.value();
-            if len > 100 {
+            if len > super::MAX_COINBASE_DATA_LEN as u64 {
                 return Err(SerializationError::Parse("coinbase has too much data"));
             }
             let mut data = Vec::with_capacity(len as usize);
             (&mut reader).take(len).read_to_end(&mut data)?;
+            if data.len() as u64 != len {
+                return Err(SerializationError::Parse(
+                    "coinbase data is shorter than its length",
+                ));
+            }
             // let (height, data) = parse_coinbase_height(data)?;
             let sequence = reader.read_u32::<LittleEndian>()?;
             // TODO: implement height parsing starting at block 227,835
@@ -204,7 +211,8 @@ impl BitcoinDeserialize for Input {
                     hash: transaction::Hash(bytes),
                     index: reader.read_u32::<LittleEndian>()?,
                 },
-                unlock_script: Script::bitcoin_deserialize(&mut reader)?,
+                unlock_script: Script::bitcoin_deserialize(&mut reader)
+                    .map_err(|e| e.context("unlock_script"))?,
                 sequence: reader.read_u32::<LittleEndian>()?,
             })
         }
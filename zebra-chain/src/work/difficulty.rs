@@ -11,7 +11,7 @@
 //! the actual work represented by the block header hash.
 #![allow(clippy::unit_arg)]
 
-use crate::{block, parameters::Network};
+use crate::block;
 use crate::{BitcoinDeserialize, BitcoinSerialize, SerializationError};
 use bitcoin_serde_derive::{BtcDeserialize, BtcSerialize};
 
@@ -137,6 +137,17 @@ impl Work {
     pub fn as_u128(self) -> u128 {
         self.0
     }
+
+    /// Returns the `Work` for a raw `u128` value, such as a stored cumulative
+    /// chain work.
+    pub fn from_u128(work: u128) -> Work {
+        Work(work)
+    }
+
+    /// Returns the total of `self` and `rhs`, or `None` on overflow.
+    pub fn checked_add(self, rhs: Work) -> Option<Work> {
+        self.0.checked_add(rhs.0).map(Work)
+    }
 }
 
 impl fmt::Debug for Work {
@@ -298,37 +309,6 @@ impl ExpandedDifficulty {
         U256::from_little_endian(&hash.0).into()
     }
 
-    /// Returns the easiest target difficulty allowed on `network`.
-    ///
-    /// See `PoWLimit` in the Zcash specification.
-    pub fn target_difficulty_limit(network: Network) -> ExpandedDifficulty {
-        let limit: U256 = match network {
-            /* 2^243 - 1 */
-            Network::Mainnet => U256::from_str_radix(
-                "00000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
-                16,
-            )
-            .expect("Mainnet difficulty is valid hex number"),
-            /* 2^251 - 1 */
-            Network::Testnet => U256::from_str_radix(
-                "00000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
-                16,
-            )
-            .expect("Testnet difficulty is valid hex number"),
-        };
-
-        // `zcashd` converts the PoWLimit into a compact representation before
-        // using it to perform difficulty filter checks.
-        //
-        // The Zcash specification converts to compact for the default difficulty
-        // filter, but not for testnet minimum difficulty blocks. (ZIP 205 and
-        // ZIP 208 don't specify this conversion either.) See #1277 for details.
-        ExpandedDifficulty(limit)
-            .to_compact()
-            .to_expanded()
-            .expect("difficulty limits are valid expanded values")
-    }
-
     /// Calculate the CompactDifficulty for an expanded difficulty.
     ///
     /// See `ToCompact()` in the Zcash Specification, and `GetCompact()`
@@ -341,7 +321,7 @@ impl ExpandedDifficulty {
     /// `ExpandedDifficulty` values are generated in two ways:
     ///   * conversion from `CompactDifficulty` values, which rejects zeroes, and
     ///   * difficulty adjustment calculations, which impose a non-zero minimum
-    ///     `target_difficulty_limit`.
+    ///     `Network::pow_limit`.
     ///
     /// Neither of these methods yield zero values.
     pub fn to_compact(&self) -> CompactDifficulty {
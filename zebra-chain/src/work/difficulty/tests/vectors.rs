@@ -2,7 +2,10 @@ use color_eyre::eyre::eyre;
 use color_eyre::eyre::Report;
 
 use crate::serialization::BitcoinDeserialize;
-use crate::{block::Block, parameters::NetworkUpgrade};
+use crate::{
+    block::Block,
+    parameters::{Network, NetworkUpgrade},
+};
 
 use super::super::*;
 
@@ -313,9 +316,9 @@ fn block_difficulty_for_network(network: Network) -> Result<(), Report> {
         /// SPANDOC: Check the PoWLimit for block {?height, ?network, ?threshold, ?hash}
         {
             // the consensus rule
-            assert!(threshold <= ExpandedDifficulty::target_difficulty_limit(network));
+            assert!(threshold <= network.pow_limit());
             // check that ordering is transitive, we checked `hash <= threshold` above
-            assert!(hash <= ExpandedDifficulty::target_difficulty_limit(network));
+            assert!(hash <= network.pow_limit());
         }
 
         /// SPANDOC: Check compact round-trip for block {?height, ?network}
@@ -385,7 +388,7 @@ fn genesis_block_difficulty_for_network(network: Network) -> Result<(), Report>
     {
         assert_eq!(
             threshold,
-            ExpandedDifficulty::target_difficulty_limit(network),
+            network.pow_limit(),
             "genesis block difficulty thresholds must be equal to the PoWLimit"
         );
     }
@@ -486,12 +489,12 @@ fn check_testnet_minimum_difficulty_block(height: block::Height) -> Result<(), R
 
     /// SPANDOC: Check that the testnet minimum difficulty is the PoWLimit {?height, ?threshold, ?hash}
     {
-        assert_eq!(threshold, ExpandedDifficulty::target_difficulty_limit(Network::Testnet),
+        assert_eq!(threshold, Network::Testnet.pow_limit(),
                    "testnet minimum difficulty thresholds should be equal to the PoWLimit. Hint: Blocks with large gaps are allowed to have the minimum difficulty, but it's not required.");
         // all blocks pass the minimum difficulty threshold, even if they aren't minimum
         // difficulty blocks, because it's the lowest permitted difficulty
         assert!(
-            hash <= ExpandedDifficulty::target_difficulty_limit(Network::Testnet),
+            hash <= Network::Testnet.pow_limit(),
             "testnet minimum difficulty hashes must be less than the PoWLimit"
         );
     }
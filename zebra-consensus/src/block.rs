@@ -37,6 +37,8 @@ mod subsidy;
 #[cfg(test)]
 mod tests;
 
+pub use check::check_block;
+
 /// Asynchronous block verification.
 #[derive(Debug)]
 pub struct BlockVerifier<S> {
@@ -59,11 +61,6 @@ pub enum VerifyBlockError {
         source: BlockError,
     },
 
-    // #[error(transparent)]
-    // Equihash {
-    //     #[from]
-    //     source: equihash::Error,
-    // },
     #[error(transparent)]
     Time(zebra_chain::block::BlockTimeError),
 
@@ -72,6 +69,12 @@ pub enum VerifyBlockError {
 
     #[error("invalid transaction")]
     Transaction(#[source] TransactionError),
+
+    #[error("unable to look up the outputs spent by block {hash} during block verification")]
+    SpentOutputs { source: BoxError, hash: block::Hash },
+
+    #[error("unable to look up the parent height of block {hash} during block verification")]
+    ParentHeight { source: BoxError, hash: block::Hash },
 }
 
 impl<S> BlockVerifier<S>
@@ -136,19 +139,34 @@ where
                 _ => unreachable!("wrong response to Request::Depth"),
             }
 
+            // Blocks can claim any height in their coinbase, so the height
+            // comes from the parent block in the state.
+            tracing::trace!("looking up the height of the parent block");
+            let parent_height = match state_service
+                .ready_and()
+                .await
+                .map_err(|source| VerifyBlockError::ParentHeight { source, hash })?
+                .call(zs::Request::AwaitHeight(block.previous_hash()))
+                .await
+                .map_err(|source| VerifyBlockError::ParentHeight { source, hash })?
+            {
+                zs::Response::Height(parent_height) => parent_height,
+                _ => unreachable!("wrong response to Request::AwaitHeight"),
+            };
+
             tracing::trace!("performing block checks");
-            let height = block
-                .coinbase_height()
-                .ok_or(BlockError::MissingHeight(hash))?;
-            if height > block::Height::MAX {
-                Err(BlockError::MaxHeight(height, hash, block::Height::MAX))?;
-            }
+            let height = Block::height_after(parent_height).ok_or(BlockError::MaxHeight(
+                parent_height,
+                hash,
+                block::Height::MAX,
+            ))?;
 
             // Do the difficulty checks first, to raise the threshold for
             // attacks that use any other fields.
+            // Bitcoin headers don't have an Equihash solution, the header
+            // hash is the proof of work.
             check::difficulty_is_valid(&block.header, network, &height, &hash)?;
-            // FIXME: replace
-            // check::equihash_solution_is_valid(&block.header)?;
+            check::version_is_valid(&block.header, network, &height)?;
 
             // Next, check the Merkle root validity, to ensure that
             // the header binds to the transactions in the blocks.
@@ -170,11 +188,11 @@ where
             check::time_is_valid_at(&block.header, now, &height, &hash)
                 .map_err(VerifyBlockError::Time)?;
             check::coinbase_is_first(&block)?;
-            check::subsidy_is_valid(&block, network)?;
+            check::coinbase_height_is_valid(&block, network, &height)?;
 
             let mut async_checks = FuturesUnordered::new();
 
-            let known_utxos = new_outputs(&block, &transaction_hashes);
+            let known_utxos = new_outputs(&block, height, &transaction_hashes);
             for transaction in &block.transactions {
                 let rsp = transaction_verifier
                     .ready_and()
@@ -195,6 +213,13 @@ where
                 result.map_err(VerifyBlockError::Transaction)?;
             }
 
+            // The miner fees depend on the outputs spent by the block, which
+            // the transaction verifier has already looked up
+            let spent_outputs = spent_outputs(&block, &known_utxos, &mut state_service)
+                .await
+                .map_err(|source| VerifyBlockError::SpentOutputs { source, hash })?;
+            check::subsidy_is_valid(&block, network, &spent_outputs)?;
+
             // Update the metrics after all the validation is finished
             tracing::trace!("verified block");
             metrics::gauge!("block.verified.block.height", height.0 as _);
@@ -230,21 +255,61 @@ where
     }
 }
 
-/// Compute an index of newly created transparent outputs, given a block and a
-/// list of precomputed transaction hashes.
+/// Returns the outputs spent by the non-coinbase transactions in `block`.
+///
+/// Outputs created earlier in the block are taken from `known_utxos`, and the
+/// rest are looked up in the state.
+async fn spent_outputs<S>(
+    block: &Block,
+    known_utxos: &HashMap<transparent::OutPoint, zs::Utxo>,
+    state_service: &mut S,
+) -> Result<HashMap<transparent::OutPoint, transparent::Output>, BoxError>
+where
+    S: Service<zs::Request, Response = zs::Response, Error = BoxError>,
+{
+    let mut spent_outputs = HashMap::new();
+
+    for transaction in block.transactions.iter().filter(|tx| !tx.is_coinbase()) {
+        for input in transaction.inputs() {
+            let outpoint = match input {
+                transparent::Input::PrevOut { outpoint, .. } => *outpoint,
+                transparent::Input::Coinbase { .. } => continue,
+            };
+
+            let utxo = match known_utxos.get(&outpoint) {
+                Some(utxo) => utxo.clone(),
+                None => match state_service
+                    .ready_and()
+                    .await?
+                    .call(zs::Request::AwaitUtxo(outpoint))
+                    .await?
+                {
+                    zs::Response::Utxo(utxo) => utxo,
+                    _ => unreachable!("AwaitUtxo always responds with Utxo"),
+                },
+            };
+            spent_outputs.insert(outpoint, utxo.output);
+        }
+    }
+
+    Ok(spent_outputs)
+}
+
+/// Compute an index of newly created transparent outputs, given a block, its
+/// height, and a list of precomputed transaction hashes.
 fn new_outputs(
     block: &Block,
+    height: block::Height,
     transaction_hashes: &[transaction::Hash],
 ) -> Arc<HashMap<transparent::OutPoint, zs::Utxo>> {
     let mut new_outputs = HashMap::default();
-    let height = block.coinbase_height().expect("block has coinbase height");
     for (transaction, hash) in block
         .transactions
         .iter()
         .zip(transaction_hashes.iter().cloned())
     {
         let from_coinbase = transaction.is_coinbase();
-        for (index, output) in transaction.outputs.iter().cloned().enumerate() {
+        for (index, output) in transaction.outputs().iter().cloned().enumerate() {
             let index = index as u32;
             new_outputs.insert(
                 transparent::OutPoint { hash, index },
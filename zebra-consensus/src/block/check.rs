@@ -1,20 +1,70 @@
 //! Consensus check functions
 
+use std::{cmp::min, collections::HashMap};
+
 use chrono::{DateTime, Utc};
 
 use zebra_chain::{
+    amount::Amount,
     block::{Block, Hash, Header, Height},
-    parameters::Network,
-    transaction,
-    work::difficulty::ExpandedDifficulty,
+    parameters::{Network, NetworkUpgrade, POW_AVERAGING_WINDOW},
+    transaction, transparent,
+    work::difficulty::CompactDifficulty,
 };
 
 use crate::error::*;
 
 use super::subsidy;
 
+/// Runs all the context-free checks on `block` at `height`, returning the first
+/// failure.
+///
+/// `height` must come from the chain (the parent block's height + 1), because
+/// a block can claim any height in its coinbase.
+///
+/// The checks run in this order, so that cheap checks which raise the cost of
+/// an attack run first:
+///   1. difficulty (PoWLimit and the difficulty filter),
+///   2. block version,
+///   3. Merkle root and duplicate transactions,
+///   4. block time, against the node's local clock (`now`),
+///   5. coinbase position and BIP34 coinbase height.
+///
+/// Transaction hashes are computed once, for the Merkle root check.
+///
+/// The block subsidy depends on the outputs spent by the block, so it is
+/// checked separately, by [`subsidy_is_valid`].
+pub fn check_block(
+    block: &Block,
+    height: Height,
+    network: Network,
+    now: DateTime<Utc>,
+) -> Result<(), BlockError> {
+    let hash = block.hash();
+    if height > Height::MAX {
+        Err(BlockError::MaxHeight(height, hash, Height::MAX))?;
+    }
+
+    difficulty_is_valid(&block.header, network, &height, &hash)?;
+    version_is_valid(&block.header, network, &height)?;
+
+    let transaction_hashes = block
+        .transactions
+        .iter()
+        .map(|tx| tx.hash())
+        .collect::<Vec<_>>();
+    merkle_root_validity(block, &transaction_hashes)?;
+
+    time_is_valid_at(&block.header, now, &height, &hash)?;
+    coinbase_is_first(block)?;
+    coinbase_height_is_valid(block, network, &height)?;
+
+    Ok(())
+}
+
 /// Returns `Ok(())` if there is exactly one coinbase transaction in `Block`,
-/// and that coinbase transaction is the first transaction in the block.
+/// that coinbase transaction is the first transaction in the block, and its
+/// coinbase data is between 2 and 100 bytes long.
 ///
 /// "The first (and only the first) transaction in a block is a coinbase
 /// transaction, which collects and spends any miner subsidy and transaction
@@ -33,6 +83,47 @@ pub fn coinbase_is_first(block: &Block) -> Result<(), BlockError> {
     if rest.any(|tx| tx.contains_coinbase_input()) {
         return Err(TransactionError::CoinbaseInputFound)?;
     }
+    if let Some(transparent::Input::Coinbase { data, .. }) = first.inputs().get(0) {
+        let len = data.as_ref().len();
+        if !(transparent::MIN_COINBASE_DATA_LEN..=transparent::MAX_COINBASE_DATA_LEN).contains(&len)
+        {
+            return Err(TransactionError::CoinbaseDataLength(len))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns `Ok(())` if the coinbase data in `block` starts with `height`, or
+/// BIP 34 isn't active at `height` on `network`.
+///
+/// After BIP 34 activates, the first item pushed by the coinbase input must be
+/// the block height, so that coinbase transactions have unique hashes. The
+/// height is parsed leniently during deserialization, so it is enforced here.
+///
+/// `height` must be the height of `block` in the chain, based on its parent
+/// block, rather than the height claimed by its coinbase.
+pub fn coinbase_height_is_valid(
+    block: &Block,
+    network: Network,
+    height: &Height,
+) -> Result<(), BlockError> {
+    if !NetworkUpgrade::BIP34.is_active_at(network, *height) {
+        return Ok(());
+    }
+
+    let coinbase_height = block
+        .transactions
+        .get(0)
+        .and_then(|tx| tx.inputs().get(0))
+        .and_then(|input| match input {
+            transparent::Input::Coinbase { data, .. } => data.bip34_height().ok(),
+            _ => None,
+        });
+
+    if coinbase_height != Some(*height) {
+        Err(BlockError::WrongCoinbaseHeight(*height, coinbase_height))?;
+    }
 
     Ok(())
 }
@@ -59,13 +150,13 @@ pub fn difficulty_is_valid(
 
     // The PowLimit check is part of `Threshold()` in the spec, but it doesn't
     // actually depend on any previous blocks.
-    if difficulty_threshold > ExpandedDifficulty::target_difficulty_limit(network) {
+    if difficulty_threshold > network.pow_limit() {
         Err(BlockError::TargetDifficultyLimit(
             *height,
             *hash,
             difficulty_threshold,
             network,
-            ExpandedDifficulty::target_difficulty_limit(network),
+            network.pow_limit(),
         ))?;
     }
 
@@ -88,20 +179,172 @@ pub fn difficulty_is_valid(
     Ok(())
 }
 
-// /// Returns `Ok(())` if the `EquihashSolution` is valid for `header`
-// pub fn equihash_solution_is_valid(header: &Header) -> Result<(), equihash::Error> {
-//     todo!()
-//     // header.solution.check(&header)
-// }
+/// Returns `Ok(())` if the version in `header` is allowed at `height` on
+/// `network`.
+///
+/// BIP 34, BIP 66, and BIP 65 were deployed by raising the block version, and
+/// are now buried deployments: after each activation height, blocks must have
+/// at least version 2, 3, and 4 respectively.
+pub fn version_is_valid(
+    header: &Header,
+    network: Network,
+    height: &Height,
+) -> Result<(), BlockError> {
+    let minimum_version = if NetworkUpgrade::BIP65.is_active_at(network, *height) {
+        4
+    } else if NetworkUpgrade::BIP66.is_active_at(network, *height) {
+        3
+    } else if NetworkUpgrade::BIP34.is_active_at(network, *height) {
+        2
+    } else {
+        1
+    };
+
+    if header.version < minimum_version {
+        Err(BlockError::WrongVersion(
+            *height,
+            header.version,
+            minimum_version,
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Returns `Ok(())` if `header` has valid proof of work.
+///
+/// Recomputes the double-SHA256 hash of `header`, and checks that:
+///   - the difficulty threshold is a valid compact value, no easier than the
+///     target difficulty limit for `network` (PoWLimit), and
+///   - the hash, as a little-endian u256, is less than or equal to the
+///     threshold.
+///
+/// Unlike [`difficulty_is_valid`], this check doesn't need the block height,
+/// so it can be used on headers before their block has been downloaded.
+pub fn pow_is_valid(header: &Header, network: Network) -> Result<(), BlockError> {
+    let hash = Hash::from(header);
+    let difficulty_threshold = header
+        .difficulty_threshold
+        .to_expanded()
+        .filter(|threshold| *threshold <= network.pow_limit())
+        .ok_or(BlockError::InvalidHeaderDifficulty(hash, network))?;
+
+    if hash > difficulty_threshold {
+        Err(BlockError::InsufficientProofOfWork(
+            hash,
+            difficulty_threshold,
+            network,
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Returns the difficulty threshold required by Bitcoin's difficulty
+/// adjustment for the block at `height`.
+///
+/// `previous_headers` are the headers of the blocks before `height`, in chain
+/// order, ending with the parent block.
+///
+/// Every [`POW_AVERAGING_WINDOW`] blocks, the threshold is retargeted, so that
+/// the previous window would have taken two weeks. The actual timespan of the
+/// window is clamped to between a quarter and four times the target timespan,
+/// and the new threshold is capped at the PoWLimit. Like Bitcoin Core, the
+/// timespan is measured from the first block in the window to the parent
+/// block. All other blocks use their parent's threshold.
+///
+/// Testnet minimum difficulty blocks are not supported yet.
+///
+/// Returns `None` if the parent's difficulty threshold is invalid.
+///
+/// # Panics
+///
+/// If `previous_headers` is empty, or if `height` is a retarget height and
+/// `previous_headers` contains fewer than [`POW_AVERAGING_WINDOW`] headers.
+pub fn expected_difficulty_threshold(
+    network: Network,
+    height: Height,
+    previous_headers: &[Header],
+) -> Option<CompactDifficulty> {
+    let parent = previous_headers
+        .last()
+        .expect("the genesis block doesn't have a difficulty adjustment");
+
+    if height.0 as usize % POW_AVERAGING_WINDOW != 0 {
+        return Some(parent.difficulty_threshold);
+    }
+
+    assert!(
+        previous_headers.len() >= POW_AVERAGING_WINDOW,
+        "retargeting requires the headers for the previous difficulty adjustment window"
+    );
+    let first = &previous_headers[previous_headers.len() - POW_AVERAGING_WINDOW];
+
+    let target_timespan =
+        NetworkUpgrade::averaging_window_timespan_for_height(network, height).num_seconds();
+    let actual_timespan = (parent.time.0 - first.time.0)
+        .num_seconds()
+        .max(target_timespan / 4)
+        .min(target_timespan * 4);
+
+    let threshold = parent.difficulty_threshold.to_expanded()? * actual_timespan / target_timespan;
+    let threshold = min(network.pow_limit(), threshold);
+
+    Some(threshold.to_compact())
+}
+
+/// Returns `Ok(())` if the difficulty threshold in `header` matches Bitcoin's
+/// difficulty adjustment.
+///
+/// See [`expected_difficulty_threshold`] for details, and the requirements on
+/// `previous_headers`.
+///
+/// If the difficulty threshold is unexpected, returns an error containing
+/// `height` and `hash`.
+pub fn difficulty_adjustment(
+    header: &Header,
+    network: Network,
+    height: &Height,
+    hash: &Hash,
+    previous_headers: &[Header],
+) -> Result<(), BlockError> {
+    let expected = expected_difficulty_threshold(network, *height, previous_headers)
+        .ok_or(BlockError::InvalidDifficulty(*height, *hash))?;
 
-/// Returns `Ok(())` if the block subsidy and miner fees in `block` are valid for `network`
+    if header.difficulty_threshold != expected {
+        Err(BlockError::UnexpectedDifficulty(
+            *height,
+            *hash,
+            header.difficulty_threshold,
+            expected,
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Returns `Ok(())` if the coinbase transaction in `block` claims no more than
+/// the block subsidy for `network`, plus the transaction fees in `block`.
 ///
-/// [3.9]: https://zips.z.cash/protocol/protocol.pdf#subsidyconcepts
-pub fn subsidy_is_valid(block: &Block, network: Network) -> Result<(), BlockError> {
+/// `spent_outputs` must contain every output spent by the non-coinbase
+/// transactions in `block`, so the fees can be calculated.
+pub fn subsidy_is_valid(
+    block: &Block,
+    network: Network,
+    spent_outputs: &HashMap<transparent::OutPoint, transparent::Output>,
+) -> Result<(), BlockError> {
     let height = block.coinbase_height().ok_or(SubsidyError::NoCoinbase)?;
     let coinbase = block.transactions.get(0).ok_or(SubsidyError::NoCoinbase)?;
 
-    // TODO: the sum of the coinbase transaction outputs must be less than or equal to the block subsidy plus transaction fees
+    let subsidy = subsidy::general::block_subsidy(height, network).map_err(SubsidyError::from)?;
+    let fees = subsidy::general::miner_fees(block, spent_outputs)?;
+    let claimed = Amount::sum(coinbase.outputs().iter().map(|output| output.value))
+        .map_err(SubsidyError::from)?;
+
+    if claimed > (subsidy + fees).map_err(SubsidyError::from)? {
+        Err(SubsidyError::InvalidMinerReward)?;
+    }
+
     Ok(())
 }
 
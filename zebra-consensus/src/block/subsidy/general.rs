@@ -2,17 +2,17 @@
 //!
 //! [7.7]: https://zips.z.cash/protocol/protocol.pdf#subsidies
 
-use std::convert::TryFrom;
+use std::{collections::HashMap, convert::TryFrom};
 
 use zebra_chain::{
     amount::{Amount, Error, NonNegative},
-    block::Height,
+    block::{Block, Height},
     parameters::Network,
     transaction::Transaction,
     transparent,
 };
 
-use crate::parameters::subsidy::*;
+use crate::{error::SubsidyError, parameters::subsidy::*};
 
 /// The `BlockSubsidy(height)`
 ///
@@ -23,10 +23,41 @@ pub fn block_subsidy(height: Height, network: Network) -> Result<Amount<NonNegat
     if halvings >= 64 {
         return Amount::try_from(0);
     }
-    let subsidy = MAX_BLOCK_SUBSIDY >> (halvings as u64);
+    let subsidy = MAX_BLOCK_SUBSIDY >> halvings;
     Amount::try_from(subsidy)
 }
 
+/// Returns the total fees paid by the non-coinbase transactions in `block`.
+///
+/// The value of each transaction input is looked up in `spent_outputs`, which
+/// must contain every output spent by the block.
+pub fn miner_fees(
+    block: &Block,
+    spent_outputs: &HashMap<transparent::OutPoint, transparent::Output>,
+) -> Result<Amount<NonNegative>, SubsidyError> {
+    let mut fees = Amount::try_from(0)?;
+
+    for transaction in block.transactions.iter().filter(|tx| !tx.is_coinbase()) {
+        let mut spent = Amount::try_from(0);
+        for input in transaction.inputs().iter() {
+            // Coinbase inputs outside the coinbase transaction are rejected
+            // by `coinbase_is_first`
+            if let transparent::Input::PrevOut { outpoint, .. } = input {
+                let output = spent_outputs
+                    .get(outpoint)
+                    .ok_or(SubsidyError::SpentOutputNotFound(*outpoint))?;
+                spent += output.value;
+            }
+        }
+
+        let created = Amount::sum(transaction.outputs().iter().map(|output| output.value))?;
+
+        fees = (fees + (spent - created)?)?;
+    }
+
+    Ok(fees)
+}
+
 /// Returns a list of outputs in `Transaction`, which have a value equal to `Amount`.
 pub fn find_output_with_amount(
     transaction: &Transaction,
@@ -34,7 +65,7 @@ pub fn find_output_with_amount(
 ) -> Vec<transparent::Output> {
     // TODO: shielded coinbase - Heartwood
     transaction
-        .outputs
+        .outputs()
         .iter()
         .filter(|o| o.value == amount)
         .cloned()
@@ -58,16 +89,20 @@ mod test {
 
     fn block_subsidy_for_network(network: Network) -> Result<(), Report> {
         // The initial block subsidy is 50 BTC
-        // TODO: troubleshoot `overflowing_literal`; this should be an i64 but is an i32 for some reason
-        // assert_eq!(
-        //     Amount::try_from(5_000_000_000),
-        //     block_subsidy(Height(0), network)
-        // );
-        // // After the first halving, it's 25 BTC
-        // assert_eq!(
-        //     Amount::try_from(2_500_000_000),
-        //     block_subsidy(HALVING_INTERVAL, network)
-        // );
+        assert_eq!(MAX_BLOCK_SUBSIDY, 5_000_000_000);
+        assert_eq!(
+            Amount::try_from(MAX_BLOCK_SUBSIDY),
+            block_subsidy(Height(0), network)
+        );
+        assert_eq!(
+            Amount::try_from(MAX_BLOCK_SUBSIDY),
+            block_subsidy(Height(HALVING_INTERVAL.0 - 1), network)
+        );
+        // After the first halving, it's 25 BTC
+        assert_eq!(
+            Amount::try_from(2_500_000_000_i64),
+            block_subsidy(HALVING_INTERVAL, network)
+        );
         // After the second halving, it's 12.5 BTC
         assert_eq!(
             Amount::try_from(1_250_000_000),
@@ -2,18 +2,22 @@
 
 use super::*;
 
-use std::sync::Arc;
+use std::{collections::HashMap, convert::TryFrom, sync::Arc};
 
 use chrono::Utc;
 use color_eyre::eyre::{eyre, Report};
 use once_cell::sync::Lazy;
+use proptest::prelude::*;
 use tower::buffer::Buffer;
 
 use zebra_chain::{
-    block::{self, Block, Height},
-    parameters::{Network, NetworkUpgrade},
+    amount::{Amount, CheckedSum, NonNegative},
+    block::{self, Block, Header, Height},
+    parameters::{Network, NetworkUpgrade, POW_AVERAGING_WINDOW},
     serialization::{BitcoinDeserialize, BitcoinDeserializeInto},
-    work::difficulty::{ExpandedDifficulty, INVALID_COMPACT_DIFFICULTY},
+    transaction::Transaction,
+    transparent::{self, OutPoint, Script},
+    work::difficulty::{CompactDifficulty, ExpandedDifficulty, INVALID_COMPACT_DIFFICULTY},
 };
 use zebra_test::transcript::{TransError, Transcript};
 
@@ -42,6 +46,7 @@ static INVALID_TIME_BLOCK_TRANSCRIPT: Lazy<Vec<(Arc<Block>, Result<block::Hash,
             .ok_or_else(|| eyre!("overflow when calculating 3 hours in the future"))
             .unwrap();
         block.header.time.0 = three_hours_in_the_future;
+        block.header.clear_cached_hash();
 
         vec![(Arc::new(block), Err(TransError::Any))]
     });
@@ -54,6 +59,7 @@ static INVALID_HEADER_SOLUTION_TRANSCRIPT: Lazy<
 
     // Change nonce to something invalid
     block.header.nonce = 0;
+    block.header.clear_cached_hash();
 
     vec![(Arc::new(block), Err(TransError::Any))]
 });
@@ -151,6 +157,58 @@ fn coinbase_is_first_for_historical_blocks() -> Result<(), Report> {
     Ok(())
 }
 
+/// Returns a copy of `block` with its coinbase data replaced by `data`, and
+/// its Merkle root updated to match.
+fn with_coinbase_data(block: &Block, data: Vec<u8>) -> Block {
+    let mut block = block.clone();
+
+    let coinbase = &block.transactions[0];
+    let sequence = match coinbase.inputs()[0] {
+        transparent::Input::Coinbase { sequence, .. } => sequence,
+        _ => unreachable!("the first transaction in a block is a coinbase"),
+    };
+    let coinbase = Transaction::new(
+        coinbase.version(),
+        vec![transparent::Input::Coinbase {
+            height: None,
+            data: transparent::CoinbaseData(data),
+            sequence,
+        }],
+        coinbase.outputs().to_vec(),
+        coinbase.locktime(),
+    );
+    block.transactions[0] = Arc::new(coinbase);
+
+    block.header.merkle_root = block.transactions.iter().map(|tx| tx.hash()).collect();
+    block.header.clear_cached_hash();
+    block
+}
+
+#[test]
+fn coinbase_data_length_validation() -> Result<(), Report> {
+    zebra_test::init();
+    use crate::error::*;
+
+    let block = zebra_test::vectors::BLOCK_MAINNET_1_BYTES.bitcoin_deserialize_into::<Block>()?;
+
+    for &len in &[2, 100] {
+        check::coinbase_is_first(&with_coinbase_data(&block, vec![0x51; len]))
+            .expect("coinbase data between 2 and 100 bytes is valid");
+    }
+
+    for &len in &[0, 1, 101] {
+        let result = check::coinbase_is_first(&with_coinbase_data(&block, vec![0x51; len]));
+        assert_eq!(
+            result,
+            Err(BlockError::Transaction(
+                TransactionError::CoinbaseDataLength(len)
+            ))
+        );
+    }
+
+    Ok(())
+}
+
 #[test]
 // TODO: Add back testnet checks
 fn difficulty_is_valid_for_historical_blocks() -> Result<(), Report> {
@@ -219,7 +277,7 @@ fn difficulty_validation_failure() -> Result<(), Report> {
     //     hash,
     //     difficulty_threshold,
     //     Network::Mainnet,
-    //     ExpandedDifficulty::target_difficulty_limit(Network::Mainnet),
+    //     Network::Mainnet.pow_limit(),
     // );
     // assert_eq!(expected, result);
 
@@ -242,33 +300,477 @@ fn difficulty_validation_failure() -> Result<(), Report> {
     Ok(())
 }
 
-// TODO: Replace with check on sha256 proof of work
-// #[test]
-// fn equihash_is_valid_for_historical_blocks() -> Result<(), Report> {
-//     zebra_test::init();
+/// The Bitcoin testnet3 genesis block header.
+const TESTNET_GENESIS_HEADER_HEX: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4adae5494dffff001d1aa4ae18";
 
-//     let block_iter = zebra_test::vectors::BLOCKS.iter();
+#[test]
+fn version_is_valid_for_historical_blocks() -> Result<(), Report> {
+    zebra_test::init();
 
-//     for block in block_iter {
-//         let block = block
-//             .bitcoin_deserialize_into::<Block>()
-//             .expect("block is structurally valid");
+    for (&height, block) in zebra_test::vectors::MAINNET_BLOCKS.iter() {
+        let header = block[..Header::len()].bitcoin_deserialize_into::<Header>()?;
 
-//         // FIXME: Replace
-//         // check::equihash_solution_is_valid(&block.header)
-//         //     .expect("the equihash solution from a historical block should be valid");
-//     }
+        check::version_is_valid(&header, Network::Mainnet, &Height(height))
+            .expect("the version of a historical block should be valid");
+    }
 
-//     Ok(())
-// }
+    Ok(())
+}
+
+#[test]
+fn version_validation_at_activation_heights() -> Result<(), Report> {
+    zebra_test::init();
+
+    let network = Network::Mainnet;
+    let mut header = Header::bitcoin_deserialize(&zebra_test::vectors::DUMMY_HEADER[..])?;
+
+    for &(upgrade, minimum_version) in &[
+        (NetworkUpgrade::BIP34, 2),
+        (NetworkUpgrade::BIP66, 3),
+        (NetworkUpgrade::BIP65, 4),
+    ] {
+        let activation_height = upgrade
+            .activation_height(network)
+            .expect("buried deployments have an activation height");
+        let before_activation = (activation_height - 1).expect("activation is after genesis");
+
+        // The old minimum version is valid until the upgrade activates
+        header.version = minimum_version - 1;
+        check::version_is_valid(&header, network, &before_activation)?;
+        assert_eq!(
+            check::version_is_valid(&header, network, &activation_height),
+            Err(BlockError::WrongVersion(
+                activation_height,
+                minimum_version - 1,
+                minimum_version
+            ))
+        );
+
+        header.version = minimum_version;
+        check::version_is_valid(&header, network, &activation_height)?;
+    }
+
+    // BIP 9 versions have the top bits set, so they are greater than 4
+    header.version = 0x2000_0000;
+    check::version_is_valid(&header, network, &Height(709_632))?;
+
+    Ok(())
+}
+
+#[test]
+fn coinbase_height_validation_at_bip34_activation() -> Result<(), Report> {
+    zebra_test::init();
+    use crate::error::*;
+
+    let network = Network::Mainnet;
+    let block: Block =
+        zebra_test::vectors::BLOCK_MAINNET_415000_BYTES.bitcoin_deserialize_into()?;
+    check::coinbase_height_is_valid(&block, network, &Height(415_000))?;
+
+    // The coinbase height must match the block height
+    assert_eq!(
+        check::coinbase_height_is_valid(&block, network, &Height(415_001)),
+        Err(BlockError::WrongCoinbaseHeight(
+            Height(415_001),
+            Some(Height(415_000))
+        ))
+    );
+
+    // But only once BIP34 has activated
+    let activation_height = NetworkUpgrade::BIP34
+        .activation_height(network)
+        .expect("buried deployments have an activation height");
+    let before_activation = (activation_height - 1).expect("activation is after genesis");
+    check::coinbase_height_is_valid(&block, network, &before_activation)?;
+    assert_eq!(
+        check::coinbase_height_is_valid(&block, network, &activation_height),
+        Err(BlockError::WrongCoinbaseHeight(
+            activation_height,
+            Some(Height(415_000))
+        ))
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn block_verifier_rejects_wrong_coinbase_height() -> Result<(), Report> {
+    zebra_test::init();
+    use crate::error::*;
+
+    let block: Arc<Block> =
+        zebra_test::vectors::BLOCK_MAINNET_415000_BYTES.bitcoin_deserialize_into()?;
+    let hash = block.hash();
+
+    // The state says the parent block is at 414,998, so the block is at
+    // 414,999, but its coinbase claims 415,000
+    let state_service = Buffer::new(
+        Transcript::from(vec![
+            (zs::Request::Depth(hash), Ok(zs::Response::Depth(None))),
+            (
+                zs::Request::AwaitHeight(block.previous_hash()),
+                Ok(zs::Response::Height(Height(414_998))),
+            ),
+        ]),
+        1,
+    );
+    let block_verifier = BlockVerifier::new(Network::Mainnet, state_service);
+
+    let result = block_verifier.oneshot(block).await.unwrap_err();
+    assert!(
+        matches!(
+            result,
+            VerifyBlockError::Block {
+                source: BlockError::WrongCoinbaseHeight(Height(414_999), Some(Height(415_000))),
+            }
+        ),
+        "unexpected verifier error: {:?}",
+        result
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn block_verifier_rejects_short_coinbase_data() -> Result<(), Report> {
+    zebra_test::init();
+    use crate::error::*;
+
+    let block = zebra_test::vectors::BLOCK_MAINNET_1_BYTES.bitcoin_deserialize_into::<Block>()?;
+
+    // Replace the coinbase data with a single OP_1, then use a re-mined
+    // nonce, so the block passes the difficulty and Merkle root checks
+    let mut block = with_coinbase_data(&block, vec![0x51]);
+    block.header.nonce = 799_699_677;
+    block.header.clear_cached_hash();
+    let block = Arc::new(block);
+    let hash = block.hash();
+    assert_eq!(
+        hash,
+        "00000000eccad47b3ab90ab88ec74183722646e67bbbdf2538ad1e176b8524ae"
+            .parse::<block::Hash>()?
+    );
+
+    let state_service = Buffer::new(
+        Transcript::from(vec![
+            (zs::Request::Depth(hash), Ok(zs::Response::Depth(None))),
+            (
+                zs::Request::AwaitHeight(block.previous_hash()),
+                Ok(zs::Response::Height(Height(0))),
+            ),
+        ]),
+        1,
+    );
+    let block_verifier = BlockVerifier::new(Network::Mainnet, state_service);
+
+    let result = block_verifier.oneshot(block).await.unwrap_err();
+    assert!(
+        matches!(
+            result,
+            VerifyBlockError::Block {
+                source: BlockError::Transaction(TransactionError::CoinbaseDataLength(1)),
+            }
+        ),
+        "unexpected verifier error: {:?}",
+        result
+    );
+
+    Ok(())
+}
+
+#[test]
+fn pow_is_valid_for_historical_headers() -> Result<(), Report> {
+    zebra_test::init();
+
+    let header_bytes = hex::decode(TESTNET_GENESIS_HEADER_HEX)?;
+    let header = Header::bitcoin_deserialize(&header_bytes[..])?;
+    assert_eq!(
+        block::Hash::from(&header),
+        "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943"
+            .parse::<block::Hash>()?
+    );
+    check::pow_is_valid(&header, Network::Testnet)
+        .expect("the testnet genesis header should have valid proof of work");
+
+    for block in zebra_test::vectors::MAINNET_BLOCKS.values() {
+        let block = block.bitcoin_deserialize_into::<Block>()?;
+        check::pow_is_valid(&block.header, Network::Mainnet)
+            .expect("historical headers should have valid proof of work");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn pow_validation_failure() -> Result<(), Report> {
+    zebra_test::init();
+    use crate::error::*;
+
+    let header_bytes = hex::decode(TESTNET_GENESIS_HEADER_HEX)?;
+    let header = Header::bitcoin_deserialize(&header_bytes[..])?;
+
+    // A corrupted nonce changes the hash, so it almost certainly doesn't meet
+    // the threshold any more
+    let mut corrupted = header;
+    corrupted.nonce = corrupted.nonce.wrapping_add(1);
+    let result = check::pow_is_valid(&corrupted, Network::Testnet).unwrap_err();
+    assert_eq!(
+        result,
+        BlockError::InsufficientProofOfWork(
+            block::Hash::from(&corrupted),
+            header
+                .difficulty_threshold
+                .to_expanded()
+                .expect("the genesis threshold is valid"),
+            Network::Testnet,
+        )
+    );
+
+    // Invalid thresholds are rejected, even if the hash is low enough
+    let mut invalid = header;
+    invalid.difficulty_threshold = INVALID_COMPACT_DIFFICULTY;
+    let result = check::pow_is_valid(&invalid, Network::Testnet).unwrap_err();
+    assert_eq!(
+        result,
+        BlockError::InvalidHeaderDifficulty(block::Hash::from(&invalid), Network::Testnet)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn difficulty_adjustment_for_historical_blocks() -> Result<(), Report> {
+    zebra_test::init();
+
+    let blocks = &zebra_test::vectors::MAINNET_BLOCKS;
+    let mut checked = 0;
+    for (&height, block) in blocks.iter() {
+        let parent = match height.checked_sub(1).and_then(|height| blocks.get(&height)) {
+            Some(parent) => parent.bitcoin_deserialize_into::<Block>()?,
+            None => continue,
+        };
+        let block = block.bitcoin_deserialize_into::<Block>()?;
+
+        check::difficulty_adjustment(
+            &block.header,
+            Network::Mainnet,
+            &Height(height),
+            &block.hash(),
+            &[parent.header],
+        )
+        .expect("the difficulty from a historical block should match its parent");
+        checked += 1;
+    }
+    assert!(checked > 0);
+
+    Ok(())
+}
+
+/// Returns the `CompactDifficulty` with the `bits` encoding.
+fn compact_difficulty(bits: u32) -> CompactDifficulty {
+    CompactDifficulty::bitcoin_deserialize(&bits.to_le_bytes()[..])
+        .expect("any 4 bytes deserialize")
+}
+
+/// Returns a difficulty adjustment window based on `header`, where the first
+/// and last blocks are `timespan` apart.
+fn retarget_window(header: &Header, timespan: chrono::Duration) -> Vec<Header> {
+    let mut window = vec![*header; POW_AVERAGING_WINDOW];
+    let last = window.last_mut().expect("window is not empty");
+    last.time.0 = last.time.0 + timespan;
+    window
+}
+
+#[test]
+fn difficulty_adjustment_retargets() -> Result<(), Report> {
+    zebra_test::init();
+    use chrono::Duration;
+
+    // The test vectors don't include a retarget window, so use a window of
+    // copies of block 415,000, which has difficulty bits 0x18058436.
+    // The expected values were calculated using Bitcoin Core's algorithm.
+    let block: Block =
+        zebra_test::vectors::BLOCK_MAINNET_415000_BYTES.bitcoin_deserialize_into()?;
+    assert_eq!(
+        block.header.difficulty_threshold,
+        compact_difficulty(0x1805_8436)
+    );
+
+    let retarget_height = Height(403_200);
+    let two_weeks = Duration::weeks(2);
+    let expected = |timespan| {
+        check::expected_difficulty_threshold(
+            Network::Mainnet,
+            retarget_height,
+            &retarget_window(&block.header, timespan),
+        )
+    };
+
+    assert_eq!(expected(two_weeks), Some(compact_difficulty(0x1805_8436)));
+    assert_eq!(
+        expected(Duration::weeks(1)),
+        Some(compact_difficulty(0x1802_c21b))
+    );
+    assert_eq!(
+        expected(Duration::weeks(4)),
+        Some(compact_difficulty(0x180b_086c))
+    );
+
+    // The timespan is clamped to between a quarter and four times two weeks
+    assert_eq!(
+        expected(Duration::weeks(8)),
+        Some(compact_difficulty(0x1816_10d8))
+    );
+    assert_eq!(
+        expected(Duration::weeks(16)),
+        Some(compact_difficulty(0x1816_10d8))
+    );
+    assert_eq!(
+        expected(Duration::days(3) + Duration::hours(12)),
+        Some(compact_difficulty(0x1801_610d))
+    );
+    assert_eq!(
+        expected(Duration::days(1)),
+        Some(compact_difficulty(0x1801_610d))
+    );
+
+    // Only the first and last blocks in the window matter
+    let mut window = retarget_window(&block.header, two_weeks);
+    window.insert(0, block.header);
+    window[1].time.0 = window[1].time.0 - two_weeks;
+    assert_eq!(
+        check::expected_difficulty_threshold(Network::Mainnet, retarget_height, &window),
+        Some(compact_difficulty(0x180b_086c))
+    );
+
+    // Other heights use the parent's threshold
+    assert_eq!(
+        check::expected_difficulty_threshold(
+            Network::Mainnet,
+            Height(403_201),
+            &retarget_window(&block.header, Duration::weeks(1)),
+        ),
+        Some(compact_difficulty(0x1805_8436))
+    );
+
+    // Easier thresholds are capped at the PoWLimit
+    let genesis: Block =
+        zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES.bitcoin_deserialize_into()?;
+    assert_eq!(
+        check::expected_difficulty_threshold(
+            Network::Mainnet,
+            Height(2016),
+            &retarget_window(&genesis.header, Duration::weeks(4)),
+        ),
+        Some(genesis.header.difficulty_threshold)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn difficulty_adjustment_failure() -> Result<(), Report> {
+    zebra_test::init();
+    use crate::error::*;
+
+    let parent: Block =
+        zebra_test::vectors::BLOCK_MAINNET_419199_BYTES.bitcoin_deserialize_into()?;
+    let mut block: Block =
+        zebra_test::vectors::BLOCK_MAINNET_419200_BYTES.bitcoin_deserialize_into()?;
+    let height = Height(419_200);
+    let hash = block.hash();
+
+    let expected = parent.header.difficulty_threshold;
+    block.header.difficulty_threshold = compact_difficulty(0x1d00_ffff);
+    let result = check::difficulty_adjustment(
+        &block.header,
+        Network::Mainnet,
+        &height,
+        &hash,
+        &[parent.header],
+    )
+    .unwrap_err();
+    assert_eq!(
+        result,
+        BlockError::UnexpectedDifficulty(height, hash, compact_difficulty(0x1d00_ffff), expected)
+    );
+
+    // An invalid parent threshold is an invalid difficulty
+    let mut parent_header = parent.header;
+    parent_header.difficulty_threshold = INVALID_COMPACT_DIFFICULTY;
+    let window = vec![parent_header; POW_AVERAGING_WINDOW];
+    let result = check::difficulty_adjustment(
+        &block.header,
+        Network::Mainnet,
+        &Height(419_328),
+        &hash,
+        &window,
+    )
+    .unwrap_err();
+    assert_eq!(result, BlockError::InvalidDifficulty(Height(419_328), hash));
+
+    Ok(())
+}
+
+/// Returns the total value of the coinbase outputs in `block`.
+fn coinbase_reward(block: &Block) -> Amount<NonNegative> {
+    block.transactions[0]
+        .outputs
+        .iter()
+        .map(|output| output.value)
+        .checked_sum()
+        .expect("coinbase outputs are valid amounts")
+}
+
+/// Returns spent outputs for the non-coinbase transactions in `block`, so
+/// that the block's total transaction fees are `fees`.
+///
+/// The first input of each transaction pays for all its outputs, and the first
+/// input in the block also pays all the fees.
+fn spent_outputs_with_fees(
+    block: &Block,
+    fees: Amount<NonNegative>,
+) -> HashMap<OutPoint, transparent::Output> {
+    let mut fees = Some(fees);
+    let mut spent_outputs = HashMap::new();
+
+    for transaction in block.transactions.iter().filter(|tx| !tx.is_coinbase()) {
+        let mut value = Some(
+            transaction
+                .outputs
+                .iter()
+                .map(|output| output.value)
+                .checked_sum()
+                .expect("outputs are valid amounts"),
+        );
+
+        for input in transaction.inputs().iter() {
+            if let transparent::Input::PrevOut { outpoint, .. } = input {
+                let value = match (value.take(), fees.take()) {
+                    (Some(value), Some(fees)) => (value + fees).expect("fees are valid"),
+                    (Some(value), None) => value,
+                    (None, _) => Amount::try_from(0).expect("zero is valid"),
+                };
+                spent_outputs.insert(
+                    *outpoint,
+                    transparent::Output {
+                        value,
+                        lock_script: Script(Vec::new()),
+                    },
+                );
+            }
+        }
+    }
+
+    spent_outputs
+}
 
 #[test]
-// TODO: Add back testnet
 fn subsidy_is_valid_for_historical_blocks() -> Result<(), Report> {
     zebra_test::init();
 
+    // TODO: Add back testnet, once the testnet vectors are Bitcoin blocks
     subsidy_is_valid_for_network(Network::Mainnet)?;
-    subsidy_is_valid_for_network(Network::Testnet)?;
 
     Ok(())
 }
@@ -284,12 +786,75 @@ fn subsidy_is_valid_for_network(network: Network) -> Result<(), Report> {
             .bitcoin_deserialize_into::<Block>()
             .expect("block is structurally valid");
 
-        check::subsidy_is_valid(&block, network).expect("subsidies should pass for this block");
+        // Version 1 blocks don't have a coinbase height
+        if block.coinbase_height().is_none() {
+            continue;
+        }
+        assert_eq!(block.coinbase_height(), Some(Height(height)));
+
+        // We don't have the outputs spent by these blocks, so use the fees
+        // implied by their coinbase
+        let subsidy = subsidy::general::block_subsidy(Height(height), network)?;
+        let fees = (coinbase_reward(&block) - subsidy)
+            .unwrap_or_else(|_| Amount::try_from(0).expect("zero is valid"));
+        let spent_outputs = spent_outputs_with_fees(&block, fees);
+
+        check::subsidy_is_valid(&block, network, &spent_outputs)
+            .expect("subsidies should pass for this block");
     }
 
     Ok(())
 }
 
+#[test]
+fn miner_reward_validation() -> Result<(), Report> {
+    zebra_test::init();
+    use crate::error::*;
+
+    let network = Network::Mainnet;
+    let block: Block =
+        zebra_test::vectors::BLOCK_MAINNET_415000_BYTES.bitcoin_deserialize_into()?;
+
+    let subsidy = subsidy::general::block_subsidy(Height(415_000), network)?;
+    assert_eq!(subsidy, Amount::<NonNegative>::try_from(1_250_000_000)?);
+    let fees = (coinbase_reward(&block) - subsidy)?;
+    let one = Amount::try_from(1)?;
+
+    // The coinbase claims exactly the subsidy plus fees
+    let spent_outputs = spent_outputs_with_fees(&block, fees);
+    assert_eq!(
+        subsidy::general::miner_fees(&block, &spent_outputs),
+        Ok(fees)
+    );
+    check::subsidy_is_valid(&block, network, &spent_outputs)?;
+
+    // Miners can claim less than they are owed
+    let spent_outputs = spent_outputs_with_fees(&block, (fees + one)?);
+    check::subsidy_is_valid(&block, network, &spent_outputs)?;
+
+    // But they can't claim any more
+    let spent_outputs = spent_outputs_with_fees(&block, (fees - one)?);
+    let result = check::subsidy_is_valid(&block, network, &spent_outputs).unwrap_err();
+    let expected =
+        BlockError::Transaction(TransactionError::Subsidy(SubsidyError::InvalidMinerReward));
+    assert_eq!(expected, result);
+
+    // Fees can't be calculated without every spent output
+    let mut spent_outputs = spent_outputs_with_fees(&block, fees);
+    let outpoint = match block.transactions[1].inputs()[0] {
+        transparent::Input::PrevOut { outpoint, .. } => outpoint,
+        transparent::Input::Coinbase { .. } => unreachable!("not a coinbase transaction"),
+    };
+    spent_outputs.remove(&outpoint);
+    let result = check::subsidy_is_valid(&block, network, &spent_outputs).unwrap_err();
+    let expected = BlockError::Transaction(TransactionError::Subsidy(
+        SubsidyError::SpentOutputNotFound(outpoint),
+    ));
+    assert_eq!(expected, result);
+
+    Ok(())
+}
+
 #[test]
 fn coinbase_validation_failure() -> Result<(), Report> {
     zebra_test::init();
@@ -313,7 +878,7 @@ fn coinbase_validation_failure() -> Result<(), Report> {
     assert_eq!(expected, result);
 
     // Validate the block using subsidy_is_valid
-    let result = check::subsidy_is_valid(&block, network).unwrap_err();
+    let result = check::subsidy_is_valid(&block, network, &HashMap::new()).unwrap_err();
     let expected = BlockError::Transaction(TransactionError::Subsidy(SubsidyError::NoCoinbase));
     assert_eq!(expected, result);
 
@@ -332,7 +897,7 @@ fn coinbase_validation_failure() -> Result<(), Report> {
     assert_eq!(expected, result);
 
     // Validate the block using subsidy_is_valid
-    let result = check::subsidy_is_valid(&block, network).unwrap_err();
+    let result = check::subsidy_is_valid(&block, network, &HashMap::new()).unwrap_err();
     let expected = BlockError::Transaction(TransactionError::Subsidy(SubsidyError::NoCoinbase));
     assert_eq!(expected, result);
 
@@ -357,12 +922,85 @@ fn coinbase_validation_failure() -> Result<(), Report> {
     assert_eq!(expected, result);
 
     // Validate the block using subsidy_is_valid, which does not detect this error
-    check::subsidy_is_valid(&block, network)
+    let subsidy = subsidy::general::block_subsidy(Height(434_873), network)?;
+    let spent_outputs = spent_outputs_with_fees(&block, (coinbase_reward(&block) - subsidy)?);
+    check::subsidy_is_valid(&block, network, &spent_outputs)
         .expect("subsidy does not check for extra coinbase transactions");
 
     Ok(())
 }
 
+#[test]
+fn check_block_accepts_historical_blocks() -> Result<(), Report> {
+    zebra_test::init();
+
+    // Version 1 blocks before BIP34 don't have a height in their coinbase
+    let block: Block = zebra_test::vectors::BLOCK_MAINNET_1_BYTES.bitcoin_deserialize_into()?;
+    assert_eq!(block.coinbase_height(), None);
+    check::check_block(&block, Height(1), Network::Mainnet, Utc::now())?;
+
+    let block: Block =
+        zebra_test::vectors::BLOCK_MAINNET_415000_BYTES.bitcoin_deserialize_into()?;
+    check::check_block(&block, Height(415_000), Network::Mainnet, Utc::now())?;
+
+    Ok(())
+}
+
+#[test]
+fn check_block_failures() -> Result<(), Report> {
+    zebra_test::init();
+    use crate::error::*;
+
+    // The checks use the height from the caller, so a version 1 block is
+    // rejected after BIP65 activates
+    let block: Block = zebra_test::vectors::BLOCK_MAINNET_1_BYTES.bitcoin_deserialize_into()?;
+    let result =
+        check::check_block(&block, Height(415_000), Network::Mainnet, Utc::now()).unwrap_err();
+    assert_eq!(result, BlockError::WrongVersion(Height(415_000), 1, 4));
+
+    let block: Block =
+        zebra_test::vectors::BLOCK_MAINNET_415000_BYTES.bitcoin_deserialize_into()?;
+    let height = Height(415_000);
+
+    // A block from the future fails the time check
+    let past = block.header.time.0 - chrono::Duration::hours(3);
+    let result = check::check_block(&block, height, Network::Mainnet, past).unwrap_err();
+    assert!(matches!(result, BlockError::Time(_)));
+
+    // Dropping a transaction fails the Merkle root check, which runs before
+    // the time check
+    let mut tampered = block.clone();
+    tampered.transactions.pop();
+    let result = check::check_block(&tampered, height, Network::Mainnet, past).unwrap_err();
+    assert!(matches!(result, BlockError::BadMerkleRoot { .. }));
+
+    // A coinbase height that doesn't match the chain fails the BIP34 check
+    let result =
+        check::check_block(&block, Height(415_001), Network::Mainnet, Utc::now()).unwrap_err();
+    assert_eq!(
+        result,
+        BlockError::WrongCoinbaseHeight(Height(415_001), Some(Height(415_000)))
+    );
+
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn arbitrary_blocks_have_valid_merkle_roots(block in any::<Block>()) {
+        zebra_test::init();
+
+        let transaction_hashes = block
+            .transactions
+            .iter()
+            .map(|tx| tx.hash())
+            .collect::<Vec<_>>();
+        prop_assert_eq!(check::merkle_root_validity(&block, &transaction_hashes), Ok(()));
+    }
+}
+
 // #[test]
 // fn founders_reward_validation_failure() -> Result<(), Report> {
 //     zebra_test::init();
@@ -900,8 +900,8 @@ where
                 .await
                 .expect("state service commit block failed: verified checkpoints must be committed transactionally")
             {
-                zs::Response::Committed(committed_hash) => {
-                    assert_eq!(committed_hash, hash, "state must commit correct hash");
+                zs::Response::CommittedFinalized(commit) => {
+                    assert_eq!(commit.hash, hash, "state must commit correct hash");
                     Ok(hash)
                 }
                 _ => unreachable!("wrong response for CommitFinalizedBlock"),
@@ -480,6 +480,7 @@ async fn wrong_checkpoint_hash_fail() -> Result<(), Report> {
     let mut bad_block0 = good_block0.clone();
     let mut bad_block0 = Arc::make_mut(&mut bad_block0);
     bad_block0.header.version = 0;
+    bad_block0.header.clear_cached_hash();
     let bad_block0: Arc<Block> = bad_block0.clone().into();
 
     // Make a checkpoint list containing the genesis block checkpoint
@@ -18,6 +18,15 @@ pub enum SubsidyError {
 
     #[error("founders reward output not found")]
     FoundersRewardNotFound,
+
+    #[error("coinbase outputs are more than the block subsidy plus transaction fees")]
+    InvalidMinerReward,
+
+    #[error("spent output {0:?} not found, so transaction fees can't be calculated")]
+    SpentOutputNotFound(zebra_chain::transparent::OutPoint),
+
+    #[error("invalid subsidy, fee, or coinbase amount")]
+    Amount(#[from] zebra_chain::amount::Error),
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -28,6 +37,9 @@ pub enum TransactionError {
     #[error("coinbase input found in non-coinbase transaction")]
     CoinbaseInputFound,
 
+    #[error("coinbase data must be between 2 and 100 bytes, but it is {0} bytes")]
+    CoinbaseDataLength(usize),
+
     #[error("coinbase transaction MUST NOT have any JoinSplit descriptions")]
     CoinbaseHasJoinSplit,
 
@@ -52,6 +64,18 @@ pub enum TransactionError {
     #[error("if there are no Spends or Outputs, the value balance MUST be 0.")]
     BadBalance,
 
+    #[error("transaction is larger than the maximum block size")]
+    Oversized,
+
+    #[error("transaction output values exceed the maximum money supply")]
+    OutputValueOverflow,
+
+    #[error("transaction spends the same outpoint more than once")]
+    DuplicateInput,
+
+    #[error("transaction is not standard: {0}")]
+    NonStandard(&'static str),
+
     #[error("could not verify a transparent script")]
     Script(#[from] zebra_script::Error),
 
@@ -123,6 +147,9 @@ pub enum BlockError {
         zebra_chain::block::Height,
     ),
 
+    #[error(transparent)]
+    Time(#[from] zebra_chain::block::BlockTimeError),
+
     #[error("invalid difficulty threshold in block header {0:?} {1:?}")]
     InvalidDifficulty(zebra_chain::block::Height, zebra_chain::block::Hash),
 
@@ -144,4 +171,35 @@ pub enum BlockError {
         zebra_chain::work::difficulty::ExpandedDifficulty,
         zebra_chain::parameters::Network,
     ),
+
+    #[error("invalid or too easy {1:?} difficulty threshold in block header {0:?}")]
+    InvalidHeaderDifficulty(zebra_chain::block::Hash, zebra_chain::parameters::Network),
+
+    #[error(
+        "block header {0:?} on {2:?} has a hash that is easier than its difficulty threshold {1:?}"
+    )]
+    InsufficientProofOfWork(
+        zebra_chain::block::Hash,
+        zebra_chain::work::difficulty::ExpandedDifficulty,
+        zebra_chain::parameters::Network,
+    ),
+
+    #[error(
+        "block {0:?} has version {1}, but blocks at this height must have version {2} or later"
+    )]
+    WrongVersion(zebra_chain::block::Height, u32, u32),
+
+    #[error("block {0:?} must start its coinbase data with its BIP34 height, but the coinbase height is {1:?}")]
+    WrongCoinbaseHeight(
+        zebra_chain::block::Height,
+        Option<zebra_chain::block::Height>,
+    ),
+
+    #[error("block {0:?} has a difficulty threshold {2:?}, but the difficulty adjustment requires {3:?}, hash: {1:?}")]
+    UnexpectedDifficulty(
+        zebra_chain::block::Height,
+        zebra_chain::block::Hash,
+        zebra_chain::work::difficulty::CompactDifficulty,
+        zebra_chain::work::difficulty::CompactDifficulty,
+    ),
 }
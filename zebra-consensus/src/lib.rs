@@ -59,9 +59,12 @@ mod transaction;
 pub mod chain;
 pub mod error;
 
+pub use block::check_block;
 pub use checkpoint::MAX_CHECKPOINT_BYTE_COUNT;
 pub use checkpoint::MAX_CHECKPOINT_HEIGHT_GAP;
 pub use config::Config;
+pub use script::verify_taproot_keypath;
+pub use transaction::check_transaction;
 
 /// A boxed [`std::error::Error`].
 pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
@@ -3,6 +3,10 @@
 use zebra_chain::{amount::COIN, block::Height};
 
 /// The largest block subsidy, used before the first halving. 50 BTC
-pub const MAX_BLOCK_SUBSIDY: u64 = (50 * COIN) as u64;
+///
+/// This is an `i64`, like `COIN` and `MAX_MONEY`, so it can be passed directly
+/// to `Amount::try_from`.
+pub const MAX_BLOCK_SUBSIDY: i64 = 50 * COIN;
 
-pub const HALVING_INTERVAL: Height = Height(210000);
+/// The number of blocks between each subsidy halving.
+pub const HALVING_INTERVAL: Height = Height(210_000);
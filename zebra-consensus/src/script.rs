@@ -9,6 +9,10 @@ use zebra_state::Utxo;
 
 use crate::BoxError;
 
+mod taproot;
+
+pub use taproot::verify_taproot_keypath;
+
 /// A timeout applied to UTXO lookup requests.
 ///
 /// The exact value is non-essential, but this should be long enough to allow
@@ -60,8 +64,8 @@ pub struct Request {
     pub known_utxos: Arc<HashMap<transparent::OutPoint, Utxo>>,
     /// The network upgrade active in the context of this verification request.
     ///
-    /// Because the consensus branch ID changes with each network upgrade,
-    /// it has to be specified on a per-request basis.
+    /// Script verification rules change with soft forks, so the upgrade has to
+    /// be specified on a per-request basis.
     pub upgrade: NetworkUpgrade,
 }
 
@@ -92,13 +96,12 @@ where
             upgrade,
         } = req;
         let input = &cached_ffi_transaction.inputs()[input_index];
-        let branch_id = upgrade.branch_id().into();
 
         match input {
             transparent::Input::PrevOut { outpoint, .. } => {
                 let outpoint = *outpoint;
 
-                let span = tracing::trace_span!("script", ?outpoint);
+                let span = tracing::trace_span!("script", ?outpoint, ?upgrade);
                 let query =
                     span.in_scope(|| self.state.call(zebra_state::Request::AwaitUtxo(outpoint)));
 
@@ -114,8 +117,7 @@ where
                     };
                     tracing::trace!(?utxo, "got UTXO");
 
-                    cached_ffi_transaction
-                        .is_valid(branch_id, (input_index as u32, utxo.output))?;
+                    cached_ffi_transaction.is_valid((input_index as u32, utxo.output))?;
                     tracing::trace!("script verification succeeded");
 
                     Ok(())
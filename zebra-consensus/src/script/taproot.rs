@@ -0,0 +1,94 @@
+//! BIP341 taproot output key verification.
+
+use secp256k1::{PublicKey, Secp256k1};
+use sha2::{Digest, Sha256};
+
+use zebra_chain::transparent::ScriptError;
+
+/// The BIP340 tag used to compute taproot tweaks.
+const TAP_TWEAK_TAG: &[u8] = b"TapTweak";
+
+/// Compute the BIP340 tagged hash `SHA256(SHA256(tag) || SHA256(tag) || msg)`.
+fn tagged_hash(tag: &[u8], msg: &[&[u8]]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag);
+    let mut hasher = Sha256::new();
+    hasher.update(&tag_hash);
+    hasher.update(&tag_hash);
+    for chunk in msg {
+        hasher.update(chunk);
+    }
+    hasher.finalize().into()
+}
+
+/// Verify that `output_key` is the BIP341 tweak of `internal_key`.
+///
+/// Both keys are x-only (32-byte) public keys. The tweak is
+/// `TaggedHash("TapTweak", internal_key || merkle_root)`, where the merkle
+/// root is omitted for outputs without a script tree. The output key must
+/// equal the x coordinate of `lift_x(internal_key) + tweak * G`.
+pub fn verify_taproot_keypath(
+    output_key: &[u8; 32],
+    internal_key: &[u8; 32],
+    merkle_root: Option<[u8; 32]>,
+) -> Result<(), ScriptError> {
+    let tweak = match merkle_root {
+        Some(root) => tagged_hash(TAP_TWEAK_TAG, &[&internal_key[..], &root[..]]),
+        None => tagged_hash(TAP_TWEAK_TAG, &[&internal_key[..]]),
+    };
+
+    // `lift_x` always picks the point with an even y coordinate.
+    let mut compressed = [0x02; 33];
+    compressed[1..].copy_from_slice(internal_key);
+    let mut tweaked =
+        PublicKey::from_slice(&compressed).map_err(|_| ScriptError::InvalidPublicKey)?;
+    tweaked
+        .add_exp_assign(&Secp256k1::verification_only(), &tweak)
+        .map_err(|_| ScriptError::InvalidTaprootTweak)?;
+
+    if tweaked.serialize()[1..] == output_key[..] {
+        Ok(())
+    } else {
+        Err(ScriptError::TaprootTweakMismatch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(hex_str: &str) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(&hex::decode(hex_str).unwrap());
+        bytes
+    }
+
+    /// BIP341 wallet test vector: key path only, no script tree.
+    #[test]
+    fn bip341_keypath_without_scripts() {
+        zebra_test::init();
+
+        let internal = key("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d");
+        let output = key("53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343");
+
+        assert_eq!(verify_taproot_keypath(&output, &internal, None), Ok(()));
+    }
+
+    /// BIP341 wallet test vector: output committing to a script tree.
+    #[test]
+    fn bip341_keypath_with_script_tree() {
+        zebra_test::init();
+
+        let internal = key("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27");
+        let root = key("5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21");
+        let output = key("147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3");
+
+        assert_eq!(
+            verify_taproot_keypath(&output, &internal, Some(root)),
+            Ok(())
+        );
+        assert_eq!(
+            verify_taproot_keypath(&output, &internal, None),
+            Err(ScriptError::TaprootTweakMismatch)
+        );
+    }
+}
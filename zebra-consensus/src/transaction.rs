@@ -26,6 +26,10 @@ use zebra_state as zs;
 use crate::{error::TransactionError, script, BoxError};
 
 mod check;
+#[cfg(test)]
+mod tests;
+
+pub use check::check_transaction;
 
 /// Asynchronous transaction verification.
 #[derive(Debug, Clone)]
@@ -1,46 +1,135 @@
 //! Transaction checks.
 //!
-//! Code in this file can freely assume that no pre-V4 transactions are present.
-
-// use zebra_chain::transaction::Transaction;
-
-// use crate::error::TransactionError;
-
-// /// Checks that the transaction has inputs and outputs.
-// ///
-// /// More specifically:
-// ///
-// /// * at least one of tx_in_count, nShieldedSpend, and nJoinSplit MUST be non-zero.
-// /// * at least one of tx_out_count, nShieldedOutput, and nJoinSplit MUST be non-zero.
-// ///
-// /// https://zips.z.cash/protocol/canopy.pdf#txnencodingandconsensus
-// pub fn has_inputs_and_outputs(tx: &Transaction) -> Result<(), TransactionError> {
-//     // The consensus rule is written in terms of numbers, but our transactions
-//     // hold enum'd data. Mixing pattern matching and numerical checks is risky,
-//     // so convert everything to counts and sum up.
-//     let inputs = &tx.inputs;
-//     let outputs = &tx.outputs;
-//     // match tx {
-//     //     Transaction::V4 {
-//     //         inputs,
-//     //         outputs,
-//     //         joinsplit_data,
-//     //         shielded_data,
-//     //         ..
-//     //     } => {
-//     let tx_in_count = inputs.len();
-//     let tx_out_count = outputs.len();
-
-//     if tx_in_count == 0 {
-//         Err(TransactionError::NoInputs)
-//     } else if tx_out_count == 0 {
-//         Err(TransactionError::NoOutputs)
-//     } else {
-//         Ok(())
-//     }
-//     //     }
-//     //     Transaction::V1 { .. } | Transaction::V2 { .. } | Transaction::V3 { .. } => {
-//     //         unreachable!("tx version is checked first")
-//     //     }
-//     // }
-// }
+//! These checks are context-free: they don't need the outputs spent by the
+//! transaction, so signature and script checks are done elsewhere.
+
+use std::collections::HashSet;
+
+use zebra_chain::{
+    amount::CheckedSum,
+    transaction::Transaction,
+    transparent::{self, Script, ScriptType},
+};
+
+use crate::error::TransactionError;
+
+/// The maximum serialized size of a transaction, which is the maximum block size.
+const MAX_TRANSACTION_SIZE: usize = 1_000_000;
+
+/// The maximum serialized size of a transaction that we relay.
+const MAX_STANDARD_TX_SIZE: usize = 100_000;
+
+/// The maximum size of a standard `scriptSig`, which is large enough for a
+/// 15-of-15 P2SH multisig spend.
+const MAX_STANDARD_SCRIPTSIG_SIZE: usize = 1650;
+
+/// The maximum size of a standard `OP_RETURN` output script.
+const MAX_OP_RETURN_RELAY: usize = 83;
+
+/// Runs all the context-free checks on `tx`, returning the first failure.
+///
+/// The consensus checks run before the standardness checks, so that invalid
+/// transactions are reported as invalid, rather than as non-standard.
+pub fn check_transaction(tx: &Transaction) -> Result<(), TransactionError> {
+    has_inputs_and_outputs(tx)?;
+    verify_structure(tx)?;
+    is_standard(tx)?;
+
+    Ok(())
+}
+
+/// Checks that the transaction has inputs and outputs.
+pub fn has_inputs_and_outputs(tx: &Transaction) -> Result<(), TransactionError> {
+    if tx.inputs().is_empty() {
+        Err(TransactionError::NoInputs)
+    } else if tx.outputs().is_empty() {
+        Err(TransactionError::NoOutputs)
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks the structural consensus rules for `tx`:
+///   * the transaction fits in a block,
+///   * the total output value is at most `MAX_MONEY`,
+///   * no outpoint is spent twice, and
+///   * only coinbase transactions have coinbase inputs.
+pub fn verify_structure(tx: &Transaction) -> Result<(), TransactionError> {
+    if tx.len() > MAX_TRANSACTION_SIZE {
+        return Err(TransactionError::Oversized);
+    }
+
+    tx.outputs()
+        .iter()
+        .map(|output| output.value)
+        .checked_sum()
+        .map_err(|_| TransactionError::OutputValueOverflow)?;
+
+    if !tx.is_coinbase() && tx.contains_coinbase_input() {
+        return Err(TransactionError::CoinbaseInputFound);
+    }
+
+    let mut outpoints = HashSet::new();
+    for input in tx.inputs() {
+        if let transparent::Input::PrevOut { outpoint, .. } = input {
+            if !outpoints.insert(outpoint) {
+                return Err(TransactionError::DuplicateInput);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the standardness (relay policy) rules for `tx`.
+///
+/// Standard transactions have a known version, are small enough to relay,
+/// have small push-only input scripts, and only pay to standard output
+/// scripts, with at most one `OP_RETURN` output.
+pub fn is_standard(tx: &Transaction) -> Result<(), TransactionError> {
+    if !(1..=2).contains(&tx.version()) {
+        return Err(TransactionError::NonStandard("version"));
+    }
+
+    if tx.len() > MAX_STANDARD_TX_SIZE {
+        return Err(TransactionError::NonStandard("tx-size"));
+    }
+
+    for input in tx.inputs() {
+        if let transparent::Input::PrevOut { unlock_script, .. } = input {
+            if unlock_script.0.len() > MAX_STANDARD_SCRIPTSIG_SIZE {
+                return Err(TransactionError::NonStandard("scriptsig-size"));
+            }
+            if !unlock_script.is_push_only() {
+                return Err(TransactionError::NonStandard("scriptsig-not-pushonly"));
+            }
+        }
+    }
+
+    let mut op_return_outputs = 0;
+    for output in tx.outputs() {
+        let script = &output.lock_script;
+        match script.classify() {
+            ScriptType::OpReturn if is_null_data(script) => op_return_outputs += 1,
+            ScriptType::P2PK
+            | ScriptType::P2PKH
+            | ScriptType::P2SH
+            | ScriptType::P2WPKH
+            | ScriptType::P2WSH => {}
+            // Future witness versions are standard, so they can be soft-forked in
+            _ if script.witness_program().is_some() => {}
+            _ => return Err(TransactionError::NonStandard("scriptpubkey")),
+        }
+    }
+
+    if op_return_outputs > 1 {
+        return Err(TransactionError::NonStandard("multi-op-return"));
+    }
+
+    Ok(())
+}
+
+/// Returns true if `script` is a standard `OP_RETURN` data carrier output.
+fn is_null_data(script: &Script) -> bool {
+    script.0.len() <= MAX_OP_RETURN_RELAY && script.op_return_data().is_some()
+}
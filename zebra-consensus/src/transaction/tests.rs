@@ -0,0 +1,164 @@
+//! Tests for context-free transaction checks
+
+use std::{convert::TryInto, sync::Arc};
+
+use color_eyre::eyre::Report;
+
+use zebra_chain::{
+    amount::MAX_MONEY,
+    block::Block,
+    serialization::BitcoinDeserializeInto,
+    transaction::Transaction,
+    transparent::{self, opcodes::*, Script},
+};
+
+use super::check;
+use crate::error::TransactionError;
+
+/// Returns a mainnet transaction with three pay-to-public-key-hash inputs and
+/// a single pay-to-public-key-hash output.
+fn mainnet_transaction() -> Transaction {
+    let block: Arc<Block> = zebra_test::vectors::BLOCK_MAINNET_347500_BYTES
+        .bitcoin_deserialize_into()
+        .expect("block is structurally valid");
+
+    block.transactions[39].as_ref().clone()
+}
+
+fn op_return_output(data: &[u8]) -> transparent::Output {
+    let mut script = Script(vec![OP_RETURN]);
+    script.push_data(data);
+
+    transparent::Output {
+        value: 0i64.try_into().unwrap(),
+        lock_script: script,
+    }
+}
+
+#[test]
+fn check_transaction_accepts_mainnet_transaction() -> Result<(), Report> {
+    zebra_test::init();
+
+    check::check_transaction(&mainnet_transaction())?;
+
+    // A single data carrier output is standard
+    let mut tx = mainnet_transaction();
+    tx.outputs_mut().push(op_return_output(b"zebra"));
+    check::check_transaction(&tx)?;
+
+    Ok(())
+}
+
+#[test]
+fn check_transaction_consensus_failures() {
+    zebra_test::init();
+
+    let mut tx = mainnet_transaction();
+    tx.inputs_mut().clear();
+    assert_eq!(
+        check::check_transaction(&tx),
+        Err(TransactionError::NoInputs)
+    );
+
+    let mut tx = mainnet_transaction();
+    tx.outputs_mut().clear();
+    assert_eq!(
+        check::check_transaction(&tx),
+        Err(TransactionError::NoOutputs)
+    );
+
+    let mut tx = mainnet_transaction();
+    tx.outputs_mut()[0].lock_script = Script(vec![OP_RETURN; 1_000_000]);
+    assert_eq!(
+        check::check_transaction(&tx),
+        Err(TransactionError::Oversized)
+    );
+
+    let mut tx = mainnet_transaction();
+    tx.outputs_mut()[0].value = MAX_MONEY.try_into().unwrap();
+    let output = tx.outputs()[0].clone();
+    tx.outputs_mut().push(output);
+    assert_eq!(
+        check::check_transaction(&tx),
+        Err(TransactionError::OutputValueOverflow)
+    );
+
+    let mut tx = mainnet_transaction();
+    let input = tx.inputs()[0].clone();
+    tx.inputs_mut().push(input);
+    assert_eq!(
+        check::check_transaction(&tx),
+        Err(TransactionError::DuplicateInput)
+    );
+
+    let mut tx = mainnet_transaction();
+    tx.inputs_mut()[1] = transparent::Input::Coinbase {
+        height: None,
+        data: transparent::CoinbaseData(vec![OP_1, OP_1]),
+        sequence: u32::MAX,
+    };
+    assert_eq!(
+        check::check_transaction(&tx),
+        Err(TransactionError::CoinbaseInputFound)
+    );
+}
+
+#[test]
+fn check_transaction_standardness_failures() {
+    zebra_test::init();
+
+    let mut tx = mainnet_transaction();
+    tx.set_version(3);
+    assert_eq!(
+        check::check_transaction(&tx),
+        Err(TransactionError::NonStandard("version"))
+    );
+
+    let mut tx = mainnet_transaction();
+    tx.outputs_mut()[0].lock_script = Script(vec![OP_RETURN; 100_000]);
+    assert_eq!(
+        check::check_transaction(&tx),
+        Err(TransactionError::NonStandard("tx-size"))
+    );
+
+    let mut tx = mainnet_transaction();
+    if let transparent::Input::PrevOut { unlock_script, .. } = &mut tx.inputs_mut()[0] {
+        *unlock_script = Script(vec![OP_1; 1651]);
+    }
+    assert_eq!(
+        check::check_transaction(&tx),
+        Err(TransactionError::NonStandard("scriptsig-size"))
+    );
+
+    let mut tx = mainnet_transaction();
+    if let transparent::Input::PrevOut { unlock_script, .. } = &mut tx.inputs_mut()[0] {
+        unlock_script.0.push(OP_DUP);
+    }
+    assert_eq!(
+        check::check_transaction(&tx),
+        Err(TransactionError::NonStandard("scriptsig-not-pushonly"))
+    );
+
+    let mut tx = mainnet_transaction();
+    tx.outputs_mut()[0].lock_script = Script(vec![OP_DUP]);
+    assert_eq!(
+        check::check_transaction(&tx),
+        Err(TransactionError::NonStandard("scriptpubkey"))
+    );
+
+    // 84 bytes: OP_RETURN OP_PUSHDATA1 <81> <81 bytes>
+    let mut tx = mainnet_transaction();
+    tx.outputs_mut().push(op_return_output(&[0xab; 81]));
+    assert_eq!(
+        check::check_transaction(&tx),
+        Err(TransactionError::NonStandard("scriptpubkey"))
+    );
+
+    let mut tx = mainnet_transaction();
+    tx.outputs_mut().push(op_return_output(b"zebra"));
+    tx.outputs_mut().push(op_return_output(b"zebra"));
+    assert_eq!(
+        check::check_transaction(&tx),
+        Err(TransactionError::NonStandard("multi-op-return"))
+    );
+}
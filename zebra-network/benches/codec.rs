@@ -0,0 +1,107 @@
+//! Benchmarks for encoding and decoding network messages.
+//!
+//! Run them with `cargo bench -p zebra-network --bench codec`.
+
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use tokio_util::codec::{Decoder, Encoder};
+
+use zebra_chain::{
+    block::{self, Block},
+    serialization::BitcoinDeserializeInto,
+};
+use zebra_network::message::{Codec, InventoryHash, Message};
+
+/// The number of entries in the benchmark `inv` message.
+const INV_COUNT: usize = 1000;
+
+/// Returns the messages to benchmark, with their names.
+fn messages() -> Vec<(&'static str, Message)> {
+    let block: Arc<Block> = zebra_test::vectors::BLOCK_MAINNET_347500_BYTES
+        .bitcoin_deserialize_into()
+        .expect("block test vector should deserialize");
+    let transaction = block.transactions[1].clone();
+
+    let headers = zebra_test::vectors::BLOCKS
+        .iter()
+        .map(|bytes| block::CountedHeader {
+            header: bytes[..block::Header::len()]
+                .bitcoin_deserialize_into()
+                .expect("header test vector should deserialize"),
+            transaction_count: 0,
+        })
+        .collect();
+
+    let inventory = block
+        .transactions
+        .iter()
+        .map(|transaction| InventoryHash::Tx(transaction.hash()))
+        .cycle()
+        .take(INV_COUNT)
+        .collect();
+
+    vec![
+        ("block", Message::Block(block)),
+        ("tx", Message::Tx(transaction)),
+        ("inv", Message::Inv(inventory)),
+        ("headers", Message::Headers(headers)),
+    ]
+}
+
+/// Returns the encoding of `msg`.
+fn encode(msg: Message) -> BytesMut {
+    let mut bytes = BytesMut::new();
+    Codec::builder()
+        .finish()
+        .encode(msg, &mut bytes)
+        .expect("benchmark messages should encode");
+    bytes
+}
+
+fn codec_benches(c: &mut Criterion) {
+    let mut encode_group = c.benchmark_group("encode");
+    for (name, msg) in messages() {
+        let len = encode(msg.clone()).len();
+        encode_group.throughput(Throughput::Bytes(len as u64));
+        encode_group.bench_function(name, |b| {
+            let mut codec = Codec::builder().finish();
+            let mut bytes = BytesMut::with_capacity(len);
+            b.iter_batched(
+                || msg.clone(),
+                |msg| {
+                    bytes.clear();
+                    codec
+                        .encode(msg, &mut bytes)
+                        .expect("benchmark messages should encode");
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    encode_group.finish();
+
+    let mut decode_group = c.benchmark_group("decode");
+    for (name, msg) in messages() {
+        let bytes = encode(msg);
+        decode_group.throughput(Throughput::Bytes(bytes.len() as u64));
+        decode_group.bench_function(name, |b| {
+            let mut codec = Codec::builder().finish();
+            b.iter_batched(
+                || bytes.clone(),
+                |mut bytes| {
+                    codec
+                        .decode(&mut bytes)
+                        .expect("benchmark messages should decode")
+                        .expect("benchmark messages are complete")
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    decode_group.finish();
+}
+
+criterion_group!(benches, codec_benches);
+criterion_main!(benches);
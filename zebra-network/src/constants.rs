@@ -55,6 +55,16 @@ pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
 /// messages from each of our peers.
 pub const TIMESTAMP_TRUNCATION_SECONDS: i64 = 30 * 60;
 
+/// The maximum number of addresses in a single `addr` message.
+///
+/// Bitcoin Core sends at most this many addresses in response to a `getaddr`,
+/// and treats peers that send more as misbehaving.
+pub const MAX_ADDRS_IN_MESSAGE: usize = 1000;
+
+/// The maximum number of inventory items in a single `inv`, `getdata`, or
+/// `notfound` message, Bitcoin Core's `MAX_INV_SZ`.
+pub const MAX_INV_IN_MESSAGE: usize = 50_000;
+
 /// The User-Agent string provided by the node.
 ///
 /// This must be a valid [BIP 14] user agent.
@@ -119,12 +129,13 @@ pub const DNS_LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Magic numbers used to identify different Zcash networks.
 pub mod magics {
+    use zebra_chain::parameters::Network;
+
     use super::*;
     /// The production mainnet.
-    pub const MAINNET: Magic = Magic([0xf9, 0xbe, 0xb4, 0xd9]);
-    // pub const MAINNET: Magic = Magic([0xD9, 0xB4, 0xBE, 0xF9]);
+    pub const MAINNET: Magic = Magic(Network::Mainnet.magic());
     /// The testnet.
-    pub const TESTNET: Magic = Magic([0x0B, 0x11, 0x09, 0x07]);
+    pub const TESTNET: Magic = Magic(Network::Testnet.magic());
 }
 
 #[cfg(test)]
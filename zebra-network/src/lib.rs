@@ -88,3 +88,10 @@ pub use crate::{
 pub mod types {
     pub use crate::{meta_addr::MetaAddr, protocol::types::PeerServices};
 }
+
+/// Bitcoin wire protocol messages, for services that answer peer requests.
+pub mod message {
+    pub use crate::protocol::external::{
+        types::FeeFilter, Codec, GetBlocks, GetHeaders, InventoryHash, Message,
+    };
+}
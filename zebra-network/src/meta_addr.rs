@@ -8,7 +8,9 @@ use std::{
 
 use chrono::{DateTime, TimeZone, Utc};
 
-use zebra_chain::serialization::{BitcoinDeserialize, BitcoinSerialize, SerializationError};
+use zebra_chain::serialization::{
+    BitcoinDeserialize, BitcoinSerialize, MinSerializedSize, SerializationError,
+};
 
 use crate::protocol::types::PeerServices;
 
@@ -26,6 +28,10 @@ pub struct MetaAddr {
 }
 
 impl MetaAddr {
+    /// The serialized size of an address: its last seen time, services, IPv6
+    /// address, and port.
+    pub(crate) const SERIALIZED_SIZE: usize = 4 + 8 + 16 + 2;
+
     /// Sanitize this `MetaAddr` before sending it to a remote peer.
     pub fn sanitize(mut self) -> MetaAddr {
         let interval = crate::constants::TIMESTAMP_TRUNCATION_SECONDS;
@@ -63,6 +69,15 @@ impl PartialOrd for MetaAddr {
     }
 }
 
+impl MinSerializedSize for MetaAddr {
+    const MIN_SERIALIZED_SIZE: usize = MetaAddr::SERIALIZED_SIZE;
+}
+
+/// An `addr` entry is a little-endian `u32` last seen time, little-endian `u64`
+/// services, a 16 byte IPv6 address, and a big-endian `u16` port. IPv4
+/// addresses are sent as IPv4-mapped IPv6 addresses.
+///
+/// Unlike the `version` message, `addr` entries include a timestamp.
 impl BitcoinSerialize for MetaAddr {
     fn bitcoin_serialize<W: Write>(&self, mut writer: W) -> Result<(), std::io::Error> {
         (self.last_seen.timestamp() as u32).bitcoin_serialize(&mut writer)?;
@@ -83,9 +98,112 @@ impl BitcoinDeserialize for MetaAddr {
     }
 }
 
+#[cfg(test)]
+impl proptest::arbitrary::Arbitrary for MetaAddr {
+    type Parameters = ();
+
+    fn arbitrary_with(_args: ()) -> Self::Strategy {
+        use proptest::prelude::*;
+        use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+        let ip = prop_oneof![
+            any::<[u8; 4]>().prop_map(|octets| IpAddr::V4(Ipv4Addr::from(octets))),
+            // IPv4-mapped IPv6 addresses are deserialized as IPv4 addresses
+            any::<[u8; 16]>()
+                .prop_map(Ipv6Addr::from)
+                .prop_filter("IPv4-mapped addresses are generated as IPv4", |ip| {
+                    ip.segments()[..6] != [0, 0, 0, 0, 0, 0xffff]
+                })
+                .prop_map(IpAddr::V6),
+        ];
+
+        (
+            ip,
+            any::<u16>(),
+            any::<u64>(),
+            // the time is serialized as a u32
+            0..=(u32::MAX as i64),
+        )
+            .prop_map(|(ip, port, services, timestamp)| MetaAddr {
+                addr: SocketAddr::new(ip, port),
+                // Unknown service bits are discarded during deserialization
+                services: PeerServices::from_bits_truncate(services),
+                last_seen: Utc.timestamp(timestamp, 0),
+            })
+            .boxed()
+    }
+
+    type Strategy = proptest::strategy::BoxedStrategy<Self>;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use std::net::{IpAddr, Ipv6Addr};
+
+    /// An `addr` entry from the Bitcoin developer reference.
+    ///
+    /// https://developer.bitcoin.org/reference/p2p_networking.html#addr
+    const ADDR_ENTRY_HEX: &str = "d91f4854\
+                                  0100000000000000\
+                                  00000000000000000000ffffc0000233\
+                                  208d";
+
+    #[test]
+    fn addr_entry_round_trip() {
+        zebra_test::init();
+
+        let bytes = hex::decode(ADDR_ENTRY_HEX).unwrap();
+        let entry = MetaAddr::bitcoin_deserialize(&bytes[..]).expect("entry should deserialize");
+        assert_eq!(
+            entry,
+            MetaAddr {
+                addr: "192.0.2.51:8333".parse().unwrap(),
+                services: PeerServices::NODE_NETWORK,
+                last_seen: Utc.timestamp(1_414_012_889, 0),
+            }
+        );
+
+        assert_eq!(bytes.len(), MetaAddr::SERIALIZED_SIZE);
+        assert_eq!(entry.bitcoin_serialize_to_vec().unwrap(), bytes);
+    }
+
+    #[test]
+    fn ipv6_addr_entry_round_trip() {
+        zebra_test::init();
+
+        // `::1` is not an IPv4-mapped address, so it stays IPv6
+        let entry = MetaAddr {
+            addr: (IpAddr::V6(Ipv6Addr::LOCALHOST), 18333).into(),
+            services: PeerServices::NODE_NETWORK,
+            last_seen: Utc.timestamp(1_414_012_889, 0),
+        };
+        let bytes = entry.bitcoin_serialize_to_vec().unwrap();
+        assert_eq!(&bytes[12..28], &Ipv6Addr::LOCALHOST.octets());
+        assert_eq!(&bytes[28..], &18333u16.to_be_bytes());
+        assert_eq!(MetaAddr::bitcoin_deserialize(&bytes[..]).unwrap(), entry);
+    }
+
+    proptest! {
+        #[test]
+        fn meta_addr_round_trip(entry in any::<MetaAddr>()) {
+            zebra_test::init();
+
+            let bytes = entry.bitcoin_serialize_to_vec()?;
+            prop_assert_eq!(bytes.len(), MetaAddr::SERIALIZED_SIZE);
+
+            // IPv4 addresses are sent as IPv4-mapped IPv6 addresses
+            if let IpAddr::V4(ip) = entry.addr.ip() {
+                prop_assert_eq!(&bytes[12..28], &ip.to_ipv6_mapped().octets()[..]);
+            }
+
+            let other = MetaAddr::bitcoin_deserialize(&bytes[..])?;
+            prop_assert_eq!(other.addr, entry.addr);
+            prop_assert_eq!(other, entry);
+        }
+    }
+
     // XXX remove this test and replace it with a proptest instance.
     #[test]
     fn sanitize_truncates_timestamps() {
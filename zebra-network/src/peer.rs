@@ -18,7 +18,7 @@ use client::MustUseOneshotSender;
 use error::ErrorSlot;
 
 pub use client::Client;
-pub use connection::Connection;
+pub use connection::{Connection, PeerState};
 pub use connector::Connector;
 pub use error::{HandshakeError, PeerError, SharedPeerError};
 pub use handshake::Handshake;
@@ -27,7 +27,9 @@ use zebra_chain::{
 use crate::{
     constants,
     protocol::{
-        external::{types::Nonce, GetBlocks, GetHeaders, InventoryHash, Message},
+        external::{
+            types::Nonce, CompactBlockRelayState, GetBlocks, GetHeaders, InventoryHash, Message,
+        },
         internal::{Request, Response},
     },
     BoxError,
@@ -321,6 +323,40 @@ pub(super) enum State {
     Failed,
 }
 
+/// Relay policy state negotiated by the remote peer.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PeerState {
+    /// The minimum fee rate, in satoshis per 1000 bytes, for transactions the
+    /// peer wants us to relay, as set by its latest `feefilter` message.
+    ///
+    /// `None` if the peer hasn't sent a `feefilter`.
+    pub min_fee_rate: Option<u64>,
+
+    /// Whether the peer has sent a `sendheaders` message.
+    ///
+    /// If this flag is set, we should announce blocks with `headers` messages
+    /// instead of `inv` messages. https://developer.bitcoin.org/reference/p2p_networking.html#sendheaders
+    pub sendheaders: bool,
+
+    /// The peer's compact block relay preferences, from its `sendcmpct`
+    /// messages.
+    pub compact_blocks: CompactBlockRelayState,
+}
+
+impl PeerState {
+    /// Returns true if a transaction paying `tx_fee_rate` satoshis per 1000
+    /// bytes should be relayed to this peer.
+    ///
+    /// Transactions below the peer's `feefilter` rate must not be announced or
+    /// sent to the peer.
+    pub fn should_relay_tx(&self, tx_fee_rate: u64) -> bool {
+        match self.min_fee_rate {
+            Some(min_fee_rate) => tx_fee_rate >= min_fee_rate,
+            None => true,
+        }
+    }
+}
+
 /// The state associated with a peer connection.
 pub struct Connection<S, Tx> {
     pub(super) state: State,
@@ -336,10 +372,8 @@ pub struct Connection<S, Tx> {
     pub(super) error_slot: ErrorSlot,
     //pub(super) peer_rx: Rx,
     pub(super) peer_tx: Tx,
-    /// A boolean flag indicating whether the remote peer has sent a `SendHeaders` message.
-    /// If this flag is set, we should advertise blocks with `Headers` messages instead of
-    /// `Inv` messages. https://developer.bitcoin.org/reference/p2p_networking.html#sendheaders
-    pub(super) sendheaders: bool,
+    /// The relay policy requested by the remote peer.
+    pub(super) peer_state: PeerState,
 }
 
 impl<S, Tx> Connection<S, Tx>
@@ -726,7 +760,7 @@ where
                 }
             }
             (AwaitingRequest, AdvertiseBlock(hash)) => {
-                let msg = match self.sendheaders {
+                let msg = match self.peer_state.sendheaders {
                   false => Message::Inv(vec![hash.into()]),
                   true =>  {
                     match self.get_header_for_block(hash).await {
@@ -836,25 +870,30 @@ where
             }
             // Zebra crawls the network proactively, to prevent
             // peers from inserting data into our address book.
-            Message::Addr(_) => {
+            Message::Addr(_) | Message::AddrV2(_) => {
                 trace!("ignoring unsolicited addr message");
                 return;
             }
             Message::Tx(transaction) => Request::PushTransaction(transaction),
-            Message::Inv(items) => match &items[..] {
-                // We don't expect to be advertised multiple blocks at a time,
-                // so we ignore any advertisements of multiple blocks.
-                [InventoryHash::Block(hash)] => Request::AdvertiseBlock(*hash),
-                [InventoryHash::Tx(_), rest @ ..]
-                    if rest.iter().all(|item| matches!(item, InventoryHash::Tx(_))) =>
-                {
-                    Request::TransactionsByHash(transaction_hashes(&items).collect())
-                }
-                _ => {
-                    self.fail_with(PeerError::WrongMessage("inv with mixed item types"));
-                    return;
+            Message::Inv(items) => {
+                // Remove duplicate items before checking the item types, so
+                // repeated advertisements of a block are still a single block.
+                let items = InventoryHash::dedup(items);
+                match &items[..] {
+                    // We don't expect to be advertised multiple blocks at a time,
+                    // so we ignore any advertisements of multiple blocks.
+                    [InventoryHash::Block(hash)] => Request::AdvertiseBlock(*hash),
+                    [InventoryHash::Tx(_), rest @ ..]
+                        if rest.iter().all(|item| matches!(item, InventoryHash::Tx(_))) =>
+                    {
+                        Request::TransactionsByHash(transaction_hashes(&items).collect())
+                    }
+                    _ => {
+                        self.fail_with(PeerError::WrongMessage("inv with mixed item types"));
+                        return;
+                    }
                 }
-            },
+            }
             Message::GetData(items) => match &items[..] {
                 [InventoryHash::Block(_), rest @ ..]
                     if rest
@@ -891,14 +930,21 @@ where
             }
             Message::GetBlockTxn(_) => todo!(),
             Message::BlockTxn(_) => todo!(),
-            Message::SendCompact(_) => {
-                todo!()
+            Message::SendCompact(send_compact) => {
+                self.peer_state.compact_blocks.update(&send_compact);
+                return;
             }
-            Message::FeeFilter(_) => {
-                todo!()
+            Message::FeeFilter(min_fee_rate) => {
+                self.peer_state.min_fee_rate = Some(min_fee_rate);
+                return;
             }
             Message::SendHeaders => {
-                self.sendheaders = true;
+                self.peer_state.sendheaders = true;
+                return;
+            }
+            // Alerts are deprecated and insecure, so we ignore them.
+            Message::Alert(_) => {
+                tracing::debug!("ignoring deprecated alert message");
                 return;
             }
         };
@@ -1045,3 +1091,30 @@ fn block_hashes(items: &'_ [InventoryHash]) -> impl Iterator<Item = block::Hash>
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relay_without_feefilter() {
+        zebra_test::init();
+
+        let peer_state = PeerState::default();
+        assert!(peer_state.should_relay_tx(0));
+        assert!(peer_state.should_relay_tx(u64::MAX));
+    }
+
+    #[test]
+    fn feefilter_blocks_low_fee_transactions() {
+        zebra_test::init();
+
+        let peer_state = PeerState {
+            min_fee_rate: Some(1000),
+            ..PeerState::default()
+        };
+        assert!(!peer_state.should_relay_tx(999));
+        assert!(peer_state.should_relay_tx(1000));
+        assert!(peer_state.should_relay_tx(5000));
+    }
+}
@@ -435,7 +435,7 @@ where
                 error_slot: slot,
                 peer_tx,
                 request_timer: None,
-                sendheaders: false,
+                peer_state: connection::PeerState::default(),
             };
 
             tokio::spawn(
@@ -1,9 +1,15 @@
 /// A Tokio codec that transforms an `AsyncRead` into a `Stream` of `Message`s.
 pub mod codec;
+/// A reusable actor that drives a peer connection.
+pub mod connection;
+/// Per-message callbacks for protocol consumers.
+mod handler;
 /// Inventory items.
 mod inv;
 /// An enum of all supported Bitcoin message types.
 mod message;
+/// A bounded queue of messages waiting to be sent to a peer.
+pub mod outbound_queue;
 
 /// A single byte enum representing a Bitcoin message type.
 mod command;
@@ -12,8 +18,10 @@ pub use command::Command;
 pub mod types;
 
 pub use codec::Codec;
+pub use handler::MessageHandler;
 pub use inv::InventoryHash;
 pub use message::{
-    BlockTxn, CompactBlock, GetBlockTxn, GetBlocks, GetHeaders, MerkleBlock, Message, SendCompact,
-    Version,
+    AddrV2Address, AddrV2Entry, BlockTxn, CompactBlock, CompactBlockRelayState, GetBlockTxn,
+    GetBlocks, GetHeaders, MerkleBlock, Message, SendCompact, ShortId, ShortIdKeys, Version,
 };
+pub use outbound_queue::OutboundQueue;
@@ -6,16 +6,19 @@ use std::{
     sync::Arc,
 };
 
-use crate::meta_addr::MetaAddr;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 use tokio_util::codec::{Decoder, Encoder};
 
 use zebra_chain::{
     block,
     parameters::Network,
-    serialization::{sha256d, BitcoinDeserialize, BitcoinSerialize, SerializationError as Error},
+    serialization::{
+        bitcoin_deserialize_bounded_vec, sha256d, BitcoinDeserialize, BitcoinSerialize,
+        MinSerializedSize, SerializationError as Error,
+    },
     transaction::Transaction,
+    transparent,
 };
 
 use crate::constants;
@@ -34,7 +37,14 @@ use super::{
 const HEADER_LEN: usize = 24usize;
 
 /// Maximum size of a protocol message body.
-const MAX_PROTOCOL_MESSAGE_LEN: usize = 2 * 1024 * 1024;
+///
+/// This matches Bitcoin Core's `MAX_PROTOCOL_MESSAGE_LENGTH`, which fits the
+/// largest segwit block.
+const MAX_PROTOCOL_MESSAGE_LEN: usize = 4 * 1000 * 1000;
+
+/// The most buffer space the decoder reserves at once while waiting for a
+/// message body. Larger bodies grow the buffer as their bytes arrive.
+const MAX_BODY_RESERVE: usize = 64 * 1024;
 
 /// A codec which produces Bitcoin messages from byte streams and vice versa.
 pub struct Codec {
@@ -48,10 +58,16 @@ pub struct Builder {
     network: Network,
     /// The protocol version to speak when encoding/decoding.
     version: ProtocolVersion,
+    /// The oldest protocol version that peers may advertise.
+    min_version: ProtocolVersion,
     /// The maximum allowable message length.
     max_len: usize,
     /// An optional label to use for reporting metrics.
-    metrics_label: Option<String>,
+    metrics_label: Option<Arc<str>>,
+    /// Whether to reject messages that aren't minimally and canonically encoded.
+    strict: bool,
+    /// Whether oversized inventory lists are recoverable errors.
+    recover_oversized_inventory: bool,
 }
 
 impl Codec {
@@ -60,8 +76,11 @@ impl Codec {
         Builder {
             network: Network::Mainnet,
             version: constants::CURRENT_VERSION,
+            min_version: ProtocolVersion(0),
             max_len: MAX_PROTOCOL_MESSAGE_LEN,
             metrics_label: None,
+            strict: false,
+            recover_oversized_inventory: false,
         }
     }
 
@@ -69,6 +88,12 @@ impl Codec {
     pub fn reconfigure_version(&mut self, version: ProtocolVersion) {
         self.builder.version = version;
     }
+
+    /// The oldest protocol version that peers may advertise, see
+    /// [`Builder::with_min_version`].
+    pub fn min_version(&self) -> ProtocolVersion {
+        self.builder.min_version
+    }
 }
 
 impl Builder {
@@ -93,6 +118,17 @@ impl Builder {
         self
     }
 
+    /// Configure the oldest protocol version that peers may advertise.
+    ///
+    /// The codec still decodes `version` messages from older peers, so the
+    /// handshake can reject them with the advertised version. By default,
+    /// every version is accepted.
+    #[allow(dead_code)]
+    pub fn with_min_version(mut self, min_version: ProtocolVersion) -> Self {
+        self.min_version = min_version;
+        self
+    }
+
     /// Configure the codec's maximum accepted payload size, in bytes.
     #[allow(dead_code)]
     pub fn with_max_body_len(mut self, len: usize) -> Self {
@@ -102,7 +138,33 @@ impl Builder {
 
     /// Configure the codec for the given peer address.
     pub fn with_metrics_label(mut self, metrics_label: String) -> Self {
-        self.metrics_label = Some(metrics_label);
+        self.metrics_label = Some(metrics_label.into());
+        self
+    }
+
+    /// Configure whether the codec holds decoded messages to strict encoding
+    /// standards.
+    ///
+    /// In strict mode, message bodies must be the canonical encoding of the
+    /// decoded message, so non-canonical `CompactInt` lengths and trailing
+    /// bytes are rejected, and transaction input scripts must only use minimal
+    /// pushes. The default lenient mode tolerates these real-world quirks.
+    #[allow(dead_code)]
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Configure how the codec reports `inv`, `getdata`, and `notfound` messages
+    /// with more than [`constants::MAX_INV_IN_MESSAGE`] entries.
+    ///
+    /// By default, oversized inventory lists are parse errors. In recoverable
+    /// mode, the codec returns [`Error::OversizedInventory`] with the message's
+    /// command instead. The message has already been consumed, so the
+    /// connection can send a `reject` and keep decoding.
+    #[allow(dead_code)]
+    pub fn recover_oversized_inventory(mut self, recover: bool) -> Self {
+        self.recover_oversized_inventory = recover;
         self
     }
 }
@@ -114,38 +176,38 @@ impl Encoder<Message> for Codec {
 
     fn encode(&mut self, item: Message, dst: &mut BytesMut) -> Result<(), Self::Error> {
         use Error::Parse;
-        // XXX(HACK): this is inefficient and does an extra allocation.
-        // instead, we should have a size estimator for the message, reserve
-        // that much space, write the header (with zeroed checksum), then the body,
-        // then write the computed checksum in-place.  for now, just do an extra alloc.
 
-        let mut body = Vec::new();
-        self.write_body(&item, &mut body)?;
+        let start = dst.len();
+        dst.reserve(HEADER_LEN + item.serialized_body_size());
 
-        if body.len() > self.builder.max_len {
+        // Write the header with a zeroed length and checksum, then the body,
+        // then fill in the length and checksum in place.
+        let command = item.command();
+        dst.extend_from_slice(&Magic::from(self.builder.network).0[..]);
+        dst.extend_from_slice(command.bytes());
+        dst.extend_from_slice(&[0u8; 8]);
+
+        let body_start = dst.len();
+        if let Err(e) = self.write_body(&item, (&mut *dst).writer()) {
+            dst.truncate(start);
+            return Err(e);
+        }
+        let body_len = dst.len() - body_start;
+
+        if body_len > self.builder.max_len {
+            dst.truncate(start);
             return Err(Parse("body length exceeded maximum size"));
         }
 
-        if let Some(label) = self.builder.metrics_label.clone() {
-            metrics::counter!("bytes.written", (body.len() + HEADER_LEN) as u64, "addr" =>  label);
+        if let Some(label) = &self.builder.metrics_label {
+            metrics::counter!("bytes.written", (body_len + HEADER_LEN) as u64, "addr" => label.to_string());
         }
 
-        let command = item.command();
-        let command = command.bytes();
-        trace!(?item, len = body.len());
-
-        // XXX this should write directly into the buffer,
-        // but leave it for now until we fix the issue above.
-        let mut header = [0u8; HEADER_LEN];
-        let mut header_writer = Cursor::new(&mut header[..]);
-        header_writer.write_all(&Magic::from(self.builder.network).0[..])?;
-        header_writer.write_all(command)?;
-        header_writer.write_u32::<LittleEndian>(body.len() as u32)?;
-        header_writer.write_all(&sha256d::Checksum::from(&body[..]).0)?;
-
-        dst.reserve(HEADER_LEN + body.len());
-        dst.extend_from_slice(&header);
-        dst.extend_from_slice(&body);
+        trace!(?item, len = body_len);
+
+        let checksum = sha256d::Checksum::from(&dst[body_start..]);
+        dst[body_start - 8..body_start - 4].copy_from_slice(&(body_len as u32).to_le_bytes());
+        dst[body_start - 4..body_start].copy_from_slice(&checksum.0);
 
         Ok(())
     }
@@ -170,16 +232,28 @@ impl Codec {
             Message::Reject {
                 message,
                 ccode,
-                reason: _,
+                reason,
                 data,
             } => {
-                // todo!()
                 message.bitcoin_serialize(&mut writer)?;
                 writer.write_u8(*ccode as u8)?;
-                message.bitcoin_serialize(&mut writer)?;
-                writer.write_all(&data.unwrap())?;
+                reason.bitcoin_serialize(&mut writer)?;
+                if let Some(data) = data {
+                    writer.write_all(data)?;
+                }
+            }
+            Message::Addr(addrs) => {
+                if addrs.len() > constants::MAX_ADDRS_IN_MESSAGE {
+                    return Err(Error::Parse("addr message has too many addresses"));
+                }
+                addrs.bitcoin_serialize(&mut writer)?
+            }
+            Message::AddrV2(addrs) => {
+                if addrs.len() > constants::MAX_ADDRS_IN_MESSAGE {
+                    return Err(Error::Parse("addrv2 message has too many addresses"));
+                }
+                addrs.bitcoin_serialize(&mut writer)?
             }
-            Message::Addr(addrs) => addrs.bitcoin_serialize(&mut writer)?,
             Message::GetAddr => { /* Empty payload -- no-op */ }
             Message::Block(block) => block.bitcoin_serialize(&mut writer)?,
             Message::GetBlocks(get_blocks) => {
@@ -216,6 +290,7 @@ impl Codec {
             Message::SendCompact(inner) => inner.bitcoin_serialize(&mut writer)?,
             Message::FeeFilter(inner) => inner.bitcoin_serialize(&mut writer)?,
             Message::SendHeaders => { /* Empty payload -- no-op */ }
+            Message::Alert(payload) => writer.write_all(payload)?,
         }
         Ok(())
     }
@@ -291,12 +366,15 @@ impl Decoder for Codec {
                     return Err(Parse("body length exceeded maximum size"));
                 }
 
-                if let Some(label) = self.builder.metrics_label.clone() {
-                    metrics::counter!("bytes.read", (body_len + HEADER_LEN) as u64, "addr" =>  label);
+                if let Some(label) = &self.builder.metrics_label {
+                    metrics::counter!("bytes.read", (body_len + HEADER_LEN) as u64, "addr" => label.to_string());
                 }
 
-                // Reserve buffer space for the expected body and the following header.
-                src.reserve(body_len + HEADER_LEN);
+                // Reserve buffer space for the part of the body and the following
+                // header that hasn't arrived yet. If it's already buffered, there's
+                // nothing to reserve, and large bodies only reserve a bounded chunk.
+                let missing_len = (body_len + HEADER_LEN).saturating_sub(src.len());
+                src.reserve(missing_len.min(MAX_BODY_RESERVE));
 
                 self.state = DecodeState::Body {
                     body_len,
@@ -305,6 +383,7 @@ impl Decoder for Codec {
                 };
 
                 // Now that the state is updated, recurse to attempt body decoding.
+                // The body state never recurses, so the stack depth is bounded.
                 self.decode(src)
             }
             DecodeState::Body {
@@ -334,27 +413,29 @@ impl Decoder for Codec {
                 // Convention: deserialize the message directly (using `bitcoin_deserialize()`) unless
                 // it requires context from the codec. In that case, use the codec's self.read_* method.
                 let msg = match command {
-                    Command::Addr => {
-                        Message::Addr(Vec::<MetaAddr>::bitcoin_deserialize(&mut body_reader)?)
-                    }
+                    Command::Addr => Message::Addr(self.read_list(&mut body_reader, body_len)?),
+                    Command::AddrV2 => Message::AddrV2(self.read_list(&mut body_reader, body_len)?),
                     Command::Version => {
                         Message::Version(Version::bitcoin_deserialize(&mut body_reader)?)
                     }
                     Command::Verack => Message::Verack,
                     Command::GetBlocks => self.read_getblocks(&mut body_reader)?,
-                    Command::GetData => Message::GetData(
-                        <Vec<InventoryHash>>::bitcoin_deserialize(&mut body_reader)?,
-                    ),
-                    Command::Block => {
-                        Message::Block(Arc::new(block::Block::deserialize_from_buf(&mut body)?))
-                    }
+                    Command::GetData => Message::GetData(self.read_inventory(
+                        &mut body_reader,
+                        body_len,
+                        "getdata",
+                    )?),
+                    Command::Block => Message::Block(Arc::new(
+                        block::Block::deserialize_from_buf(&mut body)
+                            .map_err(|e| e.context("block"))?,
+                    )),
 
                     Command::GetHeaders => self.read_getheaders(&mut body_reader)?,
-                    Command::Headers => Message::Headers(
-                        <Vec<block::CountedHeader>>::bitcoin_deserialize(&mut body_reader)?,
-                    ),
+                    Command::Headers => {
+                        Message::Headers(self.read_list(&mut body_reader, body_len)?)
+                    }
                     Command::Inv => {
-                        Message::Inv(<Vec<InventoryHash>>::bitcoin_deserialize(&mut body_reader)?)
+                        Message::Inv(self.read_inventory(&mut body_reader, body_len, "inv")?)
                     }
                     Command::MemPool => Message::Mempool,
                     Command::MerkleBlock => {
@@ -372,18 +453,16 @@ impl Decoder for Codec {
                     Command::SendCmpct => {
                         Message::SendCompact(SendCompact::bitcoin_deserialize(&mut body_reader)?)
                     }
-                    Command::NotFound => Message::NotFound(
-                        <Vec<InventoryHash>>::bitcoin_deserialize(&mut body_reader)?,
+                    Command::NotFound => Message::NotFound(self.read_inventory(
+                        &mut body_reader,
+                        body_len,
+                        "notfound",
+                    )?),
+                    Command::Tx => Message::Tx(
+                        <Arc<Transaction>>::bitcoin_deserialize(&mut body_reader)
+                            .map_err(|e| e.context("tx"))?,
                     ),
-                    Command::Tx => {
-                        Message::Tx(<Arc<Transaction>>::bitcoin_deserialize(&mut body_reader)?)
-                    }
-                    Command::Alert => {
-                        // TODO: Verify that no additional cleanup is required.
-                        self.state = DecodeState::Head;
-                        debug!("Received Alert message! Alert is insecure and deprecated");
-                        return Ok(None);
-                    }
+                    Command::Alert => Message::Alert(body.to_vec()),
                     Command::FeeFilter => {
                         Message::FeeFilter(u64::bitcoin_deserialize(&mut body_reader)?)
                     }
@@ -396,6 +475,9 @@ impl Decoder for Codec {
                     Command::Reject => self.read_reject(&mut body_reader)?,
                     Command::SendHeaders => Message::SendHeaders,
                 };
+                if self.builder.strict {
+                    self.check_strict_encoding(&msg, body_len)?;
+                }
                 trace!("finished message decoding");
                 Ok(Some(msg))
             }
@@ -404,6 +486,36 @@ impl Decoder for Codec {
 }
 
 impl Codec {
+    /// Check that `msg`, decoded from a `body_len` byte body, was minimally
+    /// and canonically encoded.
+    fn check_strict_encoding(&self, msg: &Message, body_len: usize) -> Result<(), Error> {
+        // Non-canonical `CompactInt`s and trailing bytes make the body longer
+        // than the canonical encoding of the message.
+        if msg.serialized_body_size() != body_len {
+            return Err(Error::Parse("message body is not canonically encoded"));
+        }
+
+        let transactions = match msg {
+            Message::Tx(transaction) => std::slice::from_ref(transaction),
+            Message::Block(block) => &block.transactions[..],
+            _ => &[],
+        };
+        let minimal_pushes = transactions
+            .iter()
+            .flat_map(|transaction| transaction.inputs().iter())
+            .all(|input| match input {
+                transparent::Input::PrevOut { unlock_script, .. } => {
+                    unlock_script.has_minimal_pushes()
+                }
+                transparent::Input::Coinbase { .. } => true,
+            });
+        if !minimal_pushes {
+            return Err(Error::Parse("input script has a non-minimal push"));
+        }
+
+        Ok(())
+    }
+
     fn read_reject<R: Read>(&self, mut reader: R) -> Result<Message, Error> {
         Ok(Message::Reject {
             message: String::bitcoin_deserialize(&mut reader)?,
@@ -431,6 +543,39 @@ impl Codec {
         })
     }
 
+    /// Read a list of items from a `body_len` byte message body, rejecting
+    /// declared counts that can't fit in the body before reading any items.
+    fn read_list<T, R>(&self, reader: R, body_len: usize) -> Result<Vec<T>, Error>
+    where
+        T: BitcoinDeserialize + MinSerializedSize,
+        R: Read,
+    {
+        bitcoin_deserialize_bounded_vec(reader, body_len)
+    }
+
+    /// Read an `inv`, `getdata`, or `notfound` inventory list, rejecting lists
+    /// with more than [`constants::MAX_INV_IN_MESSAGE`] entries.
+    fn read_inventory<R: Read>(
+        &self,
+        reader: R,
+        body_len: usize,
+        command: &'static str,
+    ) -> Result<Vec<InventoryHash>, Error> {
+        let inventory: Vec<InventoryHash> = self.read_list(reader, body_len)?;
+        if inventory.len() <= constants::MAX_INV_IN_MESSAGE {
+            return Ok(inventory);
+        }
+
+        if self.builder.recover_oversized_inventory {
+            Err(Error::OversizedInventory {
+                command,
+                count: inventory.len(),
+            })
+        } else {
+            Err(Error::Parse("inventory list has too many entries"))
+        }
+    }
+
     fn read_getblocks<R: Read>(&self, mut reader: R) -> Result<Message, Error> {
         let received_version = ProtocolVersion::bitcoin_deserialize(&mut reader)?;
         let get_blocks = GetBlocks::bitcoin_deserialize(&mut reader)?;
@@ -487,7 +632,9 @@ impl Codec {
 // XXX replace these interior unit tests with exterior integration tests + proptest
 #[cfg(test)]
 mod tests {
+    use super::super::{inv::InventoryHash, message::AddrV2Entry};
     use super::*;
+    use crate::meta_addr::MetaAddr;
     use futures::prelude::*;
     use tokio::runtime::Runtime;
 
@@ -604,6 +751,57 @@ mod tests {
         });
     }
 
+    #[test]
+    fn addr_message_is_capped() {
+        use chrono::{TimeZone, Utc};
+        zebra_test::init();
+
+        let rt = Runtime::new().unwrap();
+
+        let addrs: Vec<MetaAddr> = (0..1500u16)
+            .map(|port| MetaAddr {
+                addr: ([203, 0, 113, 6], port).into(),
+                services: PeerServices::NODE_NETWORK,
+                last_seen: Utc.timestamp(1_573_680_000, 0),
+            })
+            .collect();
+
+        use tokio_util::codec::{FramedRead, FramedWrite};
+
+        // An over-long addr message is rejected by the encoder
+        rt.block_on(async {
+            let mut bytes = Vec::new();
+            let mut fw = FramedWrite::new(&mut bytes, Codec::builder().finish());
+            fw.send(Message::Addr(addrs.clone()))
+                .await
+                .expect_err("addr message with 1500 entries should not encode");
+        });
+
+        let msg_bytes = rt.block_on(async {
+            let mut bytes = Vec::new();
+            {
+                let mut fw = FramedWrite::new(&mut bytes, Codec::builder().finish());
+                fw.send(Message::addr(addrs.clone()))
+                    .await
+                    .expect("truncated addr message should encode");
+            }
+            bytes
+        });
+
+        let msg = rt.block_on(async {
+            let mut fr = FramedRead::new(Cursor::new(&msg_bytes), Codec::builder().finish());
+            fr.next()
+                .await
+                .expect("a next message should be available")
+                .expect("that message should deserialize")
+        });
+
+        assert_eq!(
+            msg,
+            Message::Addr(addrs[..constants::MAX_ADDRS_IN_MESSAGE].to_vec())
+        );
+    }
+
     #[test]
     fn max_msg_size_round_trip() {
         use std::sync::Arc;
@@ -675,4 +873,644 @@ mod tests {
                 .expect("message should decode with the msg body size as max allowed value")
         });
     }
+
+    /// Encode `msg` by serializing its body into a separate buffer, then
+    /// writing the header and the body.
+    fn two_pass_encode(codec: &Codec, msg: &Message) -> Vec<u8> {
+        let mut body = Vec::new();
+        codec
+            .write_body(msg, &mut body)
+            .expect("message should serialize");
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&Magic::from(codec.builder.network).0[..]);
+        bytes.extend_from_slice(msg.command().bytes());
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&sha256d::Checksum::from(&body[..]).0);
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    /// Returns a distinct index for each `Message` variant.
+    ///
+    /// This match is exhaustive, so new variants must be added here, and to
+    /// the messages in `encode_matches_two_pass_encoding`.
+    fn variant_index(msg: &Message) -> usize {
+        match msg {
+            Message::Version(_) => 0,
+            Message::Verack => 1,
+            Message::Ping(_) => 2,
+            Message::Pong(_) => 3,
+            Message::Reject { .. } => 4,
+            Message::GetAddr => 5,
+            Message::Addr(_) => 6,
+            Message::AddrV2(_) => 7,
+            Message::GetBlocks(_) => 8,
+            Message::Inv(_) => 9,
+            Message::GetHeaders(_) => 10,
+            Message::Headers(_) => 11,
+            Message::GetData(_) => 12,
+            Message::Block(_) => 13,
+            Message::Tx(_) => 14,
+            Message::NotFound(_) => 15,
+            Message::Mempool => 16,
+            Message::FilterLoad { .. } => 17,
+            Message::FilterAdd { .. } => 18,
+            Message::FilterClear => 19,
+            Message::MerkleBlock(_) => 20,
+            Message::CompactBlock(_) => 21,
+            Message::GetBlockTxn(_) => 22,
+            Message::BlockTxn(_) => 23,
+            Message::SendCompact(_) => 24,
+            Message::FeeFilter(_) => 25,
+            Message::SendHeaders => 26,
+            Message::Alert(_) => 27,
+        }
+    }
+
+    /// The number of `Message` variants, see `variant_index`.
+    const MESSAGE_VARIANT_COUNT: usize = 28;
+
+    #[test]
+    fn encode_matches_two_pass_encoding() {
+        use super::super::message::{AddrV2Address, AddrV2Entry, PrefilledTransaction, ShortId};
+        use chrono::{TimeZone, Utc};
+        use std::{
+            collections::HashSet,
+            net::{IpAddr, Ipv4Addr, SocketAddr},
+        };
+        use zebra_chain::{compactint::CompactInt, serialization::BitcoinDeserializeInto};
+        zebra_test::init();
+
+        let block: block::Block = zebra_test::vectors::BLOCK_MAINNET_415000_BYTES
+            .bitcoin_deserialize_into()
+            .expect("block should deserialize");
+        let hash = block.hash();
+        let services = PeerServices::NODE_NETWORK;
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 6)), 8333);
+
+        // Witness data is part of the serialized transactions in some messages
+        let mut witness_tx = block.transactions[1].as_ref().clone();
+        *witness_tx.witnesses_mut() = vec![vec![vec![0x30; 71], vec![0x02; 33]]];
+
+        let messages = vec![
+            Message::Version(Version::new(
+                crate::constants::CURRENT_VERSION,
+                addr,
+                services,
+                services,
+                addr,
+                Nonce(0x9082_4908_8927_9238),
+                "Beaver".to_owned(),
+                block::Height(540_000),
+                true,
+            )),
+            Message::Verack,
+            Message::Ping(Nonce(1)),
+            Message::Pong(Nonce(2)),
+            Message::Reject {
+                message: "tx".to_owned(),
+                ccode: RejectReason::Duplicate,
+                reason: "txn-already-known".to_owned(),
+                data: Some([7; 32]),
+            },
+            Message::Reject {
+                message: "version".to_owned(),
+                ccode: RejectReason::Obsolete,
+                reason: "Version must be 31800 or greater".to_owned(),
+                data: None,
+            },
+            Message::GetAddr,
+            Message::Addr(vec![
+                MetaAddr {
+                    addr,
+                    services,
+                    last_seen: Utc.timestamp(1_573_680_000, 0),
+                };
+                3
+            ]),
+            Message::AddrV2(vec![
+                AddrV2Entry {
+                    last_seen: Utc.timestamp(1_573_680_000, 0),
+                    services,
+                    address: AddrV2Address::Ipv4(Ipv4Addr::new(203, 0, 113, 6)),
+                    port: 8333,
+                },
+                AddrV2Entry {
+                    last_seen: Utc.timestamp(1_573_680_000, 0),
+                    services,
+                    address: AddrV2Address::TorV3([0x5a; 32]),
+                    port: 8333,
+                },
+            ]),
+            Message::GetBlocks(GetBlocks {
+                block_header_hashes: vec![hash, block.header.previous_block_hash],
+                stop_hash: None,
+            }),
+            Message::GetHeaders(GetHeaders {
+                block_header_hashes: vec![hash],
+                stop_hash: Some(hash),
+            }),
+            Message::Headers(vec![block::CountedHeader {
+                header: block.header,
+                transaction_count: 0,
+            }]),
+            Message::Inv(vec![InventoryHash::Block(hash)]),
+            Message::GetData(vec![InventoryHash::Tx(block.transactions[1].hash())]),
+            Message::NotFound(Vec::new()),
+            Message::Tx(block.transactions[1].clone()),
+            Message::Tx(Arc::new(witness_tx.clone())),
+            Message::Block(Arc::new(block.clone())),
+            Message::Mempool,
+            Message::FilterLoad {
+                filter: Filter(vec![0; 100]),
+                hash_functions_count: 3,
+                tweak: Tweak(7),
+                flags: 1,
+            },
+            Message::FilterAdd { data: vec![1; 20] },
+            Message::FilterClear,
+            Message::MerkleBlock(MerkleBlock {
+                block_header: block.header,
+                transaction_count: block.transactions.len() as u32,
+                hashes: vec![block.transactions[1].hash(), block.transactions[2].hash()],
+                flags: vec![0x1d],
+            }),
+            Message::CompactBlock(CompactBlock {
+                header: block.header,
+                nonce: 7,
+                short_ids: vec![ShortId::new(1), ShortId::new(2), ShortId::new(3)],
+                prefilled_txns: vec![
+                    PrefilledTransaction {
+                        index: CompactInt::from(0),
+                        tx: block.transactions[0].as_ref().clone(),
+                    },
+                    PrefilledTransaction {
+                        index: CompactInt::from(0),
+                        tx: witness_tx.clone(),
+                    },
+                ],
+            }),
+            Message::GetBlockTxn(GetBlockTxn {
+                block_hash: hash,
+                indexes: vec![CompactInt::from(1), CompactInt::from(300)],
+            }),
+            Message::BlockTxn(BlockTxn {
+                block_hash: hash,
+                txs: vec![block.transactions[1].as_ref().clone(), witness_tx],
+            }),
+            Message::SendCompact(SendCompact {
+                announce: true,
+                version: 1,
+            }),
+            Message::FeeFilter(1000),
+            Message::SendHeaders,
+            Message::Alert(vec![2; 40]),
+        ];
+
+        assert_eq!(
+            messages.iter().map(variant_index).collect::<HashSet<_>>(),
+            (0..MESSAGE_VARIANT_COUNT).collect::<HashSet<_>>(),
+            "every message variant should be checked"
+        );
+
+        let mut codec = Codec::builder().finish();
+        let mut dst = BytesMut::new();
+        for msg in messages {
+            let expected = two_pass_encode(&codec, &msg);
+            assert_eq!(
+                expected.len(),
+                HEADER_LEN + msg.serialized_body_size(),
+                "size estimate for {} should be exact",
+                msg
+            );
+
+            // Encode several messages into the same buffer
+            let start = dst.len();
+            codec.encode(msg, &mut dst).expect("message should encode");
+            assert_eq!(&dst[start..], &expected[..]);
+        }
+    }
+
+    #[test]
+    fn oversized_encode_leaves_buffer_unchanged() {
+        zebra_test::init();
+
+        let mut codec = Codec::builder().with_max_body_len(10).finish();
+        let mut dst = BytesMut::new();
+        codec
+            .encode(Message::Ping(Nonce(1)), &mut dst)
+            .expect("ping should encode");
+        let encoded = dst.clone();
+
+        codec
+            .encode(Message::FilterAdd { data: vec![0; 11] }, &mut dst)
+            .expect_err("filteradd body is too long");
+        assert_eq!(dst, encoded);
+    }
+
+    #[test]
+    fn decoding_many_small_messages_does_not_grow_buffer() {
+        zebra_test::init();
+
+        const PING_COUNT: u64 = 10_000;
+
+        let mut codec = Codec::builder().finish();
+        let mut src = BytesMut::new();
+        for nonce in 0..PING_COUNT {
+            codec
+                .encode(Message::Ping(Nonce(nonce)), &mut src)
+                .expect("ping should encode");
+        }
+        let initial_capacity = src.capacity();
+
+        let mut decoded = 0;
+        while let Some(msg) = codec.decode(&mut src).expect("ping should decode") {
+            assert_eq!(msg, Message::Ping(Nonce(decoded)));
+            assert!(
+                src.capacity() <= initial_capacity,
+                "decoding buffered messages should not reserve more space"
+            );
+            decoded += 1;
+        }
+
+        assert_eq!(decoded, PING_COUNT);
+        assert!(src.is_empty());
+    }
+
+    /// A reader that counts how many times it is read from.
+    struct CountingReader<R> {
+        inner: R,
+        reads: usize,
+    }
+
+    impl<R: tokio::io::AsyncRead + Unpin> tokio::io::AsyncRead for CountingReader<R> {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            self.reads += 1;
+            std::pin::Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    #[test]
+    fn framed_read_drains_concatenated_messages() {
+        zebra_test::init();
+
+        const MESSAGE_COUNT: u64 = 100;
+
+        let mut codec = Codec::builder().finish();
+        let mut src = BytesMut::new();
+        for nonce in 0..MESSAGE_COUNT {
+            codec
+                .encode(Message::Ping(Nonce(nonce)), &mut src)
+                .expect("ping should encode");
+        }
+
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            use tokio_util::codec::FramedRead;
+
+            let reader = CountingReader {
+                inner: Cursor::new(src.to_vec()),
+                reads: 0,
+            };
+            let mut fr = FramedRead::new(reader, Codec::builder().finish());
+
+            // Every message is decoded from the first read
+            for nonce in 0..MESSAGE_COUNT {
+                let msg = fr
+                    .next()
+                    .await
+                    .expect("a next message should be available")
+                    .expect("ping should decode");
+                assert_eq!(msg, Message::Ping(Nonce(nonce)));
+                assert_eq!(fr.get_ref().reads, 1);
+            }
+            assert!(fr.next().await.is_none());
+        });
+    }
+
+    #[test]
+    fn partial_body_reserve_is_bounded() {
+        zebra_test::init();
+
+        let mut codec = Codec::builder().finish();
+        let mut src = BytesMut::new();
+        codec
+            .encode(Message::FilterAdd { data: vec![0; 520] }, &mut src)
+            .expect("filteradd should encode");
+        let mut header = src.split_to(HEADER_LEN);
+
+        // Claim a huge body, but only provide the header
+        header[16..20].copy_from_slice(&(MAX_PROTOCOL_MESSAGE_LEN as u32).to_le_bytes());
+        let mut src = BytesMut::new();
+        src.extend_from_slice(&header);
+
+        assert_eq!(codec.decode(&mut src).expect("header is valid"), None);
+        assert!(src.capacity() <= 2 * MAX_BODY_RESERVE);
+    }
+
+    /// Encode a single item `inv` message, then rewrite its item count as a
+    /// non-canonical three byte `CompactInt`.
+    fn non_canonical_inv() -> BytesMut {
+        inv_with_count(&[0xfd, 0x01, 0x00])
+    }
+
+    /// Returns an `inv` message with a single item, and `count` as its
+    /// encoded item count.
+    fn inv_with_count(count: &[u8]) -> BytesMut {
+        let mut codec = Codec::builder().finish();
+        let mut src = BytesMut::new();
+        codec
+            .encode(
+                Message::Inv(vec![InventoryHash::Block(block::Hash([1; 32]))]),
+                &mut src,
+            )
+            .expect("inv should encode");
+        let mut header = src.split_to(HEADER_LEN);
+        assert_eq!(src[0], 1, "the canonical count is a single byte");
+
+        let mut body = count.to_vec();
+        body.extend_from_slice(&src[1..]);
+        header[16..20].copy_from_slice(&(body.len() as u32).to_le_bytes());
+        header[20..24].copy_from_slice(&sha256d::Checksum::from(&body[..]).0);
+
+        let mut src = BytesMut::new();
+        src.extend_from_slice(&header);
+        src.extend_from_slice(&body);
+        src
+    }
+
+    #[test]
+    fn non_canonical_compactint_is_lenient_by_default() {
+        zebra_test::init();
+
+        let mut codec = Codec::builder().finish();
+        let msg = codec
+            .decode(&mut non_canonical_inv())
+            .expect("lenient mode accepts non-canonical lengths");
+        assert_eq!(
+            msg,
+            Some(Message::Inv(vec![InventoryHash::Block(block::Hash(
+                [1; 32]
+            ))]))
+        );
+    }
+
+    #[test]
+    fn non_canonical_compactint_fails_in_strict_mode() {
+        zebra_test::init();
+
+        let mut codec = Codec::builder().strict(true).finish();
+        assert_eq!(
+            codec.decode(&mut non_canonical_inv()),
+            Err(Error::Parse("message body is not canonically encoded"))
+        );
+
+        // Canonically encoded messages still decode
+        let mut src = BytesMut::new();
+        codec
+            .encode(Message::Ping(Nonce(7)), &mut src)
+            .expect("ping should encode");
+        assert_eq!(
+            codec.decode(&mut src).expect("ping is canonical"),
+            Some(Message::Ping(Nonce(7)))
+        );
+    }
+
+    #[test]
+    fn reject_message_round_trip() {
+        zebra_test::init();
+
+        let rejects = vec![
+            Message::Reject {
+                message: "tx".to_owned(),
+                ccode: RejectReason::Duplicate,
+                reason: "txn-already-known".to_owned(),
+                data: Some([7; 32]),
+            },
+            Message::Reject {
+                message: "version".to_owned(),
+                ccode: RejectReason::Obsolete,
+                reason: "Version must be 31800 or greater".to_owned(),
+                data: None,
+            },
+        ];
+
+        let mut codec = Codec::builder().finish();
+        for reject in rejects {
+            let mut src = BytesMut::new();
+            codec
+                .encode(reject.clone(), &mut src)
+                .expect("reject should encode");
+            assert_eq!(
+                codec.decode(&mut src).expect("reject should decode"),
+                Some(reject)
+            );
+            assert!(src.is_empty());
+        }
+    }
+
+    #[test]
+    fn addrv2_message_round_trip() {
+        use super::super::message::AddrV2Address;
+        use chrono::{TimeZone, Utc};
+        use std::net::{Ipv4Addr, Ipv6Addr};
+
+        zebra_test::init();
+
+        let entry = |address| AddrV2Entry {
+            last_seen: Utc.timestamp(1_414_012_889, 0),
+            services: PeerServices::NODE_NETWORK,
+            address,
+            port: 8333,
+        };
+        let addrv2 = Message::AddrV2(vec![
+            entry(AddrV2Address::Ipv4(Ipv4Addr::new(192, 0, 2, 51))),
+            entry(AddrV2Address::Ipv6(Ipv6Addr::new(
+                0x2001, 0xdb8, 0, 0, 0, 0, 0, 1,
+            ))),
+            entry(AddrV2Address::TorV3([0x5a; 32])),
+        ]);
+
+        let mut codec = Codec::builder().finish();
+        let mut src = BytesMut::new();
+        codec
+            .encode(addrv2.clone(), &mut src)
+            .expect("addrv2 should encode");
+        assert_eq!(
+            codec.decode(&mut src).expect("addrv2 should decode"),
+            Some(addrv2)
+        );
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn alert_message_is_decoded() {
+        zebra_test::init();
+
+        // An alert from a peer, followed by a ping
+        let payload = vec![0xa5; 50];
+        let mut src = BytesMut::new();
+        src.extend_from_slice(&Magic::from(Network::Mainnet).0);
+        src.extend_from_slice(b"alert\0\0\0\0\0\0\0");
+        src.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        src.extend_from_slice(&sha256d::Checksum::from(&payload[..]).0);
+        src.extend_from_slice(&payload);
+
+        let mut codec = Codec::builder().finish();
+        codec
+            .encode(Message::Ping(Nonce(5)), &mut src)
+            .expect("ping should encode");
+
+        assert_eq!(
+            codec.decode(&mut src).expect("alert should decode"),
+            Some(Message::Alert(payload))
+        );
+        assert_eq!(
+            codec.decode(&mut src).expect("ping should decode"),
+            Some(Message::Ping(Nonce(5)))
+        );
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn inv_count_larger_than_body_is_rejected() {
+        zebra_test::init();
+
+        // Declare 2^24 items, but only send one
+        let mut codec = Codec::builder().finish();
+        assert_eq!(
+            codec.decode(&mut inv_with_count(&[0xfe, 0x00, 0x00, 0x00, 0x01])),
+            Err(Error::Parse(
+                "declared item count is larger than the remaining data"
+            ))
+        );
+    }
+
+    #[test]
+    fn oversized_inv_is_a_recoverable_error() {
+        zebra_test::init();
+
+        let oversized = Message::Inv(vec![
+            InventoryHash::Block(block::Hash([1; 32]));
+            constants::MAX_INV_IN_MESSAGE + 1
+        ]);
+        let encode = |msg: Message| {
+            let mut src = BytesMut::new();
+            let mut codec = Codec::builder().finish();
+            codec.encode(msg, &mut src).expect("inv should encode");
+            codec
+                .encode(Message::Ping(Nonce(6)), &mut src)
+                .expect("ping should encode");
+            src
+        };
+
+        // By default, oversized inventory lists are parse errors
+        let mut src = encode(oversized.clone());
+        assert_eq!(
+            Codec::builder().finish().decode(&mut src),
+            Err(Error::Parse("inventory list has too many entries"))
+        );
+
+        // In recoverable mode, the error carries the command, and the next
+        // message can still be decoded
+        let mut src = encode(oversized);
+        let mut codec = Codec::builder().recover_oversized_inventory(true).finish();
+        assert_eq!(
+            codec.decode(&mut src),
+            Err(Error::OversizedInventory {
+                command: "inv",
+                count: 50_001,
+            })
+        );
+        assert_eq!(
+            codec.decode(&mut src).expect("ping should decode"),
+            Some(Message::Ping(Nonce(6)))
+        );
+
+        // Lists at the limit are accepted
+        let mut src = encode(Message::Inv(vec![
+            InventoryHash::Block(block::Hash([2; 32]));
+            constants::MAX_INV_IN_MESSAGE
+        ]));
+        assert!(matches!(
+            codec.decode(&mut src),
+            Ok(Some(Message::Inv(inventory))) if inventory.len() == constants::MAX_INV_IN_MESSAGE
+        ));
+    }
+
+    #[test]
+    fn metrics_labels_are_attached_to_byte_counters() {
+        use metrics_util::{DebugValue, DebuggingRecorder};
+
+        zebra_test::init();
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        recorder
+            .install()
+            .expect("no other test installs a metrics recorder");
+
+        let mut codec = Codec::builder()
+            .with_metrics_label("192.0.2.1".to_owned())
+            .finish();
+        let mut buf = BytesMut::new();
+        codec
+            .encode(Message::Ping(Nonce(1)), &mut buf)
+            .expect("ping should encode");
+        let ping_len = buf.len() as u64;
+        assert_eq!(
+            codec.decode(&mut buf).expect("ping should decode"),
+            Some(Message::Ping(Nonce(1)))
+        );
+
+        // Other tests can record metrics concurrently, so only look at the
+        // counters with this test's label
+        let labeled_count = |name: &str| {
+            snapshotter
+                .snapshot()
+                .into_iter()
+                .filter_map(|(key, _unit, _description, value)| {
+                    let key = key.key();
+                    let labeled = key
+                        .labels()
+                        .any(|label| label.key() == "addr" && label.value() == "192.0.2.1");
+                    match value {
+                        DebugValue::Counter(count) if labeled && key.name().to_string() == name => {
+                            Some(count)
+                        }
+                        _ => None,
+                    }
+                })
+                .sum::<u64>()
+        };
+        assert_eq!(labeled_count("bytes.written"), ping_len);
+        assert_eq!(labeled_count("bytes.read"), ping_len);
+    }
+
+    #[test]
+    fn notfound_inventory_limit() {
+        zebra_test::init();
+
+        let round_trip = |count: usize| {
+            let msg = Message::NotFound(vec![InventoryHash::Block(block::Hash([3; 32])); count]);
+            let mut src = BytesMut::new();
+            let mut codec = Codec::builder().finish();
+            codec.encode(msg, &mut src).expect("notfound should encode");
+            codec.decode(&mut src)
+        };
+
+        assert!(matches!(
+            round_trip(constants::MAX_INV_IN_MESSAGE),
+            Ok(Some(Message::NotFound(inventory))) if inventory.len() == constants::MAX_INV_IN_MESSAGE
+        ));
+        assert_eq!(
+            round_trip(constants::MAX_INV_IN_MESSAGE + 1),
+            Err(Error::Parse("inventory list has too many entries"))
+        );
+    }
 }
@@ -20,6 +20,7 @@ pub enum Command {
     NotFound,
     Tx,
     Addr,
+    AddrV2,
     Alert,
     FeeFilter,
     FilterAdd,
@@ -51,6 +52,7 @@ impl Command {
             Command::NotFound => b"notfound\0\0\0\0",
             Command::Tx => b"tx\0\0\0\0\0\0\0\0\0\0",
             Command::Addr => b"addr\0\0\0\0\0\0\0\0",
+            Command::AddrV2 => b"addrv2\0\0\0\0\0\0",
             Command::Alert => b"alert\0\0\0\0\0\0\0",
             Command::FeeFilter => b"feefilter\0\0\0",
             Command::FilterAdd => b"filteradd\0\0\0",
@@ -97,6 +99,7 @@ impl BitcoinDeserialize for Command {
             b"notfound\0\0\0\0" => Command::NotFound,
             b"tx\0\0\0\0\0\0\0\0\0\0" => Command::Tx,
             b"addr\0\0\0\0\0\0\0\0" => Command::Addr,
+            b"addrv2\0\0\0\0\0\0" => Command::AddrV2,
             b"alert\0\0\0\0\0\0\0" => Command::Alert,
             b"feefilter\0\0\0" => Command::FeeFilter,
             b"filteradd\0\0\0" => Command::FilterAdd,
@@ -0,0 +1,369 @@
+//! A reusable actor that drives a single peer connection.
+
+use futures::{
+    channel::mpsc,
+    future::{self, Either},
+    prelude::*,
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::Framed;
+use zebra_chain::serialization::SerializationError;
+
+use crate::peer::{HandshakeError, PeerError, PeerState};
+
+use super::{message::RejectReason, types::Nonce, Codec, Message, MessageHandler, Version};
+
+/// A connection to a single remote peer, generic over its message handler.
+///
+/// The connection owns the framed transport and the state requested by the
+/// peer. Its [`Connection::run`] loop answers pings, matches pongs to our
+/// pings, records the peer's `feefilter` and `sendheaders` preferences, and
+/// then dispatches every message to the handler. If the codec reports
+/// oversized inventory lists as recoverable errors, the connection rejects
+/// them and keeps running. Other tasks send messages to
+/// the peer through the request channel returned by [`Connection::new`].
+#[allow(dead_code)]
+pub struct Connection<T, H> {
+    framed: Framed<T, Codec>,
+    peer_state: PeerState,
+    handler: H,
+    requests: mpsc::Receiver<Message>,
+    /// The nonce of our last ping, if the peer hasn't answered it yet.
+    pending_ping: Option<Nonce>,
+}
+
+#[allow(dead_code)]
+impl<T, H> Connection<T, H>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    H: MessageHandler,
+{
+    /// Returns a new connection over `transport`, and a channel for sending
+    /// messages to the peer.
+    pub fn new(transport: T, codec: Codec, handler: H) -> (Self, mpsc::Sender<Message>) {
+        let (request_tx, requests) = mpsc::channel(0);
+        let connection = Connection {
+            framed: Framed::new(transport, codec),
+            peer_state: PeerState::default(),
+            handler,
+            requests,
+            pending_ping: None,
+        };
+        (connection, request_tx)
+    }
+
+    /// Returns the relay state requested by the peer.
+    pub fn peer_state(&self) -> &PeerState {
+        &self.peer_state
+    }
+
+    /// Returns the handler for this connection's messages.
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    /// Exchange `version` and `verack` messages with the peer, returning the
+    /// peer's `version`.
+    ///
+    /// Returns an error if the peer's version is older than the codec's
+    /// [`Codec::min_version`]. After the handshake, the codec uses the lower
+    /// of our protocol version and the peer's.
+    pub async fn handshake(&mut self, version: Version) -> Result<Version, HandshakeError> {
+        let our_version = version.version;
+        self.framed.send(Message::Version(version)).await?;
+
+        let remote_version = match self.next_message().await? {
+            Message::Version(remote_version) => remote_version,
+            msg => return Err(HandshakeError::UnexpectedMessage(Box::new(msg))),
+        };
+        if !remote_version.is_compatible(self.framed.codec().min_version()) {
+            return Err(HandshakeError::ObsoleteVersion(remote_version.version));
+        }
+
+        self.framed.send(Message::Verack).await?;
+        match self.next_message().await? {
+            Message::Verack => {}
+            msg => return Err(HandshakeError::UnexpectedMessage(Box::new(msg))),
+        }
+
+        let negotiated_version = std::cmp::min(remote_version.version, our_version);
+        self.framed
+            .codec_mut()
+            .reconfigure_version(negotiated_version);
+
+        Ok(remote_version)
+    }
+
+    /// Run the connection until the peer disconnects, or every request sender
+    /// is dropped.
+    ///
+    /// Messages from the peer are handled in the order they arrive. Requests
+    /// are sent to the peer when no peer message is ready.
+    pub async fn run(&mut self) -> Result<(), PeerError> {
+        loop {
+            match future::select(self.framed.next(), self.requests.next()).await {
+                Either::Left((None, _)) => return Err(PeerError::ConnectionClosed),
+                Either::Left((
+                    Some(Err(SerializationError::OversizedInventory { command, count })),
+                    _,
+                )) => self.reject_oversized_inventory(command, count).await?,
+                Either::Left((Some(msg), _)) => self.handle_message(msg?).await?,
+                Either::Right((None, _)) => {
+                    trace!("request channel closed, ending connection");
+                    return Ok(());
+                }
+                Either::Right((Some(msg), _)) => self.send_request(msg).await?,
+            }
+        }
+    }
+
+    /// Read the next handshake message from the peer.
+    async fn next_message(&mut self) -> Result<Message, HandshakeError> {
+        Ok(self
+            .framed
+            .next()
+            .await
+            .ok_or(HandshakeError::ConnectionClosed)??)
+    }
+
+    /// Send `msg` to the peer, tracking any ping until its pong arrives.
+    async fn send_request(&mut self, msg: Message) -> Result<(), PeerError> {
+        if let Message::Ping(nonce) = msg {
+            self.pending_ping = Some(nonce);
+        }
+        self.framed.send(msg).await?;
+        Ok(())
+    }
+
+    /// Send a `reject` for an inventory message with too many entries.
+    async fn reject_oversized_inventory(
+        &mut self,
+        command: &'static str,
+        count: usize,
+    ) -> Result<(), PeerError> {
+        debug!(command, count, "rejecting oversized inventory list");
+        self.framed
+            .send(Message::Reject {
+                message: command.to_owned(),
+                ccode: RejectReason::Malformed,
+                reason: "oversized inventory".to_owned(),
+                data: None,
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Update the connection state for `msg`, then pass it to the handler.
+    async fn handle_message(&mut self, msg: Message) -> Result<(), PeerError> {
+        match msg {
+            Message::Ping(nonce) => self.framed.send(Message::Pong(nonce)).await?,
+            Message::Pong(nonce) if self.pending_ping == Some(nonce) => self.pending_ping = None,
+            Message::Pong(nonce) => debug!(?nonce, "got unsolicited pong"),
+            Message::FeeFilter(min_fee_rate) => self.peer_state.min_fee_rate = Some(min_fee_rate),
+            Message::SendHeaders => self.peer_state.sendheaders = true,
+            Message::SendCompact(ref send_compact) => {
+                self.peer_state.compact_blocks.update(send_compact)
+            }
+            Message::Version(_) | Message::Verack => return Err(PeerError::DuplicateHandshake),
+            _ => {}
+        }
+
+        self.handler.dispatch(msg);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    use zebra_chain::block;
+
+    use super::*;
+    use crate::{
+        constants,
+        protocol::external::{
+            types::{PeerServices, ProtocolVersion},
+            InventoryHash,
+        },
+    };
+
+    /// A handler that records the pings and pongs it receives.
+    #[derive(Default)]
+    struct PingRecorder {
+        pings: Vec<Nonce>,
+        pongs: Vec<Nonce>,
+    }
+
+    impl MessageHandler for PingRecorder {
+        fn on_ping(&mut self, nonce: Nonce) {
+            self.pings.push(nonce);
+        }
+
+        fn on_pong(&mut self, nonce: Nonce) {
+            self.pongs.push(nonce);
+        }
+    }
+
+    fn version(nonce: Nonce) -> Version {
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8333);
+        Version::new(
+            constants::CURRENT_VERSION,
+            addr,
+            PeerServices::NODE_NETWORK,
+            PeerServices::NODE_NETWORK,
+            addr,
+            nonce,
+            "Beaver".to_owned(),
+            block::Height(0),
+            true,
+        )
+    }
+
+    async fn next(peer: &mut Framed<tokio::io::DuplexStream, Codec>) -> Message {
+        peer.next()
+            .await
+            .expect("connection should stay open")
+            .expect("message should decode")
+    }
+
+    #[tokio::test]
+    async fn handshake_and_ping_through_connection() {
+        zebra_test::init();
+
+        let (local, remote) = tokio::io::duplex(64 * 1024);
+        let mut peer = Framed::new(remote, Codec::builder().finish());
+        let (mut connection, mut requests) =
+            Connection::new(local, Codec::builder().finish(), PingRecorder::default());
+
+        let peer_handshake = async {
+            assert!(matches!(next(&mut peer).await, Message::Version(_)));
+            peer.send(Message::Version(version(Nonce(2))))
+                .await
+                .expect("version should send");
+            peer.send(Message::Verack)
+                .await
+                .expect("verack should send");
+            assert_eq!(next(&mut peer).await, Message::Verack);
+        };
+        let (remote_version, ()) =
+            futures::join!(connection.handshake(version(Nonce(1))), peer_handshake);
+        assert_eq!(
+            remote_version.expect("handshake should succeed").nonce,
+            Nonce(2)
+        );
+
+        let peer_messages = async move {
+            peer.send(Message::FeeFilter(1000))
+                .await
+                .expect("feefilter should send");
+            peer.send(Message::SendHeaders)
+                .await
+                .expect("sendheaders should send");
+            peer.send(Message::Ping(Nonce(7)))
+                .await
+                .expect("ping should send");
+            assert_eq!(next(&mut peer).await, Message::Pong(Nonce(7)));
+
+            requests
+                .send(Message::Ping(Nonce(8)))
+                .await
+                .expect("connection should be running");
+            assert_eq!(next(&mut peer).await, Message::Ping(Nonce(8)));
+            peer.send(Message::Pong(Nonce(8)))
+                .await
+                .expect("pong should send");
+
+            // Closing the request channel stops the connection
+            requests.close_channel();
+        };
+        let (result, ()) = futures::join!(connection.run(), peer_messages);
+        result.expect("connection should stop cleanly");
+
+        assert_eq!(connection.peer_state().min_fee_rate, Some(1000));
+        assert!(connection.peer_state().sendheaders);
+        assert_eq!(connection.pending_ping, None);
+        assert_eq!(connection.handler().pings, vec![Nonce(7)]);
+        assert_eq!(connection.handler().pongs, vec![Nonce(8)]);
+    }
+
+    /// Run a handshake with a peer advertising `peer_version`, using a codec
+    /// with a minimum version of `min_version`.
+    async fn handshake_with_min_version(
+        peer_version: ProtocolVersion,
+        min_version: ProtocolVersion,
+    ) -> Result<Version, HandshakeError> {
+        let (local, remote) = tokio::io::duplex(64 * 1024);
+        let mut peer = Framed::new(remote, Codec::builder().finish());
+        let codec = Codec::builder().with_min_version(min_version).finish();
+        let (mut connection, _requests) = Connection::new(local, codec, PingRecorder::default());
+
+        let peer_handshake = async {
+            assert!(matches!(next(&mut peer).await, Message::Version(_)));
+            let mut remote_version = version(Nonce(2));
+            remote_version.version = peer_version;
+            peer.send(Message::Version(remote_version))
+                .await
+                .expect("version should send");
+            peer.send(Message::Verack)
+                .await
+                .expect("verack should send");
+        };
+        let (result, ()) = futures::join!(connection.handshake(version(Nonce(1))), peer_handshake);
+        result
+    }
+
+    #[tokio::test]
+    async fn handshake_enforces_minimum_version() {
+        zebra_test::init();
+
+        let min_version = ProtocolVersion(70_015);
+
+        let result = handshake_with_min_version(ProtocolVersion(70_014), min_version).await;
+        assert!(matches!(
+            result,
+            Err(HandshakeError::ObsoleteVersion(ProtocolVersion(70_014)))
+        ));
+
+        let remote_version = handshake_with_min_version(min_version, min_version)
+            .await
+            .expect("peers at the minimum version are accepted");
+        assert_eq!(remote_version.version, min_version);
+    }
+
+    #[tokio::test]
+    async fn oversized_inventory_is_rejected_without_disconnecting() {
+        zebra_test::init();
+
+        let (local, remote) = tokio::io::duplex(64 * 1024);
+        let mut peer = Framed::new(remote, Codec::builder().finish());
+        let codec = Codec::builder().recover_oversized_inventory(true).finish();
+        let (mut connection, mut requests) = Connection::new(local, codec, PingRecorder::default());
+
+        let peer_messages = async move {
+            let inventory =
+                vec![InventoryHash::Block(block::Hash([1; 32])); constants::MAX_INV_IN_MESSAGE + 1];
+            peer.send(Message::Inv(inventory))
+                .await
+                .expect("inv should send");
+            match next(&mut peer).await {
+                Message::Reject { message, ccode, .. } => {
+                    assert_eq!(message, "inv");
+                    assert_eq!(ccode, RejectReason::Malformed);
+                }
+                msg => panic!("expected a reject, got {:?}", msg),
+            }
+
+            peer.send(Message::Ping(Nonce(9)))
+                .await
+                .expect("ping should send");
+            assert_eq!(next(&mut peer).await, Message::Pong(Nonce(9)));
+
+            requests.close_channel();
+        };
+        let (result, ()) = futures::join!(connection.run(), peer_messages);
+        result.expect("connection should stop cleanly");
+
+        assert_eq!(connection.handler().pings, vec![Nonce(9)]);
+    }
+}
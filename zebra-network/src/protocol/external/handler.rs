@@ -0,0 +1,204 @@
+//! Per-message callbacks for consumers of the external protocol.
+
+use std::sync::Arc;
+
+use zebra_chain::{
+    block::{self, Block},
+    transaction::Transaction,
+};
+
+use super::{
+    inv::InventoryHash,
+    message::{
+        AddrV2Entry, BlockTxn, CompactBlock, GetBlockTxn, GetBlocks, GetHeaders, MerkleBlock,
+        Message, RejectReason, SendCompact, Version,
+    },
+    types::*,
+};
+use crate::meta_addr::MetaAddr;
+
+/// A handler for incoming [`Message`]s.
+///
+/// Each message type has a callback that does nothing by default, so
+/// implementations only override the handlers they care about, and use
+/// [`MessageHandler::dispatch`] to route a message to the right one.
+#[allow(unused_variables)]
+pub trait MessageHandler {
+    /// Route `msg` to the handler for its message type.
+    fn dispatch(&mut self, msg: Message) {
+        match msg {
+            Message::Version(version) => self.on_version(version),
+            Message::Verack => self.on_verack(),
+            Message::Ping(nonce) => self.on_ping(nonce),
+            Message::Pong(nonce) => self.on_pong(nonce),
+            Message::Reject {
+                message,
+                ccode,
+                reason,
+                data,
+            } => self.on_reject(message, ccode, reason, data),
+            Message::GetAddr => self.on_getaddr(),
+            Message::Addr(addrs) => self.on_addr(addrs),
+            Message::AddrV2(addrs) => self.on_addrv2(addrs),
+            Message::GetBlocks(get_blocks) => self.on_getblocks(get_blocks),
+            Message::Inv(inventory) => self.on_inv(inventory),
+            Message::GetHeaders(get_headers) => self.on_getheaders(get_headers),
+            Message::Headers(headers) => self.on_headers(headers),
+            Message::GetData(inventory) => self.on_getdata(inventory),
+            Message::Block(block) => self.on_block(block),
+            Message::Tx(transaction) => self.on_tx(transaction),
+            Message::NotFound(inventory) => self.on_notfound(inventory),
+            Message::Mempool => self.on_mempool(),
+            Message::FilterLoad {
+                filter,
+                hash_functions_count,
+                tweak,
+                flags,
+            } => self.on_filterload(filter, hash_functions_count, tweak, flags),
+            Message::FilterAdd { data } => self.on_filteradd(data),
+            Message::FilterClear => self.on_filterclear(),
+            Message::MerkleBlock(merkle_block) => self.on_merkleblock(merkle_block),
+            Message::CompactBlock(compact_block) => self.on_compactblock(compact_block),
+            Message::GetBlockTxn(get_block_txn) => self.on_getblocktxn(get_block_txn),
+            Message::BlockTxn(block_txn) => self.on_blocktxn(block_txn),
+            Message::SendCompact(send_compact) => self.on_sendcompact(send_compact),
+            Message::FeeFilter(fee_rate) => self.on_feefilter(fee_rate),
+            Message::SendHeaders => self.on_sendheaders(),
+            Message::Alert(payload) => self.on_alert(payload),
+        }
+    }
+
+    /// Handle a `version` message.
+    fn on_version(&mut self, version: Version) {}
+
+    /// Handle a `verack` message.
+    fn on_verack(&mut self) {}
+
+    /// Handle a `ping` message.
+    fn on_ping(&mut self, nonce: Nonce) {}
+
+    /// Handle a `pong` message.
+    fn on_pong(&mut self, nonce: Nonce) {}
+
+    /// Handle a `reject` message.
+    fn on_reject(
+        &mut self,
+        message: String,
+        ccode: RejectReason,
+        reason: String,
+        data: Option<[u8; 32]>,
+    ) {
+    }
+
+    /// Handle a `getaddr` message.
+    fn on_getaddr(&mut self) {}
+
+    /// Handle an `addr` message.
+    fn on_addr(&mut self, addrs: Vec<MetaAddr>) {}
+
+    /// Handle an `addrv2` message.
+    fn on_addrv2(&mut self, addrs: Vec<AddrV2Entry>) {}
+
+    /// Handle a `getblocks` message.
+    fn on_getblocks(&mut self, get_blocks: GetBlocks) {}
+
+    /// Handle an `inv` message.
+    fn on_inv(&mut self, inventory: Vec<InventoryHash>) {}
+
+    /// Handle a `getheaders` message.
+    fn on_getheaders(&mut self, get_headers: GetHeaders) {}
+
+    /// Handle a `headers` message.
+    fn on_headers(&mut self, headers: Vec<block::CountedHeader>) {}
+
+    /// Handle a `getdata` message.
+    fn on_getdata(&mut self, inventory: Vec<InventoryHash>) {}
+
+    /// Handle a `block` message.
+    fn on_block(&mut self, block: Arc<Block>) {}
+
+    /// Handle a `tx` message.
+    fn on_tx(&mut self, transaction: Arc<Transaction>) {}
+
+    /// Handle a `notfound` message.
+    fn on_notfound(&mut self, inventory: Vec<InventoryHash>) {}
+
+    /// Handle a `mempool` message.
+    fn on_mempool(&mut self) {}
+
+    /// Handle a `filterload` message.
+    fn on_filterload(
+        &mut self,
+        filter: Filter,
+        hash_functions_count: u32,
+        tweak: Tweak,
+        flags: u8,
+    ) {
+    }
+
+    /// Handle a `filteradd` message.
+    fn on_filteradd(&mut self, data: Vec<u8>) {}
+
+    /// Handle a `filterclear` message.
+    fn on_filterclear(&mut self) {}
+
+    /// Handle a `merkleblock` message.
+    fn on_merkleblock(&mut self, merkle_block: MerkleBlock) {}
+
+    /// Handle a `cmpctblock` message.
+    fn on_compactblock(&mut self, compact_block: CompactBlock) {}
+
+    /// Handle a `getblocktxn` message.
+    fn on_getblocktxn(&mut self, get_block_txn: GetBlockTxn) {}
+
+    /// Handle a `blocktxn` message.
+    fn on_blocktxn(&mut self, block_txn: BlockTxn) {}
+
+    /// Handle a `sendcmpct` message.
+    fn on_sendcompact(&mut self, send_compact: SendCompact) {}
+
+    /// Handle a `feefilter` message.
+    fn on_feefilter(&mut self, fee_rate: u64) {}
+
+    /// Handle a `sendheaders` message.
+    fn on_sendheaders(&mut self) {}
+
+    /// Handle an `alert` message.
+    ///
+    /// Alerts are deprecated and insecure, so their signatures aren't verified.
+    fn on_alert(&mut self, payload: Vec<u8>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct PingCounter {
+        pings: usize,
+        last_nonce: Option<Nonce>,
+    }
+
+    impl MessageHandler for PingCounter {
+        fn on_ping(&mut self, nonce: Nonce) {
+            self.pings += 1;
+            self.last_nonce = Some(nonce);
+        }
+    }
+
+    #[test]
+    fn dispatch_routes_pings() {
+        zebra_test::init();
+
+        let mut handler = PingCounter::default();
+
+        handler.dispatch(Message::Ping(Nonce(1)));
+        handler.dispatch(Message::Verack);
+        handler.dispatch(Message::Pong(Nonce(2)));
+        handler.dispatch(Message::Ping(Nonce(3)));
+        handler.dispatch(Message::SendHeaders);
+
+        assert_eq!(handler.pings, 2);
+        assert_eq!(handler.last_nonce, Some(Nonce(3)));
+    }
+}
@@ -6,12 +6,16 @@
 
 use std::io::{Read, Write};
 
+use indexmap::IndexSet;
+
 use zebra_chain::{
     block,
-    serialization::{BitcoinDeserialize, BitcoinSerialize, SerializationError},
+    serialization::{BitcoinDeserialize, BitcoinSerialize, MinSerializedSize, SerializationError},
     transaction,
 };
 
+use crate::constants;
+
 /// An inventory hash which refers to some advertised or requested data.
 ///
 /// Bitcoin calls this an "inventory vector" but it is just a typed hash, not a
@@ -52,6 +56,39 @@ impl From<block::Hash> for InventoryHash {
     }
 }
 
+impl InventoryHash {
+    /// Returns `items` with each item once, keeping the first-seen order.
+    ///
+    /// Peers can advertise or request the same hash several times in one
+    /// `inv` or `getdata`.
+    pub fn dedup<I>(items: I) -> Vec<InventoryHash>
+    where
+        I: IntoIterator<Item = InventoryHash>,
+    {
+        items
+            .into_iter()
+            .collect::<IndexSet<InventoryHash>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Split `items` into lists that each fit in a single `getdata` message.
+    ///
+    /// Each list has at most [`constants::MAX_INV_IN_MESSAGE`] items, and the
+    /// lists keep the order of `items`.
+    pub fn chunk_for_getdata(items: &[InventoryHash]) -> Vec<Vec<InventoryHash>> {
+        items
+            .chunks(constants::MAX_INV_IN_MESSAGE)
+            .map(<[InventoryHash]>::to_vec)
+            .collect()
+    }
+}
+
+/// A `u32` type code, followed by a 32 byte hash.
+impl MinSerializedSize for InventoryHash {
+    const MIN_SERIALIZED_SIZE: usize = 4 + 32;
+}
+
 impl BitcoinSerialize for InventoryHash {
     fn bitcoin_serialize<W: Write>(&self, mut writer: W) -> Result<(), std::io::Error> {
         let (code, bytes) = match *self {
@@ -84,3 +121,51 @@ impl BitcoinDeserialize for InventoryHash {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dedup_keeps_the_first_of_each_item() {
+        zebra_test::init();
+
+        let first = InventoryHash::Tx(transaction::Hash([1; 32]));
+        let second = InventoryHash::Block(block::Hash([2; 32]));
+        let third = InventoryHash::Tx(transaction::Hash([3; 32]));
+
+        let inv = vec![first, second, first, third, second, first];
+        assert_eq!(InventoryHash::dedup(inv), vec![first, second, third]);
+    }
+
+    #[test]
+    fn getdata_chunks_respect_the_inventory_limit() {
+        zebra_test::init();
+
+        let items: Vec<InventoryHash> = (0..=2 * constants::MAX_INV_IN_MESSAGE)
+            .map(|i| InventoryHash::Tx(transaction::Hash([(i % 256) as u8; 32])))
+            .collect();
+
+        assert!(InventoryHash::chunk_for_getdata(&[]).is_empty());
+
+        let chunks = InventoryHash::chunk_for_getdata(&items[..constants::MAX_INV_IN_MESSAGE]);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0], &items[..constants::MAX_INV_IN_MESSAGE]);
+
+        let chunks = InventoryHash::chunk_for_getdata(&items[..constants::MAX_INV_IN_MESSAGE + 1]);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].len(), constants::MAX_INV_IN_MESSAGE);
+        assert_eq!(chunks[1], vec![items[constants::MAX_INV_IN_MESSAGE]]);
+
+        let chunks = InventoryHash::chunk_for_getdata(&items);
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![
+                constants::MAX_INV_IN_MESSAGE,
+                constants::MAX_INV_IN_MESSAGE,
+                1
+            ]
+        );
+        assert_eq!(chunks.concat(), items);
+    }
+}
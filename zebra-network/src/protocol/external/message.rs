@@ -5,6 +5,7 @@ use std::{fmt, sync::Arc};
 
 use zebra_chain::{
     block::{self, Block},
+    compactint::CompactInt,
     transaction::Transaction,
 };
 
@@ -25,7 +26,7 @@ mod merkle_block;
 pub use merkle_block::MerkleBlock;
 
 mod compact_block;
-pub use compact_block::CompactBlock;
+pub use compact_block::{CompactBlock, PrefilledTransaction, Reconstructed, ShortId, ShortIdKeys};
 
 mod get_block_txn;
 pub use get_block_txn::GetBlockTxn;
@@ -34,7 +35,10 @@ mod block_txn;
 pub use block_txn::BlockTxn;
 
 mod send_compact;
-pub use send_compact::SendCompact;
+pub use send_compact::{CompactBlockRelayState, SendCompact};
+
+mod addr_v2;
+pub use addr_v2::{AddrV2Address, AddrV2Entry};
 
 use super::Command;
 
@@ -126,6 +130,12 @@ pub enum Message {
     /// [Bitcoin reference](https://en.bitcoin.it/wiki/Protocol_documentation#addr)
     Addr(Vec<MetaAddr>),
 
+    /// An `addrv2` message, which can also carry Tor v3, I2P, and CJDNS
+    /// addresses.
+    ///
+    /// [BIP155](https://github.com/bitcoin/bips/blob/master/bip-0155.mediawiki)
+    AddrV2(Vec<AddrV2Entry>),
+
     /// A `getblocks` message.
     ///
     /// `known_blocks` is a series of known block hashes spaced out along the
@@ -318,6 +328,14 @@ pub enum Message {
     /// The “sendheaders” message tells the receiving peer to send new block
     /// announcements using a “headers” message rather than an “inv” message.
     SendHeaders,
+
+    /// An `alert` message, with its raw payload.
+    ///
+    /// Alerts are deprecated and insecure, so their signatures aren't
+    /// verified, and the payload isn't parsed. It's up to the receiver to
+    /// decide whether to ignore them.
+    /// [Bitcoin reference](https://en.bitcoin.it/wiki/Protocol_documentation#alert)
+    Alert(Vec<u8>),
 }
 
 impl<E> From<E> for Message
@@ -368,6 +386,7 @@ impl fmt::Display for Message {
             Message::Reject { .. } => "reject",
             Message::GetAddr => "getaddr",
             Message::Addr(_) => "addr",
+            Message::AddrV2(_) => "addrv2",
             Message::GetBlocks { .. } => "getblocks",
             Message::Inv(_) => "inv",
             Message::GetHeaders { .. } => "getheaders",
@@ -387,14 +406,164 @@ impl fmt::Display for Message {
             Message::SendCompact(_) => "sendcmpct",
             Message::FeeFilter(_) => "feefilter",
             Message::SendHeaders => "sendheaders",
+            Message::Alert(_) => "alert",
         })
     }
 }
 
 impl Message {
+    /// Build a `verack` message, acknowledging a peer's `version`.
+    pub fn verack() -> Self {
+        Message::Verack
+    }
+
+    /// Build a `ping` message with a freshly generated random nonce.
+    ///
+    /// The nonce is returned alongside the message so that the caller can
+    /// match it against the peer's `pong` response.
+    pub fn ping() -> (Self, Nonce) {
+        let nonce = Nonce::default();
+        (Message::Ping(nonce), nonce)
+    }
+
+    /// Build a `pong` message in response to a `ping` carrying `nonce`.
+    pub fn pong(nonce: Nonce) -> Self {
+        Message::Pong(nonce)
+    }
+
+    /// Build an `addr` message, keeping at most
+    /// [`MAX_ADDRS_IN_MESSAGE`](crate::constants::MAX_ADDRS_IN_MESSAGE)
+    /// entries from `addrs`.
+    pub fn addr(mut addrs: Vec<MetaAddr>) -> Self {
+        addrs.truncate(crate::constants::MAX_ADDRS_IN_MESSAGE);
+        Message::Addr(addrs)
+    }
+
+    /// Returns the serialized size of this message's body, in bytes.
+    ///
+    /// `getblocks` and `getheaders` bodies include the 4-byte protocol version
+    /// written by the codec.
+    pub fn serialized_body_size(&self) -> usize {
+        let inventory_size =
+            |items: &Vec<InventoryHash>| CompactInt::size(items.len()) + 36 * items.len();
+
+        match self {
+            Message::Version(version) => version.serialized_size(),
+            Message::Verack => 0,
+            Message::Ping(_) => 8,
+            Message::Pong(_) => 8,
+            Message::Reject {
+                message,
+                reason,
+                data,
+                ..
+            } => {
+                CompactInt::size(message.len())
+                    + message.len()
+                    + 1
+                    + CompactInt::size(reason.len())
+                    + reason.len()
+                    + data.map_or(0, |data| data.len())
+            }
+            Message::GetAddr => 0,
+            Message::Addr(addrs) => {
+                CompactInt::size(addrs.len()) + addrs.len() * MetaAddr::SERIALIZED_SIZE
+            }
+            Message::AddrV2(addrs) => {
+                CompactInt::size(addrs.len())
+                    + addrs
+                        .iter()
+                        .map(AddrV2Entry::serialized_size)
+                        .sum::<usize>()
+            }
+            Message::GetBlocks(get_blocks) => 4 + get_blocks.serialized_size(),
+            Message::Inv(items) => inventory_size(items),
+            Message::GetHeaders(get_headers) => 4 + get_headers.serialized_size(),
+            Message::Headers(headers) => {
+                CompactInt::size(headers.len())
+                    + headers
+                        .iter()
+                        .map(|counted| {
+                            block::Header::len() + CompactInt::size(counted.transaction_count)
+                        })
+                        .sum::<usize>()
+            }
+            Message::GetData(items) => inventory_size(items),
+            Message::Block(block) => block.serialized_size(),
+            Message::Tx(transaction) => transaction.serialized_size_with_header(),
+            Message::NotFound(items) => inventory_size(items),
+            Message::Mempool => 0,
+            Message::FilterLoad { filter, .. } => filter.0.len() + 4 + 4 + 1,
+            Message::FilterAdd { data } => data.len(),
+            Message::FilterClear => 0,
+            Message::MerkleBlock(merkle_block) => merkle_block.serialized_size(),
+            Message::CompactBlock(compact_block) => compact_block.serialized_size(),
+            Message::GetBlockTxn(get_block_txn) => get_block_txn.serialized_size(),
+            Message::BlockTxn(block_txn) => block_txn.serialized_size(),
+            Message::SendCompact(send_compact) => send_compact.serialized_size(),
+            Message::FeeFilter(_) => 8,
+            Message::SendHeaders => 0,
+            Message::Alert(payload) => payload.len(),
+        }
+    }
+
+    /// Returns true if this message is a request that the peer should reply to.
+    ///
+    /// Announcements like `inv`, `addr`, or unsolicited `tx` and `block`
+    /// messages don't expect any response.
+    pub fn expects_response(&self) -> bool {
+        matches!(
+            self,
+            Message::Version(_)
+                | Message::Ping(_)
+                | Message::GetAddr
+                | Message::GetBlocks(_)
+                | Message::GetHeaders(_)
+                | Message::GetData(_)
+                | Message::Mempool
+                | Message::GetBlockTxn(_)
+        )
+    }
+
+    /// Returns true if this message is a response to `request`.
+    ///
+    /// Responses to `getdata` must contain one of the requested items, and
+    /// `pong` must echo the `ping` nonce. Any request can be answered by a
+    /// `reject` for its command.
+    pub fn is_response_to(&self, request: &Message) -> bool {
+        let requested = |item: InventoryHash| match request {
+            Message::GetData(items) => items.contains(&item),
+            _ => false,
+        };
+
+        match (self, request) {
+            (Message::Reject { message, .. }, _) => {
+                request.expects_response() && *message == request.to_string()
+            }
+            (Message::Verack, Message::Version(_)) => true,
+            (Message::Pong(nonce), Message::Ping(ping_nonce)) => nonce == ping_nonce,
+            (Message::Addr(_), Message::GetAddr) | (Message::AddrV2(_), Message::GetAddr) => true,
+            (Message::Inv(_), Message::GetBlocks(_)) | (Message::Inv(_), Message::Mempool) => true,
+            (Message::Headers(_), Message::GetHeaders(_)) => true,
+            (Message::Block(block), _) => requested(InventoryHash::Block(block.hash())),
+            (Message::Tx(transaction), _) => requested(InventoryHash::Tx(transaction.hash())),
+            (Message::MerkleBlock(merkle_block), _) => requested(InventoryHash::FilteredBlock(
+                merkle_block.block_header.hash(),
+            )),
+            (Message::NotFound(items), _) => items.iter().any(|item| requested(*item)),
+            (Message::BlockTxn(block_txn), Message::GetBlockTxn(get_block_txn)) => {
+                block_txn.block_hash == get_block_txn.block_hash
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the [`Command`] that identifies this message on the wire.
     pub fn command(&self) -> Command {
         match self {
             Message::Addr { .. } => Command::Addr,
+            Message::AddrV2 { .. } => Command::AddrV2,
+            Message::Alert { .. } => Command::Alert,
             Message::BlockTxn { .. } => Command::BlockTxn,
             Message::Block { .. } => Command::Block,
             Message::CompactBlock { .. } => Command::CmpctBlock,
@@ -411,7 +580,7 @@ impl Message {
             Message::Inv { .. } => Command::Inv,
             Message::Mempool {} => Command::MemPool,
             Message::MerkleBlock { .. } => Command::MerkleBlock,
-            Message::NotFound { .. } => Command::MemPool,
+            Message::NotFound { .. } => Command::NotFound,
             Message::Ping { .. } => Command::Ping,
             Message::Pong { .. } => Command::Pong,
             Message::Reject { .. } => Command::Reject,
@@ -423,3 +592,225 @@ impl Message {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ping_has_nonzero_nonce() {
+        zebra_test::init();
+
+        let (message, nonce) = Message::ping();
+        assert_eq!(message, Message::Ping(nonce));
+        // A zero nonce is possible but vanishingly unlikely, so retry once
+        // before treating it as a failure.
+        let nonce = if nonce.0 == 0 {
+            Message::ping().1
+        } else {
+            nonce
+        };
+        assert_ne!(nonce.0, 0);
+    }
+
+    #[test]
+    fn pong_echoes_nonce() {
+        zebra_test::init();
+
+        let (_, nonce) = Message::ping();
+        assert_eq!(Message::pong(nonce), Message::Pong(nonce));
+        assert_eq!(Message::verack(), Message::Verack);
+    }
+
+    #[test]
+    fn requests_expect_responses() {
+        zebra_test::init();
+
+        let (ping, _) = Message::ping();
+        assert!(ping.expects_response());
+        assert!(Message::GetAddr.expects_response());
+        assert!(Message::Mempool.expects_response());
+        assert!(Message::GetData(Vec::new()).expects_response());
+
+        assert!(!Message::pong(Nonce(1)).expects_response());
+        assert!(!Message::Verack.expects_response());
+        assert!(!Message::Inv(Vec::new()).expects_response());
+        assert!(!Message::Addr(Vec::new()).expects_response());
+        assert!(!Message::SendHeaders.expects_response());
+    }
+
+    #[test]
+    fn responses_match_their_requests() {
+        zebra_test::init();
+
+        let (ping, nonce) = Message::ping();
+        assert!(Message::pong(nonce).is_response_to(&ping));
+        assert!(!Message::pong(Nonce(nonce.0.wrapping_add(1))).is_response_to(&ping));
+        assert!(!ping.is_response_to(&Message::pong(nonce)));
+
+        let get_headers = Message::GetHeaders(GetHeaders {
+            block_header_hashes: vec![block::Hash([1; 32])],
+            stop_hash: None,
+        });
+        assert!(Message::Headers(Vec::new()).is_response_to(&get_headers));
+        assert!(!Message::Headers(Vec::new()).is_response_to(&Message::GetAddr));
+
+        assert!(Message::Addr(Vec::new()).is_response_to(&Message::GetAddr));
+        assert!(Message::Inv(Vec::new()).is_response_to(&Message::Mempool));
+        assert!(!Message::Inv(Vec::new()).is_response_to(&get_headers));
+
+        let wanted = InventoryHash::Block(block::Hash([2; 32]));
+        let other = InventoryHash::Block(block::Hash([3; 32]));
+        let getdata = Message::GetData(vec![wanted]);
+        assert!(Message::NotFound(vec![other, wanted]).is_response_to(&getdata));
+        assert!(!Message::NotFound(vec![other]).is_response_to(&getdata));
+
+        let reject = |message: &str| Message::Reject {
+            message: message.to_owned(),
+            ccode: RejectReason::Invalid,
+            reason: String::new(),
+            data: None,
+        };
+        assert!(reject("getdata").is_response_to(&getdata));
+        assert!(!reject("getheaders").is_response_to(&getdata));
+        assert!(!reject("inv").is_response_to(&Message::Inv(Vec::new())));
+    }
+
+    /// The command string that `msg` must be sent with.
+    fn expected_command(msg: &Message) -> &'static [u8; 12] {
+        match msg {
+            Message::Version(_) => b"version\0\0\0\0\0",
+            Message::Verack => b"verack\0\0\0\0\0\0",
+            Message::Ping(_) => b"ping\0\0\0\0\0\0\0\0",
+            Message::Pong(_) => b"pong\0\0\0\0\0\0\0\0",
+            Message::Reject { .. } => b"reject\0\0\0\0\0\0",
+            Message::GetAddr => b"getaddr\0\0\0\0\0",
+            Message::Addr(_) => b"addr\0\0\0\0\0\0\0\0",
+            Message::AddrV2(_) => b"addrv2\0\0\0\0\0\0",
+            Message::GetBlocks(_) => b"getblocks\0\0\0",
+            Message::Inv(_) => b"inv\0\0\0\0\0\0\0\0\0",
+            Message::GetHeaders(_) => b"getheaders\0\0",
+            Message::Headers(_) => b"headers\0\0\0\0\0",
+            Message::GetData(_) => b"getdata\0\0\0\0\0",
+            Message::Block(_) => b"block\0\0\0\0\0\0\0",
+            Message::Tx(_) => b"tx\0\0\0\0\0\0\0\0\0\0",
+            Message::NotFound(_) => b"notfound\0\0\0\0",
+            Message::Mempool => b"mempool\0\0\0\0\0",
+            Message::FilterLoad { .. } => b"filterload\0\0",
+            Message::FilterAdd { .. } => b"filteradd\0\0\0",
+            Message::FilterClear => b"filterclear\0",
+            Message::MerkleBlock(_) => b"merkleblock\0",
+            Message::CompactBlock(_) => b"cmpctblock\0\0",
+            Message::GetBlockTxn(_) => b"getblocktxn\0",
+            Message::BlockTxn(_) => b"blocktxn\0\0\0\0",
+            Message::SendCompact(_) => b"sendcmpct\0\0\0",
+            Message::FeeFilter(_) => b"feefilter\0\0\0",
+            Message::SendHeaders => b"sendheaders\0",
+            Message::Alert(_) => b"alert\0\0\0\0\0\0\0",
+        }
+    }
+
+    #[test]
+    fn command_matches_wire_string() {
+        use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+        use zebra_chain::serialization::BitcoinDeserializeInto;
+        zebra_test::init();
+
+        let block: Arc<Block> = zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES
+            .bitcoin_deserialize_into()
+            .expect("block should deserialize");
+        let hash = block.hash();
+        let header = block.header;
+        let transaction = block.transactions[0].clone();
+        let inv = vec![InventoryHash::Block(hash)];
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8333);
+
+        let messages = vec![
+            Message::Version(Version::new(
+                crate::constants::CURRENT_VERSION,
+                addr,
+                PeerServices::NODE_NETWORK,
+                PeerServices::NODE_NETWORK,
+                addr,
+                Nonce(1),
+                "Beaver".to_owned(),
+                block::Height(0),
+                true,
+            )),
+            Message::Verack,
+            Message::Ping(Nonce(1)),
+            Message::Pong(Nonce(1)),
+            Message::Reject {
+                message: "tx".to_owned(),
+                ccode: RejectReason::Invalid,
+                reason: "bad-txns".to_owned(),
+                data: None,
+            },
+            Message::GetAddr,
+            Message::Addr(Vec::new()),
+            Message::AddrV2(Vec::new()),
+            Message::GetBlocks(GetBlocks {
+                block_header_hashes: vec![hash],
+                stop_hash: None,
+            }),
+            Message::Inv(inv.clone()),
+            Message::GetHeaders(GetHeaders {
+                block_header_hashes: vec![hash],
+                stop_hash: None,
+            }),
+            Message::Headers(vec![block::CountedHeader {
+                header,
+                transaction_count: 0,
+            }]),
+            Message::GetData(inv.clone()),
+            Message::Block(block.clone()),
+            Message::Tx(transaction.clone()),
+            Message::NotFound(inv),
+            Message::Mempool,
+            Message::FilterLoad {
+                filter: Filter(vec![0; 10]),
+                hash_functions_count: 1,
+                tweak: Tweak(0),
+                flags: 0,
+            },
+            Message::FilterAdd { data: vec![1; 4] },
+            Message::FilterClear,
+            Message::MerkleBlock(MerkleBlock {
+                block_header: header,
+                transaction_count: 1,
+                hashes: vec![transaction.hash()],
+                flags: vec![1],
+            }),
+            Message::CompactBlock(CompactBlock {
+                header,
+                nonce: 0,
+                short_ids: Vec::new(),
+                prefilled_txns: Vec::new(),
+            }),
+            Message::GetBlockTxn(GetBlockTxn {
+                block_hash: hash,
+                indexes: Vec::new(),
+            }),
+            Message::BlockTxn(BlockTxn {
+                block_hash: hash,
+                txs: vec![transaction.as_ref().clone()],
+            }),
+            Message::SendCompact(SendCompact {
+                announce: false,
+                version: 1,
+            }),
+            Message::FeeFilter(1000),
+            Message::SendHeaders,
+            Message::Alert(vec![1; 10]),
+        ];
+
+        for msg in messages {
+            assert_eq!(
+                msg.command().bytes(),
+                expected_command(&msg),
+                "wrong command for {}",
+                msg
+            );
+        }
+    }
+}
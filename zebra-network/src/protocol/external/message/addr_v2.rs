@@ -0,0 +1,227 @@
+use std::{
+    convert::{TryFrom, TryInto},
+    io::{Read, Write},
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+use chrono::{DateTime, TimeZone, Utc};
+use zebra_chain::{
+    compactint::CompactInt, serialization::MinSerializedSize, BitcoinDeserialize, BitcoinSerialize,
+    SerializationError,
+};
+
+use super::super::types::PeerServices;
+
+/// The longest address that BIP155 allows in an `addrv2` entry, for any network.
+const MAX_ADDRV2_ADDRESS_LEN: u64 = 512;
+
+/// A network address in an `addrv2` entry, identified by its BIP155 network id.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AddrV2Address {
+    /// An IPv4 address, network id 1.
+    Ipv4(Ipv4Addr),
+    /// An IPv6 address, network id 2.
+    Ipv6(Ipv6Addr),
+    /// A Tor v2 onion service address, network id 3.
+    TorV2([u8; 10]),
+    /// A Tor v3 onion service public key, network id 4.
+    TorV3([u8; 32]),
+    /// An I2P address hash, network id 5.
+    I2p([u8; 32]),
+    /// A CJDNS address, network id 6.
+    Cjdns(Ipv6Addr),
+    /// An address on a network that BIP155 doesn't define.
+    ///
+    /// Peers must still parse these entries, but can't connect to them.
+    Unknown {
+        /// The network id.
+        network_id: u8,
+        /// The raw address bytes.
+        address: Vec<u8>,
+    },
+}
+
+impl AddrV2Address {
+    /// Returns the BIP155 network id for this address.
+    pub fn network_id(&self) -> u8 {
+        match self {
+            AddrV2Address::Ipv4(_) => 1,
+            AddrV2Address::Ipv6(_) => 2,
+            AddrV2Address::TorV2(_) => 3,
+            AddrV2Address::TorV3(_) => 4,
+            AddrV2Address::I2p(_) => 5,
+            AddrV2Address::Cjdns(_) => 6,
+            AddrV2Address::Unknown { network_id, .. } => *network_id,
+        }
+    }
+
+    /// Returns the raw address bytes.
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            AddrV2Address::Ipv4(addr) => addr.octets().to_vec(),
+            AddrV2Address::Ipv6(addr) | AddrV2Address::Cjdns(addr) => addr.octets().to_vec(),
+            AddrV2Address::TorV2(addr) => addr.to_vec(),
+            AddrV2Address::TorV3(addr) | AddrV2Address::I2p(addr) => addr.to_vec(),
+            AddrV2Address::Unknown { address, .. } => address.clone(),
+        }
+    }
+
+    /// Parse `address` for `network_id`, checking its length for known networks.
+    fn from_bytes(network_id: u8, address: Vec<u8>) -> Result<Self, SerializationError> {
+        let wrong_length = |_| SerializationError::Parse("addrv2 address has the wrong length");
+        Ok(match network_id {
+            1 => AddrV2Address::Ipv4(
+                <[u8; 4]>::try_from(&address[..])
+                    .map_err(wrong_length)?
+                    .into(),
+            ),
+            2 => AddrV2Address::Ipv6(
+                <[u8; 16]>::try_from(&address[..])
+                    .map_err(wrong_length)?
+                    .into(),
+            ),
+            3 => AddrV2Address::TorV2(address[..].try_into().map_err(wrong_length)?),
+            4 => AddrV2Address::TorV3(address[..].try_into().map_err(wrong_length)?),
+            5 => AddrV2Address::I2p(address[..].try_into().map_err(wrong_length)?),
+            6 => AddrV2Address::Cjdns(
+                <[u8; 16]>::try_from(&address[..])
+                    .map_err(wrong_length)?
+                    .into(),
+            ),
+            _ => AddrV2Address::Unknown {
+                network_id,
+                address,
+            },
+        })
+    }
+}
+
+/// An entry in an `addrv2` message.
+///
+/// [BIP155](https://github.com/bitcoin/bips/blob/master/bip-0155.mediawiki)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AddrV2Entry {
+    /// When the peer was last seen.
+    pub last_seen: DateTime<Utc>,
+    /// The services advertised by the peer.
+    ///
+    /// Unknown service bits are kept, because they change the length of the
+    /// `CompactSize` encoding.
+    pub services: PeerServices,
+    /// The peer's network address.
+    pub address: AddrV2Address,
+    /// The peer's port.
+    pub port: u16,
+}
+
+impl AddrV2Entry {
+    /// The serialized size of the entry.
+    pub fn serialized_size(&self) -> usize {
+        let address_len = self.address.to_bytes().len();
+        4 + CompactInt::size(self.services.bits() as usize)
+            + 1
+            + CompactInt::size(address_len)
+            + address_len
+            + 2
+    }
+}
+
+/// The time, one byte services, the network id, an empty address, and the port.
+impl MinSerializedSize for AddrV2Entry {
+    const MIN_SERIALIZED_SIZE: usize = 4 + 1 + 1 + 1 + 2;
+}
+
+/// An `addrv2` entry is a little-endian `u32` last seen time, `CompactSize`
+/// services, a network id byte, a `CompactSize` length prefixed address, and
+/// a big-endian `u16` port.
+impl BitcoinSerialize for AddrV2Entry {
+    fn bitcoin_serialize<W: Write>(&self, mut writer: W) -> Result<(), std::io::Error> {
+        writer.write_u32::<LittleEndian>(self.last_seen.timestamp() as u32)?;
+        CompactInt::from(self.services.bits() as usize).bitcoin_serialize(&mut writer)?;
+        writer.write_u8(self.address.network_id())?;
+        self.address.to_bytes().bitcoin_serialize(&mut writer)?;
+        writer.write_u16::<BigEndian>(self.port)
+    }
+}
+
+impl BitcoinDeserialize for AddrV2Entry {
+    fn bitcoin_deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        let last_seen = Utc.timestamp(reader.read_u32::<LittleEndian>()? as i64, 0);
+        let services = PeerServices::from_bits_preserving(
+            CompactInt::bitcoin_deserialize(&mut reader)?.value(),
+        );
+        let network_id = reader.read_u8()?;
+
+        let address_len =
+            CompactInt::bitcoin_deserialize(&mut reader)?.checked_len(MAX_ADDRV2_ADDRESS_LEN)?;
+        let mut address = vec![0; address_len];
+        reader.read_exact(&mut address)?;
+        let address = AddrV2Address::from_bytes(network_id, address)?;
+
+        Ok(AddrV2Entry {
+            last_seen,
+            services,
+            address,
+            port: reader.read_u16::<BigEndian>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ipv4_entry_encoding() {
+        zebra_test::init();
+
+        let entry = AddrV2Entry {
+            last_seen: Utc.timestamp(1_414_012_889, 0),
+            services: PeerServices::NODE_NETWORK,
+            address: AddrV2Address::Ipv4(Ipv4Addr::new(192, 0, 2, 51)),
+            port: 8333,
+        };
+        let bytes = entry.bitcoin_serialize_to_vec().unwrap();
+        assert_eq!(hex::encode(&bytes), "d91f4854010104c0000233208d");
+        assert_eq!(bytes.len(), entry.serialized_size());
+        assert_eq!(AddrV2Entry::bitcoin_deserialize(&bytes[..]).unwrap(), entry);
+    }
+
+    #[test]
+    fn unknown_service_bits_are_kept() {
+        zebra_test::init();
+
+        // NODE_NETWORK, NODE_WITNESS, and NODE_NETWORK_LIMITED
+        let bytes = hex::decode("d91f4854fd09040104c0000233208d").unwrap();
+        let entry = AddrV2Entry::bitcoin_deserialize(&bytes[..]).unwrap();
+        assert_eq!(entry.services.bits(), 0x0409);
+        assert_eq!(entry.bitcoin_serialize_to_vec().unwrap(), bytes);
+    }
+
+    #[test]
+    fn known_network_address_lengths_are_checked() {
+        zebra_test::init();
+
+        // An IPv4 network id with a 5 byte address
+        let bytes = hex::decode("d91f4854010105c000023300208d").unwrap();
+        assert_eq!(
+            AddrV2Entry::bitcoin_deserialize(&bytes[..]),
+            Err(SerializationError::Parse(
+                "addrv2 address has the wrong length"
+            ))
+        );
+
+        // Unknown networks can have any length, up to the BIP155 limit
+        let bytes = hex::decode("d91f4854012a03abcdef208d").unwrap();
+        let entry = AddrV2Entry::bitcoin_deserialize(&bytes[..]).unwrap();
+        assert_eq!(
+            entry.address,
+            AddrV2Address::Unknown {
+                network_id: 42,
+                address: vec![0xab, 0xcd, 0xef],
+            }
+        );
+        assert_eq!(entry.bitcoin_serialize_to_vec().unwrap(), bytes);
+    }
+}
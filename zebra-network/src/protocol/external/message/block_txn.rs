@@ -14,7 +14,7 @@ impl BlockTxn {
         let mut size = 32;
         size += CompactInt::size(self.txs.len());
         for transaction in self.txs.iter() {
-            size += transaction.len();
+            size += transaction.serialized_size_with_header();
         }
         size
     }
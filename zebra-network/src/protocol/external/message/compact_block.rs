@@ -1,4 +1,9 @@
-use std::convert::TryInto;
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    io::{Read, Write},
+    sync::Arc,
+};
 
 // use super::PrefilledTransaction;
 // use bytes::Buf;
@@ -7,11 +12,54 @@ use std::convert::TryInto;
 // use shared::CompactInt;
 // use shared::Serializable;
 use bitcoin_serde_derive::{BtcDeserialize, BtcSerialize};
+use sha2::{Digest, Sha256};
 use zebra_chain::{
-    block, compactint::CompactInt, transaction::Transaction, BitcoinDeserialize, BitcoinSerialize,
-    SerializationError,
+    block::{self, Block},
+    compactint::CompactInt,
+    transaction::{self, Transaction},
+    BitcoinDeserialize, BitcoinSerialize, SerializationError,
 };
 
+/// Short transaction ids are the low 6 bytes of a SipHash-2-4 output.
+const SHORT_ID_MASK: u64 = 0xffff_ffff_ffff;
+
+/// The serialized length of a short transaction id, in bytes.
+const SHORT_ID_LEN: usize = 6;
+
+/// A BIP152 short transaction id.
+///
+/// Short ids are 6 bytes on the wire, so only the low 6 bytes of the value
+/// are used.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ShortId(u64);
+
+impl ShortId {
+    /// Returns the short id made of the low 6 bytes of `value`.
+    pub fn new(value: u64) -> ShortId {
+        ShortId(value & SHORT_ID_MASK)
+    }
+
+    /// Returns the value of this short id.
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Short ids are serialized as 6 little-endian bytes.
+impl BitcoinSerialize for ShortId {
+    fn bitcoin_serialize<W: Write>(&self, mut writer: W) -> Result<(), std::io::Error> {
+        writer.write_all(&self.0.to_le_bytes()[..SHORT_ID_LEN])
+    }
+}
+
+impl BitcoinDeserialize for ShortId {
+    fn bitcoin_deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        let mut bytes = [0u8; 8];
+        reader.read_exact(&mut bytes[..SHORT_ID_LEN])?;
+        Ok(ShortId(u64::from_le_bytes(bytes)))
+    }
+}
+
 #[derive(BtcSerialize, BtcDeserialize, PartialEq, Eq, Debug, Clone)]
 pub struct PrefilledTransaction {
     pub index: CompactInt,
@@ -21,29 +69,332 @@ pub struct PrefilledTransaction {
 impl PrefilledTransaction {
     /// Returns the serialized length of a PrefilledTx
     pub fn len(&self) -> usize {
-        self.tx.len() + CompactInt::size(self.index.value().try_into().unwrap())
+        self.tx.serialized_size_with_header()
+            + CompactInt::size(self.index.value().try_into().unwrap())
     }
 }
 #[derive(BtcSerialize, BtcDeserialize, PartialEq, Eq, Debug, Clone)]
 pub struct CompactBlock {
     pub header: block::Header,
     pub nonce: u64,
-    pub short_ids: Vec<u64>,
+    pub short_ids: Vec<ShortId>,
     pub prefilled_txns: Vec<PrefilledTransaction>,
 }
 
 impl CompactBlock {
-    fn serialized_size(&self) -> usize {
+    /// The serialized size of the message.
+    pub fn serialized_size(&self) -> usize {
         let mut len = block::Header::len()
             + 8
             + CompactInt::size(self.short_ids.len())
-            + 8 * self.short_ids.len()
+            + SHORT_ID_LEN * self.short_ids.len()
             + CompactInt::size(self.prefilled_txns.len());
         for txn in self.prefilled_txns.iter() {
             len += txn.len();
         }
         len
     }
+
+    /// Returns the BIP152 short id for the transaction with `txid`.
+    ///
+    /// This hashes the header to derive the short id keys, so use
+    /// [`CompactBlock::short_id_keys`] when computing many short ids.
+    pub fn short_id(&self, txid: &transaction::Hash) -> ShortId {
+        self.short_id_keys().short_id(txid)
+    }
+
+    /// Returns the SipHash keys for this block's short ids.
+    ///
+    /// The keys are the first two little-endian `u64`s of the SHA256 of the
+    /// serialized header, followed by the little-endian nonce.
+    ///
+    /// [BIP152](https://github.com/bitcoin/bips/blob/master/bip-0152.mediawiki#short-transaction-ids)
+    pub fn short_id_keys(&self) -> ShortIdKeys {
+        let mut hasher = Sha256::new();
+        hasher.update(
+            self.header
+                .bitcoin_serialize_to_vec()
+                .expect("serializing into a vec never fails"),
+        );
+        hasher.update(self.nonce.to_le_bytes());
+        let key = hasher.finalize();
+
+        ShortIdKeys {
+            k0: u64::from_le_bytes(key[0..8].try_into().unwrap()),
+            k1: u64::from_le_bytes(key[8..16].try_into().unwrap()),
+        }
+    }
+
+    /// Rebuild the block from the prefilled transactions in this message,
+    /// and the transactions in `mempool`, keyed by their short ids.
+    ///
+    /// Returns the indexes of any transactions that aren't in `mempool`, so
+    /// they can be requested with a `getblocktxn` message. Messages with
+    /// out of range prefilled indexes, or duplicate short ids, are rejected.
+    pub fn reconstruct(
+        &self,
+        mempool: &HashMap<ShortId, Transaction>,
+    ) -> Result<Reconstructed, SerializationError> {
+        use SerializationError::Parse;
+
+        let tx_count = self
+            .short_ids
+            .len()
+            .checked_add(self.prefilled_txns.len())
+            .ok_or(Parse("compact block has too many transactions"))?;
+        let mut transactions: Vec<Option<Arc<Transaction>>> = vec![None; tx_count];
+
+        // Prefilled indexes are differentially encoded: each index is the
+        // number of transactions skipped since the previous prefilled one.
+        let mut next_index = 0usize;
+        for prefilled in &self.prefilled_txns {
+            let index = next_index
+                .checked_add(prefilled.index.value() as usize)
+                .filter(|index| *index < tx_count)
+                .ok_or(Parse("compact block prefilled index is out of range"))?;
+            transactions[index] = Some(Arc::new(prefilled.tx.clone()));
+            next_index = index + 1;
+        }
+
+        let mut seen = HashSet::with_capacity(self.short_ids.len());
+        if !self.short_ids.iter().all(|short_id| seen.insert(*short_id)) {
+            return Err(Parse("compact block has duplicate short ids"));
+        }
+
+        let mut missing = Vec::new();
+        let mut short_ids = self.short_ids.iter();
+        for (index, slot) in transactions.iter_mut().enumerate() {
+            if slot.is_some() {
+                continue;
+            }
+            let short_id = short_ids
+                .next()
+                .expect("every unfilled slot has a short id");
+            match mempool.get(short_id) {
+                Some(tx) => *slot = Some(Arc::new(tx.clone())),
+                None => missing.push(index),
+            }
+        }
+
+        if !missing.is_empty() {
+            return Ok(Reconstructed::Missing(missing));
+        }
+
+        Ok(Reconstructed::Block(Block {
+            header: self.header.clone(),
+            transactions: transactions.into_iter().map(Option::unwrap).collect(),
+        }))
+    }
+}
+
+/// The SipHash keys for the short ids in a [`CompactBlock`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ShortIdKeys {
+    k0: u64,
+    k1: u64,
+}
+
+impl ShortIdKeys {
+    /// Returns the BIP152 short id for the transaction with `txid`.
+    ///
+    /// Short ids are SipHash-2-4 of the txid, truncated to 6 bytes.
+    pub fn short_id(&self, txid: &transaction::Hash) -> ShortId {
+        ShortId::new(siphash_2_4(self.k0, self.k1, &txid.0))
+    }
+}
+
+/// The result of reconstructing a block from a [`CompactBlock`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Reconstructed {
+    /// Every transaction was prefilled or found in the mempool.
+    Block(Block),
+    /// The indexes of the transactions that are still missing, in block order.
+    Missing(Vec<usize>),
+}
+
+/// SipHash-2-4 of `data`, keyed by `k0` and `k1`.
+fn siphash_2_4(k0: u64, k1: u64, data: &[u8]) -> u64 {
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        compress(&mut v, u64::from_le_bytes(chunk.try_into().unwrap()));
+    }
+
+    // The last word holds the remaining bytes, and the low byte of the length
+    let mut last = [0u8; 8];
+    last[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    last[7] = data.len() as u8;
+    compress(&mut v, u64::from_le_bytes(last));
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        sip_round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+/// Mix the message word `m` into the SipHash state, with two rounds.
+fn compress(v: &mut [u64; 4], m: u64) {
+    v[3] ^= m;
+    sip_round(v);
+    sip_round(v);
+    v[0] ^= m;
+}
+
+fn sip_round(v: &mut [u64; 4]) {
+    v[0] = v[0].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(13) ^ v[0];
+    v[0] = v[0].rotate_left(32);
+    v[2] = v[2].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(16) ^ v[2];
+    v[0] = v[0].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(21) ^ v[0];
+    v[2] = v[2].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(17) ^ v[2];
+    v[2] = v[2].rotate_left(32);
+}
+
+#[cfg(test)]
+mod tests {
+    use zebra_chain::serialization::BitcoinDeserializeInto;
+
+    use super::*;
+
+    #[test]
+    fn siphash_reference_vectors() {
+        zebra_test::init();
+
+        // From the SipHash paper, and the reference implementation's vectors
+        let k0 = u64::from_le_bytes([0, 1, 2, 3, 4, 5, 6, 7]);
+        let k1 = u64::from_le_bytes([8, 9, 10, 11, 12, 13, 14, 15]);
+        let message: Vec<u8> = (0..15).collect();
+        assert_eq!(siphash_2_4(k0, k1, &message), 0xa129_ca61_49be_45e5);
+        assert_eq!(siphash_2_4(k0, k1, &[]), 0x726f_db47_dd0e_0e31);
+    }
+
+    #[test]
+    fn reconstruct_mainnet_block() {
+        zebra_test::init();
+
+        let block: Block = zebra_test::vectors::BLOCK_MAINNET_347500_BYTES
+            .bitcoin_deserialize_into()
+            .expect("block should deserialize");
+        let mut compact = CompactBlock {
+            header: block.header.clone(),
+            nonce: 0x0123_4567_89ab_cdef,
+            short_ids: Vec::new(),
+            prefilled_txns: vec![PrefilledTransaction {
+                index: CompactInt::from(0),
+                tx: (*block.transactions[0]).clone(),
+            }],
+        };
+        let keys = compact.short_id_keys();
+        compact.short_ids = block.transactions[1..]
+            .iter()
+            .map(|tx| keys.short_id(&tx.hash()))
+            .collect();
+        assert_eq!(
+            compact.short_id(&block.transactions[1].hash()),
+            compact.short_ids[0]
+        );
+
+        // Computed independently, from the block's header and txids
+        assert_eq!(compact.short_ids[0], ShortId::new(0xc366_d0f4_049f));
+        assert_eq!(compact.short_ids[1], ShortId::new(0xe80c_505d_0415));
+
+        let mut mempool: HashMap<ShortId, Transaction> = block.transactions[1..]
+            .iter()
+            .map(|tx| (keys.short_id(&tx.hash()), (**tx).clone()))
+            .collect();
+        let missing = mempool.remove(&compact.short_ids[1]).unwrap();
+
+        assert_eq!(
+            compact.reconstruct(&mempool),
+            Ok(Reconstructed::Missing(vec![2]))
+        );
+
+        mempool.insert(compact.short_ids[1], missing);
+        assert_eq!(
+            compact.reconstruct(&mempool),
+            Ok(Reconstructed::Block(block))
+        );
+    }
+
+    #[test]
+    fn compact_block_wire_format() {
+        zebra_test::init();
+
+        let block_bytes: &[u8] = &zebra_test::vectors::BLOCK_MAINNET_347500_BYTES;
+        let block: Block = block_bytes
+            .bitcoin_deserialize_into()
+            .expect("block should deserialize");
+        let coinbase_len = block.transactions[0].serialized_size_with_header();
+
+        // A cmpctblock for this block: the header, the nonce, one short id,
+        // and the coinbase, prefilled at index 0
+        let mut message_bytes = block_bytes[..80].to_vec();
+        message_bytes.extend_from_slice(&0x0123_4567_89ab_cdef_u64.to_le_bytes());
+        message_bytes.push(1);
+        message_bytes.extend_from_slice(&hex::decode("9f04f4d066c3").unwrap());
+        message_bytes.extend_from_slice(&[1, 0]);
+        message_bytes.extend_from_slice(&block_bytes[81..81 + coinbase_len]);
+
+        let compact: CompactBlock = message_bytes
+            .as_slice()
+            .bitcoin_deserialize_into()
+            .expect("compact block should deserialize");
+        assert_eq!(compact.header, block.header);
+        assert_eq!(compact.nonce, 0x0123_4567_89ab_cdef);
+        assert_eq!(compact.short_ids, vec![ShortId::new(0xc366_d0f4_049f)]);
+        assert_eq!(compact.prefilled_txns.len(), 1);
+        assert_eq!(compact.prefilled_txns[0].index.value(), 0);
+        assert_eq!(compact.prefilled_txns[0].tx, *block.transactions[0]);
+
+        // The short id is the one for the block's second transaction
+        assert_eq!(
+            compact.short_id(&block.transactions[1].hash()),
+            compact.short_ids[0]
+        );
+
+        assert_eq!(compact.serialized_size(), message_bytes.len());
+        assert_eq!(
+            compact
+                .bitcoin_serialize_to_vec()
+                .expect("serializing into a vec never fails"),
+            message_bytes
+        );
+    }
+
+    #[test]
+    fn reconstruct_rejects_bad_prefilled_indexes() {
+        zebra_test::init();
+
+        let block: Block = zebra_test::vectors::BLOCK_MAINNET_347500_BYTES
+            .bitcoin_deserialize_into()
+            .expect("block should deserialize");
+        let compact = CompactBlock {
+            header: block.header.clone(),
+            nonce: 0,
+            short_ids: vec![ShortId::new(1)],
+            prefilled_txns: vec![PrefilledTransaction {
+                index: CompactInt::from(2),
+                tx: (*block.transactions[0]).clone(),
+            }],
+        };
+
+        assert_eq!(
+            compact.reconstruct(&HashMap::new()),
+            Err(SerializationError::Parse(
+                "compact block prefilled index is out of range"
+            ))
+        );
+    }
 }
 
 // FIXME: swap to proptest
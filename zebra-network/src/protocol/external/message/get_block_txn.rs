@@ -10,7 +10,8 @@ pub struct GetBlockTxn {
 }
 
 impl GetBlockTxn {
-    fn serialized_size(&self) -> usize {
+    /// The serialized size of the message.
+    pub fn serialized_size(&self) -> usize {
         let mut len = 32 + CompactInt::size(self.indexes.len());
         for index in self.indexes.iter() {
             len += CompactInt::size(index.value() as usize);
@@ -3,6 +3,10 @@ use zebra_chain::{
 };
 // use tracing::warn;
 
+/// A `getblocks` message, requesting an `inv` of the block hashes after the
+/// first locator hash in our best chain.
+///
+/// [Bitcoin reference](https://en.bitcoin.it/wiki/Protocol_documentation#getblocks)
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct GetBlocks {
     /// A list of the sender's bets known block hashes, ordered from newest to oldest
@@ -91,7 +95,7 @@ impl GetBlocks {
 
 impl GetBlocks {
     /// The serialized size of the message, excluding the protocol version
-    fn serialized_size(&self) -> usize {
+    pub fn serialized_size(&self) -> usize {
         //block header hashes, and stop_hash
         CompactInt::size(self.block_header_hashes.len())
             + (self.block_header_hashes.len() * 32)
@@ -1,6 +1,10 @@
 use zebra_chain::{
     block, compactint::CompactInt, BitcoinDeserialize, BitcoinSerialize, SerializationError,
 };
+/// A `getheaders` message, requesting the block headers after the first
+/// locator hash in our best chain.
+///
+/// [Bitcoin reference](https://en.bitcoin.it/wiki/Protocol_documentation#getheaders)
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct GetHeaders {
     /// A list of the sender's bets known block hashes, ordered from newest to oldest
@@ -42,7 +46,7 @@ impl BitcoinSerialize for GetHeaders {
 impl GetHeaders {
     /// The serialized size of the message, excluding the protocol version
 
-    fn serialized_size(&self) -> usize {
+    pub fn serialized_size(&self) -> usize {
         CompactInt::size(self.block_header_hashes.len())
             + (self.block_header_hashes.len() * 32)
             + 32 //protocol version, block header hashes, and stop_hash
@@ -1,7 +1,9 @@
+use std::io::Write;
+
 use bitcoin_serde_derive::{BtcDeserialize, BtcSerialize};
 use zebra_chain::{
-    block, compactint::CompactInt, transaction, BitcoinDeserialize, BitcoinSerialize,
-    SerializationError,
+    block, compactint::CompactInt, serialization::sha256d, transaction, BitcoinDeserialize,
+    BitcoinSerialize, SerializationError,
 };
 
 #[derive(BtcDeserialize, BtcSerialize, Debug, Clone, PartialEq, Eq)]
@@ -12,7 +14,8 @@ pub struct MerkleBlock {
     pub flags: Vec<u8>,
 }
 impl MerkleBlock {
-    fn serialized_size(&self) -> usize {
+    /// The serialized size of the message.
+    pub fn serialized_size(&self) -> usize {
         block::Header::len()
             + 4
             + CompactInt::size(self.hashes.len())
@@ -20,6 +23,129 @@ impl MerkleBlock {
             + CompactInt::size(self.flags.len())
             + self.flags.len()
     }
+
+    /// Walk the partial merkle tree in this message, returning the merkle
+    /// root it commits to, and the hashes of the matched transactions, in
+    /// block order.
+    ///
+    /// Callers must check that the returned root matches
+    /// `block_header.merkle_root`. Trees with unused or missing hashes or flag
+    /// bits are rejected, as are trees with identical sibling hashes, which
+    /// could hide duplicate transactions (CVE-2012-2459).
+    ///
+    /// [BIP37](https://github.com/bitcoin/bips/blob/master/bip-0037.mediawiki#partial-merkle-branch-format)
+    pub fn extract_matches(
+        &self,
+    ) -> Result<(block::merkle::Root, Vec<transaction::Hash>), SerializationError> {
+        use SerializationError::Parse;
+
+        if self.transaction_count == 0 {
+            return Err(Parse("merkleblock has no transactions"));
+        }
+        if self.hashes.len() > self.transaction_count as usize {
+            return Err(Parse("merkleblock has more hashes than transactions"));
+        }
+        if self.hashes.len() > self.flags.len() * 8 {
+            return Err(Parse("merkleblock has fewer flag bits than hashes"));
+        }
+
+        let mut height = 0;
+        while tree_width(self.transaction_count, height) > 1 {
+            height += 1;
+        }
+
+        let mut traversal = Traversal {
+            merkle_block: self,
+            bits_used: 0,
+            hashes_used: 0,
+            matches: Vec::new(),
+        };
+        let root = traversal.extract(height, 0)?;
+
+        if traversal.hashes_used != self.hashes.len() {
+            return Err(Parse("merkleblock has unused hashes"));
+        }
+        if (traversal.bits_used + 7) / 8 != self.flags.len() {
+            return Err(Parse("merkleblock has unused flag bytes"));
+        }
+
+        Ok((block::merkle::Root(root), traversal.matches))
+    }
+}
+
+/// Returns the number of nodes at `height` in the merkle tree of a block with
+/// `transaction_count` transactions. The transactions are at height 0.
+fn tree_width(transaction_count: u32, height: u32) -> u64 {
+    (u64::from(transaction_count) + (1 << height) - 1) >> height
+}
+
+/// The state of a depth-first walk through a [`MerkleBlock`]'s partial tree.
+struct Traversal<'a> {
+    merkle_block: &'a MerkleBlock,
+    bits_used: usize,
+    hashes_used: usize,
+    matches: Vec<transaction::Hash>,
+}
+
+impl Traversal<'_> {
+    fn next_bit(&mut self) -> Result<bool, SerializationError> {
+        let flags = &self.merkle_block.flags;
+        let byte = flags
+            .get(self.bits_used / 8)
+            .ok_or(SerializationError::Parse(
+                "merkleblock has too few flag bits",
+            ))?;
+        let bit = (byte >> (self.bits_used % 8)) & 1 == 1;
+        self.bits_used += 1;
+        Ok(bit)
+    }
+
+    fn next_hash(&mut self) -> Result<[u8; 32], SerializationError> {
+        let hash = self
+            .merkle_block
+            .hashes
+            .get(self.hashes_used)
+            .ok_or(SerializationError::Parse("merkleblock has too few hashes"))?;
+        self.hashes_used += 1;
+        Ok(hash.0)
+    }
+
+    /// Returns the hash of the node at `height` and `position`, recording any
+    /// matched transactions below it.
+    fn extract(&mut self, height: u32, position: u64) -> Result<[u8; 32], SerializationError> {
+        let parent_of_match = self.next_bit()?;
+
+        if height == 0 || !parent_of_match {
+            let hash = self.next_hash()?;
+            if height == 0 && parent_of_match {
+                self.matches.push(transaction::Hash(hash));
+            }
+            return Ok(hash);
+        }
+
+        let left = self.extract(height - 1, position * 2)?;
+        let width = tree_width(self.merkle_block.transaction_count, height - 1);
+        let right = if position * 2 + 1 < width {
+            let right = self.extract(height - 1, position * 2 + 1)?;
+            if right == left {
+                return Err(SerializationError::Parse(
+                    "merkleblock has identical sibling hashes",
+                ));
+            }
+            right
+        } else {
+            left
+        };
+
+        let mut writer = sha256d::Writer::default();
+        writer
+            .write_all(&left)
+            .expect("writing to a hash writer never fails");
+        writer
+            .write_all(&right)
+            .expect("writing to a hash writer never fails");
+        Ok(writer.finish())
+    }
 }
 
 // #[test]
@@ -41,3 +167,89 @@ impl MerkleBlock {
 //     assert_eq!(serial.len(), msg.serialized_size());
 //     assert_eq!(serial.len(), serial.capacity())
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A mainnet `merkleblock` from the Bitcoin developer reference, matching
+    /// the fifth of the block's seven transactions.
+    const MERKLEBLOCK_HEX: &str = "01000000\
+        82bb869cf3a793432a66e826e05a6fc37469f8efb7421dc88067010000000000\
+        7f16c5962e8bd963659c793ce370d95f093bc7e367117b3c30c1f8fdd0d97287\
+        76381b4d4c86041b554b8529\
+        07000000\
+        04\
+        3612262624047ee87660be1a707519a443b1c1ce3d248cbfc6c15870f6c5daa2\
+        019f5b01d4195ecbc9398fbf3c3b1fa9bb3183301d7a1fb3bd174fcfa40a2b65\
+        41ed70551dd7e841883ab8f0b16bf04176b7d1480e4f0af9f3d4c3595768d068\
+        20d2a7bc994987302e5b1ac80fc425fe25f8b63169ea78e68fbaaefa59379bbf\
+        011d";
+
+    fn merkle_block() -> MerkleBlock {
+        let bytes = hex::decode(MERKLEBLOCK_HEX).unwrap();
+        MerkleBlock::bitcoin_deserialize(&bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn extract_matches_from_captured_merkleblock() {
+        zebra_test::init();
+
+        let merkle_block = merkle_block();
+        assert_eq!(
+            merkle_block.bitcoin_serialize_to_vec().unwrap(),
+            hex::decode(MERKLEBLOCK_HEX).unwrap()
+        );
+
+        let (root, matches) = merkle_block.extract_matches().unwrap();
+        assert_eq!(root, merkle_block.block_header.merkle_root);
+        assert_eq!(matches, vec![merkle_block.hashes[1]]);
+    }
+
+    #[test]
+    fn extract_matches_rejects_malformed_trees() {
+        zebra_test::init();
+
+        let expect_error = |merkle_block: MerkleBlock, error| {
+            assert_eq!(
+                merkle_block.extract_matches(),
+                Err(SerializationError::Parse(error))
+            );
+        };
+
+        let mut extra_hash = merkle_block();
+        extra_hash.hashes.push(transaction::Hash([7; 32]));
+        expect_error(extra_hash, "merkleblock has unused hashes");
+
+        let mut missing_hash = merkle_block();
+        missing_hash.hashes.pop();
+        expect_error(missing_hash, "merkleblock has too few hashes");
+
+        let mut extra_flags = merkle_block();
+        extra_flags.flags.push(0);
+        expect_error(extra_flags, "merkleblock has unused flag bytes");
+
+        let mut missing_flags = merkle_block();
+        missing_flags.flags.clear();
+        expect_error(missing_flags, "merkleblock has fewer flag bits than hashes");
+
+        let mut no_transactions = merkle_block();
+        no_transactions.transaction_count = 0;
+        expect_error(no_transactions, "merkleblock has no transactions");
+
+        let mut too_many_hashes = merkle_block();
+        too_many_hashes.transaction_count = 3;
+        expect_error(
+            too_many_hashes,
+            "merkleblock has more hashes than transactions",
+        );
+
+        // Duplicating the right subtree's hash in its left sibling
+        let mut identical_siblings = merkle_block();
+        identical_siblings.hashes[2] = identical_siblings.hashes[1];
+        expect_error(
+            identical_siblings,
+            "merkleblock has identical sibling hashes",
+        );
+    }
+}
@@ -13,6 +13,47 @@ impl SendCompact {
     }
 }
 
+/// A peer's compact block relay preferences, from its `sendcmpct` messages.
+///
+/// Compact block versions 1 and 2 (which includes witness data) are
+/// supported. Messages with other versions are ignored, and the last
+/// supported message replaces any earlier ones.
+///
+/// [BIP152](https://github.com/bitcoin/bips/blob/master/bip-0152.mediawiki)
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CompactBlockRelayState {
+    /// The version and announce flag from the last supported `sendcmpct`.
+    last: Option<(u64, bool)>,
+}
+
+impl CompactBlockRelayState {
+    /// The compact block versions that we understand.
+    pub const SUPPORTED_VERSIONS: [u64; 2] = [1, 2];
+
+    /// Update the state for a `sendcmpct` message from the peer.
+    pub fn update(&mut self, msg: &SendCompact) {
+        if Self::SUPPORTED_VERSIONS.contains(&msg.version) {
+            self.last = Some((msg.version, msg.announce));
+        }
+    }
+
+    /// Returns true if the peer can receive compact blocks.
+    pub fn wants_compact(&self) -> bool {
+        self.last.is_some()
+    }
+
+    /// Returns true if the peer wants new blocks announced with `cmpctblock`
+    /// messages, rather than `inv` or `headers`.
+    pub fn high_bandwidth(&self) -> bool {
+        matches!(self.last, Some((_, true)))
+    }
+
+    /// Returns the compact block version the peer asked for, if any.
+    pub fn version(&self) -> Option<u64> {
+        self.last.map(|(version, _)| version)
+    }
+}
+
 #[test]
 fn serial_size() {
     let msg = SendCompact {
@@ -25,3 +66,48 @@ fn serial_size() {
     assert_eq!(serial.len(), msg.serialized_size());
     // assert_eq!(serial.len(), serial.capacity())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sendcmpct(announce: bool, version: u64) -> SendCompact {
+        SendCompact { announce, version }
+    }
+
+    #[test]
+    fn compact_block_relay_mode_transitions() {
+        zebra_test::init();
+
+        let mut state = CompactBlockRelayState::default();
+        assert!(!state.wants_compact());
+        assert!(!state.high_bandwidth());
+
+        // Unsupported versions are ignored
+        state.update(&sendcmpct(true, 3));
+        assert!(!state.wants_compact());
+        assert_eq!(state.version(), None);
+
+        // Low bandwidth mode
+        state.update(&sendcmpct(false, 1));
+        assert!(state.wants_compact());
+        assert!(!state.high_bandwidth());
+        assert_eq!(state.version(), Some(1));
+
+        // Switching to high bandwidth mode, with witness data
+        state.update(&sendcmpct(true, 2));
+        assert!(state.wants_compact());
+        assert!(state.high_bandwidth());
+        assert_eq!(state.version(), Some(2));
+
+        // An unsupported version doesn't replace the last supported message
+        state.update(&sendcmpct(false, 0));
+        assert!(state.high_bandwidth());
+        assert_eq!(state.version(), Some(2));
+
+        // Back to low bandwidth mode
+        state.update(&sendcmpct(false, 2));
+        assert!(state.wants_compact());
+        assert!(!state.high_bandwidth());
+    }
+}
@@ -5,7 +5,8 @@ use chrono::{DateTime, TimeZone, Utc};
 use std::net;
 use std::net::SocketAddr;
 use zebra_chain::{
-    serialization::BigUnixTime, BitcoinDeserialize, BitcoinSerialize, SerializationError,
+    compactint::CompactInt, serialization::BigUnixTime, BitcoinDeserialize, BitcoinSerialize,
+    SerializationError,
 };
 
 use zebra_chain::block;
@@ -48,6 +49,10 @@ pub struct Version {
     pub user_agent: String,
 
     /// The last block received by the emitting node.
+    ///
+    /// Bitcoin calls this `start_height`, and encodes it as a little-endian
+    /// `int32`. `block::Height` is serialized as a little-endian `u32`, which
+    /// has the same encoding for every valid height.
     pub best_block: block::Height,
 
     /// Whether the remote peer should announce relayed
@@ -83,6 +88,26 @@ impl Version {
             relay,
         }
     }
+
+    /// The serialized size of the message.
+    pub fn serialized_size(&self) -> usize {
+        // version, services, and timestamp
+        4 + 8 + 8
+            // address_recv and address_from: services, IPv6 address, and port
+            + 2 * (8 + 16 + 2)
+            + 8
+            + CompactInt::size(self.user_agent.len())
+            + self.user_agent.len()
+            // best_block and relay
+            + 4
+            + 1
+    }
+
+    /// Returns true if the sender's protocol version is at least `min`.
+    pub fn is_compatible(&self, min: ProtocolVersion) -> bool {
+        self.version >= min
+    }
+
     // pub fn protocol_version(&self) -> ProtocolVersion {
     //     self.protocol_version
     // }
@@ -144,3 +169,51 @@ impl Version {
 //     assert_eq!(serial.len(), msg.serialized_size());
 //     assert_eq!(serial.len(), serial.capacity())
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example `version` payload from the Bitcoin wiki, sent by a
+    /// `/Satoshi:0.7.2/` node at height 212672, with a trailing `relay` byte.
+    const VERSION_PAYLOAD: &str = concat!(
+        "62ea0000",
+        "0100000000000000",
+        "11b2d05000000000",
+        "010000000000000000000000000000000000ffff000000000000",
+        "010000000000000000000000000000000000ffff000000000000",
+        "3b2eb35d8ce61765",
+        "0f2f5361746f7368693a302e372e322f",
+        "c03e0300",
+        "01",
+    );
+
+    #[test]
+    fn start_height_is_four_le_bytes() {
+        zebra_test::init();
+
+        let payload = hex::decode(VERSION_PAYLOAD).unwrap();
+        let version = Version::bitcoin_deserialize(&payload[..]).unwrap();
+        assert_eq!(version.user_agent, "/Satoshi:0.7.2/");
+        assert_eq!(version.best_block, block::Height(212_672));
+
+        let serialized = version.bitcoin_serialize_to_vec().unwrap();
+        assert_eq!(serialized, payload);
+
+        let start_height = &serialized[serialized.len() - 5..serialized.len() - 1];
+        assert_eq!(start_height, &[0xc0, 0x3e, 0x03, 0x00]);
+    }
+
+    #[test]
+    fn compatibility_with_minimum_version() {
+        zebra_test::init();
+
+        let payload = hex::decode(VERSION_PAYLOAD).unwrap();
+        let version = Version::bitcoin_deserialize(&payload[..]).unwrap();
+        assert_eq!(version.version, ProtocolVersion(60_002));
+
+        assert!(version.is_compatible(ProtocolVersion(60_001)));
+        assert!(version.is_compatible(ProtocolVersion(60_002)));
+        assert!(!version.is_compatible(ProtocolVersion(60_003)));
+    }
+}
@@ -0,0 +1,268 @@
+//! A bounded queue of outbound messages, which coalesces inventory
+//! announcements when the peer is slow.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    pin::Pin,
+    task::Poll,
+};
+
+use futures::{future, prelude::*};
+
+use crate::constants;
+
+use super::{InventoryHash, Message};
+
+/// A bounded queue of messages waiting to be sent to a peer through `sink`.
+///
+/// Messages are passed to the sink as soon as it is ready, and only queued
+/// while it isn't. While messages are queued, new `inv` announcements are
+/// merged into the most recently queued `inv`, so a slow peer doesn't make
+/// them pile up. Other messages, including handshake messages and pings, are
+/// never dropped: when the queue is full, sending them waits until the sink
+/// accepts a queued message.
+#[allow(dead_code)]
+pub struct OutboundQueue<S> {
+    sink: S,
+    pending: VecDeque<Message>,
+    capacity: usize,
+    /// The items in the most recently queued `inv`, for fast duplicate
+    /// checks when coalescing.
+    queued_inv_items: HashSet<InventoryHash>,
+}
+
+#[allow(dead_code)]
+impl<S> OutboundQueue<S>
+where
+    S: Sink<Message> + Unpin,
+{
+    /// Returns a new queue of up to `capacity` messages for `sink`.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is zero.
+    pub fn new(sink: S, capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "outbound queues must hold at least one message"
+        );
+        OutboundQueue {
+            sink,
+            pending: VecDeque::with_capacity(capacity),
+            capacity,
+            queued_inv_items: HashSet::new(),
+        }
+    }
+
+    /// Returns the number of messages waiting to be sent.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns true if there are no messages waiting to be sent.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Returns the underlying sink.
+    pub fn get_ref(&self) -> &S {
+        &self.sink
+    }
+
+    /// Queue `msg` to be sent.
+    ///
+    /// If the sink isn't ready, `inv` messages are coalesced into a queued
+    /// `inv` where possible. If the queue is full, this waits for the sink to
+    /// accept the oldest queued message.
+    pub async fn send(&mut self, msg: Message) -> Result<(), S::Error> {
+        self.feed_ready().await?;
+
+        if let Message::Inv(items) = &msg {
+            if !self.pending.is_empty() && self.coalesce_inv(items) {
+                return Ok(());
+            }
+        }
+
+        while self.pending.len() >= self.capacity {
+            self.send_next().await?;
+        }
+        self.push(msg);
+
+        self.feed_ready().await
+    }
+
+    /// Send all queued messages, then flush the sink.
+    pub async fn flush(&mut self) -> Result<(), S::Error> {
+        while !self.pending.is_empty() {
+            self.send_next().await?;
+        }
+        self.sink.flush().await
+    }
+
+    /// Pass queued messages to the sink for as long as it is ready, without
+    /// waiting for it.
+    async fn feed_ready(&mut self) -> Result<(), S::Error> {
+        future::poll_fn(|cx| {
+            while !self.pending.is_empty() {
+                match Pin::new(&mut self.sink).poll_ready(cx)? {
+                    Poll::Ready(()) => {
+                        let msg = self.pop().expect("queue is not empty");
+                        Pin::new(&mut self.sink).start_send(msg)?;
+                    }
+                    Poll::Pending => break,
+                }
+            }
+            Poll::Ready(Ok(()))
+        })
+        .await
+    }
+
+    /// Wait for the sink, then pass it the oldest queued message.
+    async fn send_next(&mut self) -> Result<(), S::Error> {
+        if let Some(msg) = self.pop() {
+            self.sink.feed(msg).await?;
+        }
+        Ok(())
+    }
+
+    /// Add `msg` to the back of the queue.
+    fn push(&mut self, msg: Message) {
+        if let Message::Inv(items) = &msg {
+            self.queued_inv_items = items.iter().copied().collect();
+        }
+        self.pending.push_back(msg);
+    }
+
+    /// Remove the oldest message from the queue.
+    fn pop(&mut self) -> Option<Message> {
+        let msg = self.pending.pop_front()?;
+        if matches!(msg, Message::Inv(_))
+            && !self
+                .pending
+                .iter()
+                .any(|queued| matches!(queued, Message::Inv(_)))
+        {
+            self.queued_inv_items.clear();
+        }
+        Some(msg)
+    }
+
+    /// Merge `items` into the most recently queued `inv`, skipping items that
+    /// are already queued.
+    ///
+    /// Returns false if there is no queued `inv`, or the merged `inv` would
+    /// have too many items.
+    fn coalesce_inv(&mut self, items: &[InventoryHash]) -> bool {
+        let queued = self.pending.iter_mut().rev().find_map(|msg| match msg {
+            Message::Inv(queued) => Some(queued),
+            _ => None,
+        });
+        let queued = match queued {
+            Some(queued) => queued,
+            None => return false,
+        };
+
+        let mut new_items = Vec::new();
+        let mut seen = HashSet::new();
+        for item in items {
+            if !self.queued_inv_items.contains(item) && seen.insert(*item) {
+                new_items.push(*item);
+            }
+        }
+        if queued.len() + new_items.len() > constants::MAX_INV_IN_MESSAGE {
+            return false;
+        }
+
+        self.queued_inv_items.extend(new_items.iter().copied());
+        queued.extend(new_items);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::channel::mpsc;
+    use zebra_chain::transaction;
+
+    use super::*;
+    use crate::protocol::external::types::Nonce;
+
+    fn tx_inv(id: u8) -> InventoryHash {
+        InventoryHash::Tx(transaction::Hash([id; 32]))
+    }
+
+    #[tokio::test]
+    async fn ready_sinks_are_sent_to_directly() {
+        zebra_test::init();
+
+        let mut queue = OutboundQueue::new(Vec::new(), 1);
+        for id in 0..10 {
+            queue
+                .send(Message::Inv(vec![tx_inv(id)]))
+                .await
+                .expect("vec sinks never fail");
+            assert!(queue.is_empty());
+        }
+
+        // Nothing is coalesced, because the sink is always ready
+        assert_eq!(queue.get_ref().len(), 10);
+    }
+
+    #[tokio::test]
+    async fn flooding_a_slow_peer_coalesces_inv_announcements() {
+        zebra_test::init();
+
+        // The sink accepts one message, then isn't ready until it is read
+        let (sink, stream) = mpsc::channel(0);
+        let mut queue = OutboundQueue::new(sink, 4);
+        let messages = vec![
+            Message::Ping(Nonce(1)),
+            Message::Inv(vec![tx_inv(0)]),
+            Message::Ping(Nonce(2)),
+            Message::Inv(vec![tx_inv(1)]),
+        ];
+        for msg in messages {
+            queue.send(msg).await.expect("the receiver is open");
+        }
+        // The first ping was sent, and the second inv was merged into the
+        // first, because the sink wasn't ready
+        assert_eq!(queue.len(), 2);
+
+        // Later announcements are merged into the queued inv too, without
+        // growing the queue
+        for id in (2..=255).chain(1..=255) {
+            queue
+                .send(Message::Inv(vec![tx_inv(id)]))
+                .await
+                .expect("the receiver is open");
+            assert_eq!(queue.len(), 2);
+        }
+
+        // Pings are never dropped
+        queue
+            .send(Message::Ping(Nonce(3)))
+            .await
+            .expect("the receiver is open");
+        assert_eq!(queue.len(), 3);
+
+        let (flushed, received) = future::join(
+            async move {
+                let result = queue.flush().await;
+                assert!(queue.is_empty());
+                result
+            },
+            stream.collect::<Vec<_>>(),
+        )
+        .await;
+        flushed.expect("the receiver is open");
+        assert_eq!(
+            received,
+            vec![
+                Message::Ping(Nonce(1)),
+                Message::Inv((0..=255).map(tx_inv).collect()),
+                Message::Ping(Nonce(2)),
+                Message::Ping(Nonce(3)),
+            ]
+        );
+    }
+}
@@ -1,11 +1,10 @@
 #![allow(clippy::unit_arg)]
 
-use crate::constants::magics;
-
 use bitcoin_serde_derive::{BtcDeserialize, BtcSerialize};
 use std::fmt;
 
 use zebra_chain::{
+    amount::{Amount, NonNegative},
     block,
     parameters::{
         Network::{self, *},
@@ -31,10 +30,7 @@ impl fmt::Debug for Magic {
 impl From<Network> for Magic {
     /// Get the magic value associated to this `Network`.
     fn from(network: Network) -> Self {
-        match network {
-            Network::Mainnet => magics::MAINNET,
-            Network::Testnet => magics::TESTNET,
-        }
+        Magic(network.magic())
     }
 }
 
@@ -65,8 +61,6 @@ impl ProtocolVersion {
     }
 
     /// Returns the current minimum protocol version for `network` and `height`.
-    ///
-    /// Returns None if the network has no branch id at this height.
     #[allow(dead_code)]
     pub fn current_min(network: Network, height: block::Height) -> ProtocolVersion {
         let network_upgrade = NetworkUpgrade::current(network, height);
@@ -88,6 +82,14 @@ bitflags! {
     }
 }
 
+impl PeerServices {
+    /// Returns the services in `bits`, keeping any service bits that Zebra
+    /// doesn't know about, so that they round-trip unchanged.
+    pub fn from_bits_preserving(bits: u64) -> PeerServices {
+        PeerServices { bits }
+    }
+}
+
 /// A nonce used in the networking layer to identify messages.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, BtcSerialize, BtcDeserialize)]
 pub struct Nonce(pub u64);
@@ -115,6 +117,27 @@ impl Default for Tweak {
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Filter(pub Vec<u8>);
 
+/// The minimum fee rate, in satoshis per 1000 bytes, of transactions that a
+/// peer wants announced to it, as sent in a `feefilter` message.
+///
+/// [BIP133](https://github.com/bitcoin/bips/blob/master/bip-0133.mediawiki)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FeeFilter(pub u64);
+
+impl FeeFilter {
+    /// Returns true if a `tx_size` byte transaction paying `tx_fee` has a fee
+    /// rate of at least this filter's rate, so it should be relayed.
+    ///
+    /// The fee rate is `tx_fee * 1000 / tx_size`, rounded down. Zero size
+    /// transactions don't have a fee rate, so they are always relayed.
+    pub fn should_relay(&self, tx_fee: Amount<NonNegative>, tx_size: usize) -> bool {
+        // Compare `tx_fee * 1000 >= self.0 * tx_size`, which is equivalent
+        // to comparing the rounded down rate, but can't overflow or divide
+        // by zero.
+        u128::from(u64::from(tx_fee)) * 1000 >= u128::from(self.0) * tx_size as u128
+    }
+}
+
 #[cfg(test)]
 mod proptest {
 
@@ -128,8 +151,8 @@ mod proptest {
     fn magic_debug() {
         zebra_test::init();
 
-        assert_eq!(format!("{:?}", magics::MAINNET), "Magic(\"d9b4bef9\")");
-        assert_eq!(format!("{:?}", magics::TESTNET), "Magic(\"0709110b\")");
+        assert_eq!(format!("{:?}", magics::MAINNET), "Magic(\"f9beb4d9\")");
+        assert_eq!(format!("{:?}", magics::TESTNET), "Magic(\"0b110907\")");
     }
 
     proptest! {
@@ -145,8 +168,44 @@ mod proptest {
 
 #[cfg(test)]
 mod test {
+    use std::convert::TryFrom;
+
     use super::*;
 
+    fn fee(satoshis: u64) -> Amount<NonNegative> {
+        Amount::try_from(satoshis).expect("fee is a valid amount")
+    }
+
+    #[test]
+    fn fee_filter_threshold() {
+        zebra_test::init();
+
+        // 1000 satoshis per 1000 bytes
+        let filter = FeeFilter(1000);
+
+        // A 250 byte transaction is exactly at the threshold with a 250
+        // satoshi fee
+        assert!(filter.should_relay(fee(250), 250));
+        assert!(!filter.should_relay(fee(249), 250));
+        assert!(filter.should_relay(fee(251), 250));
+
+        // The rate is rounded down, so 999.6 satoshis per 1000 bytes is
+        // below the threshold
+        assert!(!filter.should_relay(fee(2499), 2500));
+        assert!(filter.should_relay(fee(2500), 2500));
+
+        // A zero rate filter relays everything
+        assert!(FeeFilter(0).should_relay(fee(0), 250));
+    }
+
+    #[test]
+    fn fee_filter_zero_size() {
+        zebra_test::init();
+
+        assert!(FeeFilter(1000).should_relay(fee(0), 0));
+        assert!(FeeFilter(u64::MAX).should_relay(fee(1), 0));
+    }
+
     #[test]
     fn version_extremes_mainnet() {
         version_extremes(Mainnet)
@@ -191,10 +250,10 @@ mod test {
         zebra_test::init();
 
         let highest_network_upgrade = NetworkUpgrade::current(network, block::Height::MAX);
-        assert!(highest_network_upgrade == SegWit,
+        assert!(highest_network_upgrade == Taproot,
                 "expected coverage of all network upgrades: add the new network upgrade to the list in this test");
 
-        for &network_upgrade in &[Genesis, BIP34, BIP66, BIP65, CSV, SegWit] {
+        for &network_upgrade in &[Genesis, BIP34, BIP66, BIP65, CSV, SegWit, Taproot] {
             let height = network_upgrade.activation_height(network);
             if let Some(height) = height {
                 assert_eq!(
@@ -14,10 +14,7 @@ use zcash_script::{
     zcash_script_error_t_zcash_script_ERR_TX_INDEX,
     zcash_script_error_t_zcash_script_ERR_TX_SIZE_MISMATCH,
 };
-use zebra_chain::{
-    parameters::ConsensusBranchId, serialization::BitcoinSerialize, transaction::Transaction,
-    transparent,
-};
+use zebra_chain::{serialization::BitcoinSerialize, transaction::Transaction, transparent};
 
 #[derive(Debug, Display, Error, PartialEq)]
 #[non_exhaustive]
@@ -83,12 +80,11 @@ impl CachedFfiTransaction {
     }
 
     pub fn inputs(&self) -> &[transparent::Input] {
-        &self.transaction.inputs
+        self.transaction.inputs()
     }
 
     /// Verify a script within a transaction given the corresponding
-    /// `transparent::Output` it is spending and the `ConsensusBranchId` of the block
-    /// containing the transaction.
+    /// `transparent::Output` it is spending.
     ///
     /// # Details
     ///
@@ -96,7 +92,6 @@ impl CachedFfiTransaction {
     /// `transaction` used to identify the `previous_output`.
     pub fn is_valid(
         &self,
-        branch_id: ConsensusBranchId,
         (input_index, previous_output): (u32, transparent::Output),
     ) -> Result<(), Error> {
         let transparent::Output { value, lock_script } = previous_output;
@@ -110,7 +105,9 @@ impl CachedFfiTransaction {
         let flags = zcash_script::zcash_script_SCRIPT_FLAGS_VERIFY_P2SH
             | zcash_script::zcash_script_SCRIPT_FLAGS_VERIFY_CHECKLOCKTIMEVERIFY;
 
-        let consensus_branch_id = branch_id.into();
+        // Bitcoin doesn't have consensus branch ids, and zcash_script ignores
+        // them for transactions without a Zcash transaction version.
+        let consensus_branch_id = 0;
 
         let mut err = 0;
 
@@ -179,9 +176,7 @@ mod tests {
     use hex::FromHex;
     use std::convert::TryInto;
     use std::sync::Arc;
-    use zebra_chain::{
-        parameters::NetworkUpgrade::*, serialization::BitcoinDeserializeInto, transparent,
-    };
+    use zebra_chain::{serialization::BitcoinDeserializeInto, transparent};
     use zebra_test::prelude::*;
 
     lazy_static::lazy_static! {
@@ -204,10 +199,9 @@ mod tests {
             lock_script: transparent::Script(SCRIPT_PUBKEY.clone()),
         };
         let input_index = 0;
-        let branch_id = SegWit.branch_id().into();
 
         let verifier = super::CachedFfiTransaction::new(transaction);
-        verifier.is_valid(branch_id, (input_index, output))?;
+        verifier.is_valid((input_index, output))?;
 
         Ok(())
     }
@@ -225,12 +219,9 @@ mod tests {
             lock_script: transparent::Script(SCRIPT_PUBKEY.clone()),
         };
         let input_index = 0;
-        let branch_id = SegWit.branch_id().into();
 
         let verifier = super::CachedFfiTransaction::new(transaction);
-        verifier
-            .is_valid(branch_id, (input_index, output))
-            .unwrap_err();
+        verifier.is_valid((input_index, output)).unwrap_err();
 
         Ok(())
     }
@@ -246,21 +237,20 @@ mod tests {
         let verifier = super::CachedFfiTransaction::new(transaction);
 
         let input_index = 0;
-        let branch_id = SegWit.branch_id().into();
 
         let amount = 212 * coin;
         let output = transparent::Output {
             value: amount.try_into()?,
             lock_script: transparent::Script(SCRIPT_PUBKEY.clone()),
         };
-        verifier.is_valid(branch_id, (input_index, output))?;
+        verifier.is_valid((input_index, output))?;
 
         let amount = 212 * coin;
         let output = transparent::Output {
             value: amount.try_into()?,
             lock_script: transparent::Script(SCRIPT_PUBKEY.clone()),
         };
-        verifier.is_valid(branch_id, (input_index, output))?;
+        verifier.is_valid((input_index, output))?;
 
         Ok(())
     }
@@ -276,23 +266,20 @@ mod tests {
         let verifier = super::CachedFfiTransaction::new(transaction);
 
         let input_index = 0;
-        let branch_id = SegWit.branch_id().into();
 
         let amount = 212 * coin;
         let output = transparent::Output {
             value: amount.try_into()?,
             lock_script: transparent::Script(SCRIPT_PUBKEY.clone()),
         };
-        verifier.is_valid(branch_id, (input_index, output))?;
+        verifier.is_valid((input_index, output))?;
 
         let amount = 211 * coin;
         let output = transparent::Output {
             value: amount.try_into()?,
             lock_script: transparent::Script(SCRIPT_PUBKEY.clone()),
         };
-        verifier
-            .is_valid(branch_id, (input_index, output))
-            .unwrap_err();
+        verifier.is_valid((input_index, output)).unwrap_err();
 
         Ok(())
     }
@@ -308,23 +295,20 @@ mod tests {
         let verifier = super::CachedFfiTransaction::new(transaction);
 
         let input_index = 0;
-        let branch_id = SegWit.branch_id().into();
 
         let amount = 211 * coin;
         let output = transparent::Output {
             value: amount.try_into()?,
             lock_script: transparent::Script(SCRIPT_PUBKEY.clone()),
         };
-        verifier
-            .is_valid(branch_id, (input_index, output))
-            .unwrap_err();
+        verifier.is_valid((input_index, output)).unwrap_err();
 
         let amount = 212 * coin;
         let output = transparent::Output {
             value: amount.try_into()?,
             lock_script: transparent::Script(SCRIPT_PUBKEY.clone()),
         };
-        verifier.is_valid(branch_id, (input_index, output))?;
+        verifier.is_valid((input_index, output))?;
 
         Ok(())
     }
@@ -340,25 +324,20 @@ mod tests {
         let verifier = super::CachedFfiTransaction::new(transaction);
 
         let input_index = 0;
-        let branch_id = SegWit.branch_id().into();
 
         let amount = 211 * coin;
         let output = transparent::Output {
             value: amount.try_into()?,
             lock_script: transparent::Script(SCRIPT_PUBKEY.clone()),
         };
-        verifier
-            .is_valid(branch_id, (input_index, output))
-            .unwrap_err();
+        verifier.is_valid((input_index, output)).unwrap_err();
 
         let amount = 210 * coin;
         let output = transparent::Output {
             value: amount.try_into()?,
             lock_script: transparent::Script(SCRIPT_PUBKEY.clone()),
         };
-        verifier
-            .is_valid(branch_id, (input_index, output))
-            .unwrap_err();
+        verifier.is_valid((input_index, output)).unwrap_err();
 
         Ok(())
     }
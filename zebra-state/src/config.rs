@@ -43,6 +43,29 @@ pub struct Config {
     ///
     /// Set to `None` by default: Zebra continues syncing indefinitely.
     pub debug_stop_at_height: Option<u32>,
+
+    /// How often committed blocks are synced to disk.
+    ///
+    /// Set to [`Durability::Never`] by default, which leaves syncing to the
+    /// database. With this setting, or [`Durability::Periodic`], Zebra can
+    /// lose recently committed blocks if it crashes. (It downloads them again
+    /// on restart.) [`Durability::EveryBlock`] avoids this, but makes the
+    /// initial sync slower.
+    pub durability: Durability,
+}
+
+/// How often the finalized state syncs committed blocks to disk.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Durability {
+    /// Sync every committed block to disk before returning.
+    EveryBlock,
+    /// Sync every `n`th committed block to disk, along with all the blocks
+    /// committed before it.
+    Periodic(u32),
+    /// Leave syncing to the database, apart from explicit calls to
+    /// `FinalizedState::flush`.
+    Never,
 }
 
 fn gen_temp_path(prefix: &str) -> PathBuf {
@@ -267,6 +290,7 @@ impl Default for Config {
             cache_dir,
             ephemeral: false,
             debug_stop_at_height: None,
+            durability: Durability::Never,
         }
     }
 }
@@ -15,8 +15,17 @@ pub const MIN_TRANSPARENT_COINBASE_MATURITY: u32 = 100;
 /// FIXME: This is not correct for Bitcoin
 pub const MAX_BLOCK_REORG_HEIGHT: u32 = MIN_TRANSPARENT_COINBASE_MATURITY - 1;
 
+/// The maximum number of block hashes in an `inv` response to `getblocks`.
+pub const MAX_GETBLOCKS_HASHES: usize = 500;
+
+/// The maximum number of headers in a `headers` response to `getheaders`.
+pub const MAX_GETHEADERS_HEADERS: usize = 2000;
+
 /// The database format version, incremented each time the database format changes.
-pub const DATABASE_FORMAT_VERSION: u32 = 0;
+///
+/// Version 1 adds the `header_by_height`, `undo_by_height` and
+/// `chain_work_by_height` column families.
+pub const DATABASE_FORMAT_VERSION: u32 = 1;
 
 use lazy_static::lazy_static;
 use regex::Regex;
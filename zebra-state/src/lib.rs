@@ -28,10 +28,10 @@ mod utxo;
 #[cfg(test)]
 mod tests;
 
-pub use config::Config;
+pub use config::{Config, Durability};
 pub use constants::MAX_BLOCK_REORG_HEIGHT;
 pub use error::{BoxError, CloneError, CommitBlockError, ValidateContextError};
 pub use request::{FinalizedBlock, HashOrHeight, PreparedBlock, Request};
 pub use response::Response;
-pub use service::init;
+pub use service::{init, FinalizedCommit};
 pub use utxo::Utxo;
@@ -111,6 +111,16 @@ impl From<Arc<Block>> for FinalizedBlock {
         let height = block
             .coinbase_height()
             .expect("finalized blocks must have a valid coinbase height");
+        Self::with_height(block, height)
+    }
+}
+
+impl FinalizedBlock {
+    /// Prepare `block` for commitment at `height`.
+    ///
+    /// Blocks before BIP 34 activation don't encode their height in the
+    /// coinbase, so the caller must supply it.
+    pub fn with_height(block: Arc<Block>, height: block::Height) -> Self {
         let hash = block.hash();
         let transaction_hashes = block
             .transactions
@@ -126,7 +136,7 @@ impl From<Arc<Block>> for FinalizedBlock {
             .zip(transaction_hashes.iter().cloned())
         {
             let from_coinbase = transaction.is_coinbase();
-            for (index, output) in transaction.outputs.iter().cloned().enumerate() {
+            for (index, output) in transaction.outputs().iter().cloned().enumerate() {
                 let index = index as u32;
                 new_outputs.insert(
                     transparent::OutPoint { hash, index },
@@ -195,8 +205,9 @@ pub enum Request {
     /// valid and final. This request can be made out-of-order; the state service
     /// will queue it until its parent is ready.
     ///
-    /// Returns [`Response::Committed`] with the hash of the newly committed
-    /// block, or an error.
+    /// Returns [`Response::CommittedFinalized`] with the hash of the newly
+    /// committed block, the new finalized tip, and the number of queued blocks
+    /// that were committed along with it, or an error.
     ///
     /// This request cannot be cancelled once submitted; dropping the response
     /// future will have no effect on whether it is eventually processed.
@@ -220,8 +231,8 @@ pub enum Request {
     /// Returns [`Response::BlockLocator`] with hashes starting
     /// from the best chain tip, and following the chain of previous
     /// hashes. The first hash is the best chain tip. The last hash is
-    /// the tip of the finalized portion of the state. Block locators
-    /// are not continuous - some intermediate hashes might be skipped.
+    /// the genesis hash. Block locators are not continuous - some
+    /// intermediate hashes might be skipped.
     ///
     /// If the state is empty, the block locator is also empty.
     BlockLocator,
@@ -256,6 +267,16 @@ pub enum Request {
     /// handle missing UTXOs.
     AwaitUtxo(transparent::OutPoint),
 
+    /// Request the height of the block identified by the given hash, waiting
+    /// until it becomes available if it is unknown.
+    ///
+    /// The block can be in any chain, or queued for contextual verification.
+    /// Returns [`Response::Height`] with the height of the block.
+    ///
+    /// Code making this request should apply a timeout layer to the service to
+    /// handle missing blocks.
+    AwaitHeight(block::Hash),
+
     /// Finds the first hash that's in the peer's `known_blocks` and the local best chain.
     /// Returns a list of hashes that follow that intersection, from the best chain.
     ///
@@ -4,7 +4,7 @@ use zebra_chain::{
     transaction::Transaction,
 };
 
-use crate::Utxo;
+use crate::{FinalizedCommit, Utxo};
 
 // Allow *only* this unused import, so that rustdoc link resolution
 // will work with inline links.
@@ -18,6 +18,11 @@ pub enum Response {
     /// successfully committed to the state.
     Committed(block::Hash),
 
+    /// Response to [`Request::CommitFinalizedBlock`] indicating that a block
+    /// was successfully committed to the finalized state, along with the new
+    /// finalized tip.
+    CommittedFinalized(FinalizedCommit),
+
     /// Response to [`Request::Depth`] with the depth of the specified block.
     Depth(Option<u32>),
 
@@ -36,6 +41,9 @@ pub enum Response {
     /// The response to a `AwaitUtxo` request.
     Utxo(Utxo),
 
+    /// The response to a `AwaitHeight` request.
+    Height(block::Height),
+
     /// The response to a `FindBlockHashes` request.
     BlockHashes(Vec<block::Hash>),
 
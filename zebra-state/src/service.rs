@@ -29,11 +29,16 @@ use crate::{
 mod check;
 mod finalized_state;
 mod non_finalized_state;
+mod pending_heights;
 mod pending_utxos;
 #[cfg(test)]
 mod tests;
 
-use self::{finalized_state::FinalizedState, pending_utxos::PendingUtxos};
+use self::{
+    finalized_state::FinalizedState, pending_heights::PendingHeights, pending_utxos::PendingUtxos,
+};
+
+pub use finalized_state::FinalizedCommit;
 
 pub type QueuedBlock = (
     PreparedBlock,
@@ -41,7 +46,7 @@ pub type QueuedBlock = (
 );
 pub type QueuedFinalized = (
     FinalizedBlock,
-    oneshot::Sender<Result<block::Hash, BoxError>>,
+    oneshot::Sender<Result<FinalizedCommit, BoxError>>,
 );
 
 struct StateService {
@@ -53,6 +58,8 @@ struct StateService {
     queued_blocks: QueuedBlocks,
     /// The set of outpoints with pending requests for their associated transparent::Output
     pending_utxos: PendingUtxos,
+    /// The set of block hashes with pending requests for their block heights
+    pending_heights: PendingHeights,
     /// The configured Zcash network
     network: Network,
     /// Instant tracking the last time `pending_utxos` was pruned
@@ -67,12 +74,14 @@ impl StateService {
         let mem = NonFinalizedState::default();
         let queued_blocks = QueuedBlocks::default();
         let pending_utxos = PendingUtxos::default();
+        let pending_heights = PendingHeights::default();
 
         Self {
             disk,
             mem,
             queued_blocks,
             pending_utxos,
+            pending_heights,
             network,
             last_prune: Instant::now(),
         }
@@ -201,17 +210,17 @@ impl StateService {
     }
 
     /// Create a block locator for the current best chain.
+    ///
+    /// Uses the same spacing as [`FinalizedState::block_locator`], so the
+    /// locator reaches back to genesis. Heights that are missing from the
+    /// best chain are skipped.
     fn block_locator(&self) -> Option<Vec<block::Hash>> {
         let tip_height = self.best_tip()?.0;
 
-        let heights = crate::util::block_locator_heights(tip_height);
-        let mut hashes = Vec::with_capacity(heights.len());
-
-        for height in heights {
-            if let Some(hash) = self.best_hash(height) {
-                hashes.push(hash);
-            }
-        }
+        let hashes = crate::util::block_locator_heights(tip_height)
+            .into_iter()
+            .filter_map(|height| self.best_hash(height))
+            .collect();
 
         Some(hashes)
     }
@@ -274,6 +283,14 @@ impl StateService {
             .or_else(|| self.disk.height(hash))
     }
 
+    /// Return the height for the block at `hash` in any chain, or in the queues
+    /// of blocks waiting to be committed.
+    pub fn any_or_queued_height(&self, hash: block::Hash) -> Option<block::Height> {
+        self.any_height_by_hash(hash)
+            .or_else(|| self.queued_blocks.height(&hash))
+            .or_else(|| self.disk.queued_height(hash))
+    }
+
     /// Return the [`Utxo`] pointed to by `outpoint` if it exists in any chain.
     pub fn any_utxo(&self, outpoint: &transparent::OutPoint) -> Option<Utxo> {
         self.mem
@@ -538,6 +555,7 @@ impl Service<Request> for StateService {
             let old_len = self.pending_utxos.len();
 
             self.pending_utxos.prune();
+            self.pending_heights.prune();
             self.last_prune = now;
 
             let new_len = self.pending_utxos.len();
@@ -567,6 +585,8 @@ impl Service<Request> for StateService {
                 metrics::counter!("state.requests", 1, "type" => "commit_block");
 
                 self.pending_utxos.check_against(&prepared.new_outputs);
+                self.pending_heights
+                    .respond(&prepared.hash, prepared.height);
                 let rsp_rx = self.queue_and_commit_non_finalized(prepared);
 
                 async move {
@@ -584,13 +604,15 @@ impl Service<Request> for StateService {
                 let (rsp_tx, rsp_rx) = oneshot::channel();
 
                 self.pending_utxos.check_against(&finalized.new_outputs);
+                self.pending_heights
+                    .respond(&finalized.hash, finalized.height);
                 self.disk.queue_and_commit_finalized((finalized, rsp_tx));
 
                 async move {
                     rsp_rx
                         .await
                         .expect("sender is not dropped")
-                        .map(Response::Committed)
+                        .map(Response::CommittedFinalized)
                         .map_err(Into::into)
                 }
                 .boxed()
@@ -631,6 +653,17 @@ impl Service<Request> for StateService {
 
                 fut.boxed()
             }
+            Request::AwaitHeight(hash) => {
+                metrics::counter!("state.requests", 1, "type" => "await_height");
+
+                let fut = self.pending_heights.queue(hash);
+
+                if let Some(height) = self.any_or_queued_height(hash) {
+                    self.pending_heights.respond(&hash, height);
+                }
+
+                fut.boxed()
+            }
             Request::FindBlockHashes { known_blocks, stop } => {
                 const MAX_FIND_BLOCK_HASHES_RESULTS: usize = 500;
                 let res =
@@ -182,7 +182,7 @@ impl AdjustedDifficulty {
                 Network::Testnet,
                 "invalid network: the minimum difficulty rule only applies on testnet"
             );
-            ExpandedDifficulty::target_difficulty_limit(self.network).to_compact()
+            self.network.pow_limit_compact()
         } else {
             self.threshold_bits()
         }
@@ -204,10 +204,7 @@ impl AdjustedDifficulty {
 
         let threshold = (self.mean_target_difficulty() / averaging_window_timespan.num_seconds())
             * self.median_timespan_bounded().num_seconds();
-        let threshold = min(
-            ExpandedDifficulty::target_difficulty_limit(self.network),
-            threshold,
-        );
+        let threshold = min(self.network.pow_limit(), threshold);
 
         threshold.to_compact()
     }
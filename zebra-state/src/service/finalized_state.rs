@@ -2,21 +2,139 @@
 
 mod disk_format;
 
-use std::{collections::HashMap, convert::TryInto, sync::Arc};
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    future::Future,
+    ops::Deref,
+    sync::Arc,
+};
 
-use zebra_chain::transparent;
+use bytes::BytesMut;
+use tokio::sync::oneshot;
+use zebra_chain::transparent::{self, ScriptType};
 use zebra_chain::{
+    amount::{Amount, CheckedSum, NonNegative},
     block::{self, Block},
-    parameters::{Network, GENESIS_PREVIOUS_BLOCK_HASH},
+    parameters::{genesis_block, Network, GENESIS_PREVIOUS_BLOCK_HASH},
+    serialization::BitcoinDeserialize,
     transaction::{self, Transaction},
+    work::difficulty::Work,
 };
+use zebra_network::message::{GetBlocks, GetHeaders, InventoryHash, Message};
 
-use crate::{BoxError, Config, FinalizedBlock, HashOrHeight, Utxo};
+use crate::{
+    constants::{MAX_GETBLOCKS_HASHES, MAX_GETHEADERS_HEADERS},
+    BoxError, Config, Durability, FinalizedBlock, HashOrHeight, Utxo,
+};
 
-use self::disk_format::{DiskDeserialize, DiskSerialize, FromDisk, IntoDisk, TransactionLocation};
+use self::disk_format::{
+    DiskDeserialize, DiskSerialize, FromDisk, IntoDisk, SpentOutputs, TransactionLocation,
+};
 
 use super::QueuedFinalized;
 
+/// A summary of the finalized UTXO set, similar to `gettxoutsetinfo`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UtxoSetInfo {
+    /// The number of unspent outputs.
+    pub count: usize,
+    /// The total value of all unspent outputs.
+    pub total_value: Amount<NonNegative>,
+    /// The number of pay-to-public-key outputs.
+    pub p2pk: usize,
+    /// The number of pay-to-public-key-hash outputs.
+    pub p2pkh: usize,
+    /// The number of pay-to-script-hash outputs.
+    pub p2sh: usize,
+    /// The number of pay-to-witness-public-key-hash outputs.
+    pub p2wpkh: usize,
+    /// The number of pay-to-witness-script-hash outputs.
+    pub p2wsh: usize,
+    /// The number of outputs with any other script.
+    pub other: usize,
+}
+
+/// The response to a successfully committed queued finalized block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FinalizedCommit {
+    /// The hash of the committed block.
+    pub hash: block::Hash,
+    /// The finalized tip after committing the block, and any queued
+    /// descendants that were committed along with it.
+    pub tip: (block::Height, block::Hash),
+    /// The number of blocks that were committed together, including this one.
+    pub committed_count: usize,
+}
+
+/// The channel used to respond to a queued finalized block.
+type CommitResponder = oneshot::Sender<Result<FinalizedCommit, BoxError>>;
+
+/// The column families in the finalized state database.
+const COLUMN_FAMILIES: &[&str] = &[
+    "hash_by_height",
+    "height_by_hash",
+    "block_by_height",
+    "header_by_height",
+    "tx_by_hash",
+    "utxo_by_outpoint",
+    "undo_by_height",
+    "chain_work_by_height",
+    "sprout_nullifiers",
+    "sapling_nullifiers",
+];
+
+/// A rocksdb database, which is deleted when it is dropped if it is ephemeral.
+///
+/// The database is shared between the finalized state and the futures
+/// returned by its async read methods, so it is only deleted once all of them
+/// have been dropped.
+struct DiskDb {
+    db: rocksdb::DB,
+    ephemeral: bool,
+}
+
+impl DiskDb {
+    /// If the database is `ephemeral`, delete it.
+    fn delete_ephemeral(&self) {
+        if self.ephemeral {
+            let path = self.db.path();
+            tracing::debug!("removing temporary database files {:?}", path);
+            // We'd like to use `rocksdb::Env::mem_env` for ephemeral databases,
+            // but the Zcash blockchain might not fit in memory. So we just
+            // delete the database files instead.
+            //
+            // We'd like to call `DB::destroy` here, but calling destroy on a
+            // live DB is undefined behaviour:
+            // https://github.com/facebook/rocksdb/wiki/RocksDB-FAQ#basic-readwrite
+            //
+            // So we assume that all the database files are under `path`, and
+            // delete them using standard filesystem APIs. Deleting open files
+            // might cause errors on non-Unix platforms, so we ignore the result.
+            // (The OS will delete them eventually anyway.)
+            let _res = std::fs::remove_dir_all(path);
+        }
+    }
+}
+
+impl Deref for DiskDb {
+    type Target = rocksdb::DB;
+
+    fn deref(&self) -> &rocksdb::DB {
+        &self.db
+    }
+}
+
+// Drop isn't guaranteed to run, such as when we panic, or if someone stored
+// their FinalizedState in a static, but it should be fine if we don't clean
+// this up since the files are placed in the os temp dir and should be cleaned
+// up automatically eventually.
+impl Drop for DiskDb {
+    fn drop(&mut self) {
+        self.delete_ephemeral()
+    }
+}
+
 /// The finalized part of the chain state, stored in the db.
 pub struct FinalizedState {
     /// Queued blocks that arrived out of order, indexed by their parent block hash.
@@ -27,24 +145,24 @@ pub struct FinalizedState {
     /// as a break in the graph.
     max_queued_height: f64,
 
-    db: rocksdb::DB,
-    ephemeral: bool,
+    /// The database, shared with the futures returned by the async read methods.
+    db: Arc<DiskDb>,
+    /// The network that this state stores blocks for.
+    network: Network,
     /// Commit blocks to the finalized state up to this height, then exit Zebra.
     debug_stop_at_height: Option<block::Height>,
+    /// How often committed blocks are synced to disk.
+    durability: Durability,
+    /// The number of blocks committed since the last synced commit.
+    unsynced_blocks: u32,
 }
 
 impl FinalizedState {
     pub fn new(config: &Config, network: Network) -> Self {
         let (path, db_options) = config.db_config(network);
-        let column_families = vec![
-            rocksdb::ColumnFamilyDescriptor::new("hash_by_height", db_options.clone()),
-            rocksdb::ColumnFamilyDescriptor::new("height_by_hash", db_options.clone()),
-            rocksdb::ColumnFamilyDescriptor::new("block_by_height", db_options.clone()),
-            rocksdb::ColumnFamilyDescriptor::new("tx_by_hash", db_options.clone()),
-            rocksdb::ColumnFamilyDescriptor::new("utxo_by_outpoint", db_options.clone()),
-            rocksdb::ColumnFamilyDescriptor::new("sprout_nullifiers", db_options.clone()),
-            rocksdb::ColumnFamilyDescriptor::new("sapling_nullifiers", db_options.clone()),
-        ];
+        let column_families = COLUMN_FAMILIES
+            .iter()
+            .map(|name| rocksdb::ColumnFamilyDescriptor::new(*name, db_options.clone()));
         let db_result = rocksdb::DB::open_cf_descriptors(&db_options, &path, column_families);
 
         let db = match db_result {
@@ -64,9 +182,14 @@ impl FinalizedState {
         let new_state = Self {
             queued_by_prev_hash: HashMap::new(),
             max_queued_height: f64::NAN,
-            db,
-            ephemeral: config.ephemeral,
+            db: Arc::new(DiskDb {
+                db,
+                ephemeral: config.ephemeral,
+            }),
+            network,
             debug_stop_at_height: config.debug_stop_at_height.map(block::Height),
+            durability: config.durability,
+            unsynced_blocks: 0,
         };
 
         if let Some(tip_height) = new_state.finalized_tip_height() {
@@ -127,12 +250,26 @@ impl FinalizedState {
         let height = queued.0.height;
         self.queued_by_prev_hash.insert(prev_hash, queued);
 
+        let mut committed = Vec::new();
         while let Some(queued_block) = self.queued_by_prev_hash.remove(&self.finalized_tip_hash()) {
-            self.commit_finalized(queued_block);
+            committed.extend(self.commit_finalized(queued_block));
             metrics::counter!("state.finalized.committed.block.count", 1);
             metrics::gauge!("state.finalized.committed.block.height", height.0 as _);
         }
 
+        // Respond once the cascade is finished, so every response has the new tip
+        if !committed.is_empty() {
+            let tip = self.tip().expect("blocks were just committed");
+            let committed_count = committed.len();
+            for (hash, rsp_tx) in committed {
+                let _ = rsp_tx.send(Ok(FinalizedCommit {
+                    hash,
+                    tip,
+                    committed_count,
+                }));
+            }
+        }
+
         if self.queued_by_prev_hash.is_empty() {
             self.max_queued_height = f64::NAN;
         } else if self.max_queued_height.is_nan() || self.max_queued_height < height.0 as _ {
@@ -151,6 +288,19 @@ impl FinalizedState {
         );
     }
 
+    /// Commit the genesis block for this state's network, if the state is
+    /// empty.
+    ///
+    /// Returns the hash of the block at height 0.
+    pub fn ensure_genesis(&mut self) -> Result<block::Hash, BoxError> {
+        if let Some(genesis_hash) = self.hash(block::Height(0)) {
+            return Ok(genesis_hash);
+        }
+
+        let genesis = genesis_block(self.network);
+        self.commit_finalized_direct(FinalizedBlock::with_height(genesis, block::Height(0)))
+    }
+
     /// Returns the hash of the current finalized tip block.
     pub fn finalized_tip_hash(&self) -> block::Hash {
         self.tip()
@@ -179,19 +329,10 @@ impl FinalizedState {
     ) -> Result<block::Hash, BoxError> {
         block_precommit_metrics(&finalized);
 
-        let FinalizedBlock {
-            block,
-            hash,
-            height,
-            new_outputs,
-            transaction_hashes,
-        } = finalized;
-
+        let hash = finalized.hash;
+        let height = finalized.height;
+        let block = &finalized.block;
         let hash_by_height = self.db.cf_handle("hash_by_height").unwrap();
-        let height_by_hash = self.db.cf_handle("height_by_hash").unwrap();
-        let block_by_height = self.db.cf_handle("block_by_height").unwrap();
-        let tx_by_hash = self.db.cf_handle("tx_by_hash").unwrap();
-        let utxo_by_outpoint = self.db.cf_handle("utxo_by_outpoint").unwrap();
 
         // Assert that callers (including unit tests) get the chain order correct
         if self.is_empty(hash_by_height) {
@@ -221,84 +362,238 @@ impl FinalizedState {
             );
         }
 
-        // We use a closure so we can use an early return for control flow in
-        // the genesis case
-        let prepare_commit = || -> rocksdb::WriteBatch {
-            let mut batch = rocksdb::WriteBatch::default();
+        let parent_work = self.tip_work()?;
+        let mut batch = rocksdb::WriteBatch::default();
+        self.prepare_commit(&mut batch, finalized, &mut HashMap::new(), parent_work);
+
+        // Syncing a write also syncs all the writes before it
+        let sync = match self.durability {
+            Durability::EveryBlock => true,
+            Durability::Periodic(interval) => self.unsynced_blocks + 1 >= interval,
+            Durability::Never => false,
+        };
+        let mut write_options = rocksdb::WriteOptions::default();
+        write_options.set_sync(sync);
+
+        let result = self.db.write_opt(batch, &write_options).map(|()| hash);
+        if result.is_ok() {
+            self.unsynced_blocks = if sync { 0 } else { self.unsynced_blocks + 1 };
+        }
+
+        if result.is_ok() && self.is_at_stop_height(height) {
+            tracing::info!(?height, ?hash, "stopping at configured height");
+            // We'd like to drop the database here, because that closes the
+            // column families and the database. But Rust's ownership rules
+            // make that difficult, so we just flush instead.
+            self.flush().expect("flush is successful");
+            self.db.delete_ephemeral();
+            std::process::exit(0);
+        }
 
-            // Index the block
-            batch.zs_insert(hash_by_height, height, hash);
-            batch.zs_insert(height_by_hash, hash, height);
-            batch.zs_insert(block_by_height, height, &block);
+        result.map_err(Into::into)
+    }
 
-            // TODO: sprout and sapling anchors (per block)
+    /// Commit `blocks` to the finalized state in a single database write,
+    /// returning the new finalized tip.
+    ///
+    /// The first block must be a child of the finalized tip, or a genesis
+    /// block if the state is empty, and each later block must be a child of
+    /// the one before it. If the blocks don't form a chain, or the write
+    /// fails, the state is unchanged and the error is returned.
+    ///
+    /// This is faster than committing the blocks one at a time during
+    /// initial sync, because all their index updates are written together.
+    ///
+    /// If the configured stop height is inside the batch, only the blocks up
+    /// to the stop height are committed, then Zebra exits.
+    pub fn batch_commit(
+        &mut self,
+        mut blocks: Vec<Arc<Block>>,
+    ) -> Result<(block::Height, block::Hash), BoxError> {
+        let first_height = match self.tip() {
+            Some((height, _)) => block::Height(height.0 + 1),
+            None => block::Height(0),
+        };
 
-            // Consensus-critical bug in zcashd: transactions in the
-            // genesis block are ignored.
-            if block.header.previous_block_hash == block::Hash([0; 32]) {
-                return batch;
+        // Don't commit any blocks above the stop height
+        if let Some(stop_height) = self.debug_stop_at_height {
+            if stop_height >= first_height {
+                blocks.truncate((stop_height.0 - first_height.0) as usize + 1);
             }
+        }
+        let tip_hash = blocks
+            .last()
+            .ok_or("batch must contain at least one block")?
+            .hash();
+        let block_count = blocks.len() as u32;
+        let tip_height = block::Height(first_height.0 + block_count - 1);
 
-            // Index all new transparent outputs
-            for (outpoint, utxo) in new_outputs.into_iter() {
-                batch.zs_insert(utxo_by_outpoint, outpoint, utxo);
+        // Check the chain before changing the state
+        let mut parent_hash = self.finalized_tip_hash();
+        for block in blocks.iter() {
+            if block.header.previous_block_hash != parent_hash {
+                return Err("batch blocks must form a chain from the finalized tip".into());
             }
+            parent_hash = block.hash();
+        }
 
-            // Index each transaction, spent inputs, nullifiers
-            // TODO: move computation into FinalizedBlock as with transparent outputs
-            for (transaction_index, (transaction, transaction_hash)) in block
-                .transactions
-                .iter()
-                .zip(transaction_hashes.into_iter())
-                .enumerate()
-            {
-                let transaction_location = TransactionLocation {
-                    height,
-                    index: transaction_index
-                        .try_into()
-                        .expect("no more than 4 billion transactions per block"),
-                };
-                batch.zs_insert(tx_by_hash, transaction_hash, transaction_location);
-
-                // Mark all transparent inputs as spent
-                for input in transaction.inputs.iter() {
-                    match input {
-                        transparent::Input::PrevOut { outpoint, .. } => {
-                            batch.delete_cf(utxo_by_outpoint, outpoint.as_bytes());
-                        }
-                        // Coinbase inputs represent new coins,
-                        // so there are no UTXOs to mark as spent.
-                        transparent::Input::Coinbase { .. } => {}
-                    }
-                }
+        let mut chain_work = self.tip_work()?;
+        let mut batch = rocksdb::WriteBatch::default();
+        let mut batch_utxos = HashMap::new();
+        for (height, block) in (first_height.0..).zip(blocks) {
+            let finalized = FinalizedBlock::with_height(block, block::Height(height));
+            block_precommit_metrics(&finalized);
+            chain_work = self.prepare_commit(&mut batch, finalized, &mut batch_utxos, chain_work);
+        }
 
-                // // Mark sprout and sapling nullifiers as spent
-                // for sprout_nullifier in transaction.sprout_nullifiers() {
-                //     batch.zs_insert(sprout_nullifiers, sprout_nullifier, ());
-                // }
-                // for sapling_nullifier in transaction.sapling_nullifiers() {
-                //     batch.zs_insert(sapling_nullifiers, sapling_nullifier, ());
-                // }
-            }
+        // Syncing a write also syncs all the writes before it
+        let sync = match self.durability {
+            Durability::EveryBlock => true,
+            Durability::Periodic(interval) => self.unsynced_blocks + block_count >= interval,
+            Durability::Never => false,
+        };
+        let mut write_options = rocksdb::WriteOptions::default();
+        write_options.set_sync(sync);
 
-            batch
+        self.db.write_opt(batch, &write_options)?;
+        self.unsynced_blocks = if sync {
+            0
+        } else {
+            self.unsynced_blocks + block_count
         };
 
-        let batch = prepare_commit();
+        if self.is_at_stop_height(tip_height) {
+            tracing::info!(?tip_height, ?tip_hash, "stopping at configured height");
+            self.flush().expect("flush is successful");
+            self.db.delete_ephemeral();
+            std::process::exit(0);
+        }
 
-        let result = self.db.write(batch).map(|()| hash);
+        Ok((tip_height, tip_hash))
+    }
 
-        if result.is_ok() && self.is_at_stop_height(height) {
-            tracing::info!(?height, ?hash, "stopping at configured height");
-            // We'd like to drop the database here, because that closes the
-            // column families and the database. But Rust's ownership rules
-            // make that difficult, so we just flush instead.
-            self.db.flush().expect("flush is successful");
-            self.delete_ephemeral();
-            std::process::exit(0);
+    /// Add the database writes that commit `finalized` to `batch`.
+    ///
+    /// `batch_utxos` holds the spendable outputs created by earlier blocks in
+    /// `batch`, which aren't in the database yet. The outputs created by
+    /// `finalized` are added to it.
+    ///
+    /// `parent_work` is the cumulative chain work up to the parent block.
+    /// Returns the cumulative chain work including `finalized`.
+    fn prepare_commit(
+        &self,
+        batch: &mut rocksdb::WriteBatch,
+        finalized: FinalizedBlock,
+        batch_utxos: &mut HashMap<transparent::OutPoint, Utxo>,
+        parent_work: Work,
+    ) -> Work {
+        let FinalizedBlock {
+            block,
+            hash,
+            height,
+            new_outputs,
+            transaction_hashes,
+        } = finalized;
+
+        let hash_by_height = self.db.cf_handle("hash_by_height").unwrap();
+        let height_by_hash = self.db.cf_handle("height_by_hash").unwrap();
+        let block_by_height = self.db.cf_handle("block_by_height").unwrap();
+        let header_by_height = self.db.cf_handle("header_by_height").unwrap();
+        let tx_by_hash = self.db.cf_handle("tx_by_hash").unwrap();
+        let utxo_by_outpoint = self.db.cf_handle("utxo_by_outpoint").unwrap();
+        let undo_by_height = self.db.cf_handle("undo_by_height").unwrap();
+        let chain_work_by_height = self.db.cf_handle("chain_work_by_height").unwrap();
+
+        let block_work = block
+            .header
+            .difficulty_threshold
+            .to_work()
+            .expect("work has already been validated");
+        let chain_work = parent_work
+            .checked_add(block_work)
+            .expect("Work values do not overflow");
+
+        // Index the block
+        batch.zs_insert(hash_by_height, height, hash);
+        batch.zs_insert(height_by_hash, hash, height);
+        batch.zs_insert(block_by_height, height, &block);
+        batch.zs_insert(header_by_height, height, &block.header);
+        batch.zs_insert(chain_work_by_height, height, chain_work);
+
+        // TODO: sprout and sapling anchors (per block)
+
+        // Consensus-critical bug in zcashd: transactions in the
+        // genesis block are ignored.
+        if block.header.previous_block_hash == block::Hash([0; 32]) {
+            return chain_work;
         }
 
-        result.map_err(Into::into)
+        // Record the outputs spent by this block, so it can be reverted.
+        // Outputs can be spent by a later transaction in the same block.
+        let spent = block
+            .transactions
+            .iter()
+            .flat_map(|transaction| transaction.inputs().iter())
+            .filter_map(|input| match input {
+                transparent::Input::PrevOut { outpoint, .. } => {
+                    let utxo = new_outputs
+                        .get(outpoint)
+                        .or_else(|| batch_utxos.get(outpoint))
+                        .cloned()
+                        .or_else(|| self.utxo(outpoint))?;
+                    Some((*outpoint, utxo))
+                }
+                transparent::Input::Coinbase { .. } => None,
+            })
+            .collect();
+        batch.zs_insert(undo_by_height, height, SpentOutputs(spent));
+
+        // Index all new transparent outputs that can be spent
+        for (outpoint, utxo) in new_outputs.into_iter() {
+            if !utxo.output.lock_script.is_provably_unspendable() {
+                batch.zs_insert(utxo_by_outpoint, outpoint, &utxo);
+                batch_utxos.insert(outpoint, utxo);
+            }
+        }
+
+        // Index each transaction, spent inputs, nullifiers
+        // TODO: move computation into FinalizedBlock as with transparent outputs
+        for (transaction_index, (transaction, transaction_hash)) in block
+            .transactions
+            .iter()
+            .zip(transaction_hashes.into_iter())
+            .enumerate()
+        {
+            let transaction_location = TransactionLocation {
+                height,
+                index: transaction_index
+                    .try_into()
+                    .expect("no more than 4 billion transactions per block"),
+            };
+            batch.zs_insert(tx_by_hash, transaction_hash, transaction_location);
+
+            // Mark all transparent inputs as spent
+            for input in transaction.inputs().iter() {
+                match input {
+                    transparent::Input::PrevOut { outpoint, .. } => {
+                        batch.delete_cf(utxo_by_outpoint, outpoint.as_bytes());
+                    }
+                    // Coinbase inputs represent new coins,
+                    // so there are no UTXOs to mark as spent.
+                    transparent::Input::Coinbase { .. } => {}
+                }
+            }
+
+            // // Mark sprout and sapling nullifiers as spent
+            // for sprout_nullifier in transaction.sprout_nullifiers() {
+            //     batch.zs_insert(sprout_nullifiers, sprout_nullifier, ());
+            // }
+            // for sapling_nullifier in transaction.sapling_nullifiers() {
+            //     batch.zs_insert(sapling_nullifiers, sapling_nullifier, ());
+            // }
+        }
+
+        chain_work
     }
 
     /// Commit a finalized block to the state.
@@ -307,24 +602,168 @@ impl FinalizedState {
     /// order. This function is called by [`queue`], which ensures order.
     /// It is intentionally not exposed as part of the public API of the
     /// [`FinalizedState`].
-    fn commit_finalized(&mut self, queued_block: QueuedFinalized) {
+    ///
+    /// Returns the committed hash and the response channel, so the caller can
+    /// respond after any queued descendants have also been committed. If the
+    /// commit fails, responds with the error immediately, and returns `None`.
+    fn commit_finalized(
+        &mut self,
+        queued_block: QueuedFinalized,
+    ) -> Option<(block::Hash, CommitResponder)> {
         let (finalized, rsp_tx) = queued_block;
-        let result = self.commit_finalized_direct(finalized);
-        let _ = rsp_tx.send(result.map_err(Into::into));
+        match self.commit_finalized_direct(finalized) {
+            Ok(hash) => Some((hash, rsp_tx)),
+            Err(error) => {
+                let _ = rsp_tx.send(Err(error));
+                None
+            }
+        }
+    }
+
+    /// Replace the finalized blocks above `disconnect_to` with `connect`,
+    /// returning the new finalized tip.
+    ///
+    /// The first block in `connect` must be a child of the block at
+    /// `disconnect_to`, and each later block must be a child of the one
+    /// before it. The old blocks are disconnected in a single database write.
+    /// If any new block fails to commit, the new blocks are disconnected, the
+    /// old blocks are restored, and the error is returned.
+    pub fn reorg(
+        &mut self,
+        disconnect_to: block::Height,
+        connect: Vec<Arc<Block>>,
+    ) -> Result<(block::Height, block::Hash), BoxError> {
+        let fork_hash = self
+            .hash(disconnect_to)
+            .ok_or("reorg fork point is not in the finalized state")?;
+
+        // Check the new chain before changing the state
+        let mut parent_hash = fork_hash;
+        for block in connect.iter() {
+            if block.header.previous_block_hash != parent_hash {
+                return Err("reorg blocks must form a chain from the fork point".into());
+            }
+            parent_hash = block.hash();
+        }
+
+        let (tip_height, _) = self.tip().expect("state has the fork point block");
+        let disconnected = (disconnect_to.0 + 1..=tip_height.0)
+            .map(|height| {
+                self.block(block::Height(height).into())
+                    .ok_or("can't disconnect a block with a pruned body")
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.revert_finalized(disconnect_to)?;
+        if let Err(error) = self.connect_after(disconnect_to, connect) {
+            tracing::warn!(
+                ?error,
+                ?disconnect_to,
+                "reorg failed, restoring the old chain"
+            );
+            self.revert_finalized(disconnect_to)
+                .expect("disconnecting blocks that were just connected succeeds");
+            self.connect_after(disconnect_to, disconnected)
+                .expect("reconnecting blocks that were just disconnected succeeds");
+            return Err(error);
+        }
+
+        Ok(self.tip().expect("state has the fork point block"))
+    }
+
+    /// Commit `blocks` in order, starting with a child of the finalized tip
+    /// at `parent_height`.
+    fn connect_after(
+        &mut self,
+        parent_height: block::Height,
+        blocks: Vec<Arc<Block>>,
+    ) -> Result<(), BoxError> {
+        for (height, block) in (parent_height.0 + 1..).zip(blocks) {
+            self.commit_finalized_direct(FinalizedBlock::with_height(
+                block,
+                block::Height(height),
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Revert every finalized block above `height`, in a single database
+    /// write.
+    ///
+    /// Outputs created by the reverted blocks are removed, and the outputs
+    /// they spent are restored from `undo_by_height`.
+    pub fn revert_finalized(&mut self, height: block::Height) -> Result<(), BoxError> {
+        let (tip_height, _) = match self.tip() {
+            Some(tip) => tip,
+            None => return Ok(()),
+        };
+
+        let hash_by_height = self.db.cf_handle("hash_by_height").unwrap();
+        let height_by_hash = self.db.cf_handle("height_by_hash").unwrap();
+        let block_by_height = self.db.cf_handle("block_by_height").unwrap();
+        let header_by_height = self.db.cf_handle("header_by_height").unwrap();
+        let tx_by_hash = self.db.cf_handle("tx_by_hash").unwrap();
+        let utxo_by_outpoint = self.db.cf_handle("utxo_by_outpoint").unwrap();
+        let undo_by_height = self.db.cf_handle("undo_by_height").unwrap();
+        let chain_work_by_height = self.db.cf_handle("chain_work_by_height").unwrap();
+
+        let mut batch = rocksdb::WriteBatch::default();
+
+        // Revert blocks in reverse order, and restore spent outputs before
+        // removing created outputs, so outputs that were created and spent
+        // within the reverted blocks end up deleted.
+        for revert_height in (height.0 + 1..=tip_height.0).rev() {
+            let revert_height = block::Height(revert_height);
+            let block = self
+                .block(revert_height.into())
+                .ok_or("can't revert a block with a pruned body")?;
+            let SpentOutputs(spent) = self
+                .db
+                .zs_get(undo_by_height, &revert_height)
+                .ok_or("can't revert a block without undo data")?;
+
+            batch.delete_cf(hash_by_height, revert_height.as_bytes());
+            batch.delete_cf(height_by_hash, block.hash().as_bytes());
+            batch.delete_cf(block_by_height, revert_height.as_bytes());
+            batch.delete_cf(header_by_height, revert_height.as_bytes());
+            batch.delete_cf(undo_by_height, revert_height.as_bytes());
+            batch.delete_cf(chain_work_by_height, revert_height.as_bytes());
+
+            for (outpoint, utxo) in spent {
+                batch.zs_insert(utxo_by_outpoint, outpoint, utxo);
+            }
+
+            for transaction in block.transactions.iter() {
+                let transaction_hash = transaction.hash();
+                batch.delete_cf(tx_by_hash, transaction_hash.as_bytes());
+
+                for index in 0..transaction.outputs().len() {
+                    let outpoint = transparent::OutPoint {
+                        hash: transaction_hash,
+                        index: index as u32,
+                    };
+                    batch.delete_cf(utxo_by_outpoint, outpoint.as_bytes());
+                }
+            }
+        }
+
+        self.db.write(batch)?;
+
+        Ok(())
     }
 
     /// Returns the tip height and hash if there is one.
     pub fn tip(&self) -> Option<(block::Height, block::Hash)> {
-        let hash_by_height = self.db.cf_handle("hash_by_height").unwrap();
-        self.db
-            .iterator_cf(hash_by_height, rocksdb::IteratorMode::End)
-            .next()
-            .map(|(height_bytes, hash_bytes)| {
-                let height = block::Height::from_bytes(height_bytes);
-                let hash = block::Hash::from_bytes(hash_bytes);
+        read_tip(&self.db)
+    }
 
-                (height, hash)
-            })
+    /// Returns a future that resolves to the tip height and hash, if there is one.
+    ///
+    /// The future owns a reference to the database, so it can outlive `self`.
+    pub fn tip_async(&self) -> impl Future<Output = Option<(block::Height, block::Hash)>> {
+        let db = self.db.clone();
+        async move { read_tip(&db) }
     }
 
     /// Returns the height of the given block if it exists.
@@ -333,20 +772,196 @@ impl FinalizedState {
         self.db.zs_get(&height_by_hash, &hash)
     }
 
+    /// Returns the height of the given block if it is queued to be committed.
+    pub fn queued_height(&self, hash: block::Hash) -> Option<block::Height> {
+        self.queued_by_prev_hash
+            .values()
+            .map(|(finalized, _)| finalized)
+            .find(|finalized| finalized.hash == hash)
+            .map(|finalized| finalized.height)
+    }
+
+    /// Returns the number of blocks between the finalized tip and the block
+    /// with `hash`, or `None` if that block isn't in the finalized state.
+    ///
+    /// The tip has a depth of zero.
+    pub fn depth(&self, hash: block::Hash) -> Option<u32> {
+        let (tip_height, _) = self.tip()?;
+        let height = self.height(hash)?;
+
+        tip_height.checked_sub(height.0).map(|depth| depth.0)
+    }
+
     /// Returns the given block if it exists.
+    ///
+    /// Returns `None` if the block body has been pruned, see
+    /// [`FinalizedState::prune_bodies_below`].
     pub fn block(&self, hash_or_height: HashOrHeight) -> Option<Arc<Block>> {
+        read_block(&self.db, hash_or_height)
+    }
+
+    /// Returns the header of the given block if it exists.
+    ///
+    /// Headers are kept when block bodies are pruned.
+    pub fn header(&self, hash_or_height: HashOrHeight) -> Option<block::Header> {
         let height_by_hash = self.db.cf_handle("height_by_hash").unwrap();
-        let block_by_height = self.db.cf_handle("block_by_height").unwrap();
+        let header_by_height = self.db.cf_handle("header_by_height").unwrap();
         let height = hash_or_height.height_or_else(|hash| self.db.zs_get(height_by_hash, &hash))?;
 
-        self.db.zs_get(block_by_height, &height)
+        self.db.zs_get(header_by_height, &height)
+    }
+
+    /// Returns the height and header of the finalized block with `hash`, if it
+    /// exists.
+    ///
+    /// Reads the `height_by_hash` and `header_by_height` column families,
+    /// without reading the block body. Returns an error if the database read
+    /// fails, or the stored header doesn't deserialize.
+    pub fn header_and_height(
+        &self,
+        hash: &block::Hash,
+    ) -> Result<Option<(block::Height, block::Header)>, BoxError> {
+        let height_by_hash = self.db.cf_handle("height_by_hash").unwrap();
+        let header_by_height = self.db.cf_handle("header_by_height").unwrap();
+
+        let height = match self.db.get_pinned_cf(height_by_hash, hash.as_bytes())? {
+            Some(height_bytes) => block::Height::from_bytes(height_bytes),
+            None => return Ok(None),
+        };
+        let header = match self.db.get_pinned_cf(header_by_height, height.as_bytes())? {
+            Some(header_bytes) => block::Header::bitcoin_deserialize(&header_bytes[..])?,
+            None => return Ok(None),
+        };
+
+        Ok(Some((height, header)))
+    }
+
+    /// Delete the bodies and undo data of all finalized blocks below
+    /// `height`, returning the number of bodies that were removed.
+    ///
+    /// Headers, hashes, and heights are kept, so locators can still be built
+    /// and headers served. Transactions in pruned blocks can no longer be
+    /// looked up.
+    ///
+    /// Reverting a block needs its body and undo data, so after pruning,
+    /// [`FinalizedState::revert_finalized`] can't revert below `height`.
+    pub fn prune_bodies_below(&mut self, height: block::Height) -> Result<usize, BoxError> {
+        let block_by_height = self.db.cf_handle("block_by_height").unwrap();
+        let undo_by_height = self.db.cf_handle("undo_by_height").unwrap();
+
+        let mut batch = rocksdb::WriteBatch::default();
+        let mut pruned = 0;
+        for (height_bytes, _) in self
+            .db
+            .iterator_cf(block_by_height, rocksdb::IteratorMode::Start)
+            .take_while(|(height_bytes, _)| block::Height::from_bytes(height_bytes) < height)
+        {
+            batch.delete_cf(block_by_height, height_bytes);
+            pruned += 1;
+        }
+        for (height_bytes, _) in self
+            .db
+            .iterator_cf(undo_by_height, rocksdb::IteratorMode::Start)
+            .take_while(|(height_bytes, _)| block::Height::from_bytes(height_bytes) < height)
+        {
+            batch.delete_cf(undo_by_height, height_bytes);
+        }
+
+        self.db.write(batch)?;
+
+        Ok(pruned)
+    }
+
+    /// Returns a future that resolves to the given block, if it exists.
+    ///
+    /// The future owns a reference to the database, so it can outlive `self`.
+    pub fn block_async(
+        &self,
+        hash_or_height: HashOrHeight,
+    ) -> impl Future<Output = Option<Arc<Block>>> {
+        let db = self.db.clone();
+        async move { read_block(&db, hash_or_height) }
+    }
+
+    /// Returns an iterator over every finalized block body, in ascending
+    /// height order, from genesis to the tip.
+    ///
+    /// Blocks are read directly from the `block_by_height` column family, and
+    /// only deserialized as the iterator is advanced. The iterator reads a
+    /// snapshot of the state, so it ends at the tip when it was created.
+    /// Bodies removed by [`FinalizedState::prune_bodies_below`] are skipped.
+    pub fn iter_blocks(
+        &self,
+    ) -> impl Iterator<Item = Result<(block::Height, Arc<Block>), BoxError>> + '_ {
+        let block_by_height = self.db.cf_handle("block_by_height").unwrap();
+
+        self.db
+            .iterator_cf(block_by_height, rocksdb::IteratorMode::Start)
+            .map(|(height_bytes, block_bytes)| {
+                let height = block::Height::from_bytes(height_bytes);
+                let block = Block::deserialize_from_buf(&mut BytesMut::from(&block_bytes[..]))?;
+                Ok((height, Arc::new(block)))
+            })
     }
 
     /// Returns the `transparent::Output` pointed to by the given
     /// `transparent::OutPoint` if it is present.
     pub fn utxo(&self, outpoint: &transparent::OutPoint) -> Option<Utxo> {
+        read_utxo(&self.db, outpoint)
+    }
+
+    /// Returns a future that resolves to the `transparent::Output` pointed to
+    /// by the given `transparent::OutPoint`, if it is present.
+    ///
+    /// The future owns a reference to the database, so it can outlive `self`.
+    pub fn utxo_async(
+        &self,
+        outpoint: transparent::OutPoint,
+    ) -> impl Future<Output = Option<Utxo>> {
+        let db = self.db.clone();
+        async move { read_utxo(&db, &outpoint) }
+    }
+
+    /// Summarize the finalized UTXO set, by iterating over every unspent output.
+    ///
+    /// This reads the entire `utxo_by_outpoint` column family, so it is slow on
+    /// a large state.
+    pub fn utxo_set_info(&self) -> Result<UtxoSetInfo, BoxError> {
         let utxo_by_outpoint = self.db.cf_handle("utxo_by_outpoint").unwrap();
-        self.db.zs_get(utxo_by_outpoint, outpoint)
+
+        let mut info = UtxoSetInfo {
+            count: 0,
+            total_value: Amount::try_from(0)?,
+            p2pk: 0,
+            p2pkh: 0,
+            p2sh: 0,
+            p2wpkh: 0,
+            p2wsh: 0,
+            other: 0,
+        };
+
+        let values = self
+            .db
+            .iterator_cf(utxo_by_outpoint, rocksdb::IteratorMode::Start)
+            .map(|(_, utxo_bytes)| {
+                let utxo = Utxo::from_bytes(utxo_bytes);
+
+                info.count += 1;
+                match utxo.output.lock_script.classify() {
+                    ScriptType::P2PK => info.p2pk += 1,
+                    ScriptType::P2PKH => info.p2pkh += 1,
+                    ScriptType::P2SH => info.p2sh += 1,
+                    ScriptType::P2WPKH => info.p2wpkh += 1,
+                    ScriptType::P2WSH => info.p2wsh += 1,
+                    ScriptType::OpReturn | ScriptType::NonStandard => info.other += 1,
+                }
+
+                utxo.output.value
+            });
+        let total_value = values.checked_sum()?;
+        info.total_value = total_value;
+
+        Ok(info)
     }
 
     /// Returns the finalized hash for a given `block::Height` if it is present.
@@ -355,50 +970,219 @@ impl FinalizedState {
         self.db.zs_get(hash_by_height, &height)
     }
 
-    /// Returns the given transaction if it exists.
-    pub fn transaction(&self, hash: transaction::Hash) -> Option<Arc<Transaction>> {
-        let tx_by_hash = self.db.cf_handle("tx_by_hash").unwrap();
-        self.db
-            .zs_get(tx_by_hash, &hash)
-            .map(|TransactionLocation { index, height }| {
-                let block = self
-                    .block(height.into())
-                    .expect("block will exist if TransactionLocation does");
+    /// Returns the cumulative chain work up to and including the finalized
+    /// block at `height`, if it is present.
+    pub fn chain_work(&self, height: block::Height) -> Option<Work> {
+        let chain_work_by_height = self.db.cf_handle("chain_work_by_height").unwrap();
+        self.db.zs_get(chain_work_by_height, &height)
+    }
+
+    /// Returns the cumulative chain work of the finalized tip.
+    ///
+    /// Returns zero work if the state is empty, and an error if the tip's
+    /// chain work isn't stored.
+    pub fn tip_work(&self) -> Result<Work, BoxError> {
+        match self.tip() {
+            Some((height, _)) => self
+                .chain_work(height)
+                .ok_or_else(|| "finalized tip has no stored chain work".into()),
+            None => Ok(Work::default()),
+        }
+    }
+
+    /// Returns up to `max` consecutive finalized block hashes, starting at
+    /// `start`.
+    ///
+    /// Stops early at the finalized tip. Returns an empty list if `start` is
+    /// above the tip.
+    ///
+    /// Reads hashes directly from the `hash_by_height` column family, without
+    /// reading any blocks.
+    pub fn block_hashes_in_range(
+        &self,
+        start: block::Height,
+        max: usize,
+    ) -> Result<Vec<block::Hash>, BoxError> {
+        let hash_by_height = self.db.cf_handle("hash_by_height").unwrap();
+        let start_bytes = start.as_bytes();
+
+        let hashes = self
+            .db
+            .iterator_cf(
+                hash_by_height,
+                rocksdb::IteratorMode::From(&start_bytes[..], rocksdb::Direction::Forward),
+            )
+            .take(max)
+            .map(|(_, hash_bytes)| block::Hash::from_bytes(hash_bytes))
+            .collect();
+
+        Ok(hashes)
+    }
+
+    /// Returns the `inv` response to a `getblocks` request.
+    ///
+    /// Finds the first hash in the request's locator that is in the finalized
+    /// chain, then lists up to [`MAX_GETBLOCKS_HASHES`] block hashes after it.
+    /// Like Bitcoin Core, the list ends just before the request's stop hash,
+    /// and starts after genesis if none of the locator hashes are finalized.
+    pub fn respond_getblocks(&self, msg: &GetBlocks) -> Result<Message, BoxError> {
+        let intersection = self.locator_intersection(&msg.block_header_hashes);
+
+        let hashes = match intersection + 1 {
+            Some(start) => self.block_hashes_in_range(start, MAX_GETBLOCKS_HASHES)?,
+            None => Vec::new(),
+        };
+
+        let inventory = hashes
+            .into_iter()
+            .take_while(|hash| Some(*hash) != msg.stop_hash)
+            .map(InventoryHash::Block)
+            .collect();
+
+        Ok(Message::Inv(inventory))
+    }
+
+    /// Returns the `headers` response to a `getheaders` request.
+    ///
+    /// Finds the first hash in the request's locator that is in the finalized
+    /// chain, then lists up to [`MAX_GETHEADERS_HEADERS`] headers after it,
+    /// reading them from the `header_by_height` column family. Like Bitcoin
+    /// Core, the list ends with the request's stop header, and starts after
+    /// genesis if none of the locator hashes are finalized.
+    pub fn respond_getheaders(&self, msg: &GetHeaders) -> Result<Message, BoxError> {
+        let header_by_height = self.db.cf_handle("header_by_height").unwrap();
+        let intersection = self.locator_intersection(&msg.block_header_hashes);
+
+        let start = match intersection + 1 {
+            Some(start) => start,
+            None => return Ok(Message::Headers(Vec::new())),
+        };
+        let start_bytes = start.as_bytes();
+
+        let mut headers = Vec::new();
+        for (_, header_bytes) in self
+            .db
+            .iterator_cf(
+                header_by_height,
+                rocksdb::IteratorMode::From(&start_bytes[..], rocksdb::Direction::Forward),
+            )
+            .take(MAX_GETHEADERS_HEADERS)
+        {
+            let header = block::Header::from_bytes(header_bytes);
+            let is_stop = Some(block::Hash::from(&header)) == msg.stop_hash;
+
+            // Bitcoin `headers` messages always have a zero transaction count
+            headers.push(block::CountedHeader {
+                header,
+                transaction_count: 0,
+            });
+
+            if is_stop {
+                break;
+            }
+        }
+
+        Ok(Message::Headers(headers))
+    }
+
+    /// Returns the height of the first hash in `locator` that is in the
+    /// finalized chain, or the genesis height if there are none.
+    fn locator_intersection(&self, locator: &[block::Hash]) -> block::Height {
+        locator
+            .iter()
+            .find_map(|hash| self.height(*hash))
+            .unwrap_or(block::Height(0))
+    }
+
+    /// Returns a `getheaders` or `getblocks` locator covering the whole
+    /// finalized chain, or an empty list if the finalized state is empty.
+    ///
+    /// The locator starts with the ten most recent hashes, then doubles the
+    /// step back to the next hash each time, and always finishes with the
+    /// genesis hash. Hashes are returned newest-first, as the protocol
+    /// expects. Heights that are missing from the database (for example,
+    /// during a rollback) are skipped.
+    pub fn block_locator(&self) -> Vec<block::Hash> {
+        let tip_height = match self.tip() {
+            Some((height, _)) => height,
+            None => return Vec::new(),
+        };
+
+        crate::util::block_locator_heights(tip_height)
+            .into_iter()
+            .filter_map(|height| self.hash(height))
+            .collect()
+    }
+
+    /// Returns the given transaction if it exists.
+    ///
+    /// Returns `None` if the transaction's block body has been pruned.
+    pub fn transaction(&self, hash: transaction::Hash) -> Option<Arc<Transaction>> {
+        let tx_by_hash = self.db.cf_handle("tx_by_hash").unwrap();
+        self.db
+            .zs_get(tx_by_hash, &hash)
+            .and_then(|TransactionLocation { index, height }| {
+                let block = self.block(height.into())?;
 
-                block.transactions[index as usize].clone()
+                Some(block.transactions[index as usize].clone())
             })
     }
 
-    /// If the database is `ephemeral`, delete it.
-    fn delete_ephemeral(&self) {
-        if self.ephemeral {
-            let path = self.db.path();
-            tracing::debug!("removing temporary database files {:?}", path);
-            // We'd like to use `rocksdb::Env::mem_env` for ephemeral databases,
-            // but the Zcash blockchain might not fit in memory. So we just
-            // delete the database files instead.
-            //
-            // We'd like to call `DB::destroy` here, but calling destroy on a
-            // live DB is undefined behaviour:
-            // https://github.com/facebook/rocksdb/wiki/RocksDB-FAQ#basic-readwrite
-            //
-            // So we assume that all the database files are under `path`, and
-            // delete them using standard filesystem APIs. Deleting open files
-            // might cause errors on non-Unix platforms, so we ignore the result.
-            // (The OS will delete them eventually anyway.)
-            let _res = std::fs::remove_dir_all(path);
+    /// Returns the height of the block containing the given transaction, and
+    /// the transaction's index in that block, if the transaction is finalized.
+    ///
+    /// Unlike [`FinalizedState::transaction`], this works for transactions
+    /// whose block body has been pruned.
+    pub fn transaction_location(
+        &self,
+        hash: transaction::Hash,
+    ) -> Result<Option<(block::Height, u32)>, BoxError> {
+        let tx_by_hash = self.db.cf_handle("tx_by_hash").unwrap();
+        let location = self
+            .db
+            .get_cf(tx_by_hash, hash.as_bytes())?
+            .map(TransactionLocation::from_bytes);
+
+        Ok(location.map(|TransactionLocation { height, index }| (height, index)))
+    }
+
+    /// Flush all committed blocks to disk, regardless of the configured
+    /// [`Durability`].
+    ///
+    /// `DB::flush` only flushes the default column family, so each column
+    /// family is flushed separately.
+    pub fn flush(&self) -> Result<(), BoxError> {
+        for name in COLUMN_FAMILIES {
+            let cf = self.db.cf_handle(name).unwrap();
+            self.db.flush_cf(cf)?;
         }
+        Ok(())
     }
 }
 
-// Drop isn't guaranteed to run, such as when we panic, or if someone stored
-// their FinalizedState in a static, but it should be fine if we don't clean
-// this up since the files are placed in the os temp dir and should be cleaned
-// up automatically eventually.
-impl Drop for FinalizedState {
-    fn drop(&mut self) {
-        self.delete_ephemeral()
-    }
+fn read_tip(db: &rocksdb::DB) -> Option<(block::Height, block::Hash)> {
+    let hash_by_height = db.cf_handle("hash_by_height").unwrap();
+    db.iterator_cf(hash_by_height, rocksdb::IteratorMode::End)
+        .next()
+        .map(|(height_bytes, hash_bytes)| {
+            let height = block::Height::from_bytes(height_bytes);
+            let hash = block::Hash::from_bytes(hash_bytes);
+
+            (height, hash)
+        })
+}
+
+fn read_block(db: &rocksdb::DB, hash_or_height: HashOrHeight) -> Option<Arc<Block>> {
+    let height_by_hash = db.cf_handle("height_by_hash").unwrap();
+    let block_by_height = db.cf_handle("block_by_height").unwrap();
+    let height = hash_or_height.height_or_else(|hash| db.zs_get(height_by_hash, &hash))?;
+
+    db.zs_get(block_by_height, &height)
+}
+
+fn read_utxo(db: &rocksdb::DB, outpoint: &transparent::OutPoint) -> Option<Utxo> {
+    let utxo_by_outpoint = db.cf_handle("utxo_by_outpoint").unwrap();
+    db.zs_get(utxo_by_outpoint, outpoint)
 }
 
 fn block_precommit_metrics(finalized: &FinalizedBlock) {
@@ -408,14 +1192,14 @@ fn block_precommit_metrics(finalized: &FinalizedBlock) {
     let transparent_prevout_count = block
         .transactions
         .iter()
-        .flat_map(|t| t.inputs.iter())
+        .flat_map(|t| t.inputs().iter())
         .count()
         // Each block has a single coinbase input which is not a previous output.
         - 1;
     let transparent_newout_count = block
         .transactions
         .iter()
-        .flat_map(|t| t.outputs.iter())
+        .flat_map(|t| t.outputs().iter())
         .count();
 
     // let sprout_nullifier_count = block
@@ -461,3 +1245,917 @@ fn block_precommit_metrics(finalized: &FinalizedBlock) {
     //     sapling_nullifier_count as u64
     // );
 }
+
+#[cfg(test)]
+mod tests {
+    use std::ops::RangeInclusive;
+
+    use zebra_chain::{amount::COIN, serialization::BitcoinDeserializeInto};
+
+    use super::*;
+
+    /// Commit the mainnet test vectors at `heights` to `state`, returning the
+    /// committed blocks.
+    fn commit_mainnet_vectors(
+        state: &mut FinalizedState,
+        heights: RangeInclusive<u32>,
+    ) -> Result<Vec<Arc<Block>>, BoxError> {
+        let mut blocks = Vec::new();
+        for (&height, block_bytes) in zebra_test::vectors::MAINNET_BLOCKS.range(heights) {
+            let block = block_bytes.bitcoin_deserialize_into::<Arc<Block>>()?;
+            state.commit_finalized_direct(FinalizedBlock::with_height(
+                block.clone(),
+                block::Height(height),
+            ))?;
+            blocks.push(block);
+        }
+
+        Ok(blocks)
+    }
+
+    /// Returns the first coinbase output of `block`.
+    fn coinbase_outpoint(block: &Block) -> transparent::OutPoint {
+        transparent::OutPoint {
+            hash: block.transactions[0].hash(),
+            index: 0,
+        }
+    }
+
+    /// Returns a transaction that spends the first coinbase output of `block`
+    /// into a copy of that output.
+    ///
+    /// The state doesn't check the merkle root or the spend's script, so the
+    /// spend can be added to any later block.
+    fn spend_coinbase(block: &Block) -> Transaction {
+        Transaction::new(
+            1,
+            vec![transparent::Input::PrevOut {
+                outpoint: coinbase_outpoint(block),
+                unlock_script: transparent::Script(Vec::new()),
+                sequence: u32::MAX,
+            }],
+            vec![block.transactions[0].outputs()[0].clone()],
+            transaction::LockTime::Height(block::Height(0)),
+        )
+    }
+
+    #[test]
+    fn utxo_set_info_sums_values() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let mut state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+
+        commit_mainnet_vectors(&mut state, 0..=2)?;
+
+        // Genesis outputs are unspendable, so the state only contains the
+        // 50 BTC pay-to-public-key coinbase outputs from blocks 1 and 2.
+        let info = state.utxo_set_info()?;
+        assert_eq!(info.count, 2);
+        assert_eq!(info.p2pk, 2);
+        assert_eq!(info.other, 0);
+        assert_eq!(
+            info.total_value,
+            Amount::<NonNegative>::try_from(100 * COIN)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ensure_genesis_bootstraps_an_empty_state() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        for &network in &[Network::Mainnet, Network::Testnet] {
+            let mut state = FinalizedState::new(&Config::ephemeral(), network);
+            assert_eq!(state.finalized_tip_hash(), GENESIS_PREVIOUS_BLOCK_HASH);
+
+            let genesis_hash = zebra_chain::parameters::genesis_hash(network);
+            assert_eq!(state.ensure_genesis()?, genesis_hash);
+            assert_eq!(state.tip(), Some((block::Height(0), genesis_hash)));
+
+            // Bootstrapping again doesn't change the state
+            assert_eq!(state.ensure_genesis()?, genesis_hash);
+            assert_eq!(state.tip(), Some((block::Height(0), genesis_hash)));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn chain_work_is_cumulative() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let mut state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+        assert_eq!(state.tip_work()?, Work::default());
+
+        let mut expected_work = Work::default();
+        for (&height, block_bytes) in zebra_test::vectors::MAINNET_BLOCKS.range(0..=10) {
+            let block = block_bytes.bitcoin_deserialize_into::<Arc<Block>>()?;
+            let block_work = block
+                .header
+                .difficulty_threshold
+                .to_work()
+                .expect("test vectors have valid difficulty");
+            expected_work = expected_work
+                .checked_add(block_work)
+                .expect("test vector work doesn't overflow");
+
+            let previous_work = state.tip_work()?;
+            state.commit_finalized_direct(FinalizedBlock::with_height(
+                block,
+                block::Height(height),
+            ))?;
+
+            assert!(state.tip_work()? > previous_work);
+            assert_eq!(state.tip_work()?, expected_work);
+            assert_eq!(state.chain_work(block::Height(height)), Some(expected_work));
+        }
+
+        // Reverting blocks also reverts their work
+        let work_at_5 = state.chain_work(block::Height(5));
+        state.revert_finalized(block::Height(5))?;
+        assert_eq!(Some(state.tip_work()?), work_at_5);
+        assert_eq!(state.chain_work(block::Height(6)), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn iter_blocks_walks_the_chain_in_order() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let mut state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+        assert!(state.iter_blocks().next().is_none());
+
+        let blocks = commit_mainnet_vectors(&mut state, 0..=10)?;
+
+        let iterated = state.iter_blocks().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            iterated,
+            (0..).map(block::Height).zip(blocks).collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn header_and_height_by_hash() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let mut state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+
+        commit_mainnet_vectors(&mut state, 0..=2)?;
+
+        let block =
+            zebra_test::vectors::BLOCK_MAINNET_1_BYTES.bitcoin_deserialize_into::<Block>()?;
+        assert_eq!(
+            state.header_and_height(&block.hash())?,
+            Some((block::Height(1), block.header))
+        );
+
+        let absent = block::Hash([0xab; 32]);
+        assert_eq!(state.header_and_height(&absent)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn block_locator_ends_with_genesis() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let mut state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+        assert_eq!(state.block_locator(), vec![]);
+
+        for height in 0..=10 {
+            commit_mainnet_vectors(&mut state, height..=height)?;
+
+            let locator = state.block_locator();
+            assert_eq!(locator.first(), state.tip().map(|(_, hash)| hash).as_ref());
+            assert_eq!(locator.last(), state.hash(block::Height(0)).as_ref());
+
+            let heights: Vec<_> = locator
+                .iter()
+                .map(|hash| state.height(*hash).expect("locator hashes are finalized"))
+                .collect();
+            assert!(heights.windows(2).all(|w| w[0] > w[1]));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn explicit_flush_persists_unsynced_blocks() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let cache_dir = tempdir::TempDir::new("zebra-state-durability")?;
+        let config = Config {
+            cache_dir: cache_dir.path().to_owned(),
+            durability: Durability::Never,
+            ..Config::default()
+        };
+
+        let mut state = FinalizedState::new(&config, Network::Mainnet);
+        commit_mainnet_vectors(&mut state, 0..=2)?;
+        let tip = state.tip();
+
+        // The commits are only in the write-ahead log and memtables
+        let memtable_entries = |name| {
+            let cf = state.db.cf_handle(name).unwrap();
+            state
+                .db
+                .property_int_value_cf(cf, "rocksdb.num-entries-active-mem-table")
+        };
+        assert_eq!(state.unsynced_blocks, 3);
+        assert_eq!(memtable_entries("hash_by_height")?, Some(3));
+        assert_eq!(memtable_entries("block_by_height")?, Some(3));
+
+        state.flush()?;
+        for name in COLUMN_FAMILIES {
+            assert_eq!(memtable_entries(*name)?, Some(0));
+        }
+
+        std::mem::drop(state);
+        let state = FinalizedState::new(&config, Network::Mainnet);
+        assert_eq!(state.tip(), tip);
+        assert_eq!(tip.map(|(height, _)| height), Some(block::Height(2)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn transaction_location_finds_each_transaction() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let mut state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+
+        commit_mainnet_vectors(&mut state, 0..=1)?;
+
+        // Add some later transactions to block 2, so it has more than one.
+        // The state doesn't check the merkle root or the spent outputs.
+        let donor =
+            zebra_test::vectors::BLOCK_MAINNET_347500_BYTES.bitcoin_deserialize_into::<Block>()?;
+        let mut block =
+            zebra_test::vectors::BLOCK_MAINNET_2_BYTES.bitcoin_deserialize_into::<Block>()?;
+        block
+            .transactions
+            .extend(donor.transactions[1..4].iter().cloned());
+        let block = Arc::new(block);
+        state.commit_finalized_direct(FinalizedBlock::with_height(
+            block.clone(),
+            block::Height(2),
+        ))?;
+
+        for (index, transaction) in block.transactions.iter().enumerate() {
+            assert_eq!(
+                state.transaction_location(transaction.hash())?,
+                Some((block::Height(2), index as u32))
+            );
+        }
+
+        let block1 =
+            zebra_test::vectors::BLOCK_MAINNET_1_BYTES.bitcoin_deserialize_into::<Block>()?;
+        assert_eq!(
+            state.transaction_location(block1.transactions[0].hash())?,
+            Some((block::Height(1), 0))
+        );
+        assert_eq!(
+            state.transaction_location(donor.transactions[4].hash())?,
+            None
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn revert_finalized_restores_spent_outputs() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let mut state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+
+        let blocks = commit_mainnet_vectors(&mut state, 0..=2)?;
+
+        // Spend the coinbase output of block 1 in block 3
+        let spent = coinbase_outpoint(&blocks[1]);
+        let spent_utxo = state.utxo(&spent).expect("block 1 output is unspent");
+        let spend = spend_coinbase(&blocks[1]);
+        let created = transparent::OutPoint {
+            hash: spend.hash(),
+            index: 0,
+        };
+        let mut block =
+            zebra_test::vectors::BLOCK_MAINNET_3_BYTES.bitcoin_deserialize_into::<Block>()?;
+        block.transactions.push(Arc::new(spend));
+        state.commit_finalized_direct(FinalizedBlock::with_height(
+            Arc::new(block),
+            block::Height(3),
+        ))?;
+
+        assert_eq!(state.utxo(&spent), None);
+        assert!(state.utxo(&created).is_some());
+
+        state.revert_finalized(block::Height(1))?;
+
+        assert_eq!(state.tip(), Some((block::Height(1), blocks[1].hash())));
+        assert_eq!(state.utxo(&spent), Some(spent_utxo));
+        assert_eq!(state.utxo(&created), None);
+        let block2_output = coinbase_outpoint(&blocks[2]);
+        assert_eq!(state.utxo(&block2_output), None);
+
+        // The reverted blocks can be committed again
+        state.commit_finalized_direct(FinalizedBlock::with_height(
+            blocks[2].clone(),
+            block::Height(2),
+        ))?;
+        assert!(state.utxo(&block2_output).is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn provably_unspendable_outputs_are_not_utxos() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let mut state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+
+        let blocks = commit_mainnet_vectors(&mut state, 0..=1)?;
+
+        // Spend the coinbase output of block 1 into an `OP_RETURN` output and
+        // a normal output
+        let op_return_output = transparent::Output {
+            value: Amount::try_from(0)?,
+            lock_script: transparent::Script(vec![0x6a, 2, 0xab, 0xcd]),
+        };
+        let mut spend = spend_coinbase(&blocks[1]);
+        spend.outputs_mut().insert(0, op_return_output);
+        let spend_hash = spend.hash();
+
+        let mut block =
+            zebra_test::vectors::BLOCK_MAINNET_2_BYTES.bitcoin_deserialize_into::<Block>()?;
+        block.transactions.push(Arc::new(spend));
+        state.commit_finalized_direct(FinalizedBlock::with_height(
+            Arc::new(block),
+            block::Height(2),
+        ))?;
+
+        let outpoint = |index| transparent::OutPoint {
+            hash: spend_hash,
+            index,
+        };
+        assert_eq!(state.utxo(&outpoint(0)), None);
+        assert!(state.utxo(&outpoint(1)).is_some());
+
+        Ok(())
+    }
+
+    /// Returns a unique synthetic block hash for `height`.
+    fn synthetic_hash(height: u32) -> block::Hash {
+        let mut hash = [0; 32];
+        hash[..4].copy_from_slice(&height.to_le_bytes());
+        block::Hash(hash)
+    }
+
+    /// Index a synthetic hash for each height up to `tip`, without any blocks.
+    fn index_synthetic_hashes(state: &FinalizedState, tip: u32) -> Result<(), BoxError> {
+        let hash_by_height = state.db.cf_handle("hash_by_height").unwrap();
+        let height_by_hash = state.db.cf_handle("height_by_hash").unwrap();
+
+        let mut batch = rocksdb::WriteBatch::default();
+        for height in 0..=tip {
+            batch.zs_insert(
+                hash_by_height,
+                block::Height(height),
+                synthetic_hash(height),
+            );
+            batch.zs_insert(
+                height_by_hash,
+                synthetic_hash(height),
+                block::Height(height),
+            );
+        }
+        state.db.write(batch)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn block_hashes_in_range_stops_at_tip() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+        assert_eq!(state.block_hashes_in_range(block::Height(0), 500)?, vec![]);
+
+        // Only hashes are read, so we can index synthetic hashes directly
+        index_synthetic_hashes(&state, 700)?;
+
+        let hashes = state.block_hashes_in_range(block::Height(1), 500)?;
+        assert_eq!(hashes.len(), 500);
+        assert_eq!(hashes, (1..=500).map(synthetic_hash).collect::<Vec<_>>());
+
+        // Stop early at the tip
+        let hashes = state.block_hashes_in_range(block::Height(601), 500)?;
+        assert_eq!(hashes, (601..=700).map(synthetic_hash).collect::<Vec<_>>());
+
+        assert_eq!(
+            state.block_hashes_in_range(block::Height(701), 500)?,
+            vec![]
+        );
+        assert_eq!(state.block_hashes_in_range(block::Height(1), 0)?, vec![]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn block_locator_backs_off_exponentially() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+        assert_eq!(state.block_locator(), vec![]);
+
+        // A synthetic 1000 block chain, with its tip at height 999
+        index_synthetic_hashes(&state, 999)?;
+
+        let locator = state.block_locator();
+        let heights: Vec<u32> = locator
+            .iter()
+            .map(|hash| state.height(*hash).expect("locator hashes are finalized").0)
+            .collect();
+
+        // The ten most recent blocks, newest first
+        assert_eq!(heights[..10], (990..=999).rev().collect::<Vec<_>>()[..]);
+
+        // Then the step back doubles each time, until we reach genesis
+        let gaps: Vec<u32> = heights.windows(2).map(|w| w[0] - w[1]).collect();
+        let (genesis_gap, doubling_gaps) = gaps[9..].split_last().unwrap();
+        for (i, gap) in doubling_gaps.iter().enumerate() {
+            assert_eq!(*gap, 2 << i);
+        }
+        assert!(*genesis_gap <= 2 << doubling_gaps.len());
+
+        assert_eq!(locator.first(), Some(&synthetic_hash(999)));
+        assert_eq!(locator.last(), Some(&synthetic_hash(0)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn respond_getblocks_lists_hashes_after_intersection() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+        index_synthetic_hashes(&state, 1000)?;
+
+        let inv = |heights: std::ops::RangeInclusive<u32>| {
+            Message::Inv(
+                heights
+                    .map(|height| InventoryHash::Block(synthetic_hash(height)))
+                    .collect(),
+            )
+        };
+
+        // The first locator hash isn't in our chain, so the intersection is
+        // at height 100
+        let mut request = GetBlocks {
+            block_header_hashes: vec![
+                synthetic_hash(5000),
+                synthetic_hash(100),
+                synthetic_hash(50),
+                synthetic_hash(0),
+            ],
+            stop_hash: None,
+        };
+        assert_eq!(state.respond_getblocks(&request)?, inv(101..=600));
+
+        // The stop hash isn't included
+        request.stop_hash = Some(synthetic_hash(150));
+        assert_eq!(state.respond_getblocks(&request)?, inv(101..=149));
+
+        // Stop early at the tip
+        request.block_header_hashes = vec![synthetic_hash(900)];
+        request.stop_hash = None;
+        assert_eq!(state.respond_getblocks(&request)?, inv(901..=1000));
+
+        // Start after genesis if there is no intersection
+        request.block_header_hashes = vec![synthetic_hash(5000)];
+        assert_eq!(state.respond_getblocks(&request)?, inv(1..=500));
+
+        Ok(())
+    }
+
+    #[test]
+    fn respond_getheaders_stops_at_stop_hash() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+
+        // Only headers and hashes are read, so we can index a synthetic chain
+        // of headers directly
+        let genesis = zebra_test::vectors::BLOCK_MAINNET_GENESIS_BYTES
+            .bitcoin_deserialize_into::<Block>()?
+            .header;
+        let hash_by_height = state.db.cf_handle("hash_by_height").unwrap();
+        let height_by_hash = state.db.cf_handle("height_by_hash").unwrap();
+        let header_by_height = state.db.cf_handle("header_by_height").unwrap();
+
+        let mut headers = vec![genesis];
+        let mut batch = rocksdb::WriteBatch::default();
+        for height in 0..=2500 {
+            if height > 0 {
+                let previous = headers.last().expect("headers is not empty");
+                let header = block::Header::new(
+                    previous.version,
+                    block::Hash::from(previous),
+                    previous.merkle_root,
+                    previous.time.0 + chrono::Duration::minutes(10),
+                    previous.difficulty_threshold,
+                    height,
+                );
+                headers.push(header);
+            }
+            let header = headers[height as usize];
+            let hash = block::Hash::from(&header);
+
+            batch.zs_insert(hash_by_height, block::Height(height), hash);
+            batch.zs_insert(height_by_hash, hash, block::Height(height));
+            batch.zs_insert(header_by_height, block::Height(height), header);
+        }
+        state.db.write(batch)?;
+
+        let hash = |height: usize| block::Hash::from(&headers[height]);
+        let response = |range: std::ops::RangeInclusive<usize>| {
+            Message::Headers(
+                headers[range]
+                    .iter()
+                    .map(|header| block::CountedHeader {
+                        header: *header,
+                        transaction_count: 0,
+                    })
+                    .collect(),
+            )
+        };
+
+        // The response includes the stop header
+        let mut request = GetHeaders {
+            block_header_hashes: vec![hash(100), hash(50), hash(0)],
+            stop_hash: Some(hash(150)),
+        };
+        assert_eq!(state.respond_getheaders(&request)?, response(101..=150));
+
+        // The response is capped at 2000 headers
+        request.stop_hash = None;
+        assert_eq!(state.respond_getheaders(&request)?, response(101..=2100));
+
+        // A stop hash after the cap is ignored
+        request.stop_hash = Some(hash(2400));
+        assert_eq!(state.respond_getheaders(&request)?, response(101..=2100));
+
+        // Stop early at the tip
+        request.block_header_hashes = vec![hash(2000)];
+        assert_eq!(state.respond_getheaders(&request)?, response(2001..=2500));
+
+        Ok(())
+    }
+
+    #[test]
+    fn prune_bodies_keeps_headers() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let mut state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+
+        commit_mainnet_vectors(&mut state, 0..=10)?;
+
+        assert_eq!(state.prune_bodies_below(block::Height(5))?, 5);
+
+        let undo_by_height = state.db.cf_handle("undo_by_height").unwrap();
+        for height in 0..=10 {
+            let height = block::Height(height);
+            let undo: Option<SpentOutputs> = state.db.zs_get(undo_by_height, &height);
+            assert_eq!(undo.is_some(), height >= block::Height(5));
+        }
+
+        for height in 0..=10 {
+            let height = block::Height(height);
+            let hash = state.hash(height).expect("hashes are never pruned");
+            let header = state
+                .header(height.into())
+                .expect("headers are never pruned");
+            assert_eq!(block::Hash::from(&header), hash);
+            assert_eq!(state.header(hash.into()), Some(header));
+
+            if height < block::Height(5) {
+                assert_eq!(state.block(height.into()), None);
+                assert_eq!(state.block(hash.into()), None);
+            } else {
+                let block = state.block(height.into()).expect("body was not pruned");
+                assert_eq!(block.header, header);
+            }
+        }
+
+        // Locators only need hashes, so they still reach genesis
+        assert_eq!(
+            state.block_locator().last().copied(),
+            state.hash(block::Height(0))
+        );
+
+        // Pruning is idempotent
+        assert_eq!(state.prune_bodies_below(block::Height(5))?, 0);
+
+        // Blocks above the prune height can still be reverted, but pruned
+        // blocks can't
+        state.revert_finalized(block::Height(6))?;
+        assert_eq!(
+            state.tip().map(|(height, _)| height),
+            Some(block::Height(6))
+        );
+        assert!(state.revert_finalized(block::Height(3)).is_err());
+        assert_eq!(
+            state.tip().map(|(height, _)| height),
+            Some(block::Height(6))
+        );
+
+        Ok(())
+    }
+
+    /// Returns a copy of `block` with a different coinbase output value, as a
+    /// child of `parent`.
+    fn fork_block(block: &Block, parent: block::Hash, value: Amount<NonNegative>) -> Arc<Block> {
+        let coinbase = &block.transactions[0];
+        let mut outputs = coinbase.outputs().to_vec();
+        outputs[0].value = value;
+        let coinbase = Transaction::new(
+            coinbase.version(),
+            coinbase.inputs().to_vec(),
+            outputs,
+            coinbase.locktime(),
+        );
+
+        let header = &block.header;
+        Arc::new(Block {
+            header: block::Header::new(
+                header.version,
+                parent,
+                header.merkle_root,
+                header.time.0,
+                header.difficulty_threshold,
+                header.nonce,
+            ),
+            transactions: vec![Arc::new(coinbase)],
+        })
+    }
+
+    #[test]
+    fn reorg_replaces_tail_and_utxos() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let mut state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+
+        let mut blocks = commit_mainnet_vectors(&mut state, 0..=3)?;
+        blocks.push(
+            zebra_test::vectors::BLOCK_MAINNET_4_BYTES.bitcoin_deserialize_into::<Arc<Block>>()?,
+        );
+        let old_tail = &blocks[1..=3];
+
+        // Build a competing 4 block tail on top of genesis
+        let value = Amount::<NonNegative>::try_from(25 * COIN)?;
+        let mut parent = blocks[0].hash();
+        let mut new_tail = Vec::new();
+        for block in blocks[1..=4].iter() {
+            let fork = fork_block(block, parent, value);
+            parent = fork.hash();
+            new_tail.push(fork);
+        }
+
+        // A tail that doesn't link to the fork point is rejected, and the
+        // state is unchanged
+        let old_tip = state.tip();
+        assert!(state
+            .reorg(block::Height(0), new_tail[1..].to_vec())
+            .is_err());
+        assert_eq!(state.tip(), old_tip);
+
+        assert_eq!(state.depth(blocks[0].hash()), Some(3));
+        assert_eq!(state.depth(blocks[3].hash()), Some(0));
+
+        let tip = state.reorg(block::Height(0), new_tail.clone())?;
+        assert_eq!(tip, (block::Height(4), new_tail[3].hash()));
+        assert_eq!(state.tip(), Some(tip));
+
+        assert_eq!(state.depth(blocks[0].hash()), Some(4));
+        assert_eq!(state.depth(new_tail[3].hash()), Some(0));
+
+        for block in old_tail {
+            assert_eq!(state.depth(block.hash()), None);
+            let coinbase_hash = block.transactions[0].hash();
+            assert_eq!(state.height(block.hash()), None);
+            assert_eq!(state.transaction(coinbase_hash), None);
+            let outpoint = transparent::OutPoint {
+                hash: coinbase_hash,
+                index: 0,
+            };
+            assert_eq!(state.utxo(&outpoint), None);
+        }
+        for (height, block) in (1..).zip(new_tail.iter()) {
+            assert_eq!(state.height(block.hash()), Some(block::Height(height)));
+            let outpoint = transparent::OutPoint {
+                hash: block.transactions[0].hash(),
+                index: 0,
+            };
+            let utxo = state
+                .utxo(&outpoint)
+                .expect("new coinbase output is unspent");
+            assert_eq!(utxo.height, block::Height(height));
+            assert_eq!(utxo.output.value, value);
+        }
+
+        let info = state.utxo_set_info()?;
+        assert_eq!(info.count, 4);
+        assert_eq!(
+            info.total_value,
+            Amount::<NonNegative>::try_from(100 * COIN)?
+        );
+
+        Ok(())
+    }
+
+    /// Returns every key and value in each column family of `state`.
+    fn db_contents(state: &FinalizedState) -> Vec<Vec<(Box<[u8]>, Box<[u8]>)>> {
+        [
+            "hash_by_height",
+            "height_by_hash",
+            "block_by_height",
+            "header_by_height",
+            "tx_by_hash",
+            "utxo_by_outpoint",
+            "undo_by_height",
+            "chain_work_by_height",
+        ]
+        .iter()
+        .map(|name| {
+            let cf = state.db.cf_handle(name).unwrap();
+            state
+                .db
+                .iterator_cf(cf, rocksdb::IteratorMode::Start)
+                .collect()
+        })
+        .collect()
+    }
+
+    #[test]
+    fn batch_commit_matches_one_by_one_commits() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        const BLOCK_COUNT: usize = 1000;
+
+        // Extend the mainnet test vectors with synthetic coinbase-only blocks,
+        // using a different coinbase value for each block so that their
+        // transaction ids are unique
+        let mut blocks = zebra_test::vectors::MAINNET_BLOCKS
+            .range(0..=10)
+            .map(|(_, block_bytes)| block_bytes.bitcoin_deserialize_into::<Arc<Block>>())
+            .collect::<Result<Vec<_>, _>>()?;
+        while blocks.len() < BLOCK_COUNT {
+            let parent = blocks.last().unwrap().hash();
+            let value = Amount::<NonNegative>::try_from(blocks.len() as i64)?;
+            blocks.push(fork_block(&blocks[1], parent, value));
+        }
+
+        // Spend the coinbase output of block 100 in block 500, so the undo
+        // data for block 500 refers to an output created in the same batch
+        let spent = coinbase_outpoint(&blocks[100]);
+        let spend = spend_coinbase(&blocks[100]);
+        let mut block = blocks[500].as_ref().clone();
+        block.transactions.push(Arc::new(spend));
+        blocks[500] = Arc::new(block);
+        for height in 501..BLOCK_COUNT {
+            let parent = blocks[height - 1].hash();
+            let value = Amount::<NonNegative>::try_from(height as i64)?;
+            blocks[height] = fork_block(&blocks[1], parent, value);
+        }
+
+        let mut one_by_one = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+        for (height, block) in (0..).zip(blocks.iter().cloned()) {
+            one_by_one.commit_finalized_direct(FinalizedBlock::with_height(
+                block,
+                block::Height(height),
+            ))?;
+        }
+
+        let mut batched = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+        assert!(batched.batch_commit(Vec::new()).is_err());
+        // Blocks that don't link to the tip are rejected
+        assert!(batched.batch_commit(blocks[1..].to_vec()).is_err());
+        assert_eq!(batched.tip(), None);
+
+        let tip = batched.batch_commit(blocks[..600].to_vec())?;
+        assert_eq!(tip, (block::Height(599), blocks[599].hash()));
+        let tip = batched.batch_commit(blocks[600..].to_vec())?;
+        assert_eq!(
+            tip,
+            (
+                block::Height(BLOCK_COUNT as u32 - 1),
+                blocks[BLOCK_COUNT - 1].hash()
+            )
+        );
+
+        assert_eq!(batched.tip(), one_by_one.tip());
+        assert_eq!(batched.utxo(&spent), None);
+        assert_eq!(db_contents(&batched), db_contents(&one_by_one));
+
+        // The undo data restores outputs spent within a batch
+        batched.revert_finalized(block::Height(499))?;
+        one_by_one.revert_finalized(block::Height(499))?;
+        assert!(batched.utxo(&spent).is_some());
+        assert_eq!(db_contents(&batched), db_contents(&one_by_one));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn commit_response_reports_cascade_tip() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let mut state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+
+        let mut queued = Vec::new();
+        for (&height, block_bytes) in zebra_test::vectors::MAINNET_BLOCKS.range(0..=2) {
+            let block = block_bytes.bitcoin_deserialize_into::<Arc<Block>>()?;
+            queued.push(FinalizedBlock::with_height(block, block::Height(height)));
+        }
+        let hashes: Vec<_> = queued.iter().map(|finalized| finalized.hash).collect();
+
+        // Queue the descendants first, so they wait for their parent
+        let mut responses = Vec::new();
+        for finalized in queued.into_iter().rev() {
+            let (rsp_tx, rsp_rx) = oneshot::channel();
+            state.queue_and_commit_finalized((finalized, rsp_tx));
+            responses.push(rsp_rx);
+        }
+        responses.reverse();
+
+        let tip = (block::Height(2), hashes[2]);
+        assert_eq!(state.tip(), Some(tip));
+        for (rsp_rx, hash) in responses.into_iter().zip(hashes) {
+            let commit = rsp_rx.await.expect("sender is not dropped")?;
+            assert_eq!(
+                commit,
+                FinalizedCommit {
+                    hash,
+                    tip,
+                    committed_count: 3,
+                }
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn async_reads_match_sync_reads() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let mut state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+        assert_eq!(state.tip_async().await, None);
+
+        commit_mainnet_vectors(&mut state, 0..=1)?;
+
+        let (tip_height, tip_hash) = state.tip().expect("state is not empty");
+        assert_eq!(state.tip_async().await, Some((tip_height, tip_hash)));
+
+        let by_height = state.block_async(tip_height.into());
+        let by_hash = state.block_async(tip_hash.into());
+        let expected = state.block(tip_height.into());
+        assert!(expected.is_some());
+        assert_eq!(by_height.await, expected);
+        assert_eq!(by_hash.await, expected);
+
+        let coinbase = &expected.expect("block was just committed").transactions[0];
+        let outpoint = transparent::OutPoint {
+            hash: coinbase.hash(),
+            index: 0,
+        };
+        let utxo = state.utxo_async(outpoint).await;
+        assert!(utxo.is_some());
+        assert_eq!(utxo, state.utxo(&outpoint));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ephemeral_database_outlives_pending_async_reads() -> Result<(), BoxError> {
+        zebra_test::init();
+
+        let mut state = FinalizedState::new(&Config::ephemeral(), Network::Mainnet);
+        commit_mainnet_vectors(&mut state, 0..=1)?;
+        let path = state.db.path().to_owned();
+        let expected = state.block(block::Height(1).into());
+
+        let block = state.block_async(block::Height(1).into());
+        std::mem::drop(state);
+        assert!(path.exists());
+        assert_eq!(block.await, expected);
+
+        // The database is deleted when the last reference is dropped
+        assert!(!path.exists());
+
+        Ok(())
+    }
+}
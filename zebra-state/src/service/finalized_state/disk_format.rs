@@ -7,6 +7,7 @@ use zebra_chain::{
     block::Block,
     serialization::{BitcoinDeserialize, BitcoinDeserializeInto, BitcoinSerialize},
     transaction, transparent,
+    work::difficulty::Work,
 };
 
 use crate::Utxo;
@@ -17,6 +18,11 @@ pub struct TransactionLocation {
     pub index: u32,
 }
 
+/// The outputs spent by a finalized block, so they can be restored if the
+/// block is reverted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpentOutputs(pub Vec<(transparent::OutPoint, Utxo)>);
+
 // Helper trait for defining the exact format used to interact with disk per
 // type.
 pub trait IntoDisk {
@@ -88,6 +94,22 @@ impl FromDisk for Block {
     }
 }
 
+impl IntoDisk for block::Header {
+    type Bytes = Vec<u8>;
+
+    fn as_bytes(&self) -> Self::Bytes {
+        self.bitcoin_serialize_to_vec()
+            .expect("serialization to vec doesn't fail")
+    }
+}
+
+impl FromDisk for block::Header {
+    fn from_bytes(bytes: impl AsRef<[u8]>) -> Self {
+        block::Header::deserialize_from_buf(bytes.as_ref())
+            .expect("deserialization format should match the serialization format used by IntoDisk")
+    }
+}
+
 impl IntoDisk for TransactionLocation {
     type Bytes = [u8; 8];
 
@@ -170,6 +192,21 @@ impl FromDisk for block::Height {
     }
 }
 
+impl IntoDisk for Work {
+    type Bytes = [u8; 16];
+
+    fn as_bytes(&self) -> Self::Bytes {
+        self.as_u128().to_be_bytes()
+    }
+}
+
+impl FromDisk for Work {
+    fn from_bytes(bytes: impl AsRef<[u8]>) -> Self {
+        let array = bytes.as_ref().try_into().unwrap();
+        Work::from_u128(u128::from_be_bytes(array))
+    }
+}
+
 impl IntoDisk for Utxo {
     type Bytes = Vec<u8>;
 
@@ -209,6 +246,41 @@ impl IntoDisk for transparent::OutPoint {
     }
 }
 
+impl IntoDisk for SpentOutputs {
+    type Bytes = Vec<u8>;
+
+    fn as_bytes(&self) -> Self::Bytes {
+        let mut bytes = Vec::new();
+        for (outpoint, utxo) in self.0.iter() {
+            let utxo_bytes = utxo.as_bytes();
+            bytes.extend_from_slice(&outpoint.as_bytes());
+            bytes.extend_from_slice(&(utxo_bytes.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(&utxo_bytes);
+        }
+        bytes
+    }
+}
+
+impl FromDisk for SpentOutputs {
+    fn from_bytes(bytes: impl AsRef<[u8]>) -> Self {
+        let mut bytes = bytes.as_ref();
+        let mut spent = Vec::new();
+        while !bytes.is_empty() {
+            let (outpoint_bytes, rest) = bytes.split_at(transparent::OutPoint::len());
+            let outpoint = transparent::OutPoint {
+                hash: transaction::Hash(outpoint_bytes[0..32].try_into().unwrap()),
+                index: u32::from_le_bytes(outpoint_bytes[32..36].try_into().unwrap()),
+            };
+            let (len_bytes, rest) = rest.split_at(4);
+            let len = u32::from_be_bytes(len_bytes.try_into().unwrap()) as usize;
+            let (utxo_bytes, rest) = rest.split_at(len);
+            spent.push((outpoint, Utxo::from_bytes(utxo_bytes)));
+            bytes = rest;
+        }
+        SpentOutputs(spent)
+    }
+}
+
 /// Helper trait for inserting (Key, Value) pairs into rocksdb with a consistently
 /// defined format
 pub trait DiskSerialize {
@@ -372,4 +444,12 @@ mod tests {
 
         proptest!(|(val in any::<Utxo>())| assert_value_properties(val));
     }
+
+    #[test]
+    fn roundtrip_spent_outputs() {
+        zebra_test::init();
+
+        let spent = proptest::collection::vec(any::<(transparent::OutPoint, Utxo)>(), 0..4);
+        proptest!(|(val in spent)| assert_value_properties(SpentOutputs(val)));
+    }
 }
@@ -120,7 +120,7 @@ impl Chain {
 /// This trait was motivated by the length of the `push` and `pop_root` functions
 /// and fear that it would be easy to introduce bugs when updating them unless
 /// the code was reorganized to keep related operations adjacent to eachother.
-trait UpdateWith<T> {
+trait UpdateWith<T: ?Sized> {
     /// Update `Chain` cumulative data members to add data that are derived from
     /// `T`
     fn update_chain_state_with(&mut self, _: &T);
@@ -161,7 +161,7 @@ impl UpdateWith<PreparedBlock> for Chain {
             .zip(transaction_hashes.iter().cloned())
             .enumerate()
         {
-            let inputs = &transaction.inputs;
+            let inputs = transaction.inputs();
 
             // add key `transaction.hash` and value `(height, tx_index)` to `tx_by_hash`
             let prior_pair = self
@@ -209,7 +209,7 @@ impl UpdateWith<PreparedBlock> for Chain {
         for (transaction, transaction_hash) in
             block.transactions.iter().zip(transaction_hashes.iter())
         {
-            let inputs = &transaction.inputs;
+            let inputs = transaction.inputs();
 
             // remove `transaction.hash` from `tx_by_hash`
             assert!(
@@ -241,8 +241,8 @@ impl UpdateWith<HashMap<transparent::OutPoint, Utxo>> for Chain {
     }
 }
 
-impl UpdateWith<Vec<transparent::Input>> for Chain {
-    fn update_chain_state_with(&mut self, inputs: &Vec<transparent::Input>) {
+impl UpdateWith<[transparent::Input]> for Chain {
+    fn update_chain_state_with(&mut self, inputs: &[transparent::Input]) {
         for consumed_utxo in inputs {
             match consumed_utxo {
                 transparent::Input::PrevOut { outpoint, .. } => {
@@ -253,7 +253,7 @@ impl UpdateWith<Vec<transparent::Input>> for Chain {
         }
     }
 
-    fn revert_chain_state_with(&mut self, inputs: &Vec<transparent::Input>) {
+    fn revert_chain_state_with(&mut self, inputs: &[transparent::Input]) {
         for consumed_utxo in inputs {
             match consumed_utxo {
                 transparent::Input::PrevOut { outpoint, .. } => {
@@ -138,6 +138,11 @@ impl QueuedBlocks {
         self.blocks.get_mut(&hash)
     }
 
+    /// Return the height of the queued block with `hash`, if it is queued
+    pub fn height(&self, hash: &block::Hash) -> Option<block::Height> {
+        self.blocks.get(hash).map(|(prepared, _)| prepared.height)
+    }
+
     /// Update metrics after the queue is modified
     fn update_metrics(&self) {
         if let Some(max_height) = self.by_height.keys().next_back() {
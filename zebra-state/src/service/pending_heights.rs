@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use tokio::sync::broadcast;
+
+use zebra_chain::block;
+
+use crate::{BoxError, Response};
+
+#[derive(Debug, Default)]
+pub struct PendingHeights(HashMap<block::Hash, broadcast::Sender<block::Height>>);
+
+impl PendingHeights {
+    /// Returns a future that will resolve to the height of the block with the
+    /// given hash when it is available.
+    pub fn queue(&mut self, hash: block::Hash) -> impl Future<Output = Result<Response, BoxError>> {
+        let mut receiver = self
+            .0
+            .entry(hash)
+            .or_insert_with(|| {
+                let (sender, _) = broadcast::channel(1);
+                sender
+            })
+            .subscribe();
+
+        async move {
+            receiver
+                .recv()
+                .await
+                .map(Response::Height)
+                .map_err(BoxError::from)
+        }
+    }
+
+    /// Notify all requests waiting for the height of the block with the given
+    /// hash that the block has arrived.
+    pub fn respond(&mut self, hash: &block::Hash, height: block::Height) {
+        if let Some(sender) = self.0.remove(hash) {
+            tracing::trace!(?hash, ?height, "found pending block height");
+            let _ = sender.send(height);
+        }
+    }
+
+    /// Scan the set of waiting height requests for channels where all receivers
+    /// have been dropped and remove the corresponding sender.
+    pub fn prune(&mut self) {
+        self.0.retain(|_, chan| chan.receiver_count() > 0);
+    }
+}
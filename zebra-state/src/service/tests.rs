@@ -64,6 +64,8 @@ async fn test_populated_state_responds_correctly(
             Ok(Response::Block(Some(block.clone()))),
         ));
 
+        transcript.push((Request::AwaitHeight(hash), Ok(Response::Height(height))));
+
         transcript.push((
             Request::Depth(block.hash()),
             Ok(Response::Depth(Some(LAST_BLOCK_HEIGHT - height.0))),
@@ -85,7 +87,7 @@ async fn test_populated_state_responds_correctly(
                 ));
 
                 let from_coinbase = transaction.is_coinbase();
-                for (index, output) in transaction.outputs.iter().cloned().enumerate() {
+                for (index, output) in transaction.outputs().iter().cloned().enumerate() {
                     let outpoint = transparent::OutPoint {
                         hash: transaction_hash,
                         index: index as _,
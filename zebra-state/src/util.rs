@@ -1,28 +1,24 @@
-use std::iter;
 use zebra_chain::block;
 
-use crate::constants;
-
 /// Get the heights of the blocks for constructing a block_locator list
+///
+/// The heights start at the tip, with the ten most recent heights, then
+/// double the step back to the next height each time. They always finish
+/// with the genesis height, and are returned newest-first, as the protocol
+/// expects.
 pub fn block_locator_heights(tip_height: block::Height) -> Vec<block::Height> {
-    // Stop at the reorg limit, or the genesis block.
-    let min_locator_height = tip_height
-        .0
-        .saturating_sub(constants::MAX_BLOCK_REORG_HEIGHT);
-    let locators = iter::successors(Some(1u32), |h| h.checked_mul(2))
-        .flat_map(move |step| tip_height.0.checked_sub(step));
-    let locators = iter::once(tip_height.0)
-        .chain(locators)
-        .take_while(move |&height| height > min_locator_height)
-        .chain(iter::once(min_locator_height))
-        .map(block::Height);
+    let mut locators = Vec::new();
+    let mut height = tip_height.0;
+    let mut step = 1;
+    while height > 0 {
+        locators.push(block::Height(height));
+        if locators.len() >= 10 {
+            step = step.saturating_mul(2);
+        }
+        height = height.saturating_sub(step);
+    }
+    locators.push(block::Height(0));
 
-    let locators = locators.collect();
-    tracing::info!(
-        ?tip_height,
-        ?min_locator_height,
-        ?locators,
-        "created block locator"
-    );
+    tracing::info!(?tip_height, ?locators, "created block locator");
     locators
 }
@@ -2,7 +2,11 @@ use color_eyre::eyre::Report;
 use once_cell::sync::Lazy;
 use std::sync::Arc;
 use tempdir::TempDir;
-use zebra_chain::{block::Block, parameters::Network, serialization::BitcoinDeserialize};
+use zebra_chain::{
+    block::{self, Block},
+    parameters::Network,
+    serialization::BitcoinDeserialize,
+};
 use zebra_test::transcript::{TransError, Transcript};
 
 use zebra_state::*;
@@ -18,7 +22,11 @@ static COMMIT_FINALIZED_BLOCK_MAINNET: Lazy<Vec<(Request, Result<Response, Trans
         vec![
             (
                 Request::CommitFinalizedBlock(block.into()),
-                Ok(Response::Committed(hash)),
+                Ok(Response::CommittedFinalized(FinalizedCommit {
+                    hash,
+                    tip: (block::Height(0), hash),
+                    committed_count: 1,
+                })),
             ),
             (
                 Request::Block(hash.into()),
@@ -38,7 +46,11 @@ static COMMIT_FINALIZED_BLOCK_TESTNET: Lazy<Vec<(Request, Result<Response, Trans
         vec![
             (
                 Request::CommitFinalizedBlock(block.into()),
-                Ok(Response::Committed(hash)),
+                Ok(Response::CommittedFinalized(FinalizedCommit {
+                    hash,
+                    tip: (block::Height(0), hash),
+                    committed_count: 1,
+                })),
             ),
             (
                 Request::Block(hash.into()),
@@ -196,16 +196,18 @@ lazy_static! {
     // for i in 653599 653600 653601; do
     //     zcash-cli getblock $i 0 > block-main-$[i/1000000]-$[i/1000%1000]-$[i%1000].txt
     // done
-    // TODO: Add back after implementing SegWit
-    // pub static ref BLOCK_MAINNET_653599_BYTES: Vec<u8> =
-    //     <Vec<u8>>::from_hex(include_str!("block-main-0-653-599.txt").trim())
-    //         .expect("Block bytes are in valid hex representation");
-    // pub static ref BLOCK_MAINNET_653600_BYTES: Vec<u8> =
-    //     <Vec<u8>>::from_hex(include_str!("block-main-0-653-600.txt").trim())
-    //         .expect("Block bytes are in valid hex representation");
-    // pub static ref BLOCK_MAINNET_653601_BYTES: Vec<u8> =
-    //     <Vec<u8>>::from_hex(include_str!("block-main-0-653-601.txt").trim())
-    //         .expect("Block bytes are in valid hex representation");
+    //
+    // These SegWit blocks are larger than 1 MB including their witness data.
+    // TODO: Add them to MAINNET_BLOCKS once block deserialization accepts them
+    pub static ref BLOCK_MAINNET_653599_BYTES: Vec<u8> =
+        <Vec<u8>>::from_hex(include_str!("block-main-0-653-599.txt").trim())
+            .expect("Block bytes are in valid hex representation");
+    pub static ref BLOCK_MAINNET_653600_BYTES: Vec<u8> =
+        <Vec<u8>>::from_hex(include_str!("block-main-0-653-600.txt").trim())
+            .expect("Block bytes are in valid hex representation");
+    pub static ref BLOCK_MAINNET_653601_BYTES: Vec<u8> =
+        <Vec<u8>>::from_hex(include_str!("block-main-0-653-601.txt").trim())
+            .expect("Block bytes are in valid hex representation");
 
 
     // TODO: one more Canopy Mainnet block